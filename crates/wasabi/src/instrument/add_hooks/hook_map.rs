@@ -213,7 +213,7 @@ impl HookMap {
                 let js_args = &args[0].to_lowlevel_long_expr();
                 Hook::new(ll_name, args, "drop", js_args)
             }
-            Select => {
+            Select(_) => {
                 assert_eq!(polymorphic_tys.len(), 2, "select has two polymorphic arguments");
                 assert_eq!(polymorphic_tys[0], polymorphic_tys[1], "select arguments must be equal");
                 let args = args!(condition: I32, input0: polymorphic_tys[0], input1: polymorphic_tys[1]);
@@ -257,6 +257,28 @@ impl HookMap {
             /* instructions that need additional information and thus have own method */
 
             Block(_) | Loop(_) | Else | End => panic!("cannot get hook for block-type instruction with this method, please use the other methods specialized to the block type"),
+
+            /* SIMD instructions are not instrumented (yet), since v128 values cannot be passed to JavaScript */
+
+            LoadLane(..) | StoreLane(..) | Lane(..) | I8x16Shuffle(_) | V128Bitselect => panic!("SIMD instructions are not supported by Wasabi's hooks"),
+
+            /* Bulk memory instructions are not instrumented (yet), since there are no hooks for them */
+
+            MemoryInit(..) | DataDrop(_) | MemoryCopy { .. } | MemoryFill(_) | TableInit(..) | ElemDrop(_) | TableCopy { .. } => panic!("bulk memory instructions are not supported by Wasabi's hooks"),
+
+            /* Reference instructions are not instrumented (yet), since reference values are not passed to JavaScript */
+
+            RefNull(_) | RefIsNull | RefFunc(_) => panic!("reference instructions are not supported by Wasabi's hooks"),
+
+            /* Atomic instructions are not instrumented (yet), since there are no hooks for them */
+
+            Atomic(..) | AtomicFence => panic!("atomic instructions are not supported by Wasabi's hooks"),
+
+            /* Tail calls are not instrumented (yet), since the return hooks of the caller would need to run before them */
+
+            ReturnCall(_) | ReturnCallIndirect(..) => panic!("tail calls are not supported by Wasabi's hooks"),
+
+            Unparsed(_) | Unsupported(..) => panic!("unparsed instructions are not supported by Wasabi's hooks"),
         };
 
         self.get_or_insert(ll_name, generate_hook)
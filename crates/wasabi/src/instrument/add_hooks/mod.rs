@@ -74,6 +74,11 @@ pub fn add_hooks(
         if function.code().is_none() {
             return;
         }
+        // Also leave functions with unsupported instructions unchanged, since their block
+        // structure and types are unknown.
+        if function.instrs().iter().any(|instr| matches!(instr, Unsupported(..))) {
+            return;
+        }
 
         // move body out of function, so that function is not borrowed during iteration over the original body
         let original_body = {
@@ -575,7 +580,7 @@ pub fn add_hooks(
                         instrumented_body.push(instr);
                     }
                 }
-                Select => {
+                Select(_) => {
                     assert_eq!(type_stack.pop_val(), I32, "select condition should be i32");
                     let ty = type_stack.pop_val();
                     assert_eq!(type_stack.pop_val(), ty, "select arguments should have same type");
@@ -768,6 +773,57 @@ pub fn add_hooks(
                         instrumented_body.push(instr);
                     }
                 }
+
+
+                /* SIMD Instructions (not instrumented, v128 values cannot be passed to JavaScript) */
+
+                LoadLane(..) | StoreLane(..) | Lane(..) | I8x16Shuffle(_) | V128Bitselect => {
+                    type_stack.instr(&instr.simple_type().unwrap());
+                    instrumented_body.push(instr);
+                }
+
+                /* Bulk Memory Instructions (not instrumented (yet), there are no hooks for them) */
+
+                MemoryInit(..) | DataDrop(_) | MemoryCopy { .. } | MemoryFill(_) | TableInit(..) | ElemDrop(_) | TableCopy { .. } => {
+                    type_stack.instr(&instr.simple_type().unwrap());
+                    instrumented_body.push(instr);
+                }
+
+                /* Atomic Instructions (not instrumented (yet), there are no hooks for them) */
+
+                Atomic(..) | AtomicFence => {
+                    type_stack.instr(&instr.simple_type().unwrap());
+                    instrumented_body.push(instr);
+                }
+
+                /* Tail Calls (not instrumented (yet), the code after them is dead like after return) */
+
+                ReturnCall(_) | ReturnCallIndirect(..) => {
+                    instrumented_body.push(instr);
+
+                    unreachable_depth = 1;
+                }
+
+                /* Unparsed function bodies (not instrumented, their instructions and types are unknown) */
+
+                Unparsed(_) => {
+                    instrumented_body.push(instr);
+
+                    unreachable_depth = 1;
+                }
+                Unsupported(..) => unreachable!("functions with unsupported instructions are skipped above"),
+
+                /* Reference Instructions (not instrumented, reference values are not passed to JavaScript) */
+
+                RefNull(_) | RefFunc(_) => {
+                    type_stack.instr(&instr.simple_type().unwrap());
+                    instrumented_body.push(instr);
+                }
+                RefIsNull => {
+                    type_stack.pop_val();
+                    type_stack.push_val(I32);
+                    instrumented_body.push(instr);
+                }
             }
         }
 
@@ -30,7 +30,7 @@ pub struct ModuleInfo {
     pub original_function_imports_count: usize,
 }
 
-impl<'a> From<&'a Module> for ModuleInfo {
+impl From<&Module> for ModuleInfo {
     fn from(module: &Module) -> Self {
         ModuleInfo {
             functions: module.functions.iter().map(Into::into).collect(),
@@ -38,9 +38,8 @@ impl<'a> From<&'a Module> for ModuleInfo {
             start: module.start,
             // if the module has no table, there cannot be a call_indirect, so this null will never be read from JS runtime
             table_export_name: module
-                .tables
-                .get(0)
-                .and_then(|table| table.export.get(0).cloned()),
+                .tables.first()
+                .and_then(|table| table.export.first().cloned()),
             br_tables: vec![],
             original_function_imports_count: module
                 .functions
@@ -66,7 +65,7 @@ pub struct FunctionInfo {
     // in the JSON as `"name": null`, which is a lot of overhead...
 }
 
-impl<'a> From<&'a Function> for FunctionInfo {
+impl From<&Function> for FunctionInfo {
     fn from(function: &Function) -> FunctionInfo {
         FunctionInfo {
             type_: function.type_,
@@ -39,7 +39,7 @@ pub fn count_calls(module: &mut Module) {
         // ignore the functions we added
         if i != getter && i != increment {
             function.modify_instrs(|instr| match instr {
-                Call(..) | CallIndirect(..) => vec![Call(increment), instr],
+                Call(..) | CallIndirect(..) | ReturnCall(..) | ReturnCallIndirect(..) => vec![Call(increment), instr],
                 instr => vec![instr],
             })
         }
@@ -38,7 +38,7 @@ fn main() -> Result<(), MainError> {
     let output_file_wasabi_js = output_file_wasm.with_extension("wasabi.js");
 
     // instrument Wasm and generate JavaScript
-    let (mut module, _offsets, _warnings) = Module::from_file(opt.input_file)?;
+    let mut module = Module::from_file(opt.input_file)?;
     if module.metadata.used_extensions().next().is_some() {
         return Err(io_err("input file uses Wasm extensions, which are not supported yet by Wasabi").into());
     }
@@ -43,7 +43,7 @@ fn test_instrument(instrument: fn(&mut Module) -> Option<String>, instrument_nam
             return;
         }
 
-        let (mut module, _offsets, _warnings) = Module::from_file(path).unwrap();
+        let mut module = Module::from_file(path).unwrap();
         let javascript = instrument(&mut module);
 
         let output_path = output_file(path, instrument_name).unwrap();
@@ -0,0 +1,95 @@
+//! Compares the memory usage (and speed) of parsing with different heuristics for pre-allocating
+//! the instructions of function bodies, see `ParseOptions::instr_capacity`.
+//! In a separate benchmark binary, since counting allocations slows down all other benchmarks.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wasabi_wasm::*;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+
+/// Tracks the currently allocated and the peak number of bytes.
+struct CountingAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+/// Peak number of bytes allocated while running `f`, on top of what was allocated before.
+fn peak_allocated_bytes(f: impl FnOnce()) -> usize {
+    let before = ALLOCATOR.current.load(Ordering::Relaxed);
+    ALLOCATOR.peak.store(before, Ordering::Relaxed);
+    f();
+    ALLOCATOR.peak.load(Ordering::Relaxed) - before
+}
+
+type InstrCapacity = fn(usize) -> usize;
+
+const INSTR_CAPACITY_HINTS: [(&str, InstrCapacity); 3] = [
+    ("capped", default_instr_capacity),
+    // `default_instr_capacity`, but without `MAX_PREALLOCATED_INSTRS`.
+    ("uncapped", |body_byte_size| body_byte_size / 2),
+    ("none", |_| 0),
+];
+
+fn bench_instr_capacity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instr_capacity");
+    group.sample_size(10);
+    let inputs = [
+        ("many_small_functions", many_small_functions_module(100_000)),
+        ("one_large_function", one_large_function_module(1_000_000)),
+    ];
+    for (input_name, bytes) in &inputs {
+        for (hint_name, instr_capacity) in INSTR_CAPACITY_HINTS {
+            // Serially, such that the peak is not affected by the scheduling of threads.
+            let options = ParseOptions { instr_capacity, parallel: false, ..ParseOptions::default() };
+            let peak = peak_allocated_bytes(|| {
+                Module::from_bytes_with_options(bytes, options).unwrap();
+            });
+            println!("instr_capacity/{hint_name}/{input_name}: peak allocated {} KiB", peak / 1024);
+
+            group.bench_with_input(BenchmarkId::new(hint_name, input_name), bytes, |b, bytes| {
+                b.iter(|| Module::from_bytes_with_options(bytes, options).unwrap())
+            });
+        }
+    }
+}
+
+fn many_small_functions_module(function_count: usize) -> Vec<u8> {
+    let functions = "(func (param i32) (result i32) local.get 0 i32.const 1 i32.add)\n".repeat(function_count);
+    ::wat::parse_str(format!("(module {functions})")).unwrap()
+}
+
+fn one_large_function_module(instr_count: usize) -> Vec<u8> {
+    // Large constants, such that there are more bytes per instruction than assumed.
+    let instrs = "i64.const 0x7fffffffffffffff drop\n".repeat(instr_count / 2);
+    ::wat::parse_str(format!("(module (func {instrs}))")).unwrap()
+}
+
+criterion_group!(benches, bench_instr_capacity);
+criterion_main!(benches);
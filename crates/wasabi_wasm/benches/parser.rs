@@ -2,23 +2,121 @@ use wasabi_wasm::*;
 
 use criterion::criterion_group;
 use criterion::criterion_main;
+use criterion::BenchmarkId;
 use criterion::Criterion;
+use criterion::Throughput;
 
-// const WASM_TEST_INPUT_LARGE: &str = "../../test-inputs/real-world/bananabread/bb.wasm";
 const WASM_TEST_INPUT_LARGE: &str =
-    "../../test-inputs/real-world/unreal-engine-4/UE4Game-HTML5-Shipping.wasm";
+    "../../test-inputs/real-world-binaries/unreal-engine-4/UE4Game-HTML5-Shipping.wasm";
+
+// Real-world binaries of increasing size (~1 MB, ~2 MB, ~40 MB) for measuring parse throughput.
+const WASM_TEST_INPUTS_THROUGHPUT: [(&str, &str); 3] = [
+    ("sql.js", "../../test-inputs/real-world-binaries/sql.js-node/sql-wasm.wasm"),
+    ("bananabread", "../../test-inputs/real-world-binaries/bananabread/bb.wasm"),
+    ("unreal-engine-4", WASM_TEST_INPUT_LARGE),
+];
 
 fn bench_parser(c: &mut Criterion) {
     let mut group = c.benchmark_group("parser");
     group.bench_function("parse", |b| {
         b.iter(|| Module::from_file(WASM_TEST_INPUT_LARGE))
     });
-    let (module, _, _) = Module::from_file(WASM_TEST_INPUT_LARGE).unwrap();
+    // Skips conversion of the function bodies, so should be much faster than `parse`.
+    let bytes = std::fs::read(WASM_TEST_INPUT_LARGE).unwrap();
+    group.bench_function("parse_headers_only", |b| {
+        b.iter(|| Module::parse_headers_only(&bytes))
+    });
+    let module = Module::from_file(WASM_TEST_INPUT_LARGE).unwrap();
     group.bench_function("encode", |b| b.iter(|| module.to_bytes()));
+
+    // Type lookups for `call_indirect` and block types happen per instruction, so make sure
+    // they stay cheap also for modules with many (non-trivial) types.
+    let call_indirect_heavy = call_indirect_heavy_module(10_000);
+    group.bench_function("parse_call_indirect_heavy", |b| {
+        b.iter(|| Module::from_bytes(&call_indirect_heavy))
+    });
+
+    // Element and data segments are converted in parallel, so this should scale with cores.
+    let segment_heavy = segment_heavy_module(50_000);
+    group.bench_function("parse_segment_heavy", |b| {
+        b.iter(|| Module::from_bytes(&segment_heavy))
+    });
+
+    // Large debug info is copied by a full parse, but only borrowed by `parse_custom_sections`.
+    let debug_info_heavy = debug_info_heavy_module(100 * 1024 * 1024);
+    group.bench_function("parse_debug_info_heavy", |b| {
+        b.iter(|| Module::from_bytes(&debug_info_heavy))
+    });
+    group.bench_function("parse_custom_sections_debug_info_heavy", |b| {
+        b.iter(|| Module::parse_custom_sections(&debug_info_heavy))
+    });
     group.sample_size(20);
 }
 
-criterion_group!(benches, bench_parser);
+fn call_indirect_heavy_module(call_count: usize) -> Vec<u8> {
+    // Many parameters, such that the type is not representable as a Gödel number.
+    let params = "i64 ".repeat(20);
+    let args = "i64.const 0 ".repeat(20);
+    let calls = format!("{args} i32.const 0 call_indirect (type $t)\n").repeat(call_count);
+    ::wat::parse_str(format!(
+        "(module
+            (type $t (func (param {params})))
+            (table 1 funcref)
+            (func {calls}))"
+    ))
+    .unwrap()
+}
+
+fn debug_info_heavy_module(debug_info_size: usize) -> Vec<u8> {
+    let mut module = Module::new();
+    module.custom_sections.push(RawCustomSection {
+        name: ".debug_info".to_string(),
+        content: vec![0; debug_info_size],
+        previous_section: None,
+    });
+    module.to_bytes().unwrap()
+}
+
+fn segment_heavy_module(segment_count: usize) -> Vec<u8> {
+    let data = (0..segment_count)
+        .map(|i| format!("(data (i32.const {}) \"{}\")", i * 64, "x".repeat(64)))
+        .collect::<String>();
+    let elements = "(elem (i32.const 0) func $f)".repeat(segment_count);
+    ::wat::parse_str(format!(
+        "(module
+            (memory 100)
+            (table 1 funcref)
+            (func $f)
+            {data}
+            {elements})"
+    ))
+    .unwrap()
+}
+
+/// Reports throughput in bytes per second, such that binaries of different sizes can be compared.
+/// Full parsing is measured with function bodies parsed in parallel and serially, and against
+/// parsing only the headers (i.e., without function bodies) as a baseline.
+fn bench_parse_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_throughput");
+    group.sample_size(10);
+    for (name, path) in WASM_TEST_INPUTS_THROUGHPUT {
+        let bytes = std::fs::read(path).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("headers_only", name), &bytes, |b, bytes| {
+            b.iter(|| Module::parse_headers_only(bytes).unwrap())
+        });
+        for parallel in [true, false] {
+            let options = ParseOptions { parallel, ..ParseOptions::default() };
+            let id = if parallel { "full_parallel" } else { "full_serial" };
+            group.bench_with_input(BenchmarkId::new(id, name), &bytes, |b, bytes| {
+                b.iter(|| Module::from_bytes_with_options(bytes, options).unwrap())
+            });
+        }
+    }
+}
+
+criterion_group!(benches, bench_parser, bench_parse_throughput);
 criterion_main!(benches);
 
 // /*
@@ -0,0 +1,14 @@
+//! Parses arbitrary bytes as a module, which must never panic or run out of memory, only return an
+//! error for invalid inputs. Run with `cargo +nightly fuzz run parse` from `crates/wasabi_wasm`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasabi_wasm::Module;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(module) = Module::from_bytes(bytes) {
+        // Whatever we could parse, we should also be able to encode again.
+        let _ = module.to_bytes();
+    }
+});
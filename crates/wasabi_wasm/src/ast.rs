@@ -1,7 +1,8 @@
 //! High-level, simple, idiomatic-Rust WebAssembly AST:
 //!  - Types are inlined instead of referenced by type idx (i.e., no manual handling of Type "pool")
-//!  - Function + Code sections are merged into one list of functions,
-//!    same for tables: Table + Element sections and memories: Memory + Data sections.
+//!  - Function + Code sections are merged into one list of functions.
+//!    Element and data segments are stored on the module, since passive (and declared) segments
+//!    do not belong to any table or memory.
 //!  - Imports and exports are part of the respective item, not stored externally and referring to
 //!    their item by index.
 //!  - Similar instructions are grouped together, for easier uniform handling, e.g., T.const
@@ -10,6 +11,7 @@
 //!    functions, and locals).
 
 use core::fmt;
+use std::collections::{BTreeMap, HashSet};
 use std::hash;
 use std::marker::PhantomData;
 use std::path::Path;
@@ -22,14 +24,22 @@ use smallvec::SmallVec;
 pub use crate::function_type::FunctionType;
 
 use crate::extensions::WasmExtension;
+use crate::types::TypeError;
+use crate::BlockStructureError;
+use crate::ConstEvalError;
 use crate::EncodeError;
 use crate::ParseError;
+use crate::parse::ParseOptions;
 use crate::ParseWarnings;
+use crate::RefError;
+use crate::RenumberError;
+use crate::stats::InstrCategory;
 
 /* Values and types. */
 
 /// A primitive WebAssembly value, e.g., an integer or floating-point number.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Val {
     I32(i32),
     I64(i64),
@@ -37,6 +47,9 @@ pub enum Val {
     // to make it possible, e.g., to put instructions in HashSets etc.
     F32(OrderedFloat<f32>),
     F64(OrderedFloat<f64>),
+    // Stored as raw little-endian bytes, since the interpretation (i8x16, i16x8, f32x4, etc.)
+    // depends on the instructions that operate on the value, not the value itself.
+    V128([u8; 16]),
 }
 
 impl Val {
@@ -47,6 +60,7 @@ impl Val {
             Val::I64(_) => ValType::I64,
             Val::F32(_) => ValType::F32,
             Val::F64(_) => ValType::F64,
+            Val::V128(_) => ValType::V128,
         }
     }
 
@@ -59,8 +73,49 @@ impl Val {
             ValType::I64 => Val::I64(str.parse().map_err(|_| ())?),
             ValType::F32 => Val::F32(str.parse().map_err(|_| ())?),
             ValType::F64 => Val::F64(str.parse().map_err(|_| ())?),
+            ValType::V128 => {
+                // Only the i8x16 shape is supported, which is also what `Display` produces.
+                let lanes = str.trim().strip_prefix("i8x16").ok_or(())?;
+                let lanes = lanes
+                    .split_whitespace()
+                    .map(|lane| lane.parse::<u8>().or_else(|_| lane.parse::<i8>().map(|i| i as u8)))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| ())?;
+                Val::V128(lanes.try_into().map_err(|_| ())?)
+            }
+            // Reference values cannot be written as a constant, only created by instructions.
+            ValType::FuncRef | ValType::ExternRef => return Err(()),
         })
     }
+
+    /// The value if it is an `i32`, e.g., a memory address or table index.
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Val::I32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Val::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Val::F32(value) => Some(value.into_inner()),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Val::F64(value) => Some(value.into_inner()),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Val {
@@ -70,6 +125,13 @@ impl fmt::Display for Val {
             Val::I64(v) => v.fmt(f),
             Val::F32(v) => v.into_inner().fmt(f),
             Val::F64(v) => v.into_inner().fmt(f),
+            Val::V128(bytes) => {
+                f.write_str("i8x16")?;
+                for byte in bytes {
+                    write!(f, " {byte}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -77,11 +139,15 @@ impl fmt::Display for Val {
 /// A WebAssembly value type, e.g., `i32` or `f64`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
 #[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub enum ValType {
     I32,
     I64,
     F32,
     F64,
+    V128,
+    FuncRef,
+    ExternRef,
 }
 
 #[test]
@@ -91,12 +157,25 @@ fn val_type_is_small() {
 
 impl ValType {
     /// Produce a zero value (e.g., 0 or 0.0) for this type.
+    ///
+    /// Panics for reference types, whose default value `ref.null` is not a `Val`.
     pub fn zero(self) -> Val {
         match self {
             ValType::I32 => Val::I32(0),
             ValType::I64 => Val::I64(0),
             ValType::F32 => Val::F32(OrderedFloat(0.0)),
             ValType::F64 => Val::F64(OrderedFloat(0.0)),
+            ValType::V128 => Val::V128([0; 16]),
+            ValType::FuncRef | ValType::ExternRef => panic!("reference type {self} has no zero value"),
+        }
+    }
+
+    /// Returns the reference type, if this is one (i.e., `funcref` or `externref`).
+    pub fn to_ref_type(self) -> Option<RefType> {
+        match self {
+            ValType::FuncRef => Some(RefType::FuncRef),
+            ValType::ExternRef => Some(RefType::ExternRef),
+            _ => None,
         }
     }
 
@@ -108,18 +187,25 @@ impl ValType {
             ValType::I64 => "i64",
             ValType::F32 => "f32",
             ValType::F64 => "f64",
+            ValType::V128 => "v128",
+            ValType::FuncRef => "funcref",
+            ValType::ExternRef => "externref",
         }
     }
 
     /// Convert to a single character, e.g., as used by Emscripten.
     /// Lowercase is for 32 bit, uppercase is for 64 bit;
-    /// `i` for integers, `f` for floats.
+    /// `i` for integers, `f` for floats, and `V` for 128-bit SIMD vectors.
+    /// Reference types are `r` for `funcref` and `e` for `externref`.
     pub fn to_char(self) -> char {
         match self {
             ValType::I32 => 'i',
             ValType::I64 => 'I',
             ValType::F32 => 'f',
             ValType::F64 => 'F',
+            ValType::V128 => 'V',
+            ValType::FuncRef => 'r',
+            ValType::ExternRef => 'e',
         }
     }
 
@@ -130,6 +216,9 @@ impl ValType {
             'I' => Some(ValType::I64),
             'f' => Some(ValType::F32),
             'F' => Some(ValType::F64),
+            'V' => Some(ValType::V128),
+            'r' => Some(ValType::FuncRef),
+            'e' => Some(ValType::ExternRef),
             _ => None,
         }
     }
@@ -150,20 +239,91 @@ impl FromStr for ValType {
             "i64" => ValType::I64,
             "f32" => ValType::F32,
             "f64" => ValType::F64,
+            "v128" => ValType::V128,
+            "funcref" => ValType::FuncRef,
+            "externref" => ValType::ExternRef,
             _ => return Err(()),
         })
     }
 }
 
+/// A WebAssembly reference type, i.e., the type of table elements and of `ref.null`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub enum RefType {
+    FuncRef,
+    ExternRef,
+}
+
+impl RefType {
+    pub fn to_str(self) -> &'static str {
+        ValType::from(self).to_str()
+    }
+
+    /// Whether a reference of this type can be used where `expected` is required, e.g., whether
+    /// an element segment of this type can initialize a table of type `expected`.
+    /// With the reference types proposal, `funcref` and `externref` are unrelated, i.e., each is
+    /// only a subtype of itself (unlike with the function references or GC proposals).
+    pub fn is_subtype_of(self, expected: RefType) -> bool {
+        self == expected
+    }
+}
+
+impl From<RefType> for ValType {
+    fn from(ty: RefType) -> Self {
+        match ty {
+            RefType::FuncRef => ValType::FuncRef,
+            RefType::ExternRef => ValType::ExternRef,
+        }
+    }
+}
+
+impl fmt::Display for RefType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for RefType {
+    type Err = ();
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        ValType::from_str(str)?.to_ref_type().ok_or(())
+    }
+}
+
 /// Limits for tables and memories.
+/// The sizes are 64-bit for 64-bit memories (see `IndexType`), but always fit in 32 bits otherwise.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Limits {
-    pub initial_size: u32,
-    pub max_size: Option<u32>,
+    pub initial_size: u64,
+    pub max_size: Option<u64>,
+}
+
+/// Type of addresses into a memory, see the memory64 proposal:
+/// https://github.com/WebAssembly/memory64
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndexType {
+    #[default]
+    I32,
+    I64,
+}
+
+impl IndexType {
+    pub fn to_val_type(self) -> ValType {
+        match self {
+            IndexType::I32 => ValType::I32,
+            IndexType::I64 => ValType::I64,
+        }
+    }
 }
 
 /// Type of global (scalar) variables.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalType(pub ValType, pub Mutability);
 
 impl fmt::Display for GlobalType {
@@ -177,6 +337,7 @@ impl fmt::Display for GlobalType {
 
 /// Mutability of global (scalar) variables.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mutability {
     Const,
     Mut,
@@ -193,12 +354,19 @@ pub enum Mutability {
 pub struct Idx<T>(u32, PhantomData<fn() -> T>);
 
 impl<T> Idx<T> {
+    pub fn new(idx: usize) -> Self {
+        idx.into()
+    }
     pub fn to_u32(self) -> u32 {
         self.0
     }
     pub fn to_usize(self) -> usize {
         self.0 as usize
     }
+    /// The following index in the same index space.
+    pub fn next(self) -> Self {
+        Idx(self.0.checked_add(1).expect("wasm32 only allows u32 indices"), PhantomData)
+    }
 }
 
 impl<T> From<u32> for Idx<T> {
@@ -230,7 +398,7 @@ impl<T> fmt::Debug for Idx<T> {
 impl<T> Clone for Idx<T> {
     #[inline]
     fn clone(&self) -> Self {
-        self.to_usize().into()
+        *self
     }
 }
 
@@ -271,6 +439,85 @@ impl<T> Serialize for Idx<T> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Idx<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Idx::from)
+    }
+}
+
+/// Iterator over a (half-open) range of indices in the same index space.
+/// (`std::ops::Range<Idx<T>>` is not an iterator, since that would require the unstable `Step`.)
+pub struct IdxRange<T> {
+    start: u32,
+    end: u32,
+    _index_space: PhantomData<fn() -> T>,
+}
+
+impl<T> IdxRange<T> {
+    pub fn new(start: Idx<T>, end: Idx<T>) -> Self {
+        IdxRange {
+            start: start.0,
+            end: end.0.max(start.0),
+            _index_space: PhantomData,
+        }
+    }
+
+    /// All indices of an index space with `len` elements, i.e., `0..len`.
+    pub fn up_to(len: usize) -> Self {
+        Self::new(Idx::from(0u32), Idx::from(len))
+    }
+}
+
+impl<T> Iterator for IdxRange<T> {
+    type Item = Idx<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start < self.end {
+            let idx = Idx::from(self.start);
+            self.start += 1;
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.start) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IdxRange<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start < self.end {
+            self.end -= 1;
+            Some(Idx::from(self.end))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IdxRange<T> {}
+
+// Manual impls for the same reason as for `Idx` (no unnecessary requirements on `T`).
+impl<T> Clone for IdxRange<T> {
+    fn clone(&self) -> Self {
+        IdxRange {
+            start: self.start,
+            end: self.end,
+            _index_space: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for IdxRange<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}..{:?}", Idx::<T>::from(self.start), Idx::<T>::from(self.end))
+    }
+}
+
 /// Similar to indices, labels are just a typed wrapper around numbers in the binary format.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Label(u32);
@@ -305,10 +552,25 @@ impl Serialize for Label {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Label {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Label::from)
+    }
+}
+
 /* Overall module structure, sections. */
 
 /// A top-level WebAssembly module.
+///
+/// Unlike in the binary format, there is no type section: Function types are stored inline, i.e.,
+/// as `FunctionType` values in `Function::type_`, and in block and `call_indirect` instructions.
+/// Since equal `FunctionType`s are always identical (see `function_type.rs`), there is nothing to
+/// deduplicate in the AST. When encoding, the type section is created on demand with one entry
+/// per distinct type (in order of first use), so identical types in a parsed binary collapse into
+/// one, and unused types are dropped (including their names in `type_names`).
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     // From the name section, if present, e.g., compiler-generated debug info.
     pub name: Option<String>,
@@ -319,10 +581,29 @@ pub struct Module {
     // TODO make these options to ensure there is only a single one of each
     pub tables: Vec<Table>,
     pub memories: Vec<Memory>,
+    // From the tag section and tag imports (exception handling proposal). Instructions that refer
+    // to tags (e.g., `throw`) are not supported yet, see `Instr::Unsupported`.
+    pub tags: Vec<Tag>,
+
+    // In the order of the element and data sections, such that segment indices (e.g., of
+    // `elem.drop` or `memory.init`) stay valid.
+    pub elements: Vec<Element>,
+    pub data: Vec<Data>,
+    // Whether to encode a data count section (bulk memory operations) even if no function body
+    // needs one (i.e., uses `memory.init` or `data.drop`), e.g., because it was in the parsed
+    // binary. The count itself is always the number of data segments in `data`.
+    pub data_count_section: bool,
 
     pub start: Option<Idx<Function>>,
 
     pub custom_sections: Vec<RawCustomSection>,
+    // Sections with an id unknown to this library (e.g., from future proposals), kept as raw bytes
+    // such that the module still roundtrips.
+    pub unknown_sections: Vec<RawUnknownSection>,
+
+    // From the name section, if present. Since function types are inlined (and deduplicated when
+    // encoding), type names are associated with the type itself instead of an index.
+    pub type_names: BTreeMap<FunctionType, String>,
 
     pub metadata: ModuleMetadata,
 }
@@ -338,16 +619,61 @@ impl Module {
         Self::default()
     }
 
+    /// Parses a module, ignoring warnings and byte offsets.
+    /// See `from_bytes_with_offsets` if you need those.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_with_offsets(bytes).map(|(module, _offsets, _warnings)| module)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        Self::from_file_with_offsets(path).map(|(module, _offsets, _warnings)| module)
+    }
+
     // TODO Generify this to work for any R: io::Read.
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
-        crate::parse::parse_module(bytes)
+    pub fn from_bytes_with_offsets(bytes: &[u8]) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        crate::parse::parse_module(bytes, ParseOptions::default())
+    }
+
+    /// Like `from_bytes_with_offsets`, but with explicit `options`, e.g., to parse function
+    /// bodies serially or in a caller-provided thread pool, see `ParseOptions`.
+    pub fn from_bytes_with_options(bytes: &[u8], options: ParseOptions) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        crate::parse::parse_module(bytes, options)
     }
 
-    pub fn from_file(path: impl AsRef<Path>) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+    pub fn from_file_with_offsets(path: impl AsRef<Path>) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
         let bytes = std::fs::read(path)?;
+        Self::from_bytes_with_offsets(&bytes)
+    }
+
+    /// Parses a module from the WebAssembly text format, e.g., for writing small test modules.
+    /// The text is first assembled to a binary, which is then parsed as usual.
+    #[cfg(feature = "wat")]
+    pub fn from_wat(text: &str) -> Result<Self, ParseError> {
+        let bytes = crate::parse::wat_to_bytes(text)?;
         Self::from_bytes(&bytes)
     }
 
+    /// Parses only types, imports, exports, and the declarations of functions, tables, memories,
+    /// and globals, which is much faster than a full parse for modules with a large code section.
+    /// Function bodies, data segments, and custom sections are skipped without converting them.
+    pub fn parse_headers_only(bytes: &[u8]) -> Result<ModuleHeaders, ParseError> {
+        crate::parse::parse_module_headers(bytes)
+    }
+
+    /// Returns only the custom sections of the binary, without copying their content (unlike
+    /// `RawCustomSection::content` after a full parse), e.g., to read large debug info sections.
+    /// All other sections are skipped without converting them.
+    pub fn parse_custom_sections(bytes: &[u8]) -> Result<Vec<CustomSectionRef<'_>>, ParseError> {
+        crate::parse::parse_custom_sections(bytes)
+    }
+
+    /// Parses a module incrementally from `reader`, e.g., for very large binaries.
+    /// See `SectionEvent` for what is yielded. Unlike `from_bytes_with_offsets`, function bodies are not
+    /// parsed in parallel, but only a single one needs to be held in memory at a time.
+    pub fn parse_streaming<R: std::io::Read>(reader: R) -> impl Iterator<Item = Result<SectionEvent, ParseError>> {
+        crate::parse::parse_module_streaming(reader)
+    }
+
     // TODO Some standard version that prints warnings?
     // pub fn from_file_with_offsets_wasmparser(path: impl AsRef<Path>) -> Result<(Self, Offsets), Box<dyn std::error::Error>> {
     //     let bytes = std::fs::read(path)?;
@@ -375,13 +701,200 @@ impl Module {
         std::fs::write(path, bytes)?;
         Ok(len)
     }
+
+    /// Compares two modules only by their semantics, e.g., for tests after a roundtrip through the
+    /// text format or an instrumentation, where `==` is too strict.
+    ///
+    /// Compared are all functions (types, locals, instructions, imports, and exports), globals,
+    /// tables, memories, tags, element and data segments, the data count, the start function, and the
+    /// names and contents of custom (and unknown) sections.
+    /// Not compared are all names from the name section (of the module, functions, parameters,
+    /// locals, labels, types, globals, tables, memories, and segments), the position of custom
+    /// (and unknown) sections relative to other sections, and the `metadata` (used extensions, section order).
+    ///
+    /// This clones both modules, so it is not meant for performance-sensitive code.
+    pub fn structurally_eq(&self, other: &Module) -> bool {
+        fn normalize(module: &Module) -> Module {
+            let mut module = module.clone();
+            module.clear_names();
+            for custom_section in &mut module.custom_sections {
+                custom_section.previous_section = None;
+            }
+            module.custom_sections.sort_by(|a, b| (&a.name, &a.content).cmp(&(&b.name, &b.content)));
+            for unknown_section in &mut module.unknown_sections {
+                unknown_section.previous_section = None;
+            }
+            module.unknown_sections.sort_by(|a, b| (a.id, &a.content).cmp(&(b.id, &b.content)));
+            module.metadata = ModuleMetadata::default();
+            module
+        }
+        normalize(self) == normalize(other)
+    }
+
+    /// Removes the first custom section with the given name and returns it, or `None` if there is
+    /// no such section.
+    ///
+    /// The name section is not a `RawCustomSection` (unless it could not be parsed), use
+    /// `strip_names` to remove it.
+    /// A custom section that directly followed the removed one is moved to the position of the
+    /// removed section, such that the order of the remaining sections is preserved when encoding.
+    pub fn remove_custom_section(&mut self, name: &str) -> Option<RawCustomSection> {
+        let index = self.custom_sections.iter().position(|custom| custom.name == name)?;
+        Some(self.remove_custom_section_at(index))
+    }
+
+    /// DWARF debug info, i.e., the names and contents of all custom sections whose name starts
+    /// with `.debug_` (e.g., `.debug_info` or `.debug_line`), in the order of the binary.
+    ///
+    /// The bytes are not modified when encoding. Note that DWARF refers to byte offsets in the code
+    /// section, so it may become stale when function bodies are modified (e.g., by instrumentation).
+    pub fn debug_sections(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.custom_sections.iter()
+            .filter(|custom| custom.name.starts_with(".debug_"))
+            .map(|custom| (custom.name.as_str(), custom.content.as_slice()))
+    }
+
+    /// Whether the module has DWARF debug info, see `debug_sections`.
+    pub fn has_debug_info(&self) -> bool {
+        self.debug_sections().next().is_some()
+    }
+
+    /// Parses the first `producers` custom section (if any), see `Producers`.
+    ///
+    /// The section itself stays a `RawCustomSection`, such that it keeps its position in the
+    /// binary and is encoded unchanged unless modified, e.g., with `add_producer`.
+    pub fn producers(&self) -> Option<Result<Producers, ParseError>> {
+        self.custom_sections.iter()
+            .find(|custom| custom.name == "producers")
+            .map(|custom| Producers::from_bytes(&custom.content))
+    }
+
+    /// Adds a tool (or language or SDK, depending on `field`) to the `producers` section, as
+    /// tools that process a module are expected to do, e.g.,
+    /// `module.add_producer("processed-by", "wasabi", env!("CARGO_PKG_VERSION"))`.
+    /// See `Producers::add` for how existing entries are updated.
+    ///
+    /// If there is no `producers` section yet, one is added after all other sections. A malformed
+    /// existing `producers` section is replaced.
+    pub fn add_producer(&mut self, field: &str, name: &str, version: &str) {
+        let index = match self.custom_sections.iter().position(|custom| custom.name == "producers") {
+            Some(index) => index,
+            None => {
+                self.custom_sections.push(RawCustomSection {
+                    name: "producers".to_string(),
+                    content: Vec::new(),
+                    // Like the name section, place it after the data section if it is new.
+                    previous_section: Some(SectionId::Data),
+                });
+                self.custom_sections.len() - 1
+            }
+        };
+        let custom = &mut self.custom_sections[index];
+        let mut producers = Producers::from_bytes(&custom.content).unwrap_or_default();
+        producers.add(field, name, version);
+        custom.content = producers.to_bytes();
+    }
+
+    /// Keeps only the custom sections for whose name `pred` returns true.
+    ///
+    /// Like `remove_custom_section`, this keeps the relative order of the remaining sections.
+    pub fn retain_custom_sections(&mut self, pred: impl Fn(&str) -> bool) {
+        let mut index = 0;
+        while index < self.custom_sections.len() {
+            if pred(&self.custom_sections[index].name) {
+                index += 1;
+            } else {
+                self.remove_custom_section_at(index);
+            }
+        }
+    }
+
+    fn remove_custom_section_at(&mut self, index: usize) -> RawCustomSection {
+        let removed = self.custom_sections.remove(index);
+        let removed_id = SectionId::Custom(removed.name.clone());
+        if let Some(next) = self.custom_sections.get_mut(index) {
+            if next.previous_section.as_ref() == Some(&removed_id) {
+                next.previous_section = removed.previous_section.clone();
+            }
+        }
+        for unknown in &mut self.unknown_sections {
+            if unknown.previous_section.as_ref() == Some(&removed_id) {
+                unknown.previous_section = removed.previous_section.clone();
+            }
+        }
+        self.metadata.remove_section(&removed_id);
+        removed
+    }
+
+    /// Removes all names (of the module, functions, parameters, locals, labels, types, globals,
+    /// tables, memories, and segments), such that no name section is encoded.
+    ///
+    /// Custom sections that directly followed the name section take its position instead.
+    pub fn strip_names(&mut self) {
+        self.clear_names();
+        let name_id = SectionId::Custom("name".to_string());
+        let section_order = self.metadata.section_order();
+        if let Some(position) = section_order.iter().position(|section| *section == name_id) {
+            let previous_section = position.checked_sub(1).map(|previous| section_order[previous].clone());
+            for custom in &mut self.custom_sections {
+                if custom.previous_section.as_ref() == Some(&name_id) {
+                    custom.previous_section = previous_section.clone();
+                }
+            }
+            for unknown in &mut self.unknown_sections {
+                if unknown.previous_section.as_ref() == Some(&name_id) {
+                    unknown.previous_section = previous_section.clone();
+                }
+            }
+            self.metadata.remove_section(&name_id);
+        }
+    }
+
+    /// Removes all names that were (partially) parsed from the name section.
+    pub(crate) fn clear_names(&mut self) {
+        self.name = None;
+        self.type_names.clear();
+        for function in &mut self.functions {
+            function.name = None;
+            function.param_names.clear();
+            if let Some(code) = function.code_mut() {
+                code.locals.iter_mut().for_each(|local| local.name = None);
+                code.label_names.clear();
+            }
+        }
+        self.tables.iter_mut().for_each(|table| table.name = None);
+        self.memories.iter_mut().for_each(|memory| memory.name = None);
+        self.globals.iter_mut().for_each(|global| global.name = None);
+        self.elements.iter_mut().for_each(|element| element.name = None);
+        self.data.iter_mut().for_each(|data| data.name = None);
+    }
 }
 
-#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleMetadata {
     used_extensions: Vec<WasmExtension>,
+    // Order of all sections in the parsed binary (including custom sections), such that encoding
+    // can reproduce it, e.g., keep empty sections and the position of the name section.
     // TODO
     // original_section_offsets: SectionOffsets
+    section_order: Vec<SectionId>,
+}
+
+// The section order is only a detail of the binary representation (like `Offsets`), so two
+// modules that differ only in it are still considered equal.
+impl PartialEq for ModuleMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        self.used_extensions == other.used_extensions
+    }
+}
+
+impl Eq for ModuleMetadata {}
+
+impl hash::Hash for ModuleMetadata {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.used_extensions.hash(state);
+    }
 }
 
 impl ModuleMetadata {
@@ -394,15 +907,43 @@ impl ModuleMetadata {
     pub fn used_extensions(&self) -> impl Iterator<Item = WasmExtension> + '_ {
         self.used_extensions.iter().copied()
     }
+
+    /// Sections in the order of the original binary, empty if the module was not parsed.
+    pub fn section_order(&self) -> &[SectionId] {
+        &self.section_order
+    }
+
+    pub fn set_section_order(&mut self, section_order: Vec<SectionId>) {
+        self.section_order = section_order;
+    }
+
+    /// Removes the first occurrence of `section` from the section order.
+    fn remove_section(&mut self, section: &SectionId) {
+        if let Some(position) = self.section_order.iter().position(|s| s == section) {
+            self.section_order.remove(position);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImportOrPresent<T> {
     Import(String, String),
     Present(T),
 }
 
+/// Which kind of entity is imported, see `Module::imports()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImportKind {
+    Function(Idx<Function>),
+    Global(Idx<Global>),
+    Table(Idx<Table>),
+    Memory(Idx<Memory>),
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     // Type is inlined here compared to low-level/binary/spec representation.
     pub type_: FunctionType,
@@ -421,40 +962,78 @@ pub struct Function {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Global {
     pub type_: GlobalType,
     pub init: ImportOrPresent<Expr>,
     pub export: Vec<String>,
-    // TODO name from name section.
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Table {
     pub limits: Limits,
-    // Unlike functions and globals, an imported table can still be initialized with elements.
+    pub ref_type: RefType,
+    // Unlike functions and globals, an imported table can still be initialized with elements,
+    // see `Module::elements` and `ElementMode::Active`.
     pub import: Option<(String, String)>,
-    pub elements: Vec<Element>,
     pub export: Vec<String>,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memory {
     pub limits: Limits,
-    // Unlike functions and globals, an imported memory can still be initialized with data elements.
+    /// `I64` for 64-bit memories, where addresses, offsets, and sizes are `i64`.
+    pub index_type: IndexType,
+    /// Shared memories can be accessed concurrently by multiple threads (threads proposal).
+    pub shared: bool,
+    // Unlike functions and globals, an imported memory can still be initialized with data elements,
+    // see `Module::data` and `DataMode::Active`.
+    pub import: Option<(String, String)>,
+    pub export: Vec<String>,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
+}
+
+/// An exception tag (exception handling proposal), which `throw` and `catch` refer to.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tag {
+    /// The inputs are the values carried by the exception, the results are always empty.
+    pub type_: FunctionType,
     pub import: Option<(String, String)>,
-    pub data: Vec<Data>,
     pub export: Vec<String>,
 }
 
 // TODO rename: Body, and CodeOrImport -> BodyOrImport
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Code {
     pub locals: Vec<Local>,
     // TODO rename to instrs
     pub body: Expr,
+    // From the name section, if present. Keys are label indices in the order in which
+    // `block`, `loop`, and `if` instructions appear in the body.
+    pub label_names: BTreeMap<u32, String>,
+}
+
+/// The instruction a branch label refers to, see `Code::resolve_label`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LabelTarget {
+    /// Index of the targeted `loop`, i.e., the branch continues at the start of the loop body.
+    Loop(usize),
+    /// Index of the `end` of the targeted `block` or `if` (or of the function body for the
+    /// outermost label), i.e., the branch continues after it.
+    End(usize),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Local {
     pub type_: ValType,
     // From the name section, if present, e.g., compiler-generated debug info.
@@ -482,28 +1061,74 @@ pub struct ParamRef<'a> {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Element {
-    pub offset: Expr,
-    pub functions: Vec<Idx<Function>>,
+    pub mode: ElementMode,
+    pub ref_type: RefType,
+    pub items: ElementItems,
+    // From the name section, if present.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementMode {
+    /// Initializes `table` at instantiation time, starting at `offset`.
+    Active { table: Idx<Table>, offset: Expr },
+    /// Only used by `table.init` (bulk memory operations).
+    Passive,
+    /// Only declares functions for `ref.func`, never copied into a table (reference types).
+    Declared,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementItems {
+    /// The MVP representation, which only allows (non-null) function references.
+    Functions(Vec<Idx<Function>>),
+    /// One constant expression per item, e.g., `ref.func` or `ref.null` (reference types).
+    Expressions(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Data {
-    pub offset: Expr,
+    pub mode: DataMode,
     pub bytes: Vec<u8>,
+    // From the name section, if present.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataMode {
+    /// Initializes `memory` at instantiation time, starting at `offset`.
+    Active { memory: Idx<Memory>, offset: Expr },
+    /// Only used by `memory.init` (bulk memory operations).
+    Passive,
 }
 
 /// Metainformation how low-level sections and function bodies map to byte offsets in the binary.
 // TODO Attach either directly to functions/sections or to the module (but rather the former, otherwise it can get easily lost).
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Offsets {
     /// Section offsets point to the beginning of the content of a section, i.e., after the size.
+    /// In the order of the binary, i.e., sorted by offset.
     pub sections: Vec<(SectionId, usize)>,
     /// Code offsets are only present for non-imported function, and also point to after the size
     /// in the code element (similar to section offsets).
+    /// Sorted by function index, and thus also by offset, because bodies appear in index order.
     pub functions_code: Vec<(Idx<Function>, usize)>,
+    /// Offsets of all instructions of non-imported functions, followed by the offset just after
+    /// the function body. Only present if parsed with `ParseOptions::instr_offsets`.
+    /// Sorted by function index, and the offsets of each function are sorted as well.
+    pub functions_instrs: Vec<(Idx<Function>, Vec<usize>)>,
 }
 
+// The lookups below are binary searches that rely on the sort order documented above, which holds
+// for all `Offsets` returned by the parser. If you modify the fields manually, keep them sorted.
+
 impl Offsets {
     /// Returns the offsets for all sections with the given id.
     pub fn section_offsets(&self, section: SectionId) -> Vec<usize> {
@@ -515,27 +1140,211 @@ impl Offsets {
             .collect()
     }
 
-    /// Returns the (original) function index with the  given offset of its code (if any).
-    pub fn function_offset_to_idx(&self, code_offset: usize) -> Option<Idx<Function>> {
-        self.functions_code
+    /// Returns the offset of the first section with the given id (if any).
+    /// Only custom sections can appear more than once, see `section_offsets()` for those.
+    // A module has at most a few dozen sections, so a linear scan is fine here.
+    pub fn section_offset(&self, section: &SectionId) -> Option<usize> {
+        self.sections
             .iter()
-            .cloned()
-            .find_map(|(func, offset)|
-                if offset == code_offset { Some(func) } else { None })
+            .find_map(|(sec, offset)|
+                if sec == section { Some(*offset) } else { None })
+    }
+
+    /// Returns the section that contains `byte_offset` and the offset of its content (if any).
+    /// The id and size of a section belong to the preceding section.
+    pub fn section_at(&self, byte_offset: usize) -> Option<(&SectionId, usize)> {
+        let position = self.sections
+            .partition_point(|(_, offset)| *offset <= byte_offset)
+            .checked_sub(1)?;
+        let (section, offset) = &self.sections[position];
+        Some((section, *offset))
     }
 
     /// Returns the code offset of the (original) function with the given index (if any).
     pub fn function_idx_to_offset(&self, idx: Idx<Function>) -> Option<usize> {
-        self.functions_code
+        let position = self.functions_code
+            .binary_search_by_key(&idx, |(func, _)| *func)
+            .ok()?;
+        Some(self.functions_code[position].1)
+    }
+
+    /// Returns the (original) function index with the  given offset of its code (if any).
+    pub fn function_offset_to_idx(&self, code_offset: usize) -> Option<Idx<Function>> {
+        let position = self.functions_code
+            .binary_search_by_key(&code_offset, |(_, offset)| *offset)
+            .ok()?;
+        Some(self.functions_code[position].0)
+    }
+
+    /// Returns the (original) function index whose body contains `byte_offset` (e.g., the program
+    /// counter of a trap or a coverage counter), or `None` if it is before the first body.
+    /// Offsets after the last body are not detected, i.e., attributed to the last function.
+    pub fn function_at(&self, byte_offset: usize) -> Option<Idx<Function>> {
+        let position = self.functions_code
+            .partition_point(|(_, offset)| *offset <= byte_offset)
+            .checked_sub(1)?;
+        Some(self.functions_code[position].0)
+    }
+
+    /// Returns the index of the instruction in the body of function `func` that contains
+    /// `byte_offset` (e.g., the program counter of a trap), or `None` if the offset is outside of
+    /// the body or instruction offsets were not recorded, see `ParseOptions::instr_offsets`.
+    pub fn instr_at(&self, func: Idx<Function>, byte_offset: usize) -> Option<usize> {
+        let position = self.functions_instrs
+            .binary_search_by_key(&func, |(f, _)| *f)
+            .ok()?;
+        let (_, instr_offsets) = &self.functions_instrs[position];
+        let (body_end, instr_starts) = instr_offsets.split_last()?;
+        if byte_offset >= *body_end {
+            return None;
+        }
+        instr_starts
+            .partition_point(|start| *start <= byte_offset)
+            .checked_sub(1)
+    }
+
+    /// Breaks down the size of the parsed binary (of length `binary_len`) into its sections and
+    /// function bodies, e.g., to find out what makes a module large.
+    ///
+    /// The offsets only point to the beginning of section contents and function bodies, so their
+    /// ends are computed backwards from `binary_len`, assuming sizes are minimally LEB128 encoded.
+    pub fn size_profile(&self, binary_len: usize) -> SizeProfile {
+        fn leb128_len(mut value: usize) -> usize {
+            let mut len = 1;
+            while value >= 0x80 {
+                value >>= 7;
+                len += 1;
+            }
+            len
+        }
+
+        let mut sections = Vec::with_capacity(self.sections.len());
+        let mut code_section_end = binary_len;
+        let mut section_end = binary_len;
+        for (section, content_start) in self.sections.iter().rev() {
+            let content_size = section_end.saturating_sub(*content_start);
+            // Section id byte and size of the content.
+            let header_size = 1 + leb128_len(content_size);
+            if *section == SectionId::Code {
+                code_section_end = section_end;
+            }
+            sections.push((section.clone(), header_size + content_size));
+            section_end = content_start.saturating_sub(header_size);
+        }
+        sections.reverse();
+
+        let mut functions = Vec::with_capacity(self.functions_code.len());
+        let mut body_end = code_section_end;
+        for (func, body_start) in self.functions_code.iter().rev() {
+            let body_size = body_end.saturating_sub(*body_start);
+            let size_size = leb128_len(body_size);
+            functions.push((*func, size_size + body_size));
+            body_end = body_start.saturating_sub(size_size);
+        }
+        functions.reverse();
+
+        SizeProfile {
+            header: section_end,
+            sections,
+            functions,
+        }
+    }
+}
+
+/// Result of `Module::remove_unreachable_functions`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RemovedFunctions {
+    /// Indices of the removed functions _before_ the removal, sorted.
+    pub functions: Vec<Idx<Function>>,
+    /// Total number of instructions in the bodies of the removed functions.
+    pub instr_count: usize,
+}
+
+impl RemovedFunctions {
+    /// Total size in bytes of the bodies of the removed functions in the original binary, given
+    /// its `SizeProfile` (see `Offsets::size_profile`), i.e., roughly how much smaller the encoded
+    /// module is. (The entries of the function and name sections are saved on top of that.)
+    pub fn byte_size(&self, size_profile: &SizeProfile) -> usize {
+        size_profile
+            .functions
             .iter()
-            .cloned()
-            .find_map(|(func, offset)|
-                if func == idx { Some(offset) } else { None })
+            .filter(|(func, _)| self.functions.binary_search(func).is_ok())
+            .map(|(_, size)| size)
+            .sum()
+    }
+}
+
+/// Sizes in bytes of the parts of a binary module, see `Offsets::size_profile`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeProfile {
+    /// Everything before the first section, i.e., the magic number and version.
+    pub header: usize,
+    /// All sections in the order of the binary, including their id and size.
+    pub sections: Vec<(SectionId, usize)>,
+    /// Bodies of all non-imported functions, including their size. The remaining bytes of the
+    /// code section (its id, size, and number of bodies) are not attributed to any function.
+    pub functions: Vec<(Idx<Function>, usize)>,
+}
+
+impl SizeProfile {
+    /// Size of the whole binary.
+    pub fn total(&self) -> usize {
+        self.header + self.sections.iter().map(|(_, size)| size).sum::<usize>()
     }
+
+    /// Returns the size of the first section with the given id (if any).
+    pub fn section_size(&self, section: &SectionId) -> Option<usize> {
+        self.sections
+            .iter()
+            .find_map(|(sec, size)|
+                if sec == section { Some(*size) } else { None })
+    }
+}
+
+/// Produced by `Module::parse_streaming`, in the order of the sections in the binary.
+#[derive(Debug)]
+// `End` is large, but only produced once, so boxing the module is not worth it.
+#[allow(clippy::large_enum_variant)]
+pub enum SectionEvent {
+    /// A section (other than a function body) was parsed and added to the module, which is
+    /// returned at the end. The offset points to the beginning of the section content.
+    Section { id: SectionId, offset: usize },
+    /// A function body from the code section. Its instructions are NOT added to the module, so
+    /// that the caller decides whether to keep them (or instead process and throw them away).
+    /// The locals are kept in the module, since their names come only later in the name section.
+    FunctionBody {
+        idx: Idx<Function>,
+        offset: usize,
+        type_: FunctionType,
+        code: Code,
+    },
+    /// The module is complete. Non-imported functions have no instructions, see `FunctionBody`.
+    End {
+        module: Module,
+        offsets: Offsets,
+        warnings: ParseWarnings,
+    },
+}
+
+/// The imports, exports, and types of a module, without function bodies, see
+/// `Module::parse_headers_only`.
+/// Like in `Module`, imports and exports are stored in the imported or exported entities.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ModuleHeaders {
+    /// All entries of the type section, in order (unlike `Module`, where types are inlined).
+    pub types: Vec<FunctionType>,
+    /// Non-imported functions have an empty body, since the code section is not parsed.
+    pub functions: Vec<Function>,
+    pub tables: Vec<Table>,
+    pub memories: Vec<Memory>,
+    pub globals: Vec<Global>,
+    pub start: Option<Idx<Function>>,
 }
 
 /// A not-yet-parsed custom section.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawCustomSection {
     pub name: String,
     pub content: Vec<u8>,
@@ -545,9 +1354,82 @@ pub struct RawCustomSection {
     pub previous_section: Option<SectionId>,
 }
 
+/// A custom section whose name and content are borrowed from the binary instead of copied,
+/// see `Module::parse_custom_sections`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CustomSectionRef<'a> {
+    pub name: &'a str,
+    pub content: &'a [u8],
+    /// Byte offset of `content` in the binary.
+    pub offset: usize,
+}
+
+/// The contents of a `producers` custom section, i.e., the languages, tools, and SDKs that
+/// produced a module, see https://github.com/WebAssembly/tool-conventions/blob/main/ProducersSection.md
+/// and `Module::producers`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Producers {
+    pub fields: Vec<ProducersField>,
+}
+
+/// A field of the `producers` section, e.g., `language`, `processed-by`, or `sdk`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProducersField {
+    pub name: String,
+    pub values: Vec<ProducerValue>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProducerValue {
+    pub name: String,
+    pub version: String,
+}
+
+impl Producers {
+    /// Parses the content of a `producers` custom section.
+    pub fn from_bytes(content: &[u8]) -> Result<Self, ParseError> {
+        crate::parse::parse_producers(content)
+    }
+
+    /// Encodes the content of a `producers` custom section (without section id, size, and name).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::encode::encode_producers(self)
+    }
+
+    /// Adds the value `name` with `version` to `field`, or updates its version if the field
+    /// already has a value with that name. Missing fields are appended.
+    pub fn add(&mut self, field: &str, name: &str, version: &str) {
+        let field = match self.fields.iter().position(|existing| existing.name == field) {
+            Some(index) => &mut self.fields[index],
+            None => {
+                self.fields.push(ProducersField { name: field.to_string(), values: Vec::new() });
+                self.fields.last_mut().expect("just pushed")
+            }
+        };
+        match field.values.iter_mut().find(|value| value.name == name) {
+            Some(value) => value.version = version.to_string(),
+            None => field.values.push(ProducerValue { name: name.to_string(), version: version.to_string() }),
+        }
+    }
+}
+
+/// A section with an id that is not known to this library, e.g., from a future proposal.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawUnknownSection {
+    pub id: u8,
+    pub content: Vec<u8>,
+    /// The section that came _before_ this section, like `RawCustomSection::previous_section`.
+    pub previous_section: Option<SectionId>,
+}
+
 /// Marker for the different sections in a wasm module,
 /// used for ordering (custom) sections during serialization.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SectionId {
     // Order is important! Follows the ordering of sections in the binary format
     // (except for custom sections, which can appear anywhere).
@@ -557,13 +1439,18 @@ pub enum SectionId {
     Function,
     Table,
     Memory,
+    /// Exception handling proposal.
+    Tag,
     Global,
     Export,
     Start,
     Element,
+    DataCount,
     Code,
     Data,
     Custom(String),
+    /// See `RawUnknownSection`.
+    Unknown(u8),
 }
 
 /* Code. */
@@ -571,6 +1458,7 @@ pub enum SectionId {
 pub type Expr = Vec<Instr>;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memarg {
     /// The alignment of load/stores is just a hint for the VM that says "the effective address of
     /// this load/store should be aligned to <alignment>".
@@ -590,7 +1478,8 @@ pub struct Memarg {
     /// and https://webassembly.github.io/spec/core/text/instructions.html#memory-instructions.
     pub alignment_exp: u8,
 
-    pub offset: u32,
+    /// Only larger than `u32::MAX` for accesses of 64-bit memories.
+    pub offset: u64,
 }
 
 impl Memarg {
@@ -609,9 +1498,22 @@ impl Memarg {
         2u32.pow(self.alignment_exp as u32)
     }
 
+    /// Whether the alignment hint is exactly the natural alignment of `op` (i.e., the access size,
+    /// see `MemoryOp::natural_alignment_exp`), which is the default in the text format.
+    pub fn is_natural_alignment(self, op: impl MemoryOp) -> bool {
+        self.alignment_exp == op.natural_alignment_exp()
+    }
+
+    /// Whether the alignment hint is larger than the natural alignment of `op`, which makes the
+    /// module invalid, see https://webassembly.github.io/spec/core/valid/instructions.html#memory-instructions
+    /// Smaller alignments are valid, but may indicate unaligned (and thus slower) accesses.
+    pub fn is_over_aligned(self, op: impl MemoryOp) -> bool {
+        self.alignment_exp > op.natural_alignment_exp()
+    }
+
     /// Formats non-default fields, depends on natural alignment of `op`.
     pub fn fmt(&self, f: &mut fmt::Formatter<'_>, op: impl MemoryOp) -> fmt::Result {
-        let is_natural_alignment = self.alignment_exp == op.natural_alignment_exp();
+        let is_natural_alignment = self.is_natural_alignment(op);
         match (self.offset, is_natural_alignment) {
             (0, true) => Ok(()),
             (0, false) => write!(f, "align={}", self.alignment()),
@@ -670,14 +1572,21 @@ fn instr_size_should_not_be_too_large() {
     assert_eq!(std::mem::size_of::<Idx<Function>>(), 4);
     assert_eq!(std::mem::size_of::<Label>(), 4);
 
-    assert_eq!(std::mem::size_of::<Memarg>(), 8);
+    assert_eq!(std::mem::size_of::<Memarg>(), 16);
 
     // These are pretty large, but the only way to get it smaller is to store things out-of-line.
-    assert_eq!(std::mem::size_of::<Val>(), 16);
-    assert_eq!(std::mem::size_of::<Instr>(), 24);
+    // `Val` is dominated by the 16 bytes of `Val::V128` plus the tag (padded to the alignment of
+    // `i64`), and `Instr::Const` embeds a `Val`, which grows `Instr` from 24 to 32 bytes.
+    // We accept that instead of boxing the `v128` payload, because `Val` must stay `Copy` (it is
+    // passed around by value everywhere, e.g., in `Instr::as_const` and constant evaluation), and
+    // a `Box` would also cost an allocation and indirection per `v128.const`. If this assertion
+    // fails, check the memory usage of parsing with `benches/instr_capacity.rs` before adjusting it.
+    assert_eq!(std::mem::size_of::<Val>(), 24);
+    assert_eq!(std::mem::size_of::<Instr>(), 32);
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instr {
     // TODO: See below on `Block` for a plan on how to get rid of unreachable code.
     Unreachable,
@@ -713,13 +1622,19 @@ pub enum Instr {
     Call(Idx<Function>),
     // TODO: remove Idx<Table>, always 0 in MVP.
     CallIndirect(FunctionType, Idx<Table>),
+    // Tail calls proposal: like `call` and `call_indirect`, but return from the current function
+    // with the callee's results, i.e., they are followed by dead code like `return`.
+    ReturnCall(Idx<Function>),
+    ReturnCallIndirect(FunctionType, Idx<Table>),
 
     // TODO: Include the type explicitly in the instruction to remove
     // value-polymorphism.
     // However, this would require type checking during lowlevel parsing :(
     Drop,
     // TODO: Replace with `If([ty, ty] -> [ty], ...)
-    Select,
+    // The type is only present for the typed `select t` instruction of the reference types
+    // proposal, the untyped `select` is value-polymorphic like `drop`.
+    Select(Option<ValType>),
 
     // TODO: Get rid of all locals by using block params and results only + a pick or copy
     // instruction, that copies the nth value on the stack to the top.
@@ -734,12 +1649,53 @@ pub enum Instr {
     MemorySize(Idx<Memory>),
     MemoryGrow(Idx<Memory>),
 
+    // Bulk memory operations.
+    // The memory and table indices are always 0 without the multi-memory and reference types
+    // extensions, so they are not printed (like for `memory.size`).
+    MemoryInit(Idx<Data>, Idx<Memory>),
+    DataDrop(Idx<Data>),
+    MemoryCopy { dst: Idx<Memory>, src: Idx<Memory> },
+    MemoryFill(Idx<Memory>),
+    TableInit(Idx<Element>, Idx<Table>),
+    ElemDrop(Idx<Element>),
+    TableCopy { dst: Idx<Table>, src: Idx<Table> },
+
+    // Threads and atomics proposal. Like regular loads and stores, these implicitly use memory 0.
+    Atomic(AtomicOp, Memarg),
+    AtomicFence,
+
     Const(Val),
     Unary(UnaryOp),
     Binary(BinaryOp),
+
+    // Reference types instructions.
+    RefNull(RefType),
+    RefIsNull,
+    RefFunc(Idx<Function>),
+
+    // SIMD instructions that do not fit into the groups above, because they have additional
+    // immediate arguments (lane indices) or take three inputs.
+    // Lane indices are checked when parsing (from binary or text), but not when constructing
+    // instructions directly, so they must be smaller than the `lane_count()` of the op (or 32
+    // for shuffles), otherwise the encoded module is invalid.
+    LoadLane(LoadLaneOp, Memarg, u8),
+    StoreLane(StoreLaneOp, Memarg, u8),
+    Lane(LaneOp, u8),
+    I8x16Shuffle([u8; 16]),
+    V128Bitselect,
+
+    /// Placeholder for the raw bytes of a function body (without locals and the final `end`),
+    /// which contains instructions that are not supported by this library.
+    /// Only produced when parsing with `OnUnsupported::Placeholder`, see `ParseOptions`.
+    Unparsed(Vec<u8>),
+    /// Placeholder for a single instruction of an unsupported extension (e.g., `try` or `throw`
+    /// from exception handling), with its raw bytes (opcode and immediates).
+    /// Only produced when parsing with `OnUnsupported::PlaceholderInstrs`, see `ParseOptions`.
+    Unsupported(WasmExtension, Vec<u8>),
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LocalOp {
     Get,
     Set,
@@ -757,6 +1713,7 @@ impl LocalOp {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GlobalOp {
     Get,
     Set,
@@ -772,6 +1729,7 @@ impl GlobalOp {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LoadOp {
     I32Load,
     I64Load,
@@ -789,9 +1747,24 @@ pub enum LoadOp {
     I64Load16U,
     I64Load32S,
     I64Load32U,
+
+    V128Load,
+    V128Load8x8S,
+    V128Load8x8U,
+    V128Load16x4S,
+    V128Load16x4U,
+    V128Load32x2S,
+    V128Load32x2U,
+    V128Load8Splat,
+    V128Load16Splat,
+    V128Load32Splat,
+    V128Load64Splat,
+    V128Load32Zero,
+    V128Load64Zero,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StoreOp {
     I32Store,
     I64Store,
@@ -804,6 +1777,8 @@ pub enum StoreOp {
     I64Store8,
     I64Store16,
     I64Store32,
+
+    V128Store,
 }
 
 /// Common trait for `LoadOp` and `StoreOp`.
@@ -838,6 +1813,20 @@ impl MemoryOp for LoadOp {
             I64Load16U => "i64.load16_u",
             I64Load32S => "i64.load32_s",
             I64Load32U => "i64.load32_u",
+
+            V128Load => "v128.load",
+            V128Load8x8S => "v128.load8x8_s",
+            V128Load8x8U => "v128.load8x8_u",
+            V128Load16x4S => "v128.load16x4_s",
+            V128Load16x4U => "v128.load16x4_u",
+            V128Load32x2S => "v128.load32x2_s",
+            V128Load32x2U => "v128.load32x2_u",
+            V128Load8Splat => "v128.load8_splat",
+            V128Load16Splat => "v128.load16_splat",
+            V128Load32Splat => "v128.load32_splat",
+            V128Load64Splat => "v128.load64_splat",
+            V128Load32Zero => "v128.load32_zero",
+            V128Load64Zero => "v128.load64_zero",
         }
     }
 
@@ -860,6 +1849,10 @@ impl MemoryOp for LoadOp {
             I64Load16U => FunctionType::new(&[I32], &[I64]),
             I64Load32S => FunctionType::new(&[I32], &[I64]),
             I64Load32U => FunctionType::new(&[I32], &[I64]),
+
+            V128Load | V128Load8x8S | V128Load8x8U | V128Load16x4S | V128Load16x4U | V128Load32x2S | V128Load32x2U
+            | V128Load8Splat | V128Load16Splat | V128Load32Splat | V128Load64Splat
+            | V128Load32Zero | V128Load64Zero => FunctionType::new(&[I32], &[V128]),
         }
     }
 
@@ -881,119 +1874,864 @@ impl MemoryOp for LoadOp {
             I64Load16U => 1,
             I64Load32S => 2,
             I64Load32U => 2,
+
+            V128Load => 4,
+            V128Load8x8S => 3,
+            V128Load8x8U => 3,
+            V128Load16x4S => 3,
+            V128Load16x4U => 3,
+            V128Load32x2S => 3,
+            V128Load32x2U => 3,
+            V128Load8Splat => 0,
+            V128Load16Splat => 1,
+            V128Load32Splat => 2,
+            V128Load64Splat => 3,
+            V128Load32Zero => 2,
+            V128Load64Zero => 3,
+        }
+    }
+}
+
+impl MemoryOp for StoreOp {
+    fn to_name(self) -> &'static str {
+        use StoreOp::*;
+        match self {
+            I32Store => "i32.store",
+            I64Store => "i64.store",
+            F32Store => "f32.store",
+            F64Store => "f64.store",
+
+            I32Store8 => "i32.store8",
+            I32Store16 => "i32.store16",
+            I64Store8 => "i64.store8",
+            I64Store16 => "i64.store16",
+            I64Store32 => "i64.store32",
+
+            V128Store => "v128.store",
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use StoreOp::*;
+        use ValType::*;
+        match self {
+            I32Store => FunctionType::new(&[I32, I32], &[]),
+            I64Store => FunctionType::new(&[I32, I64], &[]),
+            F32Store => FunctionType::new(&[I32, F32], &[]),
+            F64Store => FunctionType::new(&[I32, F64], &[]),
+
+            I32Store8 => FunctionType::new(&[I32, I32], &[]),
+            I32Store16 => FunctionType::new(&[I32, I32], &[]),
+            I64Store8 => FunctionType::new(&[I32, I64], &[]),
+            I64Store16 => FunctionType::new(&[I32, I64], &[]),
+            I64Store32 => FunctionType::new(&[I32, I64], &[]),
+
+            V128Store => FunctionType::new(&[I32, V128], &[]),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use StoreOp::*;
+        match self {
+            I32Store => 2,
+            I64Store => 3,
+            F32Store => 2,
+            F64Store => 3,
+
+            I32Store8 => 0,
+            I32Store16 => 1,
+            I64Store8 => 0,
+            I64Store16 => 1,
+            I64Store32 => 2,
+
+            V128Store => 4,
+        }
+    }
+}
+
+impl fmt::Display for LoadOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl fmt::Display for StoreOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for LoadOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use LoadOp::*;
+        Ok(match s {
+            "i32.load" => I32Load,
+            "i64.load" => I64Load,
+            "f32.load" => F32Load,
+            "f64.load" => F64Load,
+            "i32.load8_s" => I32Load8S,
+            "i32.load8_u" => I32Load8U,
+            "i32.load16_s" => I32Load16S,
+            "i32.load16_u" => I32Load16U,
+            "i64.load8_s" => I64Load8S,
+            "i64.load8_u" => I64Load8U,
+            "i64.load16_s" => I64Load16S,
+            "i64.load16_u" => I64Load16U,
+            "i64.load32_s" => I64Load32S,
+            "i64.load32_u" => I64Load32U,
+            "v128.load" => V128Load,
+            "v128.load8x8_s" => V128Load8x8S,
+            "v128.load8x8_u" => V128Load8x8U,
+            "v128.load16x4_s" => V128Load16x4S,
+            "v128.load16x4_u" => V128Load16x4U,
+            "v128.load32x2_s" => V128Load32x2S,
+            "v128.load32x2_u" => V128Load32x2U,
+            "v128.load8_splat" => V128Load8Splat,
+            "v128.load16_splat" => V128Load16Splat,
+            "v128.load32_splat" => V128Load32Splat,
+            "v128.load64_splat" => V128Load64Splat,
+            "v128.load32_zero" => V128Load32Zero,
+            "v128.load64_zero" => V128Load64Zero,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl FromStr for StoreOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use StoreOp::*;
+        Ok(match s {
+            "i32.store" => I32Store,
+            "i64.store" => I64Store,
+            "f32.store" => F32Store,
+            "f64.store" => F64Store,
+            "i32.store8" => I32Store8,
+            "i32.store16" => I32Store16,
+            "i64.store8" => I64Store8,
+            "i64.store16" => I64Store16,
+            "i64.store32" => I64Store32,
+            "v128.store" => V128Store,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Atomic memory instructions of the threads proposal (except for `atomic.fence`, see `Instr::AtomicFence`).
+/// All of them take a `Memarg` immediate, see `Instr::Atomic`.
+/// https://github.com/WebAssembly/threads/blob/main/proposals/threads/Overview.md
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AtomicOp {
+    MemoryAtomicNotify,
+    MemoryAtomicWait32,
+    MemoryAtomicWait64,
+
+    I32AtomicLoad,
+    I64AtomicLoad,
+    I32AtomicLoad8U,
+    I32AtomicLoad16U,
+    I64AtomicLoad8U,
+    I64AtomicLoad16U,
+    I64AtomicLoad32U,
+
+    I32AtomicStore,
+    I64AtomicStore,
+    I32AtomicStore8,
+    I32AtomicStore16,
+    I64AtomicStore8,
+    I64AtomicStore16,
+    I64AtomicStore32,
+
+    I32AtomicRmwAdd,
+    I64AtomicRmwAdd,
+    I32AtomicRmw8AddU,
+    I32AtomicRmw16AddU,
+    I64AtomicRmw8AddU,
+    I64AtomicRmw16AddU,
+    I64AtomicRmw32AddU,
+
+    I32AtomicRmwSub,
+    I64AtomicRmwSub,
+    I32AtomicRmw8SubU,
+    I32AtomicRmw16SubU,
+    I64AtomicRmw8SubU,
+    I64AtomicRmw16SubU,
+    I64AtomicRmw32SubU,
+
+    I32AtomicRmwAnd,
+    I64AtomicRmwAnd,
+    I32AtomicRmw8AndU,
+    I32AtomicRmw16AndU,
+    I64AtomicRmw8AndU,
+    I64AtomicRmw16AndU,
+    I64AtomicRmw32AndU,
+
+    I32AtomicRmwOr,
+    I64AtomicRmwOr,
+    I32AtomicRmw8OrU,
+    I32AtomicRmw16OrU,
+    I64AtomicRmw8OrU,
+    I64AtomicRmw16OrU,
+    I64AtomicRmw32OrU,
+
+    I32AtomicRmwXor,
+    I64AtomicRmwXor,
+    I32AtomicRmw8XorU,
+    I32AtomicRmw16XorU,
+    I64AtomicRmw8XorU,
+    I64AtomicRmw16XorU,
+    I64AtomicRmw32XorU,
+
+    I32AtomicRmwXchg,
+    I64AtomicRmwXchg,
+    I32AtomicRmw8XchgU,
+    I32AtomicRmw16XchgU,
+    I64AtomicRmw8XchgU,
+    I64AtomicRmw16XchgU,
+    I64AtomicRmw32XchgU,
+
+    I32AtomicRmwCmpxchg,
+    I64AtomicRmwCmpxchg,
+    I32AtomicRmw8CmpxchgU,
+    I32AtomicRmw16CmpxchgU,
+    I64AtomicRmw8CmpxchgU,
+    I64AtomicRmw16CmpxchgU,
+    I64AtomicRmw32CmpxchgU,
+}
+
+impl AtomicOp {
+    /// `memory.atomic.wait32`, `memory.atomic.wait64`, and `memory.atomic.notify`, which suspend
+    /// or wake up threads (e.g., to implement locks), unlike the other atomic loads, stores,
+    /// and read-modify-write operations.
+    pub fn is_wait_or_notify(self) -> bool {
+        use AtomicOp::*;
+        matches!(self, MemoryAtomicNotify | MemoryAtomicWait32 | MemoryAtomicWait64)
+    }
+}
+
+impl MemoryOp for AtomicOp {
+    fn to_name(self) -> &'static str {
+        use AtomicOp::*;
+        match self {
+            MemoryAtomicNotify => "memory.atomic.notify",
+            MemoryAtomicWait32 => "memory.atomic.wait32",
+            MemoryAtomicWait64 => "memory.atomic.wait64",
+
+            I32AtomicLoad => "i32.atomic.load",
+            I64AtomicLoad => "i64.atomic.load",
+            I32AtomicLoad8U => "i32.atomic.load8_u",
+            I32AtomicLoad16U => "i32.atomic.load16_u",
+            I64AtomicLoad8U => "i64.atomic.load8_u",
+            I64AtomicLoad16U => "i64.atomic.load16_u",
+            I64AtomicLoad32U => "i64.atomic.load32_u",
+
+            I32AtomicStore => "i32.atomic.store",
+            I64AtomicStore => "i64.atomic.store",
+            I32AtomicStore8 => "i32.atomic.store8",
+            I32AtomicStore16 => "i32.atomic.store16",
+            I64AtomicStore8 => "i64.atomic.store8",
+            I64AtomicStore16 => "i64.atomic.store16",
+            I64AtomicStore32 => "i64.atomic.store32",
+
+            I32AtomicRmwAdd => "i32.atomic.rmw.add",
+            I64AtomicRmwAdd => "i64.atomic.rmw.add",
+            I32AtomicRmw8AddU => "i32.atomic.rmw8.add_u",
+            I32AtomicRmw16AddU => "i32.atomic.rmw16.add_u",
+            I64AtomicRmw8AddU => "i64.atomic.rmw8.add_u",
+            I64AtomicRmw16AddU => "i64.atomic.rmw16.add_u",
+            I64AtomicRmw32AddU => "i64.atomic.rmw32.add_u",
+
+            I32AtomicRmwSub => "i32.atomic.rmw.sub",
+            I64AtomicRmwSub => "i64.atomic.rmw.sub",
+            I32AtomicRmw8SubU => "i32.atomic.rmw8.sub_u",
+            I32AtomicRmw16SubU => "i32.atomic.rmw16.sub_u",
+            I64AtomicRmw8SubU => "i64.atomic.rmw8.sub_u",
+            I64AtomicRmw16SubU => "i64.atomic.rmw16.sub_u",
+            I64AtomicRmw32SubU => "i64.atomic.rmw32.sub_u",
+
+            I32AtomicRmwAnd => "i32.atomic.rmw.and",
+            I64AtomicRmwAnd => "i64.atomic.rmw.and",
+            I32AtomicRmw8AndU => "i32.atomic.rmw8.and_u",
+            I32AtomicRmw16AndU => "i32.atomic.rmw16.and_u",
+            I64AtomicRmw8AndU => "i64.atomic.rmw8.and_u",
+            I64AtomicRmw16AndU => "i64.atomic.rmw16.and_u",
+            I64AtomicRmw32AndU => "i64.atomic.rmw32.and_u",
+
+            I32AtomicRmwOr => "i32.atomic.rmw.or",
+            I64AtomicRmwOr => "i64.atomic.rmw.or",
+            I32AtomicRmw8OrU => "i32.atomic.rmw8.or_u",
+            I32AtomicRmw16OrU => "i32.atomic.rmw16.or_u",
+            I64AtomicRmw8OrU => "i64.atomic.rmw8.or_u",
+            I64AtomicRmw16OrU => "i64.atomic.rmw16.or_u",
+            I64AtomicRmw32OrU => "i64.atomic.rmw32.or_u",
+
+            I32AtomicRmwXor => "i32.atomic.rmw.xor",
+            I64AtomicRmwXor => "i64.atomic.rmw.xor",
+            I32AtomicRmw8XorU => "i32.atomic.rmw8.xor_u",
+            I32AtomicRmw16XorU => "i32.atomic.rmw16.xor_u",
+            I64AtomicRmw8XorU => "i64.atomic.rmw8.xor_u",
+            I64AtomicRmw16XorU => "i64.atomic.rmw16.xor_u",
+            I64AtomicRmw32XorU => "i64.atomic.rmw32.xor_u",
+
+            I32AtomicRmwXchg => "i32.atomic.rmw.xchg",
+            I64AtomicRmwXchg => "i64.atomic.rmw.xchg",
+            I32AtomicRmw8XchgU => "i32.atomic.rmw8.xchg_u",
+            I32AtomicRmw16XchgU => "i32.atomic.rmw16.xchg_u",
+            I64AtomicRmw8XchgU => "i64.atomic.rmw8.xchg_u",
+            I64AtomicRmw16XchgU => "i64.atomic.rmw16.xchg_u",
+            I64AtomicRmw32XchgU => "i64.atomic.rmw32.xchg_u",
+
+            I32AtomicRmwCmpxchg => "i32.atomic.rmw.cmpxchg",
+            I64AtomicRmwCmpxchg => "i64.atomic.rmw.cmpxchg",
+            I32AtomicRmw8CmpxchgU => "i32.atomic.rmw8.cmpxchg_u",
+            I32AtomicRmw16CmpxchgU => "i32.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw8CmpxchgU => "i64.atomic.rmw8.cmpxchg_u",
+            I64AtomicRmw16CmpxchgU => "i64.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw32CmpxchgU => "i64.atomic.rmw32.cmpxchg_u",
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use AtomicOp::*;
+        use ValType::*;
+        match self {
+            MemoryAtomicNotify => FunctionType::new(&[I32, I32], &[I32]),
+            MemoryAtomicWait32 => FunctionType::new(&[I32, I32, I64], &[I32]),
+            MemoryAtomicWait64 => FunctionType::new(&[I32, I64, I64], &[I32]),
+
+            I32AtomicLoad => FunctionType::new(&[I32], &[I32]),
+            I64AtomicLoad => FunctionType::new(&[I32], &[I64]),
+            I32AtomicLoad8U => FunctionType::new(&[I32], &[I32]),
+            I32AtomicLoad16U => FunctionType::new(&[I32], &[I32]),
+            I64AtomicLoad8U => FunctionType::new(&[I32], &[I64]),
+            I64AtomicLoad16U => FunctionType::new(&[I32], &[I64]),
+            I64AtomicLoad32U => FunctionType::new(&[I32], &[I64]),
+
+            I32AtomicStore => FunctionType::new(&[I32, I32], &[]),
+            I64AtomicStore => FunctionType::new(&[I32, I64], &[]),
+            I32AtomicStore8 => FunctionType::new(&[I32, I32], &[]),
+            I32AtomicStore16 => FunctionType::new(&[I32, I32], &[]),
+            I64AtomicStore8 => FunctionType::new(&[I32, I64], &[]),
+            I64AtomicStore16 => FunctionType::new(&[I32, I64], &[]),
+            I64AtomicStore32 => FunctionType::new(&[I32, I64], &[]),
+
+            I32AtomicRmwAdd => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwAdd => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8AddU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16AddU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8AddU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16AddU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32AddU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwSub => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwSub => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8SubU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16SubU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8SubU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16SubU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32SubU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwAnd => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwAnd => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8AndU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16AndU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8AndU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16AndU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32AndU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwOr => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwOr => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8OrU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16OrU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8OrU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16OrU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32OrU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwXor => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwXor => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8XorU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16XorU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8XorU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16XorU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32XorU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwXchg => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwXchg => FunctionType::new(&[I32, I64], &[I64]),
+            I32AtomicRmw8XchgU => FunctionType::new(&[I32, I32], &[I32]),
+            I32AtomicRmw16XchgU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmw8XchgU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw16XchgU => FunctionType::new(&[I32, I64], &[I64]),
+            I64AtomicRmw32XchgU => FunctionType::new(&[I32, I64], &[I64]),
+
+            I32AtomicRmwCmpxchg => FunctionType::new(&[I32, I32, I32], &[I32]),
+            I64AtomicRmwCmpxchg => FunctionType::new(&[I32, I64, I64], &[I64]),
+            I32AtomicRmw8CmpxchgU => FunctionType::new(&[I32, I32, I32], &[I32]),
+            I32AtomicRmw16CmpxchgU => FunctionType::new(&[I32, I32, I32], &[I32]),
+            I64AtomicRmw8CmpxchgU => FunctionType::new(&[I32, I64, I64], &[I64]),
+            I64AtomicRmw16CmpxchgU => FunctionType::new(&[I32, I64, I64], &[I64]),
+            I64AtomicRmw32CmpxchgU => FunctionType::new(&[I32, I64, I64], &[I64]),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicOp::*;
+        match self {
+            MemoryAtomicNotify => 2,
+            MemoryAtomicWait32 => 2,
+            MemoryAtomicWait64 => 3,
+
+            I32AtomicLoad => 2,
+            I64AtomicLoad => 3,
+            I32AtomicLoad8U => 0,
+            I32AtomicLoad16U => 1,
+            I64AtomicLoad8U => 0,
+            I64AtomicLoad16U => 1,
+            I64AtomicLoad32U => 2,
+
+            I32AtomicStore => 2,
+            I64AtomicStore => 3,
+            I32AtomicStore8 => 0,
+            I32AtomicStore16 => 1,
+            I64AtomicStore8 => 0,
+            I64AtomicStore16 => 1,
+            I64AtomicStore32 => 2,
+
+            I32AtomicRmwAdd => 2,
+            I64AtomicRmwAdd => 3,
+            I32AtomicRmw8AddU => 0,
+            I32AtomicRmw16AddU => 1,
+            I64AtomicRmw8AddU => 0,
+            I64AtomicRmw16AddU => 1,
+            I64AtomicRmw32AddU => 2,
+
+            I32AtomicRmwSub => 2,
+            I64AtomicRmwSub => 3,
+            I32AtomicRmw8SubU => 0,
+            I32AtomicRmw16SubU => 1,
+            I64AtomicRmw8SubU => 0,
+            I64AtomicRmw16SubU => 1,
+            I64AtomicRmw32SubU => 2,
+
+            I32AtomicRmwAnd => 2,
+            I64AtomicRmwAnd => 3,
+            I32AtomicRmw8AndU => 0,
+            I32AtomicRmw16AndU => 1,
+            I64AtomicRmw8AndU => 0,
+            I64AtomicRmw16AndU => 1,
+            I64AtomicRmw32AndU => 2,
+
+            I32AtomicRmwOr => 2,
+            I64AtomicRmwOr => 3,
+            I32AtomicRmw8OrU => 0,
+            I32AtomicRmw16OrU => 1,
+            I64AtomicRmw8OrU => 0,
+            I64AtomicRmw16OrU => 1,
+            I64AtomicRmw32OrU => 2,
+
+            I32AtomicRmwXor => 2,
+            I64AtomicRmwXor => 3,
+            I32AtomicRmw8XorU => 0,
+            I32AtomicRmw16XorU => 1,
+            I64AtomicRmw8XorU => 0,
+            I64AtomicRmw16XorU => 1,
+            I64AtomicRmw32XorU => 2,
+
+            I32AtomicRmwXchg => 2,
+            I64AtomicRmwXchg => 3,
+            I32AtomicRmw8XchgU => 0,
+            I32AtomicRmw16XchgU => 1,
+            I64AtomicRmw8XchgU => 0,
+            I64AtomicRmw16XchgU => 1,
+            I64AtomicRmw32XchgU => 2,
+
+            I32AtomicRmwCmpxchg => 2,
+            I64AtomicRmwCmpxchg => 3,
+            I32AtomicRmw8CmpxchgU => 0,
+            I32AtomicRmw16CmpxchgU => 1,
+            I64AtomicRmw8CmpxchgU => 0,
+            I64AtomicRmw16CmpxchgU => 1,
+            I64AtomicRmw32CmpxchgU => 2,
+        }
+    }
+}
+
+impl fmt::Display for AtomicOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicOp::*;
+        Ok(match s {
+            "memory.atomic.notify" => MemoryAtomicNotify,
+            "memory.atomic.wait32" => MemoryAtomicWait32,
+            "memory.atomic.wait64" => MemoryAtomicWait64,
+            "i32.atomic.load" => I32AtomicLoad,
+            "i64.atomic.load" => I64AtomicLoad,
+            "i32.atomic.load8_u" => I32AtomicLoad8U,
+            "i32.atomic.load16_u" => I32AtomicLoad16U,
+            "i64.atomic.load8_u" => I64AtomicLoad8U,
+            "i64.atomic.load16_u" => I64AtomicLoad16U,
+            "i64.atomic.load32_u" => I64AtomicLoad32U,
+            "i32.atomic.store" => I32AtomicStore,
+            "i64.atomic.store" => I64AtomicStore,
+            "i32.atomic.store8" => I32AtomicStore8,
+            "i32.atomic.store16" => I32AtomicStore16,
+            "i64.atomic.store8" => I64AtomicStore8,
+            "i64.atomic.store16" => I64AtomicStore16,
+            "i64.atomic.store32" => I64AtomicStore32,
+            "i32.atomic.rmw.add" => I32AtomicRmwAdd,
+            "i64.atomic.rmw.add" => I64AtomicRmwAdd,
+            "i32.atomic.rmw8.add_u" => I32AtomicRmw8AddU,
+            "i32.atomic.rmw16.add_u" => I32AtomicRmw16AddU,
+            "i64.atomic.rmw8.add_u" => I64AtomicRmw8AddU,
+            "i64.atomic.rmw16.add_u" => I64AtomicRmw16AddU,
+            "i64.atomic.rmw32.add_u" => I64AtomicRmw32AddU,
+            "i32.atomic.rmw.sub" => I32AtomicRmwSub,
+            "i64.atomic.rmw.sub" => I64AtomicRmwSub,
+            "i32.atomic.rmw8.sub_u" => I32AtomicRmw8SubU,
+            "i32.atomic.rmw16.sub_u" => I32AtomicRmw16SubU,
+            "i64.atomic.rmw8.sub_u" => I64AtomicRmw8SubU,
+            "i64.atomic.rmw16.sub_u" => I64AtomicRmw16SubU,
+            "i64.atomic.rmw32.sub_u" => I64AtomicRmw32SubU,
+            "i32.atomic.rmw.and" => I32AtomicRmwAnd,
+            "i64.atomic.rmw.and" => I64AtomicRmwAnd,
+            "i32.atomic.rmw8.and_u" => I32AtomicRmw8AndU,
+            "i32.atomic.rmw16.and_u" => I32AtomicRmw16AndU,
+            "i64.atomic.rmw8.and_u" => I64AtomicRmw8AndU,
+            "i64.atomic.rmw16.and_u" => I64AtomicRmw16AndU,
+            "i64.atomic.rmw32.and_u" => I64AtomicRmw32AndU,
+            "i32.atomic.rmw.or" => I32AtomicRmwOr,
+            "i64.atomic.rmw.or" => I64AtomicRmwOr,
+            "i32.atomic.rmw8.or_u" => I32AtomicRmw8OrU,
+            "i32.atomic.rmw16.or_u" => I32AtomicRmw16OrU,
+            "i64.atomic.rmw8.or_u" => I64AtomicRmw8OrU,
+            "i64.atomic.rmw16.or_u" => I64AtomicRmw16OrU,
+            "i64.atomic.rmw32.or_u" => I64AtomicRmw32OrU,
+            "i32.atomic.rmw.xor" => I32AtomicRmwXor,
+            "i64.atomic.rmw.xor" => I64AtomicRmwXor,
+            "i32.atomic.rmw8.xor_u" => I32AtomicRmw8XorU,
+            "i32.atomic.rmw16.xor_u" => I32AtomicRmw16XorU,
+            "i64.atomic.rmw8.xor_u" => I64AtomicRmw8XorU,
+            "i64.atomic.rmw16.xor_u" => I64AtomicRmw16XorU,
+            "i64.atomic.rmw32.xor_u" => I64AtomicRmw32XorU,
+            "i32.atomic.rmw.xchg" => I32AtomicRmwXchg,
+            "i64.atomic.rmw.xchg" => I64AtomicRmwXchg,
+            "i32.atomic.rmw8.xchg_u" => I32AtomicRmw8XchgU,
+            "i32.atomic.rmw16.xchg_u" => I32AtomicRmw16XchgU,
+            "i64.atomic.rmw8.xchg_u" => I64AtomicRmw8XchgU,
+            "i64.atomic.rmw16.xchg_u" => I64AtomicRmw16XchgU,
+            "i64.atomic.rmw32.xchg_u" => I64AtomicRmw32XchgU,
+            "i32.atomic.rmw.cmpxchg" => I32AtomicRmwCmpxchg,
+            "i64.atomic.rmw.cmpxchg" => I64AtomicRmwCmpxchg,
+            "i32.atomic.rmw8.cmpxchg_u" => I32AtomicRmw8CmpxchgU,
+            "i32.atomic.rmw16.cmpxchg_u" => I32AtomicRmw16CmpxchgU,
+            "i64.atomic.rmw8.cmpxchg_u" => I64AtomicRmw8CmpxchgU,
+            "i64.atomic.rmw16.cmpxchg_u" => I64AtomicRmw16CmpxchgU,
+            "i64.atomic.rmw32.cmpxchg_u" => I64AtomicRmw32CmpxchgU,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// SIMD loads that replace a single lane of an existing v128 value.
+/// The lane index is stored separately in the instruction, see `Instr::LoadLane`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoadLaneOp {
+    V128Load8Lane,
+    V128Load16Lane,
+    V128Load32Lane,
+    V128Load64Lane,
+}
+
+/// SIMD stores of a single lane of a v128 value.
+/// The lane index is stored separately in the instruction, see `Instr::StoreLane`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StoreLaneOp {
+    V128Store8Lane,
+    V128Store16Lane,
+    V128Store32Lane,
+    V128Store64Lane,
+}
+
+impl MemoryOp for LoadLaneOp {
+    fn to_name(self) -> &'static str {
+        use LoadLaneOp::*;
+        match self {
+            V128Load8Lane => "v128.load8_lane",
+            V128Load16Lane => "v128.load16_lane",
+            V128Load32Lane => "v128.load32_lane",
+            V128Load64Lane => "v128.load64_lane",
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use ValType::*;
+        FunctionType::new(&[I32, V128], &[V128])
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use LoadLaneOp::*;
+        match self {
+            V128Load8Lane => 0,
+            V128Load16Lane => 1,
+            V128Load32Lane => 2,
+            V128Load64Lane => 3,
         }
     }
 }
 
-impl MemoryOp for StoreOp {
+impl MemoryOp for StoreLaneOp {
     fn to_name(self) -> &'static str {
-        use StoreOp::*;
+        use StoreLaneOp::*;
         match self {
-            I32Store => "i32.store",
-            I64Store => "i64.store",
-            F32Store => "f32.store",
-            F64Store => "f64.store",
-
-            I32Store8 => "i32.store8",
-            I32Store16 => "i32.store16",
-            I64Store8 => "i64.store8",
-            I64Store16 => "i64.store16",
-            I64Store32 => "i64.store32",
+            V128Store8Lane => "v128.store8_lane",
+            V128Store16Lane => "v128.store16_lane",
+            V128Store32Lane => "v128.store32_lane",
+            V128Store64Lane => "v128.store64_lane",
         }
     }
 
     fn to_type(self) -> FunctionType {
-        use StoreOp::*;
         use ValType::*;
-        match self {
-            I32Store => FunctionType::new(&[I32, I32], &[]),
-            I64Store => FunctionType::new(&[I32, I64], &[]),
-            F32Store => FunctionType::new(&[I32, F32], &[]),
-            F64Store => FunctionType::new(&[I32, F64], &[]),
-
-            I32Store8 => FunctionType::new(&[I32, I32], &[]),
-            I32Store16 => FunctionType::new(&[I32, I32], &[]),
-            I64Store8 => FunctionType::new(&[I32, I64], &[]),
-            I64Store16 => FunctionType::new(&[I32, I64], &[]),
-            I64Store32 => FunctionType::new(&[I32, I64], &[]),
-        }
+        FunctionType::new(&[I32, V128], &[])
     }
 
     fn natural_alignment_exp(self) -> u8 {
-        use StoreOp::*;
+        use StoreLaneOp::*;
         match self {
-            I32Store => 2,
-            I64Store => 3,
-            F32Store => 2,
-            F64Store => 3,
-
-            I32Store8 => 0,
-            I32Store16 => 1,
-            I64Store8 => 0,
-            I64Store16 => 1,
-            I64Store32 => 2,
+            V128Store8Lane => 0,
+            V128Store16Lane => 1,
+            V128Store32Lane => 2,
+            V128Store64Lane => 3,
         }
     }
 }
 
-impl fmt::Display for LoadOp {
+impl LoadLaneOp {
+    /// Number of lanes of the accessed v128 value, i.e., the exclusive upper bound of the lane index.
+    pub fn lane_count(self) -> u8 {
+        16 >> self.natural_alignment_exp()
+    }
+}
+
+impl StoreLaneOp {
+    /// Number of lanes of the accessed v128 value, i.e., the exclusive upper bound of the lane index.
+    pub fn lane_count(self) -> u8 {
+        16 >> self.natural_alignment_exp()
+    }
+}
+
+impl fmt::Display for LoadLaneOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.to_name())
     }
 }
 
-impl fmt::Display for StoreOp {
+impl fmt::Display for StoreLaneOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.to_name())
     }
 }
 
-impl FromStr for LoadOp {
+/// The operation of any instruction with a `Memarg`, see `Instr::memory_access`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryAccessOp {
+    Load(LoadOp),
+    Store(StoreOp),
+    Atomic(AtomicOp),
+    LoadLane(LoadLaneOp),
+    StoreLane(StoreLaneOp),
+}
+
+impl MemoryOp for MemoryAccessOp {
+    fn to_name(self) -> &'static str {
+        use MemoryAccessOp::*;
+        match self {
+            Load(op) => op.to_name(),
+            Store(op) => op.to_name(),
+            Atomic(op) => op.to_name(),
+            LoadLane(op) => op.to_name(),
+            StoreLane(op) => op.to_name(),
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use MemoryAccessOp::*;
+        match self {
+            Load(op) => op.to_type(),
+            Store(op) => op.to_type(),
+            Atomic(op) => op.to_type(),
+            LoadLane(op) => op.to_type(),
+            StoreLane(op) => op.to_type(),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use MemoryAccessOp::*;
+        match self {
+            Load(op) => op.natural_alignment_exp(),
+            Store(op) => op.natural_alignment_exp(),
+            Atomic(op) => op.natural_alignment_exp(),
+            LoadLane(op) => op.natural_alignment_exp(),
+            StoreLane(op) => op.natural_alignment_exp(),
+        }
+    }
+}
+
+/// A memory access in a function body, see `Module::memory_accesses`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MemAccess {
+    pub func: Idx<Function>,
+    /// Index of the instruction in the body of `func`.
+    pub instr_idx: usize,
+    pub op: MemoryAccessOp,
+    pub memarg: Memarg,
+}
+
+impl FromStr for LoadLaneOp {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use LoadOp::*;
+        use LoadLaneOp::*;
         Ok(match s {
-            "i32.load" => I32Load,
-            "i64.load" => I64Load,
-            "f32.load" => F32Load,
-            "f64.load" => F64Load,
-            "i32.load8_s" => I32Load8S,
-            "i32.load8_u" => I32Load8U,
-            "i32.load16_s" => I32Load16S,
-            "i32.load16_u" => I32Load16U,
-            "i64.load8_s" => I64Load8S,
-            "i64.load8_u" => I64Load8U,
-            "i64.load16_s" => I64Load16S,
-            "i64.load16_u" => I64Load16U,
-            "i64.load32_s" => I64Load32S,
-            "i64.load32_u" => I64Load32U,
+            "v128.load8_lane" => V128Load8Lane,
+            "v128.load16_lane" => V128Load16Lane,
+            "v128.load32_lane" => V128Load32Lane,
+            "v128.load64_lane" => V128Load64Lane,
             _ => return Err(()),
         })
     }
 }
 
-impl FromStr for StoreOp {
+impl FromStr for StoreLaneOp {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use StoreOp::*;
+        use StoreLaneOp::*;
         Ok(match s {
-            "i32.store" => I32Store,
-            "i64.store" => I64Store,
-            "f32.store" => F32Store,
-            "f64.store" => F64Store,
-            "i32.store8" => I32Store8,
-            "i32.store16" => I32Store16,
-            "i64.store8" => I64Store8,
-            "i64.store16" => I64Store16,
-            "i64.store32" => I64Store32,
+            "v128.store8_lane" => V128Store8Lane,
+            "v128.store16_lane" => V128Store16Lane,
+            "v128.store32_lane" => V128Store32Lane,
+            "v128.store64_lane" => V128Store64Lane,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// SIMD instructions that extract or replace a single lane of a v128 value.
+/// The lane index is stored separately in the instruction, see `Instr::Lane`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LaneOp {
+    I8x16ExtractLaneS,
+    I8x16ExtractLaneU,
+    I8x16ReplaceLane,
+    I16x8ExtractLaneS,
+    I16x8ExtractLaneU,
+    I16x8ReplaceLane,
+    I32x4ExtractLane,
+    I32x4ReplaceLane,
+    I64x2ExtractLane,
+    I64x2ReplaceLane,
+    F32x4ExtractLane,
+    F32x4ReplaceLane,
+    F64x2ExtractLane,
+    F64x2ReplaceLane,
+}
+
+impl LaneOp {
+    pub fn to_name(self) -> &'static str {
+        use LaneOp::*;
+        match self {
+            I8x16ExtractLaneS => "i8x16.extract_lane_s",
+            I8x16ExtractLaneU => "i8x16.extract_lane_u",
+            I8x16ReplaceLane => "i8x16.replace_lane",
+            I16x8ExtractLaneS => "i16x8.extract_lane_s",
+            I16x8ExtractLaneU => "i16x8.extract_lane_u",
+            I16x8ReplaceLane => "i16x8.replace_lane",
+            I32x4ExtractLane => "i32x4.extract_lane",
+            I32x4ReplaceLane => "i32x4.replace_lane",
+            I64x2ExtractLane => "i64x2.extract_lane",
+            I64x2ReplaceLane => "i64x2.replace_lane",
+            F32x4ExtractLane => "f32x4.extract_lane",
+            F32x4ReplaceLane => "f32x4.replace_lane",
+            F64x2ExtractLane => "f64x2.extract_lane",
+            F64x2ReplaceLane => "f64x2.replace_lane",
+        }
+    }
+
+    pub fn to_type(self) -> FunctionType {
+        use LaneOp::*;
+        use ValType::*;
+        match self {
+            I8x16ExtractLaneS | I8x16ExtractLaneU | I16x8ExtractLaneS | I16x8ExtractLaneU | I32x4ExtractLane => FunctionType::new(&[V128], &[I32]),
+            I8x16ReplaceLane | I16x8ReplaceLane | I32x4ReplaceLane => FunctionType::new(&[V128, I32], &[V128]),
+            I64x2ExtractLane => FunctionType::new(&[V128], &[I64]),
+            I64x2ReplaceLane => FunctionType::new(&[V128, I64], &[V128]),
+            F32x4ExtractLane => FunctionType::new(&[V128], &[F32]),
+            F32x4ReplaceLane => FunctionType::new(&[V128, F32], &[V128]),
+            F64x2ExtractLane => FunctionType::new(&[V128], &[F64]),
+            F64x2ReplaceLane => FunctionType::new(&[V128, F64], &[V128]),
+        }
+    }
+
+    /// Number of lanes of the v128 shape, i.e., the exclusive upper bound of the lane index.
+    pub fn lane_count(self) -> u8 {
+        use LaneOp::*;
+        match self {
+            I8x16ExtractLaneS | I8x16ExtractLaneU | I8x16ReplaceLane => 16,
+            I16x8ExtractLaneS | I16x8ExtractLaneU | I16x8ReplaceLane => 8,
+            I32x4ExtractLane | I32x4ReplaceLane | F32x4ExtractLane | F32x4ReplaceLane => 4,
+            I64x2ExtractLane | I64x2ReplaceLane | F64x2ExtractLane | F64x2ReplaceLane => 2,
+        }
+    }
+}
+
+impl fmt::Display for LaneOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for LaneOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use LaneOp::*;
+        Ok(match s {
+            "i8x16.extract_lane_s" => I8x16ExtractLaneS,
+            "i8x16.extract_lane_u" => I8x16ExtractLaneU,
+            "i8x16.replace_lane" => I8x16ReplaceLane,
+            "i16x8.extract_lane_s" => I16x8ExtractLaneS,
+            "i16x8.extract_lane_u" => I16x8ExtractLaneU,
+            "i16x8.replace_lane" => I16x8ReplaceLane,
+            "i32x4.extract_lane" => I32x4ExtractLane,
+            "i32x4.replace_lane" => I32x4ReplaceLane,
+            "i64x2.extract_lane" => I64x2ExtractLane,
+            "i64x2.replace_lane" => I64x2ReplaceLane,
+            "f32x4.extract_lane" => F32x4ExtractLane,
+            "f32x4.replace_lane" => F32x4ReplaceLane,
+            "f64x2.extract_lane" => F64x2ExtractLane,
+            "f64x2.replace_lane" => F64x2ReplaceLane,
             _ => return Err(()),
         })
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     I32Eqz,
     I64Eqz,
@@ -1027,6 +2765,10 @@ pub enum UnaryOp {
     I32TruncF32U,
     I32TruncF64S,
     I32TruncF64U,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
 
     I64ExtendI32S,
     I64ExtendI32U,
@@ -1034,6 +2776,10 @@ pub enum UnaryOp {
     I64TruncF32U,
     I64TruncF64S,
     I64TruncF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
 
     F32ConvertI32S,
     F32ConvertI32U,
@@ -1051,9 +2797,84 @@ pub enum UnaryOp {
     I64ReinterpretF64,
     F32ReinterpretI32,
     F64ReinterpretI64,
+
+    // Sign-extension operators.
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+
+    // SIMD (v128) instructions with a single input.
+    I8x16Splat,
+    I16x8Splat,
+    I32x4Splat,
+    I64x2Splat,
+    F32x4Splat,
+    F64x2Splat,
+    V128Not,
+    V128AnyTrue,
+    I8x16Abs,
+    I8x16Neg,
+    I8x16Popcnt,
+    I8x16AllTrue,
+    I8x16Bitmask,
+    I16x8ExtAddPairwiseI8x16S,
+    I16x8ExtAddPairwiseI8x16U,
+    I16x8Abs,
+    I16x8Neg,
+    I16x8AllTrue,
+    I16x8Bitmask,
+    I16x8ExtendLowI8x16S,
+    I16x8ExtendHighI8x16S,
+    I16x8ExtendLowI8x16U,
+    I16x8ExtendHighI8x16U,
+    I32x4ExtAddPairwiseI16x8S,
+    I32x4ExtAddPairwiseI16x8U,
+    I32x4Abs,
+    I32x4Neg,
+    I32x4AllTrue,
+    I32x4Bitmask,
+    I32x4ExtendLowI16x8S,
+    I32x4ExtendHighI16x8S,
+    I32x4ExtendLowI16x8U,
+    I32x4ExtendHighI16x8U,
+    I64x2Abs,
+    I64x2Neg,
+    I64x2AllTrue,
+    I64x2Bitmask,
+    I64x2ExtendLowI32x4S,
+    I64x2ExtendHighI32x4S,
+    I64x2ExtendLowI32x4U,
+    I64x2ExtendHighI32x4U,
+    F32x4Ceil,
+    F32x4Floor,
+    F32x4Trunc,
+    F32x4Nearest,
+    F32x4Abs,
+    F32x4Neg,
+    F32x4Sqrt,
+    F64x2Ceil,
+    F64x2Floor,
+    F64x2Trunc,
+    F64x2Nearest,
+    F64x2Abs,
+    F64x2Neg,
+    F64x2Sqrt,
+    I32x4TruncSatF32x4S,
+    I32x4TruncSatF32x4U,
+    F32x4ConvertI32x4S,
+    F32x4ConvertI32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     I32Eq,
     I32Ne,
@@ -1138,6 +2959,140 @@ pub enum BinaryOp {
     F64Min,
     F64Max,
     F64Copysign,
+
+    // SIMD (v128) instructions with two inputs.
+    I8x16Swizzle,
+    I8x16Eq,
+    I8x16Ne,
+    I8x16LtS,
+    I8x16LtU,
+    I8x16GtS,
+    I8x16GtU,
+    I8x16LeS,
+    I8x16LeU,
+    I8x16GeS,
+    I8x16GeU,
+    I16x8Eq,
+    I16x8Ne,
+    I16x8LtS,
+    I16x8LtU,
+    I16x8GtS,
+    I16x8GtU,
+    I16x8LeS,
+    I16x8LeU,
+    I16x8GeS,
+    I16x8GeU,
+    I32x4Eq,
+    I32x4Ne,
+    I32x4LtS,
+    I32x4LtU,
+    I32x4GtS,
+    I32x4GtU,
+    I32x4LeS,
+    I32x4LeU,
+    I32x4GeS,
+    I32x4GeU,
+    I64x2Eq,
+    I64x2Ne,
+    I64x2LtS,
+    I64x2GtS,
+    I64x2LeS,
+    I64x2GeS,
+    F32x4Eq,
+    F32x4Ne,
+    F32x4Lt,
+    F32x4Gt,
+    F32x4Le,
+    F32x4Ge,
+    F64x2Eq,
+    F64x2Ne,
+    F64x2Lt,
+    F64x2Gt,
+    F64x2Le,
+    F64x2Ge,
+    V128And,
+    V128AndNot,
+    V128Or,
+    V128Xor,
+    I8x16NarrowI16x8S,
+    I8x16NarrowI16x8U,
+    I8x16Shl,
+    I8x16ShrS,
+    I8x16ShrU,
+    I8x16Add,
+    I8x16AddSatS,
+    I8x16AddSatU,
+    I8x16Sub,
+    I8x16SubSatS,
+    I8x16SubSatU,
+    I8x16MinS,
+    I8x16MinU,
+    I8x16MaxS,
+    I8x16MaxU,
+    I8x16AvgrU,
+    I16x8Q15MulrSatS,
+    I16x8NarrowI32x4S,
+    I16x8NarrowI32x4U,
+    I16x8Shl,
+    I16x8ShrS,
+    I16x8ShrU,
+    I16x8Add,
+    I16x8AddSatS,
+    I16x8AddSatU,
+    I16x8Sub,
+    I16x8SubSatS,
+    I16x8SubSatU,
+    I16x8Mul,
+    I16x8MinS,
+    I16x8MinU,
+    I16x8MaxS,
+    I16x8MaxU,
+    I16x8AvgrU,
+    I16x8ExtMulLowI8x16S,
+    I16x8ExtMulHighI8x16S,
+    I16x8ExtMulLowI8x16U,
+    I16x8ExtMulHighI8x16U,
+    I32x4Shl,
+    I32x4ShrS,
+    I32x4ShrU,
+    I32x4Add,
+    I32x4Sub,
+    I32x4Mul,
+    I32x4MinS,
+    I32x4MinU,
+    I32x4MaxS,
+    I32x4MaxU,
+    I32x4DotI16x8S,
+    I32x4ExtMulLowI16x8S,
+    I32x4ExtMulHighI16x8S,
+    I32x4ExtMulLowI16x8U,
+    I32x4ExtMulHighI16x8U,
+    I64x2Shl,
+    I64x2ShrS,
+    I64x2ShrU,
+    I64x2Add,
+    I64x2Sub,
+    I64x2Mul,
+    I64x2ExtMulLowI32x4S,
+    I64x2ExtMulHighI32x4S,
+    I64x2ExtMulLowI32x4U,
+    I64x2ExtMulHighI32x4U,
+    F32x4Add,
+    F32x4Sub,
+    F32x4Mul,
+    F32x4Div,
+    F32x4Min,
+    F32x4Max,
+    F32x4PMin,
+    F32x4PMax,
+    F64x2Add,
+    F64x2Sub,
+    F64x2Mul,
+    F64x2Div,
+    F64x2Min,
+    F64x2Max,
+    F64x2PMin,
+    F64x2PMax,
 }
 
 impl fmt::Display for UnaryOp {
@@ -1183,12 +3138,20 @@ impl UnaryOp {
             I32TruncF32U => "i32.trunc_f32_u",
             I32TruncF64S => "i32.trunc_f64_s",
             I32TruncF64U => "i32.trunc_f64_u",
+            I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            I32TruncSatF64U => "i32.trunc_sat_f64_u",
             I64ExtendI32S => "i64.extend_i32_s",
             I64ExtendI32U => "i64.extend_i32_u",
             I64TruncF32S => "i64.trunc_f32_s",
             I64TruncF32U => "i64.trunc_f32_u",
             I64TruncF64S => "i64.trunc_f64_s",
             I64TruncF64U => "i64.trunc_f64_u",
+            I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            I64TruncSatF64U => "i64.trunc_sat_f64_u",
             F32ConvertI32S => "f32.convert_i32_s",
             F32ConvertI32U => "f32.convert_i32_u",
             F32ConvertI64S => "f32.convert_i64_s",
@@ -1203,6 +3166,76 @@ impl UnaryOp {
             I64ReinterpretF64 => "i64.reinterpret_f64",
             F32ReinterpretI32 => "f32.reinterpret_i32",
             F64ReinterpretI64 => "f64.reinterpret_i64",
+            I32Extend8S => "i32.extend8_s",
+            I32Extend16S => "i32.extend16_s",
+            I64Extend8S => "i64.extend8_s",
+            I64Extend16S => "i64.extend16_s",
+            I64Extend32S => "i64.extend32_s",
+            I8x16Splat => "i8x16.splat",
+            I16x8Splat => "i16x8.splat",
+            I32x4Splat => "i32x4.splat",
+            I64x2Splat => "i64x2.splat",
+            F32x4Splat => "f32x4.splat",
+            F64x2Splat => "f64x2.splat",
+            V128Not => "v128.not",
+            V128AnyTrue => "v128.any_true",
+            I8x16Abs => "i8x16.abs",
+            I8x16Neg => "i8x16.neg",
+            I8x16Popcnt => "i8x16.popcnt",
+            I8x16AllTrue => "i8x16.all_true",
+            I8x16Bitmask => "i8x16.bitmask",
+            I16x8ExtAddPairwiseI8x16S => "i16x8.extadd_pairwise_i8x16_s",
+            I16x8ExtAddPairwiseI8x16U => "i16x8.extadd_pairwise_i8x16_u",
+            I16x8Abs => "i16x8.abs",
+            I16x8Neg => "i16x8.neg",
+            I16x8AllTrue => "i16x8.all_true",
+            I16x8Bitmask => "i16x8.bitmask",
+            I16x8ExtendLowI8x16S => "i16x8.extend_low_i8x16_s",
+            I16x8ExtendHighI8x16S => "i16x8.extend_high_i8x16_s",
+            I16x8ExtendLowI8x16U => "i16x8.extend_low_i8x16_u",
+            I16x8ExtendHighI8x16U => "i16x8.extend_high_i8x16_u",
+            I32x4ExtAddPairwiseI16x8S => "i32x4.extadd_pairwise_i16x8_s",
+            I32x4ExtAddPairwiseI16x8U => "i32x4.extadd_pairwise_i16x8_u",
+            I32x4Abs => "i32x4.abs",
+            I32x4Neg => "i32x4.neg",
+            I32x4AllTrue => "i32x4.all_true",
+            I32x4Bitmask => "i32x4.bitmask",
+            I32x4ExtendLowI16x8S => "i32x4.extend_low_i16x8_s",
+            I32x4ExtendHighI16x8S => "i32x4.extend_high_i16x8_s",
+            I32x4ExtendLowI16x8U => "i32x4.extend_low_i16x8_u",
+            I32x4ExtendHighI16x8U => "i32x4.extend_high_i16x8_u",
+            I64x2Abs => "i64x2.abs",
+            I64x2Neg => "i64x2.neg",
+            I64x2AllTrue => "i64x2.all_true",
+            I64x2Bitmask => "i64x2.bitmask",
+            I64x2ExtendLowI32x4S => "i64x2.extend_low_i32x4_s",
+            I64x2ExtendHighI32x4S => "i64x2.extend_high_i32x4_s",
+            I64x2ExtendLowI32x4U => "i64x2.extend_low_i32x4_u",
+            I64x2ExtendHighI32x4U => "i64x2.extend_high_i32x4_u",
+            F32x4Ceil => "f32x4.ceil",
+            F32x4Floor => "f32x4.floor",
+            F32x4Trunc => "f32x4.trunc",
+            F32x4Nearest => "f32x4.nearest",
+            F32x4Abs => "f32x4.abs",
+            F32x4Neg => "f32x4.neg",
+            F32x4Sqrt => "f32x4.sqrt",
+            F64x2Ceil => "f64x2.ceil",
+            F64x2Floor => "f64x2.floor",
+            F64x2Trunc => "f64x2.trunc",
+            F64x2Nearest => "f64x2.nearest",
+            F64x2Abs => "f64x2.abs",
+            F64x2Neg => "f64x2.neg",
+            F64x2Sqrt => "f64x2.sqrt",
+            I32x4TruncSatF32x4S => "i32x4.trunc_sat_f32x4_s",
+            I32x4TruncSatF32x4U => "i32x4.trunc_sat_f32x4_u",
+            F32x4ConvertI32x4S => "f32x4.convert_i32x4_s",
+            F32x4ConvertI32x4U => "f32x4.convert_i32x4_u",
+            I32x4TruncSatF64x2SZero => "i32x4.trunc_sat_f64x2_s_zero",
+            I32x4TruncSatF64x2UZero => "i32x4.trunc_sat_f64x2_u_zero",
+            F64x2ConvertLowI32x4S => "f64x2.convert_low_i32x4_s",
+            F64x2ConvertLowI32x4U => "f64x2.convert_low_i32x4_u",
+            F32x4DemoteF64x2Zero => "f32x4.demote_f64x2_zero",
+            F64x2PromoteLowF32x4 => "f64x2.promote_low_f32x4",
         }
     }
 
@@ -1221,11 +3254,11 @@ impl UnaryOp {
 
             // conversions
             I32WrapI64 => FunctionType::new(&[I64], &[I32]),
-            I32TruncF32S | I32TruncF32U => FunctionType::new(&[F32], &[I32]),
-            I32TruncF64S | I32TruncF64U => FunctionType::new(&[F64], &[I32]),
+            I32TruncF32S | I32TruncF32U | I32TruncSatF32S | I32TruncSatF32U => FunctionType::new(&[F32], &[I32]),
+            I32TruncF64S | I32TruncF64U | I32TruncSatF64S | I32TruncSatF64U => FunctionType::new(&[F64], &[I32]),
             I64ExtendI32S | I64ExtendI32U => FunctionType::new(&[I32], &[I64]),
-            I64TruncF32S | I64TruncF32U => FunctionType::new(&[F32], &[I64]),
-            I64TruncF64S | I64TruncF64U => FunctionType::new(&[F64], &[I64]),
+            I64TruncF32S | I64TruncF32U | I64TruncSatF32S | I64TruncSatF32U => FunctionType::new(&[F32], &[I64]),
+            I64TruncF64S | I64TruncF64U | I64TruncSatF64S | I64TruncSatF64U => FunctionType::new(&[F64], &[I64]),
             F32ConvertI32S | F32ConvertI32U => FunctionType::new(&[I32], &[F32]),
             F32ConvertI64S | F32ConvertI64U => FunctionType::new(&[I64], &[F32]),
             F32DemoteF64 => FunctionType::new(&[F64], &[F32]),
@@ -1236,6 +3269,16 @@ impl UnaryOp {
             I64ReinterpretF64 => FunctionType::new(&[F64], &[I64]),
             F32ReinterpretI32 => FunctionType::new(&[I32], &[F32]),
             F64ReinterpretI64 => FunctionType::new(&[I64], &[F64]),
+            I32Extend8S | I32Extend16S => FunctionType::new(&[I32], &[I32]),
+            I64Extend8S | I64Extend16S | I64Extend32S => FunctionType::new(&[I64], &[I64]),
+
+            // SIMD
+            I8x16Splat | I16x8Splat | I32x4Splat => FunctionType::new(&[I32], &[V128]),
+            I64x2Splat => FunctionType::new(&[I64], &[V128]),
+            F32x4Splat => FunctionType::new(&[F32], &[V128]),
+            F64x2Splat => FunctionType::new(&[F64], &[V128]),
+            V128Not | I8x16Abs | I8x16Neg | I8x16Popcnt | I16x8ExtAddPairwiseI8x16S | I16x8ExtAddPairwiseI8x16U | I16x8Abs | I16x8Neg | I16x8ExtendLowI8x16S | I16x8ExtendHighI8x16S | I16x8ExtendLowI8x16U | I16x8ExtendHighI8x16U | I32x4ExtAddPairwiseI16x8S | I32x4ExtAddPairwiseI16x8U | I32x4Abs | I32x4Neg | I32x4ExtendLowI16x8S | I32x4ExtendHighI16x8S | I32x4ExtendLowI16x8U | I32x4ExtendHighI16x8U | I64x2Abs | I64x2Neg | I64x2ExtendLowI32x4S | I64x2ExtendHighI32x4S | I64x2ExtendLowI32x4U | I64x2ExtendHighI32x4U | F32x4Ceil | F32x4Floor | F32x4Trunc | F32x4Nearest | F32x4Abs | F32x4Neg | F32x4Sqrt | F64x2Ceil | F64x2Floor | F64x2Trunc | F64x2Nearest | F64x2Abs | F64x2Neg | F64x2Sqrt | I32x4TruncSatF32x4S | I32x4TruncSatF32x4U | F32x4ConvertI32x4S | F32x4ConvertI32x4U | I32x4TruncSatF64x2SZero | I32x4TruncSatF64x2UZero | F64x2ConvertLowI32x4S | F64x2ConvertLowI32x4U | F32x4DemoteF64x2Zero | F64x2PromoteLowF32x4 => FunctionType::new(&[V128], &[V128]),
+            V128AnyTrue | I8x16AllTrue | I8x16Bitmask | I16x8AllTrue | I16x8Bitmask | I32x4AllTrue | I32x4Bitmask | I64x2AllTrue | I64x2Bitmask => FunctionType::new(&[V128], &[I32]),
         }
     }
 }
@@ -1273,12 +3316,20 @@ impl FromStr for UnaryOp {
             "i32.trunc_f32_u" => I32TruncF32U,
             "i32.trunc_f64_s" => I32TruncF64S,
             "i32.trunc_f64_u" => I32TruncF64U,
+            "i32.trunc_sat_f32_s" => I32TruncSatF32S,
+            "i32.trunc_sat_f32_u" => I32TruncSatF32U,
+            "i32.trunc_sat_f64_s" => I32TruncSatF64S,
+            "i32.trunc_sat_f64_u" => I32TruncSatF64U,
             "i64.extend_i32_s" => I64ExtendI32S,
             "i64.extend_i32_u" => I64ExtendI32U,
             "i64.trunc_f32_s" => I64TruncF32S,
             "i64.trunc_f32_u" => I64TruncF32U,
             "i64.trunc_f64_s" => I64TruncF64S,
             "i64.trunc_f64_u" => I64TruncF64U,
+            "i64.trunc_sat_f32_s" => I64TruncSatF32S,
+            "i64.trunc_sat_f32_u" => I64TruncSatF32U,
+            "i64.trunc_sat_f64_s" => I64TruncSatF64S,
+            "i64.trunc_sat_f64_u" => I64TruncSatF64U,
             "f32.convert_i32_s" => F32ConvertI32S,
             "f32.convert_i32_u" => F32ConvertI32U,
             "f32.convert_i64_s" => F32ConvertI64S,
@@ -1293,6 +3344,76 @@ impl FromStr for UnaryOp {
             "i64.reinterpret_f64" => I64ReinterpretF64,
             "f32.reinterpret_i32" => F32ReinterpretI32,
             "f64.reinterpret_i64" => F64ReinterpretI64,
+            "i32.extend8_s" => I32Extend8S,
+            "i32.extend16_s" => I32Extend16S,
+            "i64.extend8_s" => I64Extend8S,
+            "i64.extend16_s" => I64Extend16S,
+            "i64.extend32_s" => I64Extend32S,
+            "i8x16.splat" => I8x16Splat,
+            "i16x8.splat" => I16x8Splat,
+            "i32x4.splat" => I32x4Splat,
+            "i64x2.splat" => I64x2Splat,
+            "f32x4.splat" => F32x4Splat,
+            "f64x2.splat" => F64x2Splat,
+            "v128.not" => V128Not,
+            "v128.any_true" => V128AnyTrue,
+            "i8x16.abs" => I8x16Abs,
+            "i8x16.neg" => I8x16Neg,
+            "i8x16.popcnt" => I8x16Popcnt,
+            "i8x16.all_true" => I8x16AllTrue,
+            "i8x16.bitmask" => I8x16Bitmask,
+            "i16x8.extadd_pairwise_i8x16_s" => I16x8ExtAddPairwiseI8x16S,
+            "i16x8.extadd_pairwise_i8x16_u" => I16x8ExtAddPairwiseI8x16U,
+            "i16x8.abs" => I16x8Abs,
+            "i16x8.neg" => I16x8Neg,
+            "i16x8.all_true" => I16x8AllTrue,
+            "i16x8.bitmask" => I16x8Bitmask,
+            "i16x8.extend_low_i8x16_s" => I16x8ExtendLowI8x16S,
+            "i16x8.extend_high_i8x16_s" => I16x8ExtendHighI8x16S,
+            "i16x8.extend_low_i8x16_u" => I16x8ExtendLowI8x16U,
+            "i16x8.extend_high_i8x16_u" => I16x8ExtendHighI8x16U,
+            "i32x4.extadd_pairwise_i16x8_s" => I32x4ExtAddPairwiseI16x8S,
+            "i32x4.extadd_pairwise_i16x8_u" => I32x4ExtAddPairwiseI16x8U,
+            "i32x4.abs" => I32x4Abs,
+            "i32x4.neg" => I32x4Neg,
+            "i32x4.all_true" => I32x4AllTrue,
+            "i32x4.bitmask" => I32x4Bitmask,
+            "i32x4.extend_low_i16x8_s" => I32x4ExtendLowI16x8S,
+            "i32x4.extend_high_i16x8_s" => I32x4ExtendHighI16x8S,
+            "i32x4.extend_low_i16x8_u" => I32x4ExtendLowI16x8U,
+            "i32x4.extend_high_i16x8_u" => I32x4ExtendHighI16x8U,
+            "i64x2.abs" => I64x2Abs,
+            "i64x2.neg" => I64x2Neg,
+            "i64x2.all_true" => I64x2AllTrue,
+            "i64x2.bitmask" => I64x2Bitmask,
+            "i64x2.extend_low_i32x4_s" => I64x2ExtendLowI32x4S,
+            "i64x2.extend_high_i32x4_s" => I64x2ExtendHighI32x4S,
+            "i64x2.extend_low_i32x4_u" => I64x2ExtendLowI32x4U,
+            "i64x2.extend_high_i32x4_u" => I64x2ExtendHighI32x4U,
+            "f32x4.ceil" => F32x4Ceil,
+            "f32x4.floor" => F32x4Floor,
+            "f32x4.trunc" => F32x4Trunc,
+            "f32x4.nearest" => F32x4Nearest,
+            "f32x4.abs" => F32x4Abs,
+            "f32x4.neg" => F32x4Neg,
+            "f32x4.sqrt" => F32x4Sqrt,
+            "f64x2.ceil" => F64x2Ceil,
+            "f64x2.floor" => F64x2Floor,
+            "f64x2.trunc" => F64x2Trunc,
+            "f64x2.nearest" => F64x2Nearest,
+            "f64x2.abs" => F64x2Abs,
+            "f64x2.neg" => F64x2Neg,
+            "f64x2.sqrt" => F64x2Sqrt,
+            "i32x4.trunc_sat_f32x4_s" => I32x4TruncSatF32x4S,
+            "i32x4.trunc_sat_f32x4_u" => I32x4TruncSatF32x4U,
+            "f32x4.convert_i32x4_s" => F32x4ConvertI32x4S,
+            "f32x4.convert_i32x4_u" => F32x4ConvertI32x4U,
+            "i32x4.trunc_sat_f64x2_s_zero" => I32x4TruncSatF64x2SZero,
+            "i32x4.trunc_sat_f64x2_u_zero" => I32x4TruncSatF64x2UZero,
+            "f64x2.convert_low_i32x4_s" => F64x2ConvertLowI32x4S,
+            "f64x2.convert_low_i32x4_u" => F64x2ConvertLowI32x4U,
+            "f32x4.demote_f64x2_zero" => F32x4DemoteF64x2Zero,
+            "f64x2.promote_low_f32x4" => F64x2PromoteLowF32x4,
             _ => return Err(()),
         })
     }
@@ -1378,6 +3499,138 @@ impl BinaryOp {
             F64Min => "f64.min",
             F64Max => "f64.max",
             F64Copysign => "f64.copysign",
+            I8x16Swizzle => "i8x16.swizzle",
+            I8x16Eq => "i8x16.eq",
+            I8x16Ne => "i8x16.ne",
+            I8x16LtS => "i8x16.lt_s",
+            I8x16LtU => "i8x16.lt_u",
+            I8x16GtS => "i8x16.gt_s",
+            I8x16GtU => "i8x16.gt_u",
+            I8x16LeS => "i8x16.le_s",
+            I8x16LeU => "i8x16.le_u",
+            I8x16GeS => "i8x16.ge_s",
+            I8x16GeU => "i8x16.ge_u",
+            I16x8Eq => "i16x8.eq",
+            I16x8Ne => "i16x8.ne",
+            I16x8LtS => "i16x8.lt_s",
+            I16x8LtU => "i16x8.lt_u",
+            I16x8GtS => "i16x8.gt_s",
+            I16x8GtU => "i16x8.gt_u",
+            I16x8LeS => "i16x8.le_s",
+            I16x8LeU => "i16x8.le_u",
+            I16x8GeS => "i16x8.ge_s",
+            I16x8GeU => "i16x8.ge_u",
+            I32x4Eq => "i32x4.eq",
+            I32x4Ne => "i32x4.ne",
+            I32x4LtS => "i32x4.lt_s",
+            I32x4LtU => "i32x4.lt_u",
+            I32x4GtS => "i32x4.gt_s",
+            I32x4GtU => "i32x4.gt_u",
+            I32x4LeS => "i32x4.le_s",
+            I32x4LeU => "i32x4.le_u",
+            I32x4GeS => "i32x4.ge_s",
+            I32x4GeU => "i32x4.ge_u",
+            I64x2Eq => "i64x2.eq",
+            I64x2Ne => "i64x2.ne",
+            I64x2LtS => "i64x2.lt_s",
+            I64x2GtS => "i64x2.gt_s",
+            I64x2LeS => "i64x2.le_s",
+            I64x2GeS => "i64x2.ge_s",
+            F32x4Eq => "f32x4.eq",
+            F32x4Ne => "f32x4.ne",
+            F32x4Lt => "f32x4.lt",
+            F32x4Gt => "f32x4.gt",
+            F32x4Le => "f32x4.le",
+            F32x4Ge => "f32x4.ge",
+            F64x2Eq => "f64x2.eq",
+            F64x2Ne => "f64x2.ne",
+            F64x2Lt => "f64x2.lt",
+            F64x2Gt => "f64x2.gt",
+            F64x2Le => "f64x2.le",
+            F64x2Ge => "f64x2.ge",
+            V128And => "v128.and",
+            V128AndNot => "v128.andnot",
+            V128Or => "v128.or",
+            V128Xor => "v128.xor",
+            I8x16NarrowI16x8S => "i8x16.narrow_i16x8_s",
+            I8x16NarrowI16x8U => "i8x16.narrow_i16x8_u",
+            I8x16Shl => "i8x16.shl",
+            I8x16ShrS => "i8x16.shr_s",
+            I8x16ShrU => "i8x16.shr_u",
+            I8x16Add => "i8x16.add",
+            I8x16AddSatS => "i8x16.add_sat_s",
+            I8x16AddSatU => "i8x16.add_sat_u",
+            I8x16Sub => "i8x16.sub",
+            I8x16SubSatS => "i8x16.sub_sat_s",
+            I8x16SubSatU => "i8x16.sub_sat_u",
+            I8x16MinS => "i8x16.min_s",
+            I8x16MinU => "i8x16.min_u",
+            I8x16MaxS => "i8x16.max_s",
+            I8x16MaxU => "i8x16.max_u",
+            I8x16AvgrU => "i8x16.avgr_u",
+            I16x8Q15MulrSatS => "i16x8.q15mulr_sat_s",
+            I16x8NarrowI32x4S => "i16x8.narrow_i32x4_s",
+            I16x8NarrowI32x4U => "i16x8.narrow_i32x4_u",
+            I16x8Shl => "i16x8.shl",
+            I16x8ShrS => "i16x8.shr_s",
+            I16x8ShrU => "i16x8.shr_u",
+            I16x8Add => "i16x8.add",
+            I16x8AddSatS => "i16x8.add_sat_s",
+            I16x8AddSatU => "i16x8.add_sat_u",
+            I16x8Sub => "i16x8.sub",
+            I16x8SubSatS => "i16x8.sub_sat_s",
+            I16x8SubSatU => "i16x8.sub_sat_u",
+            I16x8Mul => "i16x8.mul",
+            I16x8MinS => "i16x8.min_s",
+            I16x8MinU => "i16x8.min_u",
+            I16x8MaxS => "i16x8.max_s",
+            I16x8MaxU => "i16x8.max_u",
+            I16x8AvgrU => "i16x8.avgr_u",
+            I16x8ExtMulLowI8x16S => "i16x8.extmul_low_i8x16_s",
+            I16x8ExtMulHighI8x16S => "i16x8.extmul_high_i8x16_s",
+            I16x8ExtMulLowI8x16U => "i16x8.extmul_low_i8x16_u",
+            I16x8ExtMulHighI8x16U => "i16x8.extmul_high_i8x16_u",
+            I32x4Shl => "i32x4.shl",
+            I32x4ShrS => "i32x4.shr_s",
+            I32x4ShrU => "i32x4.shr_u",
+            I32x4Add => "i32x4.add",
+            I32x4Sub => "i32x4.sub",
+            I32x4Mul => "i32x4.mul",
+            I32x4MinS => "i32x4.min_s",
+            I32x4MinU => "i32x4.min_u",
+            I32x4MaxS => "i32x4.max_s",
+            I32x4MaxU => "i32x4.max_u",
+            I32x4DotI16x8S => "i32x4.dot_i16x8_s",
+            I32x4ExtMulLowI16x8S => "i32x4.extmul_low_i16x8_s",
+            I32x4ExtMulHighI16x8S => "i32x4.extmul_high_i16x8_s",
+            I32x4ExtMulLowI16x8U => "i32x4.extmul_low_i16x8_u",
+            I32x4ExtMulHighI16x8U => "i32x4.extmul_high_i16x8_u",
+            I64x2Shl => "i64x2.shl",
+            I64x2ShrS => "i64x2.shr_s",
+            I64x2ShrU => "i64x2.shr_u",
+            I64x2Add => "i64x2.add",
+            I64x2Sub => "i64x2.sub",
+            I64x2Mul => "i64x2.mul",
+            I64x2ExtMulLowI32x4S => "i64x2.extmul_low_i32x4_s",
+            I64x2ExtMulHighI32x4S => "i64x2.extmul_high_i32x4_s",
+            I64x2ExtMulLowI32x4U => "i64x2.extmul_low_i32x4_u",
+            I64x2ExtMulHighI32x4U => "i64x2.extmul_high_i32x4_u",
+            F32x4Add => "f32x4.add",
+            F32x4Sub => "f32x4.sub",
+            F32x4Mul => "f32x4.mul",
+            F32x4Div => "f32x4.div",
+            F32x4Min => "f32x4.min",
+            F32x4Max => "f32x4.max",
+            F32x4PMin => "f32x4.pmin",
+            F32x4PMax => "f32x4.pmax",
+            F64x2Add => "f64x2.add",
+            F64x2Sub => "f64x2.sub",
+            F64x2Mul => "f64x2.mul",
+            F64x2Div => "f64x2.div",
+            F64x2Min => "f64x2.min",
+            F64x2Max => "f64x2.max",
+            F64x2PMin => "f64x2.pmin",
+            F64x2PMax => "f64x2.pmax",
         }
     }
 
@@ -1395,6 +3648,10 @@ impl BinaryOp {
             I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => FunctionType::new(&[I64, I64], &[I64]),
             F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign => FunctionType::new(&[F32, F32], &[F32]),
             F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => FunctionType::new(&[F64, F64], &[F64]),
+
+            // SIMD
+            I8x16Swizzle | I8x16Eq | I8x16Ne | I8x16LtS | I8x16LtU | I8x16GtS | I8x16GtU | I8x16LeS | I8x16LeU | I8x16GeS | I8x16GeU | I16x8Eq | I16x8Ne | I16x8LtS | I16x8LtU | I16x8GtS | I16x8GtU | I16x8LeS | I16x8LeU | I16x8GeS | I16x8GeU | I32x4Eq | I32x4Ne | I32x4LtS | I32x4LtU | I32x4GtS | I32x4GtU | I32x4LeS | I32x4LeU | I32x4GeS | I32x4GeU | I64x2Eq | I64x2Ne | I64x2LtS | I64x2GtS | I64x2LeS | I64x2GeS | F32x4Eq | F32x4Ne | F32x4Lt | F32x4Gt | F32x4Le | F32x4Ge | F64x2Eq | F64x2Ne | F64x2Lt | F64x2Gt | F64x2Le | F64x2Ge | V128And | V128AndNot | V128Or | V128Xor | I8x16NarrowI16x8S | I8x16NarrowI16x8U | I8x16Add | I8x16AddSatS | I8x16AddSatU | I8x16Sub | I8x16SubSatS | I8x16SubSatU | I8x16MinS | I8x16MinU | I8x16MaxS | I8x16MaxU | I8x16AvgrU | I16x8Q15MulrSatS | I16x8NarrowI32x4S | I16x8NarrowI32x4U | I16x8Add | I16x8AddSatS | I16x8AddSatU | I16x8Sub | I16x8SubSatS | I16x8SubSatU | I16x8Mul | I16x8MinS | I16x8MinU | I16x8MaxS | I16x8MaxU | I16x8AvgrU | I16x8ExtMulLowI8x16S | I16x8ExtMulHighI8x16S | I16x8ExtMulLowI8x16U | I16x8ExtMulHighI8x16U | I32x4Add | I32x4Sub | I32x4Mul | I32x4MinS | I32x4MinU | I32x4MaxS | I32x4MaxU | I32x4DotI16x8S | I32x4ExtMulLowI16x8S | I32x4ExtMulHighI16x8S | I32x4ExtMulLowI16x8U | I32x4ExtMulHighI16x8U | I64x2Add | I64x2Sub | I64x2Mul | I64x2ExtMulLowI32x4S | I64x2ExtMulHighI32x4S | I64x2ExtMulLowI32x4U | I64x2ExtMulHighI32x4U | F32x4Add | F32x4Sub | F32x4Mul | F32x4Div | F32x4Min | F32x4Max | F32x4PMin | F32x4PMax | F64x2Add | F64x2Sub | F64x2Mul | F64x2Div | F64x2Min | F64x2Max | F64x2PMin | F64x2PMax => FunctionType::new(&[V128, V128], &[V128]),
+            I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl | I16x8ShrS | I16x8ShrU | I32x4Shl | I32x4ShrS | I32x4ShrU | I64x2Shl | I64x2ShrS | I64x2ShrU => FunctionType::new(&[V128, I32], &[V128]),
         }
     }
 }
@@ -1481,6 +3738,138 @@ impl FromStr for BinaryOp {
             "f64.min" => F64Min,
             "f64.max" => F64Max,
             "f64.copysign" => F64Copysign,
+            "i8x16.swizzle" => I8x16Swizzle,
+            "i8x16.eq" => I8x16Eq,
+            "i8x16.ne" => I8x16Ne,
+            "i8x16.lt_s" => I8x16LtS,
+            "i8x16.lt_u" => I8x16LtU,
+            "i8x16.gt_s" => I8x16GtS,
+            "i8x16.gt_u" => I8x16GtU,
+            "i8x16.le_s" => I8x16LeS,
+            "i8x16.le_u" => I8x16LeU,
+            "i8x16.ge_s" => I8x16GeS,
+            "i8x16.ge_u" => I8x16GeU,
+            "i16x8.eq" => I16x8Eq,
+            "i16x8.ne" => I16x8Ne,
+            "i16x8.lt_s" => I16x8LtS,
+            "i16x8.lt_u" => I16x8LtU,
+            "i16x8.gt_s" => I16x8GtS,
+            "i16x8.gt_u" => I16x8GtU,
+            "i16x8.le_s" => I16x8LeS,
+            "i16x8.le_u" => I16x8LeU,
+            "i16x8.ge_s" => I16x8GeS,
+            "i16x8.ge_u" => I16x8GeU,
+            "i32x4.eq" => I32x4Eq,
+            "i32x4.ne" => I32x4Ne,
+            "i32x4.lt_s" => I32x4LtS,
+            "i32x4.lt_u" => I32x4LtU,
+            "i32x4.gt_s" => I32x4GtS,
+            "i32x4.gt_u" => I32x4GtU,
+            "i32x4.le_s" => I32x4LeS,
+            "i32x4.le_u" => I32x4LeU,
+            "i32x4.ge_s" => I32x4GeS,
+            "i32x4.ge_u" => I32x4GeU,
+            "i64x2.eq" => I64x2Eq,
+            "i64x2.ne" => I64x2Ne,
+            "i64x2.lt_s" => I64x2LtS,
+            "i64x2.gt_s" => I64x2GtS,
+            "i64x2.le_s" => I64x2LeS,
+            "i64x2.ge_s" => I64x2GeS,
+            "f32x4.eq" => F32x4Eq,
+            "f32x4.ne" => F32x4Ne,
+            "f32x4.lt" => F32x4Lt,
+            "f32x4.gt" => F32x4Gt,
+            "f32x4.le" => F32x4Le,
+            "f32x4.ge" => F32x4Ge,
+            "f64x2.eq" => F64x2Eq,
+            "f64x2.ne" => F64x2Ne,
+            "f64x2.lt" => F64x2Lt,
+            "f64x2.gt" => F64x2Gt,
+            "f64x2.le" => F64x2Le,
+            "f64x2.ge" => F64x2Ge,
+            "v128.and" => V128And,
+            "v128.andnot" => V128AndNot,
+            "v128.or" => V128Or,
+            "v128.xor" => V128Xor,
+            "i8x16.narrow_i16x8_s" => I8x16NarrowI16x8S,
+            "i8x16.narrow_i16x8_u" => I8x16NarrowI16x8U,
+            "i8x16.shl" => I8x16Shl,
+            "i8x16.shr_s" => I8x16ShrS,
+            "i8x16.shr_u" => I8x16ShrU,
+            "i8x16.add" => I8x16Add,
+            "i8x16.add_sat_s" => I8x16AddSatS,
+            "i8x16.add_sat_u" => I8x16AddSatU,
+            "i8x16.sub" => I8x16Sub,
+            "i8x16.sub_sat_s" => I8x16SubSatS,
+            "i8x16.sub_sat_u" => I8x16SubSatU,
+            "i8x16.min_s" => I8x16MinS,
+            "i8x16.min_u" => I8x16MinU,
+            "i8x16.max_s" => I8x16MaxS,
+            "i8x16.max_u" => I8x16MaxU,
+            "i8x16.avgr_u" => I8x16AvgrU,
+            "i16x8.q15mulr_sat_s" => I16x8Q15MulrSatS,
+            "i16x8.narrow_i32x4_s" => I16x8NarrowI32x4S,
+            "i16x8.narrow_i32x4_u" => I16x8NarrowI32x4U,
+            "i16x8.shl" => I16x8Shl,
+            "i16x8.shr_s" => I16x8ShrS,
+            "i16x8.shr_u" => I16x8ShrU,
+            "i16x8.add" => I16x8Add,
+            "i16x8.add_sat_s" => I16x8AddSatS,
+            "i16x8.add_sat_u" => I16x8AddSatU,
+            "i16x8.sub" => I16x8Sub,
+            "i16x8.sub_sat_s" => I16x8SubSatS,
+            "i16x8.sub_sat_u" => I16x8SubSatU,
+            "i16x8.mul" => I16x8Mul,
+            "i16x8.min_s" => I16x8MinS,
+            "i16x8.min_u" => I16x8MinU,
+            "i16x8.max_s" => I16x8MaxS,
+            "i16x8.max_u" => I16x8MaxU,
+            "i16x8.avgr_u" => I16x8AvgrU,
+            "i16x8.extmul_low_i8x16_s" => I16x8ExtMulLowI8x16S,
+            "i16x8.extmul_high_i8x16_s" => I16x8ExtMulHighI8x16S,
+            "i16x8.extmul_low_i8x16_u" => I16x8ExtMulLowI8x16U,
+            "i16x8.extmul_high_i8x16_u" => I16x8ExtMulHighI8x16U,
+            "i32x4.shl" => I32x4Shl,
+            "i32x4.shr_s" => I32x4ShrS,
+            "i32x4.shr_u" => I32x4ShrU,
+            "i32x4.add" => I32x4Add,
+            "i32x4.sub" => I32x4Sub,
+            "i32x4.mul" => I32x4Mul,
+            "i32x4.min_s" => I32x4MinS,
+            "i32x4.min_u" => I32x4MinU,
+            "i32x4.max_s" => I32x4MaxS,
+            "i32x4.max_u" => I32x4MaxU,
+            "i32x4.dot_i16x8_s" => I32x4DotI16x8S,
+            "i32x4.extmul_low_i16x8_s" => I32x4ExtMulLowI16x8S,
+            "i32x4.extmul_high_i16x8_s" => I32x4ExtMulHighI16x8S,
+            "i32x4.extmul_low_i16x8_u" => I32x4ExtMulLowI16x8U,
+            "i32x4.extmul_high_i16x8_u" => I32x4ExtMulHighI16x8U,
+            "i64x2.shl" => I64x2Shl,
+            "i64x2.shr_s" => I64x2ShrS,
+            "i64x2.shr_u" => I64x2ShrU,
+            "i64x2.add" => I64x2Add,
+            "i64x2.sub" => I64x2Sub,
+            "i64x2.mul" => I64x2Mul,
+            "i64x2.extmul_low_i32x4_s" => I64x2ExtMulLowI32x4S,
+            "i64x2.extmul_high_i32x4_s" => I64x2ExtMulHighI32x4S,
+            "i64x2.extmul_low_i32x4_u" => I64x2ExtMulLowI32x4U,
+            "i64x2.extmul_high_i32x4_u" => I64x2ExtMulHighI32x4U,
+            "f32x4.add" => F32x4Add,
+            "f32x4.sub" => F32x4Sub,
+            "f32x4.mul" => F32x4Mul,
+            "f32x4.div" => F32x4Div,
+            "f32x4.min" => F32x4Min,
+            "f32x4.max" => F32x4Max,
+            "f32x4.pmin" => F32x4PMin,
+            "f32x4.pmax" => F32x4PMax,
+            "f64x2.add" => F64x2Add,
+            "f64x2.sub" => F64x2Sub,
+            "f64x2.mul" => F64x2Mul,
+            "f64x2.div" => F64x2Div,
+            "f64x2.min" => F64x2Min,
+            "f64x2.max" => F64x2Max,
+            "f64x2.pmin" => F64x2PMin,
+            "f64x2.pmax" => F64x2PMax,
             _ => return Err(()),
         })
     }
@@ -1490,6 +3879,8 @@ impl Instr {
     /// Returns the instruction name as in the WebAssembly specification and
     /// text format.
     /// This is only the mnemonic, without instruction arguments.
+    /// For the reverse direction, see the `FromStr` impls of `UnaryOp`, `BinaryOp`, `LoadOp`,
+    /// `StoreOp`, etc. (or of `Instr` itself, which also parses the arguments).
     pub fn to_name(&self) -> &'static str {
         use Instr::*;
         match *self {
@@ -1509,9 +3900,11 @@ impl Instr {
             Return => "return",
             Call(_) => "call",
             CallIndirect(_, _) => "call_indirect",
+            ReturnCall(_) => "return_call",
+            ReturnCallIndirect(_, _) => "return_call_indirect",
 
             Drop => "drop",
-            Select => "select",
+            Select(_) => "select",
 
             Local(LocalOp::Get, _) => "local.get",
             Local(LocalOp::Set, _) => "local.set",
@@ -1522,15 +3915,102 @@ impl Instr {
             MemorySize(_) => "memory.size",
             MemoryGrow(_) => "memory.grow",
 
+            MemoryInit(_, _) => "memory.init",
+            DataDrop(_) => "data.drop",
+            MemoryCopy { .. } => "memory.copy",
+            MemoryFill(_) => "memory.fill",
+            TableInit(_, _) => "table.init",
+            ElemDrop(_) => "elem.drop",
+            TableCopy { .. } => "table.copy",
+
             Const(Val::I32(_)) => "i32.const",
             Const(Val::I64(_)) => "i64.const",
             Const(Val::F32(_)) => "f32.const",
             Const(Val::F64(_)) => "f64.const",
+            Const(Val::V128(_)) => "v128.const",
+
+            Atomic(op, _) => op.to_name(),
+            AtomicFence => "atomic.fence",
 
             Load(op, _) => op.to_name(),
             Store(op, _) => op.to_name(),
             Unary(op) => op.to_name(),
             Binary(op) => op.to_name(),
+
+            RefNull(_) => "ref.null",
+            RefIsNull => "ref.is_null",
+            RefFunc(_) => "ref.func",
+
+            LoadLane(op, _, _) => op.to_name(),
+            StoreLane(op, _, _) => op.to_name(),
+            Lane(op, _) => op.to_name(),
+            I8x16Shuffle(_) => "i8x16.shuffle",
+            V128Bitselect => "v128.bitselect",
+            Unparsed(_) => "unparsed",
+            Unsupported(..) => "unsupported",
+        }
+    }
+
+    // Classification predicates, following the instruction categories of the specification:
+    // https://webassembly.github.io/spec/core/syntax/instructions.html
+    // Based on the single category per instruction of `Instr::category()` (e.g., for histograms).
+
+    /// Blocks, branches, `return`, `unreachable`, `nop`, and calls (see also `is_call`).
+    pub fn is_control(&self) -> bool {
+        matches!(self.category(), InstrCategory::Control | InstrCategory::Call)
+    }
+
+    /// `call` and `call_indirect`, including their tail call variants.
+    pub fn is_call(&self) -> bool {
+        self.category() == InstrCategory::Call
+    }
+
+    /// `drop` and `select`.
+    pub fn is_parametric(&self) -> bool {
+        self.category() == InstrCategory::Parametric
+    }
+
+    /// `local.*` and `global.*`.
+    pub fn is_variable_access(&self) -> bool {
+        self.category() == InstrCategory::Variable
+    }
+
+    /// Loads and stores, including SIMD lane and atomic accesses, i.e., all instructions with a
+    /// `Memarg`. Not included are `memory.size`, `memory.grow`, and bulk memory operations.
+    pub fn is_memory_access(&self) -> bool {
+        self.category() == InstrCategory::MemoryAccess
+    }
+
+    /// The operation and `Memarg` of memory accesses (see `is_memory_access`), e.g., to check
+    /// the alignment with `Memarg::is_natural_alignment`.
+    pub fn memory_access(&self) -> Option<(MemoryAccessOp, Memarg)> {
+        use Instr::*;
+        match *self {
+            Load(op, memarg) => Some((MemoryAccessOp::Load(op), memarg)),
+            Store(op, memarg) => Some((MemoryAccessOp::Store(op), memarg)),
+            Atomic(op, memarg) => Some((MemoryAccessOp::Atomic(op), memarg)),
+            LoadLane(op, memarg, _) => Some((MemoryAccessOp::LoadLane(op), memarg)),
+            StoreLane(op, memarg, _) => Some((MemoryAccessOp::StoreLane(op), memarg)),
+            _ => None,
+        }
+    }
+
+    /// Constants and operations on numbers and vectors (see also `is_const`).
+    pub fn is_numeric(&self) -> bool {
+        matches!(self.category(), InstrCategory::Const | InstrCategory::Numeric | InstrCategory::Vector)
+    }
+
+    /// `*.const` instructions (but not `ref.null` or `ref.func`).
+    pub fn is_const(&self) -> bool {
+        self.category() == InstrCategory::Const
+    }
+
+    /// The value of `*.const` instructions (see `is_const`), e.g., `instr.as_const()?.as_i32()`
+    /// for the operand of an `i32.const`.
+    pub fn as_const(&self) -> Option<Val> {
+        match *self {
+            Instr::Const(val) => Some(val),
+            _ => None,
         }
     }
 
@@ -1545,8 +4025,13 @@ impl Instr {
             Nop => Some(FunctionType::new(&[], &[])),
             Load(ref op, _) => Some(op.to_type()),
             Store(ref op, _) => Some(op.to_type()),
+            Atomic(ref op, _) => Some(op.to_type()),
+            AtomicFence => Some(FunctionType::new(&[], &[])),
             MemorySize(_) => Some(FunctionType::new(&[], &[I32])),
             MemoryGrow(_) => Some(FunctionType::new(&[I32], &[I32])),
+            MemoryInit(_, _) | MemoryCopy { .. } | MemoryFill(_) => Some(FunctionType::new(&[I32, I32, I32], &[])),
+            TableInit(_, _) | TableCopy { .. } => Some(FunctionType::new(&[I32, I32, I32], &[])),
+            DataDrop(_) | ElemDrop(_) => Some(FunctionType::new(&[], &[])),
             Const(ref val) => Some(FunctionType::new(&[], &[val.to_type()])),
             Unary(ref op) => Some(op.to_type()),
             Binary(ref op) => Some(op.to_type()),
@@ -1554,20 +4039,155 @@ impl Instr {
                 func_ty.inputs().iter().copied().chain(std::iter::once(I32)),
                 func_ty.results().iter().copied(),
             )),
+            Select(Some(ty)) => Some(FunctionType::new(&[ty, ty, I32], &[ty])),
+            RefNull(ty) => Some(FunctionType::new(&[], &[ty.into()])),
+            RefFunc(_) => Some(FunctionType::new(&[], &[FuncRef])),
+            LoadLane(ref op, _, _) => Some(op.to_type()),
+            StoreLane(ref op, _, _) => Some(op.to_type()),
+            Lane(ref op, _) => Some(op.to_type()),
+            I8x16Shuffle(_) => Some(FunctionType::new(&[V128, V128], &[V128])),
+            V128Bitselect => Some(FunctionType::new(&[V128, V128, V128], &[V128])),
 
             // Difficult because of nesting and block types.
             Block(_) | Loop(_) | If(_) | Else | End => None,
             // Depends on the branch target block.
             Br(_) | BrIf(_) | BrTable { .. } => None,
             // Need to inspect the current/called function type.
-            Return | Call(_) => None,
+            Return | Call(_) | ReturnCall(_) | ReturnCallIndirect(..) => None,
             // Need lookup in locals/globals
             Local(_, _) | Global(_, _) => None,
             // Value-polymorphic, need abstract type stack.
-            Drop | Select => None,
+            Drop | Select(None) | RefIsNull => None,
             // Stack-polymorphic, needs type inference (br* above as well).
             Unreachable => None,
+            // Unknown, since the instructions were not parsed.
+            Unparsed(_) | Unsupported(..) => None,
+        }
+    }
+
+    /// Returns the type of memory instructions, taking the index type of the accessed memory into
+    /// account, i.e., addresses and sizes are `i64` for 64-bit memories (unlike in `simple_type()`).
+    /// Returns `None` for non-memory instructions and out-of-range memory indices.
+    pub fn memory_type(&self, module: &Module) -> Option<FunctionType> {
+        use Instr::*;
+        use ValType::*;
+        let addr = |memory: Idx<Memory>| module.memories.get(memory.to_usize()).map(|memory| memory.index_type.to_val_type());
+        let ty = match *self {
+            // Loads and stores (implicitly) access memory 0 and take the address as the first input.
+            Load(..) | Store(..) | LoadLane(..) | StoreLane(..) | Atomic(..) => {
+                let ty = self.simple_type().expect("loads and stores have a simple type");
+                let inputs = std::iter::once(addr(0u32.into())?).chain(ty.inputs()[1..].iter().copied());
+                return Some(FunctionType::from_iter(inputs, ty.results().iter().copied()));
+            }
+            MemorySize(memory) => FunctionType::new(&[], &[addr(memory)?]),
+            MemoryGrow(memory) => FunctionType::new(&[addr(memory)?], &[addr(memory)?]),
+            MemoryFill(memory) => FunctionType::new(&[addr(memory)?, I32, addr(memory)?], &[]),
+            MemoryInit(_, memory) => FunctionType::new(&[addr(memory)?, I32, I32], &[]),
+            MemoryCopy { dst, src } => {
+                let (dst, src) = (addr(dst)?, addr(src)?);
+                // The length is only 64-bit if both memories are.
+                let len = if dst == I64 && src == I64 { I64 } else { I32 };
+                FunctionType::new(&[dst, src, len], &[])
+            }
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    /// Returns the types of the values this instruction pops from and pushes onto the operand
+    /// stack, as `(inputs, results)`.
+    ///
+    /// Unlike `simple_type()`, this also resolves instructions that need the surrounding `function`
+    /// (for locals and `return`) or `module` (for calls and globals), and it does not panic on
+    /// out-of-range indices, but returns an error instead.
+    ///
+    /// Block instructions are split like in the `TypeChecker`: `block`, `loop`, and `if` pop the
+    /// block inputs (and `if` its condition) from the parent stack, but their results are only
+    /// pushed by the matching `end`.
+    /// `unreachable` is stack-polymorphic, but we return its minimal effect `[] -> []`, since any
+    /// code after it is dead anyway.
+    /// Similarly, `return_call` and `return_call_indirect` only pop the callee's arguments (and the
+    /// table index), but do not push the callee's results onto the current stack, since they are
+    /// returned to the caller of the current function directly (like with `return`).
+    /// Instructions whose effect depends on the operand stack or the surrounding blocks, i.e.,
+    /// `drop`, `select` (without type annotation), `ref.is_null`, `else`, `end`, and all branches
+    /// except `return`, return an error. Use the `TypeChecker` for those.
+    pub fn stack_effect(&self, function: &Function, module: &Module) -> Result<(Vec<ValType>, Vec<ValType>), TypeError> {
+        if let Some(ty) = self.memory_type(module).or_else(|| self.simple_type()) {
+            return Ok((ty.inputs().to_vec(), ty.results().to_vec()));
         }
+
+        use Instr::*;
+        let ty = match self {
+            Local(op, idx) => {
+                let local_ty = function.local_type(*idx)
+                    .ok_or_else(|| TypeError::from(format!("local index {} out of range", idx.to_u32())))?;
+                op.to_type(local_ty)
+            }
+            Global(op, idx) => {
+                let global = module.globals.get(idx.to_usize())
+                    .ok_or_else(|| TypeError::from(format!("global index {} out of range", idx.to_u32())))?;
+                op.to_type(global.type_.0)
+            }
+            Call(idx) => {
+                module.functions.get(idx.to_usize())
+                    .ok_or_else(|| TypeError::from(format!("function index {} out of range", idx.to_u32())))?
+                    .type_
+            }
+            Block(block_ty) | Loop(block_ty) => FunctionType::new(block_ty.inputs(), &[]),
+            If(block_ty) => FunctionType::from_iter(
+                std::iter::once(ValType::I32).chain(block_ty.inputs().iter().copied()),
+                std::iter::empty(),
+            ),
+            Return => FunctionType::new(function.type_.results(), &[]),
+            ReturnCall(idx) => {
+                let callee = module.functions.get(idx.to_usize())
+                    .ok_or_else(|| TypeError::from(format!("function index {} out of range", idx.to_u32())))?;
+                FunctionType::new(callee.type_.inputs(), &[])
+            }
+            ReturnCallIndirect(func_ty, _) => FunctionType::from_iter(
+                func_ty.inputs().iter().copied().chain(std::iter::once(ValType::I32)),
+                std::iter::empty(),
+            ),
+            Unreachable => FunctionType::empty(),
+            // For `select`, see `select_type` given the operand types.
+            Drop | Select(None) | RefIsNull => return Err(format!("value-polymorphic instruction {self} has no stack effect without the operand stack").into()),
+            Else | End | Br(_) | BrIf(_) | BrTable { .. } => return Err(format!("stack effect of {self} depends on the surrounding blocks").into()),
+            // The instructions were not parsed, see `OnUnsupported`.
+            Unparsed(_) | Unsupported(..) => return Err(format!("stack effect of placeholder instruction {self} is unknown").into()),
+            Nop | Load(..) | Store(..) | Atomic(..) | AtomicFence | MemorySize(_) | MemoryGrow(_)
+            | MemoryInit(..) | MemoryCopy { .. } | MemoryFill(_) | TableInit(..) | TableCopy { .. }
+            | DataDrop(_) | ElemDrop(_) | Const(_) | Unary(_) | Binary(_) | CallIndirect(..)
+            | Select(Some(_)) | RefNull(_) | RefFunc(_) | LoadLane(..) | StoreLane(..) | Lane(..)
+            | I8x16Shuffle(_) | V128Bitselect => unreachable!("instruction {:?} should have been handled by `simple_type()`", self),
+        };
+        Ok((ty.inputs().to_vec(), ty.results().to_vec()))
+    }
+
+    /// Type of a `select` whose two value operands (i.e., not the `i32` condition) have the given
+    /// types. Typed `select t` uses its annotation, which both operands must match. Untyped `select`
+    /// requires both operands to have the same numeric or vector type, since operands of reference
+    /// type are only allowed with typed `select`.
+    pub fn select_type(&self, operand1: ValType, operand2: ValType) -> Result<FunctionType, TypeError> {
+        let ty = match self {
+            Instr::Select(Some(ty)) => {
+                if operand1 != *ty || operand2 != *ty {
+                    return Err(format!("{self} with operands of type {operand1} and {operand2}").into());
+                }
+                *ty
+            }
+            Instr::Select(None) => {
+                if operand1 != operand2 {
+                    return Err(format!("incompatible types {operand1} and {operand2} for select arguments").into());
+                }
+                if operand1.to_ref_type().is_some() {
+                    return Err(format!("untyped select with operands of reference type {operand1}, needs typed select").into());
+                }
+                operand1
+            }
+            _ => return Err(format!("{self} is not a select instruction").into()),
+        };
+        Ok(FunctionType::new(&[ty, ty, ValType::I32], &[ty]))
     }
 }
 
@@ -1586,8 +4206,22 @@ impl FromStr for Instr {
             let u: usize = str.parse().map_err(|_| ())?;
             Ok(u.into())
         }
+        // The lane index is the last argument, after the (optional) memarg.
+        fn split_lane(str: &str, lane_count: u8) -> Result<(&str, u8), ()> {
+            let (rest, lane) = str.trim().rsplit_once(' ').unwrap_or(("", str.trim()));
+            Ok((rest, parse_lane(lane, lane_count)?))
+        }
+        fn parse_lane(str: &str, lane_count: u8) -> Result<u8, ()> {
+            let lane: u8 = str.trim().parse().map_err(|_| ())?;
+            if lane < lane_count {
+                Ok(lane)
+            } else {
+                Err(())
+            }
+        }
 
-        let (operator, rest) = str.split_once(char::is_whitespace).ok_or(())?;
+        // Instructions without immediates, e.g., `nop`, have no whitespace at all.
+        let (operator, rest) = str.split_once(char::is_whitespace).unwrap_or((str, ""));
         Ok(match operator {
             "unreachable" => Unreachable,
             "nop" => Nop,
@@ -1626,9 +4260,12 @@ impl FromStr for Instr {
                 let table_idx = Idx::from(0u32);
                 CallIndirect(ty, table_idx)
             }
+            "return_call" => ReturnCall(parse_idx(rest)?),
+            "return_call_indirect" => ReturnCallIndirect(FunctionType::from_str(rest)?, Idx::from(0u32)),
 
             "drop" => Drop,
-            "select" => Select,
+            "select" if rest.trim().is_empty() => Select(None),
+            "select" => Select(Some(ValType::from_str(rest)?)),
 
             "local.get" => Local(LocalOp::Get, parse_idx(rest)?),
             "local.set" => Local(LocalOp::Set, parse_idx(rest)?),
@@ -1641,10 +4278,34 @@ impl FromStr for Instr {
             "memory.size" => MemorySize(Idx::from(0u32)),
             "memory.grow" => MemoryGrow(Idx::from(0u32)),
 
+            "memory.init" => MemoryInit(parse_idx(rest)?, Idx::from(0u32)),
+            "data.drop" => DataDrop(parse_idx(rest)?),
+            "memory.copy" => MemoryCopy { dst: Idx::from(0u32), src: Idx::from(0u32) },
+            "memory.fill" => MemoryFill(Idx::from(0u32)),
+            "table.init" => TableInit(parse_idx(rest)?, Idx::from(0u32)),
+            "elem.drop" => ElemDrop(parse_idx(rest)?),
+            "table.copy" => TableCopy { dst: Idx::from(0u32), src: Idx::from(0u32) },
+
+            "atomic.fence" => AtomicFence,
+
             "i32.const" => Const(Val::from_str(rest, ValType::I32)?),
             "i64.const" => Const(Val::from_str(rest, ValType::I64)?),
             "f32.const" => Const(Val::from_str(rest, ValType::F32)?),
             "f64.const" => Const(Val::from_str(rest, ValType::F64)?),
+            "v128.const" => Const(Val::from_str(rest, ValType::V128)?),
+
+            "ref.null" => RefNull(RefType::from_str(rest)?),
+            "ref.is_null" => RefIsNull,
+            "ref.func" => RefFunc(parse_idx(rest)?),
+
+            "i8x16.shuffle" => {
+                let lanes = rest
+                    .split_whitespace()
+                    .map(|lane| parse_lane(lane, 32))
+                    .collect::<Result<Vec<u8>, _>>()?;
+                I8x16Shuffle(lanes.try_into().map_err(|_| ())?)
+            }
+            "v128.bitselect" => V128Bitselect,
 
             op if LoadOp::from_str(op).is_ok() => {
                 let op = LoadOp::from_str(op).unwrap();
@@ -1654,6 +4315,25 @@ impl FromStr for Instr {
                 let op = StoreOp::from_str(op).unwrap();
                 Store(op, Memarg::from_str(rest, op)?)
             }
+            op if AtomicOp::from_str(op).is_ok() => {
+                let op = AtomicOp::from_str(op).unwrap();
+                Atomic(op, Memarg::from_str(rest, op)?)
+            }
+
+            op if LoadLaneOp::from_str(op).is_ok() => {
+                let op = LoadLaneOp::from_str(op).unwrap();
+                let (memarg, lane) = split_lane(rest, op.lane_count())?;
+                LoadLane(op, Memarg::from_str(memarg, op)?, lane)
+            }
+            op if StoreLaneOp::from_str(op).is_ok() => {
+                let op = StoreLaneOp::from_str(op).unwrap();
+                let (memarg, lane) = split_lane(rest, op.lane_count())?;
+                StoreLane(op, Memarg::from_str(memarg, op)?, lane)
+            }
+            op if LaneOp::from_str(op).is_ok() => {
+                let op = LaneOp::from_str(op).unwrap();
+                Lane(op, parse_lane(rest, op.lane_count())?)
+            }
 
             op if UnaryOp::from_str(op).is_ok() => UnaryOp::from_str(op).map(Unary)?,
             op if BinaryOp::from_str(op).is_ok() => BinaryOp::from_str(op).map(Binary)?,
@@ -1672,8 +4352,17 @@ impl fmt::Display for Instr {
         use self::Instr::*;
         match self {
             // instructions without arguments
-            Unreachable | Nop | Drop | Select | Return | Else | End | MemorySize(_)
-            | MemoryGrow(_) | Unary(_) | Binary(_) => Ok(()),
+            Unreachable | Nop | Drop | Select(None) | Return | Else | End | MemorySize(_)
+            | MemoryGrow(_) | MemoryCopy { .. } | MemoryFill(_) | TableCopy { .. } | Unary(_)
+            | Binary(_) | RefIsNull | V128Bitselect | AtomicFence => Ok(()),
+
+            Unparsed(bytes) => write!(f, " ({} bytes)", bytes.len()),
+            Unsupported(extension, bytes) => write!(f, " ({}, {} bytes)", extension.id(), bytes.len()),
+
+            MemoryInit(data_idx, _) | DataDrop(data_idx) => write!(f, " {}", data_idx.to_u32()),
+            TableInit(elem_idx, _) | ElemDrop(elem_idx) => write!(f, " {}", elem_idx.to_u32()),
+
+            Select(Some(ty)) => write!(f, " {ty}"),
 
             Block(ty) | Loop(ty) | If(ty) => write!(f, " {ty}"),
 
@@ -1690,6 +4379,8 @@ impl fmt::Display for Instr {
             // We don't print the table index, because we also don't for memory.size and memory.grow,
             // and because in the MVP the table index is going to be 0 anyway.
             CallIndirect(func_ty, _table_idx) => write!(f, " {func_ty}"),
+            ReturnCall(func_idx) => write!(f, " {}", func_idx.to_u32()),
+            ReturnCallIndirect(func_ty, _table_idx) => write!(f, " {func_ty}"),
 
             Local(_, local_idx) => write!(f, " {}", local_idx.to_u32()),
             Global(_, global_idx) => write!(f, " {}", global_idx.to_u32()),
@@ -1706,8 +4397,39 @@ impl fmt::Display for Instr {
                 }
                 memarg.fmt(f, *op)
             }
+            Atomic(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
 
             Const(val) => write!(f, " {val}"),
+
+            RefNull(ty) => write!(f, " {ty}"),
+            RefFunc(func_idx) => write!(f, " {}", func_idx.to_u32()),
+
+            LoadLane(op, memarg, lane) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)?;
+                write!(f, " {lane}")
+            }
+            StoreLane(op, memarg, lane) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)?;
+                write!(f, " {lane}")
+            }
+            Lane(_, lane) => write!(f, " {lane}"),
+            I8x16Shuffle(lanes) => {
+                for lane in lanes {
+                    write!(f, " {lane}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1717,32 +4439,86 @@ impl fmt::Display for Instr {
 impl Module {
     // Convenient iterators over functions, globals, tables, and memories that include the (typed,
     // high-level) index as well.
-    // TODO Add _mut variants for globals, tables, and memories, if needed.
 
-    pub fn functions(&self) -> impl Iterator<Item = (Idx<Function>, &Function)> {
-        self.functions
-            .iter()
-            .enumerate()
-            .map(|(i, f)| (i.into(), f))
+    pub fn functions(&self) -> impl Iterator<Item = (Idx<Function>, &Function)> {
+        self.functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (i.into(), f))
+    }
+
+    pub fn functions_mut(&mut self) -> impl Iterator<Item = (Idx<Function>, &mut Function)> {
+        self.functions
+            .iter_mut()
+            .enumerate()
+            .map(|(i, f)| (i.into(), f))
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = (Idx<Global>, &Global)> {
+        self.globals.iter().enumerate().map(|(i, g)| (i.into(), g))
+    }
+
+    pub fn globals_mut(&mut self) -> impl Iterator<Item = (Idx<Global>, &mut Global)> {
+        self.globals.iter_mut().enumerate().map(|(i, g)| (i.into(), g))
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = (Idx<Table>, &Table)> {
+        self.tables.iter().enumerate().map(|(i, t)| (i.into(), t))
+    }
+
+    pub fn tables_mut(&mut self) -> impl Iterator<Item = (Idx<Table>, &mut Table)> {
+        self.tables.iter_mut().enumerate().map(|(i, t)| (i.into(), t))
+    }
+
+    pub fn memories(&self) -> impl Iterator<Item = (Idx<Memory>, &Memory)> {
+        self.memories.iter().enumerate().map(|(i, m)| (i.into(), m))
+    }
+
+    pub fn memories_mut(&mut self) -> impl Iterator<Item = (Idx<Memory>, &mut Memory)> {
+        self.memories.iter_mut().enumerate().map(|(i, m)| (i.into(), m))
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (Idx<Tag>, &Tag)> {
+        self.tags.iter().enumerate().map(|(i, t)| (i.into(), t))
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = (Idx<Element>, &Element)> {
+        self.elements.iter().enumerate().map(|(i, e)| (i.into(), e))
+    }
+
+    /// All memory accesses (i.e., instructions with a `Memarg`) in all function bodies, in order.
+    /// Lazy, i.e., does not allocate.
+    pub fn memory_accesses(&self) -> impl Iterator<Item = MemAccess> + '_ {
+        self.functions().flat_map(|(func, function)| {
+            function.instrs().iter().enumerate().filter_map(move |(instr_idx, instr)| {
+                let (op, memarg) = instr.memory_access()?;
+                Some(MemAccess { func, instr_idx, op, memarg })
+            })
+        })
+    }
+
+    /// Whether any (defined or imported) memory is shared, i.e., the module is meant to be used
+    /// by multiple threads (threads proposal).
+    pub fn uses_shared_memory(&self) -> bool {
+        self.memories.iter().any(|memory| memory.shared)
     }
 
-    pub fn functions_mut(&mut self) -> impl Iterator<Item = (Idx<Function>, &mut Function)> {
-        self.functions
-            .iter_mut()
-            .enumerate()
-            .map(|(i, f)| (i.into(), f))
+    // Only the (typed) indices, e.g., for iterating while mutating the module.
+
+    pub fn function_idxs(&self) -> IdxRange<Function> {
+        IdxRange::up_to(self.functions.len())
     }
 
-    pub fn globals(&self) -> impl Iterator<Item = (Idx<Global>, &Global)> {
-        self.globals.iter().enumerate().map(|(i, g)| (i.into(), g))
+    pub fn global_idxs(&self) -> IdxRange<Global> {
+        IdxRange::up_to(self.globals.len())
     }
 
-    pub fn tables(&self) -> impl Iterator<Item = (Idx<Table>, &Table)> {
-        self.tables.iter().enumerate().map(|(i, t)| (i.into(), t))
+    pub fn table_idxs(&self) -> IdxRange<Table> {
+        IdxRange::up_to(self.tables.len())
     }
 
-    pub fn memories(&self) -> impl Iterator<Item = (Idx<Memory>, &Memory)> {
-        self.memories.iter().enumerate().map(|(i, m)| (i.into(), m))
+    pub fn memory_idxs(&self) -> IdxRange<Memory> {
+        IdxRange::up_to(self.memories.len())
     }
 
     // Convenient accessors of functions for the typed, high-level index.
@@ -1764,6 +4540,82 @@ impl Module {
         &mut self.globals[idx.to_usize()]
     }
 
+    // Lookup of entities by their export name. An entity can have multiple export names, so
+    // this matches if any of them is equal to `name`.
+
+    pub fn function_by_export(&self, name: &str) -> Option<(Idx<Function>, &Function)> {
+        self.functions().find(|(_, f)| f.export.iter().any(|export| export == name))
+    }
+
+    pub fn global_by_export(&self, name: &str) -> Option<(Idx<Global>, &Global)> {
+        self.globals().find(|(_, g)| g.export.iter().any(|export| export == name))
+    }
+
+    pub fn table_by_export(&self, name: &str) -> Option<(Idx<Table>, &Table)> {
+        self.tables().find(|(_, t)| t.export.iter().any(|export| export == name))
+    }
+
+    pub fn memory_by_export(&self, name: &str) -> Option<(Idx<Memory>, &Memory)> {
+        self.memories().find(|(_, m)| m.export.iter().any(|export| export == name))
+    }
+
+    /// All (imported and defined) functions of type `type_`, e.g., the possible targets of a
+    /// `call_indirect` with that type. Types are compared structurally, since they are stored
+    /// inline and not as indices into the type section.
+    pub fn functions_with_type(&self, type_: FunctionType) -> Vec<Idx<Function>> {
+        self.functions()
+            .filter(|(_, function)| function.type_ == type_)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Sets the debug name of a function, i.e., in the name section, which is added when encoding
+    /// if the module did not have one before. Unlike export names, it does not affect linking.
+    pub fn set_function_name(&mut self, idx: Idx<Function>, name: impl Into<String>) {
+        self.function_mut(idx).name = Some(name.into());
+    }
+
+    /// Renames the export `old` of any function, global, table, or memory to `new`, keeping its
+    /// position among the exports of that entity. Returns false (and changes nothing) if there is
+    /// no export `old`, or if `new` is already exported, since export names must be unique.
+    pub fn rename_export(&mut self, old: &str, new: impl Into<String>) -> bool {
+        let new = new.into();
+        let functions = self.functions.iter_mut().map(|f| &mut f.export);
+        let globals = self.globals.iter_mut().map(|g| &mut g.export);
+        let tables = self.tables.iter_mut().map(|t| &mut t.export);
+        let memories = self.memories.iter_mut().map(|m| &mut m.export);
+        let mut exports: Vec<&mut String> = functions.chain(globals).chain(tables).chain(memories).flatten().collect();
+        if exports.iter().any(|export| **export == new) {
+            return false;
+        }
+        match exports.iter_mut().find(|export| **export == old) {
+            Some(export) => {
+                **export = new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `(module, name, kind)` of all imports, first of functions, then globals, tables,
+    /// and memories (i.e., not necessarily in the order of the original import section).
+    pub fn imports(&self) -> impl Iterator<Item = (&str, &str, ImportKind)> {
+        let functions = self.functions()
+            .filter_map(|(i, f)| f.import().map(|(module, name)| (module, name, ImportKind::Function(i))));
+        let globals = self.globals()
+            .filter_map(|(i, g)| g.import().map(|(module, name)| (module, name, ImportKind::Global(i))));
+        let tables = self.tables()
+            .filter_map(|(i, t)| t.import().map(|(module, name)| (module, name, ImportKind::Table(i))));
+        let memories = self.memories()
+            .filter_map(|(i, m)| m.import().map(|(module, name)| (module, name, ImportKind::Memory(i))));
+        functions.chain(globals).chain(tables).chain(memories)
+    }
+
+    /// Adds a new (non-imported) function and returns its index, which stays valid for
+    /// instructions (e.g., `call`) and exports in this module.
+    /// There is no type section to update, since function types are stored inline: they are
+    /// deduplicated when encoding. Likewise, imported functions may be added after non-imported
+    /// ones, since the encoder places them first and updates all indices accordingly.
     pub fn add_function(
         &mut self,
         type_: FunctionType,
@@ -1775,12 +4627,14 @@ impl Module {
             Code {
                 locals: locals.into_iter().map(Local::new).collect(),
                 body,
+                label_names: BTreeMap::new(),
             },
             Vec::new(),
         ));
         (self.functions.len() - 1).into()
     }
 
+    /// Adds a new imported function and returns its index, see `add_function`.
     pub fn add_function_import(
         &mut self,
         type_: FunctionType,
@@ -1792,6 +4646,392 @@ impl Module {
         (self.functions.len() - 1).into()
     }
 
+    /// Like `add_function_import`, but inserts the import right after the existing imported
+    /// functions instead of appending it, such that indices in the AST keep matching those in the
+    /// encoded binary (where imports come first), e.g., for passing function indices to hooks.
+    ///
+    /// All later functions are shifted by one, and all references to them are updated: `call`,
+    /// `return_call`, and `ref.func` instructions (in function bodies, global initializers, and
+    /// segment offsets and items), function lists of element segments, and the start function.
+    /// Exports and names from the name section are stored in the `Function` itself, so they move
+    /// along with it.
+    ///
+    /// Returns an error (and leaves the module unchanged) if a function body contains placeholders
+    /// (see `OnUnsupported`), since the function indices in their raw bytes cannot be updated.
+    pub fn insert_imported_function(&mut self, type_: FunctionType, module: String, name: String) -> Result<Idx<Function>, RenumberError> {
+        let new_idx = self.functions.iter()
+            .rposition(|function| function.is_imported())
+            .map_or(0, |last_import| last_import + 1);
+        self.remap_function_indices(|idx| Some(if idx.to_usize() >= new_idx { idx.next() } else { idx }))?;
+        self.functions.insert(new_idx, Function::new_imported(type_, module, name, Vec::new()));
+        Ok(new_idx.into())
+    }
+
+    /// Removes all non-imported functions that are unreachable, i.e., cannot be (transitively)
+    /// called from exported functions, the start function, or functions in element segments (which
+    /// includes all possible targets of `call_indirect`, see `callgraph`). Functions referenced
+    /// by `ref.func` in global initializers are kept as well.
+    ///
+    /// The remaining functions are renumbered and all references to them are updated, like in
+    /// `insert_imported_function`. For how many bytes were saved, see `RemovedFunctions::byte_size`
+    /// if the module was parsed with offsets, or compare the length of `to_bytes()` otherwise.
+    ///
+    /// Returns an error (and leaves the module unchanged) if a function body contains placeholders
+    /// (see `OnUnsupported`), since calls in their raw bytes are neither visible in the call graph
+    /// nor can they be updated, or if a kept function references a removed or non-existing one.
+    pub fn remove_unreachable_functions(&mut self) -> Result<RemovedFunctions, RenumberError> {
+        self.check_no_placeholders()?;
+
+        let mut roots: Vec<Idx<Function>> = Vec::new();
+        for element in &self.elements {
+            match &element.items {
+                ElementItems::Functions(functions) => roots.extend(functions),
+                ElementItems::Expressions(exprs) => roots.extend(exprs.iter().flatten().filter_map(|instr| match instr {
+                    Instr::RefFunc(idx) => Some(*idx),
+                    _ => None,
+                })),
+            }
+        }
+        for global in &self.globals {
+            if let ImportOrPresent::Present(init) = &global.init {
+                roots.extend(init.iter().filter_map(|instr| match instr {
+                    Instr::RefFunc(idx) => Some(*idx),
+                    _ => None,
+                }));
+            }
+        }
+        let call_graph = crate::callgraph::call_graph(self);
+        let reachable = call_graph.reachable_from(call_graph.roots.iter().copied().chain(roots));
+
+        let mut removed = RemovedFunctions::default();
+        let mut new_idx = Vec::with_capacity(self.functions.len());
+        let mut kept = 0usize;
+        for (idx, function) in self.functions() {
+            if function.is_imported() || reachable.contains(&idx) {
+                new_idx.push(Some(kept.into()));
+                kept += 1;
+            } else {
+                new_idx.push(None);
+                removed.functions.push(idx);
+                removed.instr_count += function.instr_count();
+            }
+        }
+        if removed.functions.is_empty() {
+            return Ok(removed);
+        }
+
+        self.remap_function_indices(|idx| new_idx.get(idx.to_usize()).copied().flatten())?;
+        let mut idx = 0;
+        self.functions.retain(|_| {
+            idx += 1;
+            new_idx[idx - 1].is_some()
+        });
+        Ok(removed)
+    }
+
+    /// Returns an error for the first function whose body contains placeholders, see
+    /// `RenumberError::Placeholder`.
+    pub(crate) fn check_no_placeholders(&self) -> Result<(), RenumberError> {
+        match self.functions().find(|(_, function)| function.instrs().iter().any(|instr| matches!(instr, Instr::Unparsed(_) | Instr::Unsupported(..)))) {
+            Some((idx, _)) => Err(RenumberError::Placeholder(idx)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces every reference to a function with `map(old_idx)`: `call`, `return_call`, and
+    /// `ref.func` instructions (in function bodies, global initializers, and segment offsets and
+    /// items), function lists of element segments, and the start function.
+    /// Bodies of functions that `map` to `None` are skipped, since they are about to be removed.
+    ///
+    /// Returns an error (and leaves the module unchanged) if any function body contains
+    /// placeholders, or if any other reference maps to `None`.
+    pub(crate) fn remap_function_indices(&mut self, map: impl Fn(Idx<Function>) -> Option<Idx<Function>>) -> Result<(), RenumberError> {
+        self.check_no_placeholders()?;
+        // Check all references before changing any, such that the module stays consistent.
+        let mut invalid = None;
+        self.for_each_function_ref_mut(&map, |idx| {
+            if invalid.is_none() && map(*idx).is_none() {
+                invalid = Some(*idx);
+            }
+        });
+        if let Some(idx) = invalid {
+            return Err(RenumberError::InvalidFunction(idx));
+        }
+        self.for_each_function_ref_mut(&map, |idx| *idx = map(*idx).expect("all references were checked above"));
+        Ok(())
+    }
+
+    fn for_each_function_ref_mut(&mut self, map: impl Fn(Idx<Function>) -> Option<Idx<Function>>, mut f: impl FnMut(&mut Idx<Function>)) {
+        fn visit_instr(instr: &mut Instr, f: &mut impl FnMut(&mut Idx<Function>)) {
+            if let Instr::Call(idx) | Instr::ReturnCall(idx) | Instr::RefFunc(idx) = instr {
+                f(idx);
+            }
+        }
+        for (idx, function) in self.functions.iter_mut().enumerate() {
+            if map(idx.into()).is_none() {
+                continue;
+            }
+            if let Some(instrs) = function.instrs_mut() {
+                instrs.iter_mut().for_each(|instr| visit_instr(instr, &mut f));
+            }
+        }
+        for global in &mut self.globals {
+            if let ImportOrPresent::Present(init) = &mut global.init {
+                init.iter_mut().for_each(|instr| visit_instr(instr, &mut f));
+            }
+        }
+        for element in &mut self.elements {
+            if let ElementMode::Active { offset, .. } = &mut element.mode {
+                offset.iter_mut().for_each(|instr| visit_instr(instr, &mut f));
+            }
+            match &mut element.items {
+                ElementItems::Functions(functions) => functions.iter_mut().for_each(&mut f),
+                ElementItems::Expressions(exprs) => exprs.iter_mut().flatten().for_each(|instr| visit_instr(instr, &mut f)),
+            }
+        }
+        for data in &mut self.data {
+            if let DataMode::Active { offset, .. } = &mut data.mode {
+                offset.iter_mut().for_each(|instr| visit_instr(instr, &mut f));
+            }
+        }
+        if let Some(start) = &mut self.start {
+            f(start);
+        }
+    }
+
+    /// Checks that all references between module-level entities are valid, i.e., the start
+    /// function (index and type), the uniqueness of export names, the table (index and reference
+    /// type) and functions of element segments, the memory of data segments, and the data count.
+    /// The parser already rejects most of these, but they can become invalid when a module is
+    /// modified, e.g., after removing functions.
+    ///
+    /// Returns all invalid references instead of just the first one. References in function bodies
+    /// are not checked here, see `validate::validate`.
+    pub fn check_references(&self) -> Result<(), Vec<RefError>> {
+        let mut errors = Vec::new();
+        let function_exists = |idx: Idx<Function>| idx.to_usize() < self.functions.len();
+
+        if let Some(start) = self.start {
+            if !function_exists(start) {
+                errors.push(RefError::InvalidStart(start));
+            } else if self.function(start).type_ != FunctionType::empty() {
+                errors.push(RefError::StartType(start, self.function(start).type_));
+            }
+        }
+
+        let mut export_names = HashSet::new();
+        let exports = self.functions.iter().flat_map(|function| &function.export)
+            .chain(self.globals.iter().flat_map(|global| &global.export))
+            .chain(self.tables.iter().flat_map(|table| &table.export))
+            .chain(self.memories.iter().flat_map(|memory| &memory.export));
+        for name in exports {
+            if !export_names.insert(name) {
+                errors.push(RefError::DuplicateExport(name.clone()));
+            }
+        }
+
+        for (element_idx, element) in self.elements() {
+            if let ElementMode::Active { table: table_idx, .. } = element.mode {
+                match self.tables.get(table_idx.to_usize()) {
+                    None => errors.push(RefError::InvalidElementTable { element: element_idx, table: table_idx }),
+                    Some(table) if !element.ref_type.is_subtype_of(table.ref_type) => errors.push(RefError::ElementTypeMismatch {
+                        element: element_idx,
+                        element_type: element.ref_type,
+                        table: table_idx,
+                        table_type: table.ref_type,
+                    }),
+                    Some(_) => {}
+                }
+            }
+            let functions: Vec<Idx<Function>> = match &element.items {
+                ElementItems::Functions(functions) => functions.clone(),
+                ElementItems::Expressions(exprs) => exprs.iter().flatten().filter_map(|instr| match instr {
+                    Instr::RefFunc(idx) => Some(*idx),
+                    _ => None,
+                }).collect(),
+            };
+            for function in functions {
+                if !function_exists(function) {
+                    errors.push(RefError::InvalidElementFunction { element: element_idx, function });
+                }
+            }
+        }
+
+        for (data_idx, data) in self.data.iter().enumerate() {
+            if let DataMode::Active { memory, .. } = data.mode {
+                if memory.to_usize() >= self.memories.len() {
+                    errors.push(RefError::InvalidDataMemory { data: data_idx.into(), memory });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Instruments the module such that the function `hook` with type `[i32] -> []` is called
+    /// before every call (including `call_indirect` and tail calls), with the index of the callee
+    /// as argument, or -1 if it is only known at runtime. The body of `hook` itself (if it is not
+    /// imported) is not instrumented.
+    ///
+    /// The hook is usually an import added with `insert_imported_function`, such that the indices
+    /// passed to it are the same in the AST and in the encoded binary. (In general, the index
+    /// passed to the hook is the one in the _encoded_ binary, where imported functions come first.)
+    ///
+    /// Panics if `hook` is not a function of type `[i32] -> []`.
+    pub fn instrument_calls(&mut self, hook: Idx<Function>) {
+        assert_eq!(self.function(hook).type_, FunctionType::new(&[ValType::I32], &[]), "hook {hook:?} has the wrong type");
+
+        // Same order as the encoder: first all imported, then all non-imported functions.
+        let mut encoded_idx = vec![0; self.functions.len()];
+        let imported_first = self.functions()
+            .filter(|(_, function)| function.is_imported())
+            .chain(self.functions().filter(|(_, function)| function.is_defined()));
+        for (new_idx, (idx, _)) in imported_first.enumerate() {
+            encoded_idx[idx.to_usize()] = new_idx as i32;
+        }
+
+        for (idx, function) in self.functions_mut() {
+            if idx == hook {
+                continue;
+            }
+            function.modify_instrs(|instr| {
+                let callee = match instr {
+                    Instr::Call(callee) | Instr::ReturnCall(callee) => encoded_idx[callee.to_usize()],
+                    Instr::CallIndirect(..) | Instr::ReturnCallIndirect(..) => -1,
+                    instr => return vec![instr],
+                };
+                vec![Instr::Const(Val::I32(callee)), Instr::Call(hook), instr]
+            });
+        }
+    }
+
+    /// The initial value of global `idx`, i.e., its evaluated initializer, see `eval_const_expr`.
+    /// Also works for mutable globals, where it is the value before any `global.set`.
+    ///
+    /// `imported` gives the values of imported globals (e.g., from the import object at
+    /// instantiation), both for `idx` itself and for `global.get`s in initializers. If it returns
+    /// `None`, the result is `ConstEvalError::ImportedGlobal`, so pass `|_| None` if unknown.
+    pub fn global_init_value(&self, idx: Idx<Global>, imported: impl Fn(Idx<Global>) -> Option<Val>) -> Result<Val, ConstEvalError> {
+        let global = self.globals.get(idx.to_usize()).ok_or(ConstEvalError::InvalidGlobal(idx))?;
+        match &global.init {
+            ImportOrPresent::Import(_, _) => imported(idx).ok_or(ConstEvalError::ImportedGlobal(idx)),
+            ImportOrPresent::Present(init) => crate::const_eval::eval_const_expr_with_imports(init, self, &imported),
+        }
+    }
+
+    /// Returns the function in each slot of table `idx` after instantiation, i.e., after copying all active element segments for it into the table.
+    /// Like during instantiation, later segments overwrite earlier ones if they overlap, and
+    /// `ref.null` items clear a slot. Passive and declared element segments are not included.
+    ///
+    /// Returns an error if there is no table `idx`, if an offset cannot be evaluated statically
+    /// (see `eval_const_expr`), or if an item is neither `ref.func` nor `ref.null` (e.g., a `global.get` of a `funcref` global).
+    pub fn resolved_table_elements(&self, idx: Idx<Table>) -> Result<BTreeMap<u32, Idx<Function>>, ConstEvalError> {
+        if idx.to_usize() >= self.tables.len() {
+            return Err(ConstEvalError::InvalidTable(idx));
+        }
+
+        let mut slots = BTreeMap::new();
+        for (element_idx, element) in self.elements.iter().enumerate() {
+            let element_idx: Idx<Element> = element_idx.into();
+            let ElementMode::Active { table, offset } = &element.mode else {
+                continue;
+            };
+            if *table != idx {
+                continue;
+            }
+            let offset = crate::eval_const_expr(offset, self)?
+                .as_i32()
+                .ok_or(ConstEvalError::InvalidElementOffset(element_idx))? as u32;
+
+            let slot = |item_idx: usize| {
+                u32::try_from(item_idx)
+                    .ok()
+                    .and_then(|item_idx| offset.checked_add(item_idx))
+                    .ok_or(ConstEvalError::InvalidElementOffset(element_idx))
+            };
+            match &element.items {
+                ElementItems::Functions(functions) => {
+                    for (item_idx, function) in functions.iter().enumerate() {
+                        slots.insert(slot(item_idx)?, *function);
+                    }
+                }
+                ElementItems::Expressions(exprs) => {
+                    for (item_idx, expr) in exprs.iter().enumerate() {
+                        match expr.as_slice() {
+                            [Instr::RefFunc(function), Instr::End] => {
+                                slots.insert(slot(item_idx)?, *function);
+                            }
+                            [Instr::RefNull(_), Instr::End] => {
+                                slots.remove(&slot(item_idx)?);
+                            }
+                            _ => return Err(ConstEvalError::UnresolvedElementItem { element: element_idx, item_idx }),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(slots)
+    }
+
+    /// Returns the initial contents of memory `idx`, i.e., the absolute offsets and bytes of all
+    /// active data segments for it, sorted by offset and without overlap. Like during
+    /// instantiation, later segments overwrite earlier ones where they overlap, so only the parts of
+    /// a segment that are not overwritten are included. Passive data segments are not included,
+    /// since they are only copied by `memory.init`.
+    ///
+    /// Returns an error if there is no memory `idx`, or if an offset cannot be evaluated statically
+    /// (see `eval_const_expr`) or the segment does not fit into a 32-bit address space.
+    pub fn initial_memory_bytes(&self, idx: Idx<Memory>) -> Result<Vec<(u32, &[u8])>, ConstEvalError> {
+        if idx.to_usize() >= self.memories.len() {
+            return Err(ConstEvalError::InvalidMemory(idx));
+        }
+
+        let mut chunks: BTreeMap<u32, &[u8]> = BTreeMap::new();
+        for (data_idx, data) in self.data.iter().enumerate() {
+            let data_idx: Idx<Data> = data_idx.into();
+            let DataMode::Active { memory, offset } = &data.mode else {
+                continue;
+            };
+            if *memory != idx {
+                continue;
+            }
+            let offset = match crate::eval_const_expr(offset, self)? {
+                Val::I32(offset) => offset as u32,
+                Val::I64(offset) => u32::try_from(offset).map_err(|_| ConstEvalError::InvalidDataOffset(data_idx))?,
+                _ => return Err(ConstEvalError::InvalidDataOffset(data_idx)),
+            };
+            let bytes = data.bytes.as_slice();
+            let end = u64::from(offset) + bytes.len() as u64;
+            if end > 1 << 32 {
+                return Err(ConstEvalError::InvalidDataOffset(data_idx));
+            }
+            if bytes.is_empty() {
+                continue;
+            }
+
+            // Cut the overwritten range out of earlier chunks, keeping what is left before and after it.
+            let overlapping: Vec<(u32, &[u8])> = chunks.iter()
+                .filter(|(start, chunk)| u64::from(**start) < end && u64::from(**start) + chunk.len() as u64 > u64::from(offset))
+                .map(|(start, chunk)| (*start, *chunk))
+                .collect();
+            for (start, chunk) in overlapping {
+                chunks.remove(&start);
+                if start < offset {
+                    chunks.insert(start, &chunk[..(offset - start) as usize]);
+                }
+                if u64::from(start) + chunk.len() as u64 > end {
+                    chunks.insert(end as u32, &chunk[(end - u64::from(start)) as usize..]);
+                }
+            }
+            chunks.insert(offset, bytes);
+        }
+        Ok(chunks.into_iter().collect())
+    }
+
     pub fn add_global(
         &mut self,
         type_: ValType,
@@ -1802,6 +5042,7 @@ impl Module {
             type_: GlobalType(type_, mut_),
             init: ImportOrPresent::Present(init),
             export: Vec::new(),
+            name: None,
         });
         (self.globals.len() - 1).into()
     }
@@ -1841,6 +5082,15 @@ impl Function {
         }
     }
 
+    pub fn is_imported(&self) -> bool {
+        matches!(self.code, ImportOrPresent::Import(..))
+    }
+
+    /// The opposite of `is_imported`.
+    pub fn is_defined(&self) -> bool {
+        !self.is_imported()
+    }
+
     pub fn code(&self) -> Option<&Code> {
         if let ImportOrPresent::Present(t) = &self.code {
             Some(t)
@@ -1865,10 +5115,14 @@ impl Function {
         }
     }
 
+    /// Returns the instructions of the function body, without having to match on `self.code`.
+    /// Imported functions have no body, so an empty slice is returned for them. (Use `code()` if
+    /// you need to distinguish an imported function from one with an empty body.)
     pub fn instrs(&self) -> &[Instr] {
         self.code().map(|code| code.body.as_slice()).unwrap_or(&[])
     }
 
+    /// Returns the mutable instruction body, or `None` for imported functions.
     pub fn instrs_mut(&mut self) -> Option<&mut Vec<Instr>> {
         self.code_mut().map(|code| &mut code.body)
     }
@@ -1905,17 +5159,32 @@ impl Function {
 
     // Functions for the number of parameters and non-parameter locals.
 
+    /// Number of parameters, which come first in the index space of locals.
     pub fn param_count(&self) -> usize {
         self.type_.inputs().len()
     }
 
+    /// Number of declared (non-parameter) locals, which are numbered after the parameters.
+    /// Zero for imported functions.
     pub fn local_count(&self) -> usize {
         self.code().map(|code| code.locals.len()).unwrap_or(0)
     }
 
+    /// Return the type of the function parameter or non-parameter local with index idx, or `None`
+    /// if the index is out of bounds (e.g., for any non-parameter local of an imported function).
+    ///
+    /// Unlike `param_or_local_type`, this does not panic, e.g., for validating instructions.
+    pub fn local_type(&self, idx: Idx<Local>) -> Option<ValType> {
+        let idx = idx.to_usize();
+        match idx.checked_sub(self.param_count()) {
+            None => Some(self.type_.inputs()[idx]),
+            Some(local_idx) => self.code()?.locals.get(local_idx).map(|local| local.type_),
+        }
+    }
+
     // Accessors and iterators for parameters and locals uniformly.
 
-    pub fn param_or_local(&self, idx: Idx<Local>) -> ParamOrLocalRef {
+    pub fn param_or_local(&self, idx: Idx<Local>) -> ParamOrLocalRef<'_> {
         self.param_or_locals()
             .nth(idx.to_usize())
             .expect("invalid local index")
@@ -1930,7 +5199,7 @@ impl Function {
     //         .1
     // }
 
-    pub fn param_or_locals(&self) -> impl Iterator<Item = (Idx<Local>, ParamOrLocalRef)> {
+    pub fn param_or_locals(&self) -> impl Iterator<Item = (Idx<Local>, ParamOrLocalRef<'_>)> {
         let params = self.params().map(|(i, p)| (i, ParamOrLocalRef::Param(p)));
         let locals = self.locals().map(|(i, l)| (i, ParamOrLocalRef::Local(l)));
         params.chain(locals)
@@ -1966,7 +5235,7 @@ impl Function {
     // }
 
     /// Returns the parameters (type and debug name, if any) together with their index.
-    pub fn params(&self) -> impl Iterator<Item=(Idx<Local>, ParamRef)> {
+    pub fn params(&self) -> impl Iterator<Item=(Idx<Local>, ParamRef<'_>)> {
         self.type_.inputs()
             .iter()
             .enumerate()
@@ -2087,8 +5356,263 @@ impl Code {
         Code {
             locals: Vec::new(),
             body: Vec::new(),
+            label_names: BTreeMap::new(),
+        }
+    }
+
+    /// The locals as run-length encoded groups of `(count, type)`, like in the binary format,
+    /// where consecutive locals of the same type are merged into one group.
+    /// (The parser expands the groups into individual `locals`.)
+    pub fn locals_grouped(&self) -> Vec<(u32, ValType)> {
+        let mut groups: Vec<(u32, ValType)> = Vec::new();
+        for local in &self.locals {
+            match groups.last_mut() {
+                Some((count, type_)) if *type_ == local.type_ => *count += 1,
+                _ => groups.push((1, local.type_)),
+            }
+        }
+        groups
+    }
+
+    /// Returns a human-readable listing of the body, e.g., for debugging or in test failures.
+    /// Each instruction is printed on its own line (in the format of `Instr`'s `Display`) and
+    /// indented by its block nesting depth. Unlike `wat::module_to_wat`, no names are resolved.
+    pub fn disassemble(&self) -> String {
+        use std::fmt::Write;
+        let mut out = String::new();
+        let mut depth = 0usize;
+        for instr in &self.body {
+            // `else` and `end` belong to the enclosing block, so are printed one level less indented.
+            let instr_depth = match instr {
+                Instr::Else | Instr::End => depth.saturating_sub(1),
+                _ => depth,
+            };
+            writeln!(out, "{}{instr}", "  ".repeat(instr_depth)).unwrap();
+            match instr {
+                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => depth += 1,
+                Instr::End => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// Inserts `instrs` before the instruction at index `at` (like `Vec::splice`).
+    ///
+    /// Invariants: The inserted instructions must be balanced, i.e., every `block`, `loop`, and
+    /// `if` has its matching `end` (and `else` only appears in an `if`) within `instrs`. Then, the
+    /// block structure of the existing instructions does not change, so their relative branch
+    /// labels stay valid and need no adjustment. Branches in `instrs` are relative to the nesting
+    /// at `at`, which is not checked here (see `validate::validate`).
+    /// Keys of `label_names` of blocks after `at` are shifted by the number of inserted blocks.
+    ///
+    /// Panics if `at > self.body.len()`, returns an error if `instrs` is not balanced.
+    pub fn insert_instrs(&mut self, at: usize, instrs: &[Instr]) -> Result<(), BlockStructureError> {
+        assert!(at <= self.body.len(), "insertion index {at} out of bounds for body of length {}", self.body.len());
+        check_balanced_blocks(instrs)?;
+
+        let blocks_before = count_blocks(&self.body[..at]);
+        self.shift_label_names(blocks_before, count_blocks(instrs) as isize);
+        self.body.splice(at..at, instrs.iter().cloned());
+        Ok(())
+    }
+
+    /// Removes and returns the instructions in `range` (like `Vec::drain`).
+    ///
+    /// Invariants: Like for `insert_instrs`, the removed instructions must be balanced, such that
+    /// the block structure of the remaining instructions (and thus their branch labels) stays
+    /// valid. In particular, the final `end` of the function body cannot be removed.
+    /// Names of removed blocks are removed from `label_names`, and the keys of later blocks shifted.
+    ///
+    /// Panics if `range` is out of bounds, returns an error if the removed instructions are not
+    /// balanced (in which case nothing is removed).
+    pub fn remove_range(&mut self, range: std::ops::Range<usize>) -> Result<Vec<Instr>, BlockStructureError> {
+        check_balanced_blocks(&self.body[range.clone()]).map_err(|err| BlockStructureError {
+            instr_idx: range.start + err.instr_idx,
+            ..err
+        })?;
+
+        let blocks_before = count_blocks(&self.body[..range.start]);
+        let removed_blocks = count_blocks(&self.body[range.clone()]);
+        self.label_names.retain(|&label, _| !(blocks_before..blocks_before + removed_blocks).contains(&(label as usize)));
+        self.shift_label_names(blocks_before, -(removed_blocks as isize));
+        Ok(self.body.drain(range).collect())
+    }
+
+    /// Replaces every instruction by the (zero or more) instructions returned by `f`, e.g., to
+    /// expand an instruction into several or to delete it. Unlike `Function::modify_instrs`, the
+    /// result is checked afterwards to still be a properly nested function body, i.e., every
+    /// `block`, `loop`, and `if` has its matching `end` and the body ends with the function's
+    /// final `end`. Branch labels are not adjusted (see `insert_instrs` for when they stay valid).
+    /// The name in `label_names` of a block moves to the first block in its replacement, or is
+    /// removed if there is none.
+    ///
+    /// Returns an error if the result is not properly nested (in which case nothing is changed).
+    pub fn map_instrs(&mut self, mut f: impl FnMut(Instr) -> Vec<Instr>) -> Result<(), BlockStructureError> {
+        let mut new_body = Vec::with_capacity(self.body.len());
+        let mut label_names = BTreeMap::new();
+        let mut old_block_count = 0u32;
+        let mut new_block_count = 0;
+        for instr in self.body.iter().cloned() {
+            let opens_block = matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_));
+            let replacement = f(instr);
+            if opens_block {
+                if let (Some(name), Some(first_block)) = (
+                    self.label_names.get(&old_block_count),
+                    replacement.iter().position(|instr| matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_))),
+                ) {
+                    let new_label = new_block_count + count_blocks(&replacement[..first_block]);
+                    label_names.insert(new_label as u32, name.clone());
+                }
+                old_block_count += 1;
+            }
+            new_block_count += count_blocks(&replacement);
+            new_body.extend(replacement);
+        }
+
+        match new_body.split_last() {
+            Some((Instr::End, instrs)) => check_balanced_blocks(instrs)?,
+            _ => return Err(BlockStructureError {
+                instr_idx: new_body.len(),
+                message: "function body must end with end",
+            }),
+        }
+        self.body = new_body;
+        self.label_names = label_names;
+        Ok(())
+    }
+
+    /// The instructions from the `block`, `loop`, or `if` at `block_start` up to and including its
+    /// matching `end`, such that `&self.body[range]` is the whole (nested) block. See `else_of` for
+    /// where the "else" branch of an `if` starts.
+    /// Returns `None` if there is no block at `block_start` or it has no matching `end`.
+    pub fn block_extent(&self, block_start: usize) -> Option<std::ops::Range<usize>> {
+        match self.body.get(block_start)? {
+            Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => {
+                let end = matching_end(&self.body, block_start)?;
+                Some(block_start..end + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Index of the `else` that belongs to the `if` at `if_start` (and not to a nested `if`).
+    /// Returns `None` if there is no `if` at `if_start` or it has no `else`.
+    pub fn else_of(&self, if_start: usize) -> Option<usize> {
+        let Instr::If(_) = self.body.get(if_start)? else {
+            return None;
+        };
+        let mut depth = 0usize;
+        for (instr_idx, instr) in self.body.iter().enumerate().skip(if_start) {
+            match instr {
+                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => depth += 1,
+                Instr::Else if depth == 1 => return Some(instr_idx),
+                Instr::End => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Resolves the relative `label` of a branch at `instr_idx` (e.g., `br 2`) to the instruction
+    /// it targets, by tracking which blocks are open at `instr_idx`.
+    /// Returns `None` if `instr_idx` is out of bounds, if `label` is deeper than the open blocks
+    /// (plus the function body), or if the blocks in the body are not properly nested.
+    pub fn resolve_label(&self, instr_idx: usize, label: Label) -> Option<LabelTarget> {
+        if instr_idx >= self.body.len() {
+            return None;
+        }
+        let mut open_blocks = Vec::new();
+        for (idx, instr) in self.body[..instr_idx].iter().enumerate() {
+            match instr {
+                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => open_blocks.push(idx),
+                // An `end` without open blocks is the end of the function body.
+                Instr::End => {
+                    open_blocks.pop()?;
+                }
+                _ => {}
+            }
+        }
+
+        let depth = label.to_usize();
+        if depth == open_blocks.len() {
+            return match self.body.last() {
+                Some(Instr::End) => Some(LabelTarget::End(self.body.len() - 1)),
+                _ => None,
+            };
+        }
+        let begin = open_blocks[open_blocks.len().checked_sub(depth + 1)?];
+        match self.body[begin] {
+            Instr::Loop(_) => Some(LabelTarget::Loop(begin)),
+            _ => matching_end(&self.body, begin).map(LabelTarget::End),
+        }
+    }
+
+    /// Adds `shift` to all keys of `label_names` that are at least `first_label`.
+    fn shift_label_names(&mut self, first_label: usize, shift: isize) {
+        if shift == 0 {
+            return;
+        }
+        let shifted = self.label_names.split_off(&(first_label as u32));
+        self.label_names.extend(shifted.into_iter().map(|(label, name)| ((label as isize + shift) as u32, name)));
+    }
+}
+
+/// Index of the `end` that matches the `block`, `loop`, or `if` at `begin`, if any.
+fn matching_end(instrs: &[Instr], begin: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (instr_idx, instr) in instrs.iter().enumerate().skip(begin) {
+        match instr {
+            Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => depth += 1,
+            Instr::End => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(instr_idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Number of blocks (i.e., `block`, `loop`, and `if`) in `instrs`, e.g., to index `label_names`.
+fn count_blocks(instrs: &[Instr]) -> usize {
+    instrs.iter()
+        .filter(|instr| matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_)))
+        .count()
+}
+
+/// Checks that every block in `instrs` is closed by an `end` in `instrs` (and vice versa).
+fn check_balanced_blocks(instrs: &[Instr]) -> Result<(), BlockStructureError> {
+    let error = |instr_idx, message| Err(BlockStructureError { instr_idx, message });
+    // For each open block, whether it is an `if` that can still have an `else`.
+    let mut open_blocks = Vec::new();
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        match instr {
+            Instr::Block(_) | Instr::Loop(_) => open_blocks.push(false),
+            Instr::If(_) => open_blocks.push(true),
+            Instr::Else => match open_blocks.last_mut() {
+                Some(else_allowed @ true) => *else_allowed = false,
+                _ => return error(instr_idx, "else without a matching if"),
+            },
+            Instr::End => {
+                let Some(_) = open_blocks.pop() else {
+                    return error(instr_idx, "end without a matching block, loop, or if");
+                };
+            }
+            _ => {}
         }
     }
+    if !open_blocks.is_empty() {
+        return error(instrs.len(), "block, loop, or if without a matching end");
+    }
+    Ok(())
 }
 
 impl Default for Code {
@@ -2119,6 +5643,7 @@ impl Global {
             type_,
             init: ImportOrPresent::Present(init),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2127,6 +5652,7 @@ impl Global {
             type_,
             init: ImportOrPresent::Import(import_module, import_name),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2138,6 +5664,15 @@ impl Global {
         }
     }
 
+    pub fn is_imported(&self) -> bool {
+        matches!(self.init, ImportOrPresent::Import(..))
+    }
+
+    /// The opposite of `is_imported`.
+    pub fn is_defined(&self) -> bool {
+        !self.is_imported()
+    }
+
     pub fn init(&self) -> Option<&Expr> {
         if let ImportOrPresent::Present(t) = &self.init {
             Some(t)
@@ -2148,21 +5683,23 @@ impl Global {
 }
 
 impl Table {
-    pub fn new(limits: Limits) -> Table {
+    pub fn new(limits: Limits, ref_type: RefType) -> Table {
         Table {
             limits,
+            ref_type,
             import: None,
-            elements: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
-    pub fn new_imported(limits: Limits, import_module: String, import_name: String) -> Table {
+    pub fn new_imported(limits: Limits, ref_type: RefType, import_module: String, import_name: String) -> Table {
         Table {
             limits,
+            ref_type,
             import: Some((import_module, import_name)),
-            elements: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2171,23 +5708,69 @@ impl Table {
             .as_ref()
             .map(|(module, name)| (module.as_str(), name.as_str()))
     }
+
+    pub fn is_imported(&self) -> bool {
+        self.import.is_some()
+    }
+
+    /// The opposite of `is_imported`.
+    pub fn is_defined(&self) -> bool {
+        !self.is_imported()
+    }
 }
 
 impl Memory {
     pub fn new(limits: Limits) -> Memory {
         Memory {
             limits,
+            index_type: IndexType::I32,
+            shared: false,
             import: None,
-            data: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
     pub fn new_imported(limits: Limits, import_module: String, import_name: String) -> Memory {
         Memory {
             limits,
+            index_type: IndexType::I32,
+            shared: false,
+            import: Some((import_module, import_name)),
+            export: Vec::new(),
+            name: None,
+        }
+    }
+
+    pub fn import(&self) -> Option<(&str, &str)> {
+        self.import
+            .as_ref()
+            .map(|(module, name)| (module.as_str(), name.as_str()))
+    }
+
+    pub fn is_imported(&self) -> bool {
+        self.import.is_some()
+    }
+
+    /// The opposite of `is_imported`.
+    pub fn is_defined(&self) -> bool {
+        !self.is_imported()
+    }
+}
+
+impl Tag {
+    pub fn new(type_: FunctionType) -> Tag {
+        Tag {
+            type_,
+            import: None,
+            export: Vec::new(),
+        }
+    }
+
+    pub fn new_imported(type_: FunctionType, import_module: String, import_name: String) -> Tag {
+        Tag {
+            type_,
             import: Some((import_module, import_name)),
-            data: Vec::new(),
             export: Vec::new(),
         }
     }
@@ -2197,4 +5780,13 @@ impl Memory {
             .as_ref()
             .map(|(module, name)| (module.as_str(), name.as_str()))
     }
+
+    pub fn is_imported(&self) -> bool {
+        self.import.is_some()
+    }
+
+    /// The opposite of `is_imported`.
+    pub fn is_defined(&self) -> bool {
+        !self.is_imported()
+    }
 }
@@ -0,0 +1,206 @@
+//! Static call graph of a module, with direct calls from `call` and indirect calls from
+//! `call_indirect` (and their tail call variants).
+//!
+//! Indirect calls are resolved to all functions that may be in a table and have the expected type.
+//! Which functions may be in a table is determined statically from the element segments, see
+//! `Module::resolved_table_elements`. Because `table.init` and `table.copy` can move functions from
+//! passive segments and between tables at runtime, the functions of all tables and passive
+//! segments are considered together. If a table is imported or exported (i.e., may be modified
+//! from the outside), or cannot be resolved statically, all functions of the expected type are
+//! possible targets, such that the call graph always over-approximates the actual calls.
+//!
+//! Imported functions have no body, so they have no outgoing calls, i.e., are leafs.
+
+use std::collections::BTreeSet;
+
+use crate::{ElementItems, ElementMode, Function, FunctionType, Idx, Instr, Module};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CallGraph {
+    /// Sorted by caller and then instruction index, i.e., one entry per call instruction.
+    pub calls: Vec<Call>,
+    /// Functions that can be called from the outside, i.e., exported functions and the start
+    /// function, sorted and without duplicates.
+    pub roots: Vec<Idx<Function>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Call {
+    pub caller: Idx<Function>,
+    /// Index of the call instruction in the body of `caller`.
+    pub instr_idx: usize,
+    pub kind: CallKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum CallKind {
+    Direct(Idx<Function>),
+    /// All possible targets, sorted and without duplicates. Can be empty, e.g., if no function
+    /// of the expected type is in any table, in which case the call always traps.
+    Indirect(Vec<Idx<Function>>),
+}
+
+impl CallKind {
+    pub fn callees(&self) -> &[Idx<Function>] {
+        match self {
+            CallKind::Direct(callee) => std::slice::from_ref(callee),
+            CallKind::Indirect(callees) => callees,
+        }
+    }
+}
+
+impl CallGraph {
+    /// All calls in the body of `caller`.
+    pub fn calls_from(&self, caller: Idx<Function>) -> impl Iterator<Item = &Call> + '_ {
+        // Calls are sorted by caller, so find the first one by binary search.
+        let start = self.calls.partition_point(|call| call.caller < caller);
+        self.calls[start..].iter().take_while(move |call| call.caller == caller)
+    }
+
+    /// Functions that are (directly or possibly indirectly) called from `caller`, sorted and
+    /// without duplicates.
+    pub fn callees(&self, caller: Idx<Function>) -> BTreeSet<Idx<Function>> {
+        self.calls_from(caller).flat_map(|call| call.kind.callees().iter().copied()).collect()
+    }
+
+    /// All functions that are transitively called from `roots`, including the roots themselves.
+    pub fn reachable_from(&self, roots: impl IntoIterator<Item = Idx<Function>>) -> BTreeSet<Idx<Function>> {
+        let mut reachable = BTreeSet::new();
+        let mut worklist: Vec<_> = roots.into_iter().collect();
+        while let Some(function) = worklist.pop() {
+            if reachable.insert(function) {
+                worklist.extend(self.calls_from(function).flat_map(|call| call.kind.callees().iter().copied()));
+            }
+        }
+        reachable
+    }
+
+    /// All functions that are transitively called from exported functions or the start function.
+    /// The other functions are dead code, unless they are called through a table from the outside
+    /// (e.g., if the table is exported).
+    pub fn reachable(&self) -> BTreeSet<Idx<Function>> {
+        self.reachable_from(self.roots.iter().copied())
+    }
+}
+
+/// Builds the call graph for all function bodies of `module`.
+pub fn call_graph(module: &Module) -> CallGraph {
+    let table_functions = table_functions(module);
+    let indirect_targets = |type_: FunctionType| -> Vec<Idx<Function>> {
+        match &table_functions {
+            Some(table_functions) => table_functions.iter().copied()
+                .filter(|function| module.function(*function).type_ == type_)
+                .collect(),
+            None => module.functions_with_type(type_),
+        }
+    };
+
+    let mut calls = Vec::new();
+    for (caller, function) in module.functions() {
+        for (instr_idx, instr) in function.instrs().iter().enumerate() {
+            let kind = match instr {
+                Instr::Call(callee) | Instr::ReturnCall(callee) => CallKind::Direct(*callee),
+                Instr::CallIndirect(type_, _) | Instr::ReturnCallIndirect(type_, _) => CallKind::Indirect(indirect_targets(*type_)),
+                _ => continue,
+            };
+            calls.push(Call { caller, instr_idx, kind });
+        }
+    }
+
+    let mut roots: Vec<_> = module.functions()
+        .filter(|(_, function)| !function.export.is_empty())
+        .map(|(idx, _)| idx)
+        .chain(module.start)
+        .collect();
+    roots.sort();
+    roots.dedup();
+
+    CallGraph { calls, roots }
+}
+
+/// All functions that may be in any table at runtime, or `None` if that is not known statically.
+fn table_functions(module: &Module) -> Option<BTreeSet<Idx<Function>>> {
+    let mut functions = BTreeSet::new();
+    for (idx, table) in module.tables() {
+        if table.is_imported() || !table.export.is_empty() {
+            return None;
+        }
+        functions.extend(module.resolved_table_elements(idx).ok()?.into_values());
+    }
+    for element in &module.elements {
+        if element.mode != ElementMode::Passive {
+            continue;
+        }
+        match &element.items {
+            ElementItems::Functions(items) => functions.extend(items.iter().copied()),
+            ElementItems::Expressions(exprs) => {
+                for expr in exprs {
+                    match expr.as_slice() {
+                        [Instr::RefFunc(function), Instr::End] => {
+                            functions.insert(*function);
+                        }
+                        [Instr::RefNull(_), Instr::End] => {}
+                        // E.g., `global.get` of a `funcref` global, which could be any function.
+                        _ => return None,
+                    }
+                }
+            }
+        }
+    }
+    Some(functions)
+}
+
+#[cfg(all(test, feature = "wat"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indirect_call_is_resolved_through_table() {
+        let module = Module::from_wat(r#"(module
+            (type $v (func))
+            (type $i (func (param i32)))
+            (import "env" "log" (func $log (param i32)))
+            (table 3 funcref)
+            (elem (i32.const 0) $a $b $log)
+            (func $main (export "main")
+                call $a
+                i32.const 0
+                call_indirect (type $v))
+            (func $a
+                i32.const 1
+                call $log)
+            (func $b)
+            (func $dead
+                call $b)
+        )"#).unwrap();
+        let (log, main, a, b, dead) = (0u32.into(), 1u32.into(), 2u32.into(), 3u32.into(), 4u32.into());
+
+        let graph = call_graph(&module);
+        assert_eq!(graph.roots, [main]);
+        assert_eq!(graph.calls_from(main).map(|call| call.kind.clone()).collect::<Vec<_>>(), [
+            CallKind::Direct(a),
+            CallKind::Indirect(vec![a, b]),
+        ]);
+        assert_eq!(graph.callees(a), BTreeSet::from([log]));
+        assert_eq!(graph.callees(log), BTreeSet::new());
+        assert_eq!(graph.calls_from(dead).count(), 1);
+
+        assert_eq!(graph.reachable(), BTreeSet::from([log, main, a, b]));
+        assert_eq!(graph.reachable_from([dead]), BTreeSet::from([b, dead]));
+    }
+
+    #[test]
+    fn exported_table_makes_all_functions_of_type_possible_targets() {
+        let module = Module::from_wat(r#"(module
+            (type $v (func))
+            (table (export "table") 1 funcref)
+            (func $main (export "main")
+                i32.const 0
+                call_indirect (type $v))
+            (func $f)
+            (func $g (param i32))
+        )"#).unwrap();
+        let graph = call_graph(&module);
+        assert_eq!(graph.callees(0u32.into()), BTreeSet::from([0u32.into(), 1u32.into()]));
+    }
+}
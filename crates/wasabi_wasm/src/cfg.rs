@@ -0,0 +1,431 @@
+//! Control-flow graph (CFG) of basic blocks for a function body.
+//!
+//! The structured control flow of WebAssembly (`block`, `loop`, `if`, `else`, `end`) and branches
+//! with relative label depths are resolved to explicit edges between basic blocks.
+//!
+//! Basic blocks end with any control-flow instruction, i.e., `block`, `loop`, `if`, `else`, `end`,
+//! `br`, `br_if`, `br_table`, `return`, and `unreachable`. Every block has a range of instruction
+//! indices into the function body. In addition, there is a single (empty) artificial exit block,
+//! which all `return`s and the final `end` of the function flow into.
+//!
+//! Branch targets are the block _after_ the matching `end` for `block` and `if` labels, and the
+//! block right _after_ the `loop` instruction for `loop` labels (i.e., the loop header).
+//! `unreachable` has no successors, since it always traps.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{CfgError, Code, Idx, Instr, Label};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cfg {
+    /// The first block is the entry block, the last block the (artificial, empty) exit block.
+    pub blocks: Vec<BasicBlock>,
+    /// Sorted by source block. There are no duplicate edges, e.g., when several labels of a
+    /// `br_table` target the same block.
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BasicBlock {
+    /// Indices into the instructions of the function body.
+    pub instrs: Range<usize>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Edge {
+    pub from: Idx<BasicBlock>,
+    pub to: Idx<BasicBlock>,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EdgeKind {
+    /// Control continues with the next instruction, e.g., after `block` or a not-taken `br_if`.
+    /// Also for entering the "then" branch of an `if`.
+    Fallthrough,
+    /// Control is transferred elsewhere, e.g., by `br`, a taken `br_if`, `return`, the "else"
+    /// case of an `if` (even if there is no `else`), or when reaching the `else` at the end of the
+    /// "then" branch.
+    Branch,
+}
+
+impl Cfg {
+    pub fn entry(&self) -> Idx<BasicBlock> {
+        0usize.into()
+    }
+
+    pub fn exit(&self) -> Idx<BasicBlock> {
+        (self.blocks.len() - 1).into()
+    }
+
+    pub fn block(&self, idx: Idx<BasicBlock>) -> &BasicBlock {
+        &self.blocks[idx.to_usize()]
+    }
+
+    pub fn successors(&self, idx: Idx<BasicBlock>) -> impl Iterator<Item = Idx<BasicBlock>> + '_ {
+        self.edges.iter().filter(move |edge| edge.from == idx).map(|edge| edge.to)
+    }
+
+    pub fn predecessors(&self, idx: Idx<BasicBlock>) -> impl Iterator<Item = Idx<BasicBlock>> + '_ {
+        self.edges.iter().filter(move |edge| edge.to == idx).map(|edge| edge.from)
+    }
+}
+
+/// Matching `else` (if any) and `end` instruction for every `block`, `loop`, and `if`.
+struct BlockEnds {
+    else_: HashMap<usize, usize>,
+    end: HashMap<usize, usize>,
+}
+
+impl BlockEnds {
+    fn new(instrs: &[Instr]) -> Result<Self, CfgError> {
+        let mut else_ = HashMap::new();
+        let mut end = HashMap::new();
+        let mut begin_stack = Vec::new();
+        for (instr_idx, instr) in instrs.iter().enumerate() {
+            let error = |message| CfgError { instr_idx, message };
+            match instr {
+                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => begin_stack.push(instr_idx),
+                Instr::Else => match begin_stack.last() {
+                    Some(&begin) if matches!(instrs[begin], Instr::If(_)) => {
+                        else_.insert(begin, instr_idx);
+                    }
+                    _ => return Err(error("else without matching if")),
+                },
+                Instr::End => match begin_stack.pop() {
+                    Some(begin) => {
+                        end.insert(begin, instr_idx);
+                    }
+                    // The final `end` of the function body has no matching begin.
+                    None if instr_idx + 1 == instrs.len() => {}
+                    None => return Err(error("end without matching block")),
+                },
+                // The instructions (and thus branches and blocks) in placeholders are unknown.
+                Instr::Unparsed(_) | Instr::Unsupported(..) => return Err(error("placeholder for unparsed or unsupported instructions")),
+                _ => {}
+            }
+        }
+        if let Some(&begin) = begin_stack.last() {
+            return Err(CfgError { instr_idx: begin, message: "block without matching end" });
+        }
+        Ok(BlockEnds { else_, end })
+    }
+}
+
+/// A block, loop, or if that is currently open, i.e., can be targeted by a label.
+#[derive(Debug, Copy, Clone)]
+enum Frame {
+    Function,
+    Block { end: usize },
+    Loop { begin: usize },
+}
+
+/// Builds the CFG for a function body, which should be valid (see `validate::validate`).
+/// Returns an error for malformed block structure or labels, and for bodies that contain
+/// placeholders (`Instr::Unparsed` or `Instr::Unsupported`, see `OnUnsupported`), since their
+/// control flow is unknown.
+pub fn build_cfg(code: &Code) -> Result<Cfg, CfgError> {
+    let instrs = &code.body;
+    let ends = BlockEnds::new(instrs)?;
+
+    // Instruction indices where a new basic block starts. The exit block starts at `instrs.len()`.
+    let mut leaders = block_starts(instrs);
+    leaders.push(instrs.len());
+    leaders.dedup();
+
+    let mut blocks: Vec<BasicBlock> = leaders
+        .windows(2)
+        .map(|range| BasicBlock {
+            instrs: range[0]..range[1],
+        })
+        .collect();
+    let exit: Idx<BasicBlock> = blocks.len().into();
+    blocks.push(BasicBlock {
+        instrs: instrs.len()..instrs.len(),
+    });
+
+    // Because basic blocks are sorted and contiguous, this is a binary search over the leaders.
+    let block_starting_at = |instr_idx: usize| -> Idx<BasicBlock> {
+        if instr_idx >= instrs.len() {
+            return exit;
+        }
+        let block_idx = leaders.binary_search(&instr_idx).expect("branch target should be a basic block leader");
+        block_idx.into()
+    };
+
+    let mut edges = Vec::new();
+    let mut frames = vec![Frame::Function];
+    for (block_idx, block) in blocks.iter().enumerate() {
+        let from: Idx<BasicBlock> = block_idx.into();
+        let mut add_edge = |to, kind| {
+            let edge = Edge { from, to, kind };
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        };
+
+        // The exit block has no instructions and no successors.
+        if block.instrs.is_empty() {
+            continue;
+        }
+
+        // Update the stack of open frames for all instructions, not just the last in the block.
+        for instr_idx in block.instrs.clone() {
+            match instrs[instr_idx] {
+                Instr::Block(_) | Instr::If(_) => frames.push(Frame::Block {
+                    end: ends.end[&instr_idx],
+                }),
+                Instr::Loop(_) => frames.push(Frame::Loop { begin: instr_idx }),
+                Instr::End => {
+                    frames.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let last_idx = block.instrs.end - 1;
+        let next = block_starting_at(last_idx + 1);
+        let label_target = |label: Label| -> Result<Idx<BasicBlock>, CfgError> {
+            let frame = (frames.len() - 1)
+                .checked_sub(label.to_usize())
+                .map(|frame_idx| frames[frame_idx])
+                .ok_or(CfgError { instr_idx: last_idx, message: "branch label out of range" })?;
+            Ok(match frame {
+                Frame::Function => exit,
+                Frame::Block { end } => block_starting_at(end + 1),
+                Frame::Loop { begin } => block_starting_at(begin + 1),
+            })
+        };
+
+        match &instrs[last_idx] {
+            Instr::Unreachable => {}
+            Instr::Return | Instr::ReturnCall(_) | Instr::ReturnCallIndirect(..) => add_edge(exit, EdgeKind::Branch),
+            Instr::Br(label) => add_edge(label_target(*label)?, EdgeKind::Branch),
+            Instr::BrIf(label) => {
+                add_edge(label_target(*label)?, EdgeKind::Branch);
+                add_edge(next, EdgeKind::Fallthrough);
+            }
+            Instr::BrTable { table, default } => {
+                for label in table.iter().chain(std::iter::once(default)) {
+                    add_edge(label_target(*label)?, EdgeKind::Branch);
+                }
+            }
+            Instr::If(_) => {
+                add_edge(next, EdgeKind::Fallthrough);
+                // Without an `else`, the false case continues right after the `end`.
+                let false_target = match ends.else_.get(&last_idx) {
+                    Some(else_idx) => block_starting_at(else_idx + 1),
+                    None => block_starting_at(ends.end[&last_idx] + 1),
+                };
+                add_edge(false_target, EdgeKind::Branch);
+            }
+            Instr::Else => {
+                // At the end of the "then" branch, skip over the "else" branch.
+                let Frame::Block { end } = frames[frames.len() - 1] else {
+                    unreachable!("else should be inside an if frame")
+                };
+                add_edge(block_starting_at(end + 1), EdgeKind::Branch);
+            }
+            // Includes `block`, `loop`, `end`, and blocks that end just before a branch target.
+            _ => add_edge(next, EdgeKind::Fallthrough),
+        }
+    }
+
+    edges.sort_by_key(|edge| edge.from);
+
+    Ok(Cfg { blocks, edges })
+}
+
+impl Code {
+    /// Instruction indices at which a basic block of the CFG starts, in ascending order, e.g., as
+    /// locations to instrument for coverage. Cheaper than `build_cfg`, since no edges are computed.
+    /// As in the CFG, calls do not end a basic block, and the artificial exit block is not included.
+    pub fn basic_block_starts(&self) -> Vec<usize> {
+        block_starts(&self.body)
+    }
+}
+
+fn block_starts(instrs: &[Instr]) -> Vec<usize> {
+    if instrs.is_empty() {
+        return Vec::new();
+    }
+    let mut starts = vec![0];
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        if ends_basic_block(instr) && instr_idx + 1 < instrs.len() {
+            starts.push(instr_idx + 1);
+        }
+    }
+    starts
+}
+
+fn ends_basic_block(instr: &Instr) -> bool {
+    matches!(
+        instr,
+        Instr::Block(_)
+            | Instr::Loop(_)
+            | Instr::If(_)
+            | Instr::Else
+            | Instr::End
+            | Instr::Br(_)
+            | Instr::BrIf(_)
+            | Instr::BrTable { .. }
+            | Instr::Return
+            | Instr::ReturnCall(_)
+            | Instr::ReturnCallIndirect(..)
+            | Instr::Unreachable
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BinaryOp;
+    use crate::Code;
+    use crate::FunctionType;
+    use crate::Idx;
+    use crate::Instr::*;
+    use crate::LocalOp;
+    use crate::Val;
+
+    use super::*;
+
+    fn code(body: Vec<Instr>) -> Code {
+        Code { body, ..Code::new() }
+    }
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, EdgeKind)> {
+        cfg.edges.iter().map(|edge| (edge.from.to_usize(), edge.to.to_usize(), edge.kind)).collect()
+    }
+
+    #[test]
+    fn straight_line_code() {
+        let cfg = build_cfg(&code(vec![Const(Val::I32(1)), Drop, End])).unwrap();
+        assert_eq!(cfg.blocks, vec![BasicBlock { instrs: 0..3 }, BasicBlock { instrs: 3..3 }]);
+        assert_eq!(cfg.entry(), Idx::from(0u32));
+        assert_eq!(cfg.exit(), Idx::from(1u32));
+        assert_eq!(edges(&cfg), vec![(0, 1, EdgeKind::Fallthrough)]);
+    }
+
+    #[test]
+    fn if_without_else_falls_through_after_end() {
+        let cfg = build_cfg(&code(vec![
+            Const(Val::I32(1)), // 0: block 0
+            If(FunctionType::empty()),
+            Nop, // 2: block 1 (then)
+            End,
+            Nop, // 4: block 2 (after if)
+            End,
+        ])).unwrap();
+        assert_eq!(cfg.blocks.iter().map(|block| block.instrs.clone()).collect::<Vec<_>>(), vec![0..2, 2..4, 4..6, 6..6]);
+        assert_eq!(edges(&cfg), vec![
+            (0, 1, EdgeKind::Fallthrough),
+            (0, 2, EdgeKind::Branch),
+            (1, 2, EdgeKind::Fallthrough),
+            (2, 3, EdgeKind::Fallthrough),
+        ]);
+    }
+
+    #[test]
+    fn if_with_else() {
+        let cfg = build_cfg(&code(vec![
+            Const(Val::I32(1)), // 0: block 0
+            If(FunctionType::empty()),
+            Nop, // 2: block 1 (then)
+            Else,
+            Nop, // 4: block 2 (else)
+            End,
+            End, // 6: block 3 (after if)
+        ])).unwrap();
+        assert_eq!(edges(&cfg), vec![
+            (0, 1, EdgeKind::Fallthrough),
+            (0, 2, EdgeKind::Branch),
+            (1, 3, EdgeKind::Branch),
+            (2, 3, EdgeKind::Fallthrough),
+            (3, 4, EdgeKind::Fallthrough),
+        ]);
+    }
+
+    #[test]
+    fn basic_block_starts_match_cfg() {
+        let code = code(vec![
+            Local(LocalOp::Get, Idx::from(0u32)), // 0: block 0
+            If(FunctionType::empty()),
+            Call(Idx::from(0u32)), // 2: block 1 (then)
+            Else,
+            Nop, // 4: block 2 (else)
+            End,
+            End, // 6: block 3 (after if)
+        ]);
+        assert_eq!(code.basic_block_starts(), vec![0, 2, 4, 6]);
+
+        let cfg = build_cfg(&code).unwrap();
+        let cfg_starts: Vec<usize> = cfg.blocks.iter().map(|block| block.instrs.start).collect();
+        assert_eq!(cfg_starts[..cfg_starts.len() - 1], code.basic_block_starts());
+
+        assert_eq!(Code::new().basic_block_starts(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn br_table_fans_out_to_all_targets() {
+        let cfg = build_cfg(&code(vec![
+            Block(FunctionType::empty()), // 0: block 0
+            Block(FunctionType::empty()), // 1: block 1
+            Const(Val::I32(0)),           // 2: block 2
+            BrTable { table: vec![Label::from(0u32), Label::from(1u32), Label::from(0u32)].into_boxed_slice(), default: Label::from(2u32) },
+            End, // 4: block 3 (end of inner block, unreachable)
+            End, // 5: block 4 (after inner block)
+            End, // 6: block 5 (after outer block)
+        ])).unwrap();
+        assert_eq!(edges(&cfg), vec![
+            (0, 1, EdgeKind::Fallthrough),
+            (1, 2, EdgeKind::Fallthrough),
+            // Duplicate label 0 is only added once, label 2 targets the function, i.e., returns.
+            (2, 4, EdgeKind::Branch),
+            (2, 5, EdgeKind::Branch),
+            (2, 6, EdgeKind::Branch),
+            (3, 4, EdgeKind::Fallthrough),
+            (4, 5, EdgeKind::Fallthrough),
+            (5, 6, EdgeKind::Fallthrough),
+        ]);
+    }
+
+    #[test]
+    fn loop_back_edge_and_return() {
+        let cfg = build_cfg(&code(vec![
+            Loop(FunctionType::empty()), // 0: block 0
+            Const(Val::I32(1)),          // 1: block 1 (loop header)
+            Const(Val::I32(1)),
+            Binary(BinaryOp::I32Sub),
+            BrIf(Label::from(0u32)),
+            Return, // 5: block 2
+            End,    // 6: block 3 (unreachable)
+            Unreachable, // 7: block 4
+            End, // 8: block 5
+        ])).unwrap();
+        assert_eq!(edges(&cfg), vec![
+            (0, 1, EdgeKind::Fallthrough),
+            (1, 1, EdgeKind::Branch),
+            (1, 2, EdgeKind::Fallthrough),
+            (2, 6, EdgeKind::Branch),
+            (3, 4, EdgeKind::Fallthrough),
+            (5, 6, EdgeKind::Fallthrough),
+        ]);
+        assert_eq!(cfg.predecessors(Idx::from(1u32)).collect::<Vec<_>>(), vec![Idx::from(0u32), Idx::from(1u32)]);
+        assert_eq!(cfg.successors(Idx::from(4u32)).count(), 0);
+    }
+
+    #[test]
+    fn placeholders_and_malformed_bodies_are_errors() {
+        use crate::WasmExtension;
+
+        let error = |body| build_cfg(&code(body)).unwrap_err();
+        // `try` is unsupported, so its `end` has no matching block in the parsed body.
+        assert_eq!(error(vec![Unsupported(WasmExtension::ExceptionHandling, vec![0x06, 0x40]), Nop, End, Br(Label::from(0u32)), End]),
+            CfgError { instr_idx: 0, message: "placeholder for unparsed or unsupported instructions" });
+        assert_eq!(error(vec![Unparsed(vec![0x0b]), End]).instr_idx, 0);
+        assert_eq!(error(vec![Nop, End, Br(Label::from(0u32)), End]).message, "end without matching block");
+        assert_eq!(error(vec![Else, End]).message, "else without matching if");
+        assert_eq!(error(vec![Block(FunctionType::empty()), Nop]).message, "block without matching end");
+        assert_eq!(error(vec![Br(Label::from(1u32)), End]), CfgError { instr_idx: 0, message: "branch label out of range" });
+    }
+}
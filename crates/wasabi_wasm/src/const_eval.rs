@@ -0,0 +1,61 @@
+//! Static evaluation of constant expressions, e.g., to find out where data and element segments
+//! are placed.
+
+use crate::{ConstEvalError, Global, GlobalOp, Idx, ImportOrPresent, Instr, Module, Mutability, Val};
+
+/// Evaluates the constant expression `init` (the initializer of a global, or the offset of a data
+/// or element segment) to a value.
+///
+/// Supported are `*.const` and `global.get` of immutable globals. Like at instantiation, only
+/// imported globals may be used, so `global.get` of a global defined in the module results in
+/// `ConstEvalError::NonImportedGlobalInInit`. The values of imported globals are only known at
+/// instantiation, so they result in `ConstEvalError::ImportedGlobal`. Reference instructions
+/// (`ref.null`, `ref.func`) produce no `Val` and result in `ConstEvalError::Reference`.
+pub fn eval_const_expr(init: &[Instr], module: &Module) -> Result<Val, ConstEvalError> {
+    eval(init, module, &|_| None)
+}
+
+/// Like `eval_const_expr`, but the values of imported globals are given by `imported` (e.g., from
+/// the imports at instantiation), and only if it returns `None` the result is
+/// `ConstEvalError::ImportedGlobal`.
+pub(crate) fn eval_const_expr_with_imports(init: &[Instr], module: &Module, imported: &dyn Fn(Idx<Global>) -> Option<Val>) -> Result<Val, ConstEvalError> {
+    eval(init, module, imported)
+}
+
+fn eval(init: &[Instr], module: &Module, imported: &dyn Fn(Idx<Global>) -> Option<Val>) -> Result<Val, ConstEvalError> {
+    let [instrs @ .., Instr::End] = init else {
+        return Err(ConstEvalError::Malformed);
+    };
+
+    let mut stack = Vec::with_capacity(1);
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        match instr {
+            Instr::Const(val) => stack.push(*val),
+            Instr::Global(GlobalOp::Get, idx) => stack.push(eval_global(*idx, module, imported)?),
+            Instr::RefNull(_) | Instr::RefFunc(_) => return Err(ConstEvalError::Reference {
+                instr_idx,
+                instr: instr.to_name(),
+            }),
+            _ => return Err(ConstEvalError::NonConstant {
+                instr_idx,
+                instr: instr.to_name(),
+            }),
+        }
+    }
+
+    match stack.as_slice() {
+        [val] => Ok(*val),
+        _ => Err(ConstEvalError::Malformed),
+    }
+}
+
+fn eval_global(idx: Idx<Global>, module: &Module, imported: &dyn Fn(Idx<Global>) -> Option<Val>) -> Result<Val, ConstEvalError> {
+    let global = module.globals.get(idx.to_usize()).ok_or(ConstEvalError::InvalidGlobal(idx))?;
+    if global.type_.1 == Mutability::Mut {
+        return Err(ConstEvalError::MutableGlobal(idx));
+    }
+    match &global.init {
+        ImportOrPresent::Import(_, _) => imported(idx).ok_or(ConstEvalError::ImportedGlobal(idx)),
+        ImportOrPresent::Present(_) => Err(ConstEvalError::NonImportedGlobalInInit(idx)),
+    }
+}
@@ -1,10 +1,10 @@
 //! Code for encoding our AST back to the WebAssembly binary format.
 //! Uses `wasm-encoder` for the actual low-level work.
 
-use std::convert::TryInto;
 use std::sync::RwLock;
 
 use nohash_hasher::IntMap;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 use rustc_hash::FxHashMap;
 
@@ -23,6 +23,7 @@ mod marker {
         pub struct Global;
         pub struct Table;
         pub struct Memory;
+        pub struct Tag;
     }
 }
 
@@ -39,9 +40,11 @@ struct EncodeState {
     global_idx: IntMap<Idx<Global>, Idx<marker::we::Global>>,
     table_idx: IntMap<Idx<Table>, Idx<marker::we::Table>>,
     memory_idx: IntMap<Idx<Memory>, Idx<marker::we::Memory>>,
+    tag_idx: IntMap<Idx<Tag>, Idx<marker::we::Tag>>,
 
     last_encoded_section: Option<SectionId>,
     custom_sections_encoded: usize,
+    unknown_sections_encoded: usize,
 }
 
 macro_rules! encode_state_idx_fns {
@@ -84,6 +87,7 @@ impl EncodeState {
     encode_state_idx_fns!(insert_table_idx, map_table_idx, table_idx, Table, "table");
     encode_state_idx_fns!(insert_memory_idx, map_memory_idx, memory_idx, Memory, "memory");
     encode_state_idx_fns!(insert_global_idx, map_global_idx, global_idx, Global, "global");
+    encode_state_idx_fns!(insert_tag_idx, map_tag_idx, tag_idx, Tag, "tag");
 }
 
 pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
@@ -108,9 +112,12 @@ pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
     // Then traverse all non-imported functions, globals, etc., such that their indices and
     // types are in `state`.
     let function_section = encode_functions(module, &mut state);
-    let (table_section, element_section) = encode_tables(module, &mut state)?;
-    let (memory_section, data_section) = encode_memories(module, &mut state)?;
+    let table_section = encode_tables(module, &mut state);
+    let memory_section = encode_memories(module, &mut state);
+    let tag_section = encode_tags(module, &mut state);
     let global_section = encode_globals(module, &mut state)?;
+    let element_section = encode_elements(module, &mut state)?;
+    let data_section = encode_data(module, &mut state)?;
 
     // The code section can also contain types we haven't seen so far (e.g., in `call_indirect`),
     // so it must be processed before encoding the type section.
@@ -123,46 +130,66 @@ pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
     // type section.
     let type_section = encode_types(&state);
 
+    // Put the name section at its original position, or otherwise after the data section, see
+    // https://webassembly.github.io/spec/core/appendix/custom.html#name-section
+    let mut name_section = encode_names(module, &state)?.map(|name_section| {
+        let section_order = module.metadata.section_order();
+        let previous_section = match section_order.iter().position(|section| *section == SectionId::Custom("name".to_string())) {
+            Some(position) => position.checked_sub(1).map(|previous| section_order[previous].clone()),
+            None => Some(SectionId::Data),
+        };
+        (previous_section, name_section)
+    });
+
+    // Also write sections that are empty, if they were present in the original binary, such that
+    // the section order is reproduced faithfully.
+    let was_present = |section: SectionId| module.metadata.section_order().contains(&section);
+
     // Then, write all sections in the correct order into the binary.
     // For the section order, see https://webassembly.github.io/spec/core/binary/modules.html#binary-module
     // Intersperse the correct custom sections in between as well.
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !type_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !type_section.is_empty() || was_present(SectionId::Type) {
         encoder.section(&type_section);
     }
     state.last_encoded_section = Some(SectionId::Type);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !import_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !import_section.is_empty() || was_present(SectionId::Import) {
         encoder.section(&import_section);
     }
     state.last_encoded_section = Some(SectionId::Import);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !function_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !function_section.is_empty() || was_present(SectionId::Function) {
         encoder.section(&function_section);
     }
     state.last_encoded_section = Some(SectionId::Function);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !table_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !table_section.is_empty() || was_present(SectionId::Table) {
         encoder.section(&table_section);
     }
     state.last_encoded_section = Some(SectionId::Table);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !memory_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !memory_section.is_empty() || was_present(SectionId::Memory) {
         encoder.section(&memory_section);
     }
     state.last_encoded_section = Some(SectionId::Memory);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !global_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !tag_section.is_empty() || was_present(SectionId::Tag) {
+        encoder.section(&tag_section);
+    }
+    state.last_encoded_section = Some(SectionId::Tag);
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !global_section.is_empty() || was_present(SectionId::Global) {
         encoder.section(&global_section);
     }
     state.last_encoded_section = Some(SectionId::Global);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
     let export_section = encode_exports(module, &mut state)?;
-    if !export_section.is_empty() {
+    if !export_section.is_empty() || was_present(SectionId::Export) {
         encoder.section(&export_section);
     }
     state.last_encoded_section = Some(SectionId::Export);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
     if let Some(function_idx) = module.start {
         let start_section = we::StartSection {
             function_index: state.map_function_idx(function_idx)?.to_u32(),
@@ -170,34 +197,59 @@ pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
         encoder.section(&start_section);
     }
     state.last_encoded_section = Some(SectionId::Start);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !element_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !element_section.is_empty() || was_present(SectionId::Element) {
         encoder.section(&element_section);
     }
     state.last_encoded_section = Some(SectionId::Element);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !code_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if module.data_count_section || needs_data_count(module) {
+        // Use the actual number of data segments, in case segments were added or removed.
+        encoder.section(&we::DataCountSection {
+            count: u32::try_from(module.data.len()).map_err(|_| EncodeError::message("too many data segments".to_string()))?,
+        });
+    }
+    state.last_encoded_section = Some(SectionId::DataCount);
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !code_section.is_empty() || was_present(SectionId::Code) {
         encoder.section(&code_section);
     }
     state.last_encoded_section = Some(SectionId::Code);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    if !data_section.is_empty() {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+    if !data_section.is_empty() || was_present(SectionId::Data) {
         encoder.section(&data_section);
     }
     state.last_encoded_section = Some(SectionId::Data);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
-    // Custom name section is only valid after data section, see
-    // https://webassembly.github.io/spec/core/appendix/custom.html#name-section
-    let name_section = encode_names(module, &state)?;
-    if let Some(name_section) = name_section {
+    encode_and_insert_custom(&mut encoder, &mut state, module, &mut name_section);
+
+    // Custom sections whose previous section is no longer present (e.g., because it was another
+    // custom section that got removed) could not be inserted above, so append them instead.
+    if let Some((_, name_section)) = name_section {
         encoder.section(&name_section);
-        state.last_encoded_section = Some(SectionId::Custom("name".to_string()));
     }
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    for custom in &module.custom_sections[state.custom_sections_encoded..] {
+        encoder.section(&wasm_encoder::CustomSection {
+            name: &custom.name,
+            data: &custom.content[..],
+        });
+    }
+    for unknown in &module.unknown_sections[state.unknown_sections_encoded..] {
+        encoder.section(&wasm_encoder::RawSection {
+            id: unknown.id,
+            data: &unknown.content[..],
+        });
+    }
 
     Ok(encoder.finish())
 }
 
+/// `memory.init` and `data.drop` are only valid if the module has a data count section.
+fn needs_data_count(module: &Module) -> bool {
+    module.functions.iter()
+        .flat_map(|function| function.instrs())
+        .any(|instr| matches!(instr, Instr::MemoryInit(..) | Instr::DataDrop(_)))
+}
+
 fn encode_imports(module: &Module, state: &mut EncodeState) -> we::ImportSection {
     let mut import_section = we::ImportSection::new();
 
@@ -217,9 +269,10 @@ fn encode_imports(module: &Module, state: &mut EncodeState) -> we::ImportSection
     }
 
     add_imports!(functions, insert_function_idx, Function, |f: &Function| state.get_or_insert_type(f.type_).to_u32());
-    add_imports!(tables, insert_table_idx, Table, |t: &Table| we::TableType::from(t.limits));
-    add_imports!(memories, insert_memory_idx, Memory, |m: &Memory| we::MemoryType::from(m.limits));
+    add_imports!(tables, insert_table_idx, Table, |t: &Table| we::TableType::from(t));
+    add_imports!(memories, insert_memory_idx, Memory, |m: &Memory| we::MemoryType::from(m));
     add_imports!(globals, insert_global_idx, Global, |g: &Global| we::GlobalType::from(g.type_));
+    add_imports!(tags, insert_tag_idx, Tag, |t: &Tag| encode_tag_type(t, state));
 
     import_section
 }
@@ -248,6 +301,7 @@ fn encode_exports(
     add_exports!(tables, Table, map_table_idx);
     add_exports!(memories, Memory, map_memory_idx);
     add_exports!(globals, Global, map_global_idx);
+    add_exports!(tags, Tag, map_tag_idx);
 
     Ok(export_section)
 }
@@ -293,66 +347,126 @@ fn encode_functions(module: &Module, state: &mut EncodeState) -> we::FunctionSec
     function_section
 }
 
-fn encode_tables(
-    module: &Module,
-    state: &mut EncodeState,
-) -> Result<(we::TableSection, we::ElementSection), EncodeError> {
+fn encode_tables(module: &Module, state: &mut EncodeState) -> we::TableSection {
     let mut table_section = we::TableSection::new();
-    let mut element_section = we::ElementSection::new();
 
     for (hl_table_idx, table) in module.tables() {
-        let ll_table_idx = if table.import.is_none() {
-            table_section.table(we::TableType::from(table.limits));
-            state.insert_table_idx(hl_table_idx)
-        } else {
-            state.map_table_idx(hl_table_idx)?
-        };
-
-        for hl_element in &table.elements {
-            // `wasm-encoder` uses None as the table index to signify the MVP binary format.
-            // Use that whenever possible, to avoid producing a binary using extensions.
-            let ll_table_idx = if ll_table_idx.to_u32() == 0 {
-                None
-            } else {
-                Some(ll_table_idx.to_u32())
-            };
-            let ll_offset = encode_single_instruction_with_end(&hl_element.offset, state)?;
-            let ll_elements = hl_element
-                .functions
-                .iter()
-                .map(|function_idx| state.map_function_idx(*function_idx).map(Idx::to_u32))
-                .collect::<Result<Vec<u32>, _>>()?;
-            let ll_elements = we::Elements::Functions(ll_elements.as_slice());
-            element_section.active(ll_table_idx, &ll_offset, we::ValType::FuncRef, ll_elements);
+        if table.is_defined() {
+            table_section.table(we::TableType::from(table));
+            state.insert_table_idx(hl_table_idx);
         }
     }
 
-    Ok((table_section, element_section))
+    table_section
 }
 
-fn encode_memories(
+fn encode_elements(
     module: &Module,
     state: &mut EncodeState,
-) -> Result<(we::MemorySection, we::DataSection), EncodeError> {
+) -> Result<we::ElementSection, EncodeError> {
+    let mut element_section = we::ElementSection::new();
+
+    for (_, hl_element) in module.elements() {
+        let ll_offset;
+        let ll_mode = match &hl_element.mode {
+            ElementMode::Active { table, offset } => {
+                ll_offset = encode_const_expr(offset, state)?;
+                let ll_table_idx = state.map_table_idx(*table)?.to_u32();
+                // `wasm-encoder` uses None as the table index to signify the MVP binary format.
+                // Use that whenever possible, to avoid producing a binary using extensions.
+                let ll_table_idx = if ll_table_idx == 0 && hl_element.ref_type == RefType::FuncRef {
+                    None
+                } else {
+                    Some(ll_table_idx)
+                };
+                we::ElementMode::Active {
+                    table: ll_table_idx,
+                    offset: &ll_offset,
+                }
+            }
+            ElementMode::Passive => we::ElementMode::Passive,
+            ElementMode::Declared => we::ElementMode::Declared,
+        };
+
+        let ll_functions;
+        let ll_exprs;
+        let ll_elements = match &hl_element.items {
+            ElementItems::Functions(functions) => {
+                ll_functions = functions
+                    .iter()
+                    .map(|function_idx| state.map_function_idx(*function_idx).map(Idx::to_u32))
+                    .collect::<Result<Vec<u32>, _>>()?;
+                we::Elements::Functions(ll_functions.as_slice())
+            }
+            ElementItems::Expressions(exprs) => {
+                ll_exprs = exprs
+                    .iter()
+                    .map(|expr| encode_const_expr(expr, state))
+                    .collect::<Result<Vec<we::ConstExpr>, _>>()?;
+                we::Elements::Expressions(ll_exprs.as_slice())
+            }
+        };
+
+        element_section.segment(we::ElementSegment {
+            mode: ll_mode,
+            element_type: hl_element.ref_type.into(),
+            elements: ll_elements,
+        });
+    }
+
+    Ok(element_section)
+}
+
+fn encode_memories(module: &Module, state: &mut EncodeState) -> we::MemorySection {
     let mut memory_section = we::MemorySection::new();
-    let mut data_section = we::DataSection::new();
 
     for (hl_memory_idx, memory) in module.memories() {
-        let ll_memory_idx = if memory.import.is_none() {
-            memory_section.memory(we::MemoryType::from(memory.limits));
-            state.insert_memory_idx(hl_memory_idx)
-        } else {
-            state.map_memory_idx(hl_memory_idx)?
-        };
+        if memory.is_defined() {
+            memory_section.memory(we::MemoryType::from(memory));
+            state.insert_memory_idx(hl_memory_idx);
+        }
+    }
+
+    memory_section
+}
+
+fn encode_tags(module: &Module, state: &mut EncodeState) -> we::TagSection {
+    let mut tag_section = we::TagSection::new();
+
+    for (hl_tag_idx, tag) in module.tags() {
+        if tag.is_defined() {
+            tag_section.tag(encode_tag_type(tag, state));
+            state.insert_tag_idx(hl_tag_idx);
+        }
+    }
+
+    tag_section
+}
 
-        for data in &memory.data {
-            let ll_offset = encode_single_instruction_with_end(&data.offset, state)?;
-            let ll_data = data.bytes.iter().copied();
-            data_section.active(ll_memory_idx.to_u32(), &ll_offset, ll_data);
+fn encode_tag_type(tag: &Tag, state: &EncodeState) -> we::TagType {
+    we::TagType {
+        kind: we::TagKind::Exception,
+        func_type_idx: state.get_or_insert_type(tag.type_).to_u32(),
+    }
+}
+
+fn encode_data(module: &Module, state: &mut EncodeState) -> Result<we::DataSection, EncodeError> {
+    let mut data_section = we::DataSection::new();
+
+    for data in &module.data {
+        let ll_data = data.bytes.iter().copied();
+        match &data.mode {
+            DataMode::Active { memory, offset } => {
+                let ll_offset = encode_const_expr(offset, state)?;
+                data_section.active(state.map_memory_idx(*memory)?.to_u32(), &ll_offset, ll_data);
+            }
+            DataMode::Passive => {
+                data_section.passive(ll_data);
+            }
         }
     }
 
-    Ok((memory_section, data_section))
+    Ok(data_section)
 }
 
 fn encode_globals(
@@ -364,7 +478,7 @@ fn encode_globals(
     for (global_idx, global) in module.globals() {
         if let Some(init) = global.init() {
             state.insert_global_idx(global_idx);
-            let ll_init = encode_single_instruction_with_end(init, state)?;
+            let ll_init = encode_const_expr(init, state)?;
             global_section.global(we::GlobalType::from(global.type_), &ll_init);
         }
     }
@@ -375,19 +489,25 @@ fn encode_globals(
 fn encode_code(module: &Module, state: &mut EncodeState) -> Result<we::CodeSection, EncodeError> {
     let mut code_section = we::CodeSection::new();
 
-    // Encode function bodies in parallel.
-    let ll_functions = module
-        .functions
-        .par_iter()
+    // Encode function bodies in parallel (if enabled).
+    #[cfg(feature = "parallel")]
+    let functions = module.functions.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let functions = module.functions.iter();
+    let ll_functions = functions
         .filter_map(Function::code)
         .map(|code| -> Result<we::Function, EncodeError> {
             let ll_locals_iter = code
-                .locals
-                .iter()
-                .map(|local| we::ValType::from(local.type_));
-            let mut ll_function = we::Function::new_with_locals_types(ll_locals_iter);
+                .locals_grouped()
+                .into_iter()
+                .map(|(count, type_)| (count, we::ValType::from(type_)));
+            let mut ll_function = we::Function::new(ll_locals_iter);
             for instr in &code.body {
-                ll_function.instruction(&encode_instruction(instr, state)?);
+                match instr {
+                    // The raw bytes are written as-is, i.e., indices in them are not renumbered.
+                    Instr::Unparsed(bytes) | Instr::Unsupported(_, bytes) => ll_function.raw(bytes.iter().copied()),
+                    instr => ll_function.instruction(&encode_instruction(instr, state)?),
+                };
             }
             Ok(ll_function)
         })
@@ -402,40 +522,64 @@ fn encode_code(module: &Module, state: &mut EncodeState) -> Result<we::CodeSecti
 // TODO generify to include all sections, not just custom sections
 // fn insert_section<T>(encoder: &mut wasm_encoder::Module, state: &mut EncodeState, section: T, module: &Module, previous_section: Option<SectionId>)
 //     where T: wasm_encoder::Section {
+/// Writes all custom sections (including the name section) and unknown sections that directly
+/// followed the last encoded section in the original binary.
 fn encode_and_insert_custom(
     encoder: &mut wasm_encoder::Module,
     state: &mut EncodeState,
     module: &Module,
+    name_section: &mut Option<(Option<SectionId>, we::NameSection)>,
 ) {
-    for custom in module
-        .custom_sections
-        .iter()
-        .skip(state.custom_sections_encoded)
-    {
-        // FIXME what if the reference .after section is no longer present?
-        // Right now, this would drop the custom section.
-        if state.last_encoded_section == custom.previous_section {
-            encoder.section(&wasm_encoder::CustomSection {
-                name: &custom.name,
-                data: &custom.content[..],
-            });
-            state.custom_sections_encoded += 1;
-            state.last_encoded_section = Some(SectionId::Custom(custom.name.clone()));
+    loop {
+        if matches!(name_section, Some((previous_section, _)) if *previous_section == state.last_encoded_section) {
+            let (_, section) = name_section.take().expect("checked by matches! above");
+            encoder.section(&section);
+            state.last_encoded_section = Some(SectionId::Custom("name".to_string()));
+            continue;
+        }
+
+        match module.custom_sections.get(state.custom_sections_encoded) {
+            Some(custom) if custom.previous_section == state.last_encoded_section => {
+                encoder.section(&wasm_encoder::CustomSection {
+                    name: &custom.name,
+                    data: &custom.content[..],
+                });
+                state.custom_sections_encoded += 1;
+                state.last_encoded_section = Some(SectionId::Custom(custom.name.clone()));
+                continue;
+            }
+            _ => {}
+        }
+
+        match module.unknown_sections.get(state.unknown_sections_encoded) {
+            Some(unknown) if unknown.previous_section == state.last_encoded_section => {
+                encoder.section(&wasm_encoder::RawSection {
+                    id: unknown.id,
+                    data: &unknown.content[..],
+                });
+                state.unknown_sections_encoded += 1;
+                state.last_encoded_section = Some(SectionId::Unknown(unknown.id));
+            }
+            _ => break,
         }
     }
 }
 
-fn encode_single_instruction_with_end(
+fn encode_const_expr(
     instrs: &[Instr],
     state: &mut EncodeState,
 ) -> Result<we::ConstExpr, EncodeError> {
     match instrs {
-        [single_instr, Instr::End] => {
-            let mut instr_bytes = Vec::with_capacity(8);
-            encode_instruction(single_instr, state)?.encode(&mut instr_bytes);
+        // `wasm-encoder` appends the final end instruction itself.
+        [instrs @ .., Instr::End] => {
+            // Most constant expressions are just a single instruction (e.g., `i32.const`).
+            let mut instr_bytes = Vec::with_capacity(8 * instrs.len());
+            for instr in instrs {
+                encode_instruction(instr, state)?.encode(&mut instr_bytes);
+            }
             Ok(we::ConstExpr::raw(instr_bytes))
         },
-        _ => Err(EncodeError::message(format!("expected exactly one instruction, followed by an end, but got {instrs:?}. If there is more than one instruction, this is not supported by wasm-encoder for an unknown reason."))),
+        _ => Err(EncodeError::message(format!("expected constant expression terminated by an end, but got {instrs:?}"))),
     }
 }
 
@@ -466,9 +610,15 @@ fn encode_instruction(
             ty: state.get_or_insert_type(*function_type).to_u32(),
             table: state.map_table_idx(table_idx)?.to_u32(),
         },
+        Instr::ReturnCall(function_idx) => we::Instruction::ReturnCall(state.map_function_idx(function_idx)?.to_u32()),
+        Instr::ReturnCallIndirect(ref function_type, table_idx) => we::Instruction::ReturnCallIndirect {
+            ty: state.get_or_insert_type(*function_type).to_u32(),
+            table: state.map_table_idx(table_idx)?.to_u32(),
+        },
 
         Instr::Drop => we::Instruction::Drop,
-        Instr::Select => we::Instruction::Select,
+        Instr::Select(None) => we::Instruction::Select,
+        Instr::Select(Some(ty)) => we::Instruction::TypedSelect(ty.into()),
 
         Instr::Local(LocalOp::Get, local_idx) => we::Instruction::LocalGet(local_idx.to_u32()),
         Instr::Local(LocalOp::Set, local_idx) => we::Instruction::LocalSet(local_idx.to_u32()),
@@ -490,6 +640,19 @@ fn encode_instruction(
         Instr::Load(LoadOp::I64Load16U, memarg) => we::Instruction::I64Load16U(memarg.into()),
         Instr::Load(LoadOp::I64Load32S, memarg) => we::Instruction::I64Load32S(memarg.into()),
         Instr::Load(LoadOp::I64Load32U, memarg) => we::Instruction::I64Load32U(memarg.into()),
+        Instr::Load(LoadOp::V128Load, memarg) => we::Instruction::V128Load(memarg.into()),
+        Instr::Load(LoadOp::V128Load8x8S, memarg) => we::Instruction::V128Load8x8S(memarg.into()),
+        Instr::Load(LoadOp::V128Load8x8U, memarg) => we::Instruction::V128Load8x8U(memarg.into()),
+        Instr::Load(LoadOp::V128Load16x4S, memarg) => we::Instruction::V128Load16x4S(memarg.into()),
+        Instr::Load(LoadOp::V128Load16x4U, memarg) => we::Instruction::V128Load16x4U(memarg.into()),
+        Instr::Load(LoadOp::V128Load32x2S, memarg) => we::Instruction::V128Load32x2S(memarg.into()),
+        Instr::Load(LoadOp::V128Load32x2U, memarg) => we::Instruction::V128Load32x2U(memarg.into()),
+        Instr::Load(LoadOp::V128Load8Splat, memarg) => we::Instruction::V128Load8Splat(memarg.into()),
+        Instr::Load(LoadOp::V128Load16Splat, memarg) => we::Instruction::V128Load16Splat(memarg.into()),
+        Instr::Load(LoadOp::V128Load32Splat, memarg) => we::Instruction::V128Load32Splat(memarg.into()),
+        Instr::Load(LoadOp::V128Load64Splat, memarg) => we::Instruction::V128Load64Splat(memarg.into()),
+        Instr::Load(LoadOp::V128Load32Zero, memarg) => we::Instruction::V128Load32Zero(memarg.into()),
+        Instr::Load(LoadOp::V128Load64Zero, memarg) => we::Instruction::V128Load64Zero(memarg.into()),
 
         Instr::Store(StoreOp::I32Store, memarg) => we::Instruction::I32Store(memarg.into()),
         Instr::Store(StoreOp::I64Store, memarg) => we::Instruction::I64Store(memarg.into()),
@@ -500,14 +663,116 @@ fn encode_instruction(
         Instr::Store(StoreOp::I64Store8, memarg) => we::Instruction::I64Store8(memarg.into()),
         Instr::Store(StoreOp::I64Store16, memarg) => we::Instruction::I64Store16(memarg.into()),
         Instr::Store(StoreOp::I64Store32, memarg) => we::Instruction::I64Store32(memarg.into()),
+        Instr::Store(StoreOp::V128Store, memarg) => we::Instruction::V128Store(memarg.into()),
+        Instr::LoadLane(LoadLaneOp::V128Load8Lane, memarg, lane) => we::Instruction::V128Load8Lane { memarg: memarg.into(), lane },
+        Instr::LoadLane(LoadLaneOp::V128Load16Lane, memarg, lane) => we::Instruction::V128Load16Lane { memarg: memarg.into(), lane },
+        Instr::LoadLane(LoadLaneOp::V128Load32Lane, memarg, lane) => we::Instruction::V128Load32Lane { memarg: memarg.into(), lane },
+        Instr::LoadLane(LoadLaneOp::V128Load64Lane, memarg, lane) => we::Instruction::V128Load64Lane { memarg: memarg.into(), lane },
+        Instr::StoreLane(StoreLaneOp::V128Store8Lane, memarg, lane) => we::Instruction::V128Store8Lane { memarg: memarg.into(), lane },
+        Instr::StoreLane(StoreLaneOp::V128Store16Lane, memarg, lane) => we::Instruction::V128Store16Lane { memarg: memarg.into(), lane },
+        Instr::StoreLane(StoreLaneOp::V128Store32Lane, memarg, lane) => we::Instruction::V128Store32Lane { memarg: memarg.into(), lane },
+        Instr::StoreLane(StoreLaneOp::V128Store64Lane, memarg, lane) => we::Instruction::V128Store64Lane { memarg: memarg.into(), lane },
+
+        Instr::Atomic(AtomicOp::MemoryAtomicNotify, memarg) => we::Instruction::MemoryAtomicNotify(memarg.into()),
+        Instr::Atomic(AtomicOp::MemoryAtomicWait32, memarg) => we::Instruction::MemoryAtomicWait32(memarg.into()),
+        Instr::Atomic(AtomicOp::MemoryAtomicWait64, memarg) => we::Instruction::MemoryAtomicWait64(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicLoad, memarg) => we::Instruction::I32AtomicLoad(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicLoad, memarg) => we::Instruction::I64AtomicLoad(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicLoad8U, memarg) => we::Instruction::I32AtomicLoad8U(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicLoad16U, memarg) => we::Instruction::I32AtomicLoad16U(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicLoad8U, memarg) => we::Instruction::I64AtomicLoad8U(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicLoad16U, memarg) => we::Instruction::I64AtomicLoad16U(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicLoad32U, memarg) => we::Instruction::I64AtomicLoad32U(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicStore, memarg) => we::Instruction::I32AtomicStore(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicStore, memarg) => we::Instruction::I64AtomicStore(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicStore8, memarg) => we::Instruction::I32AtomicStore8(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicStore16, memarg) => we::Instruction::I32AtomicStore16(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicStore8, memarg) => we::Instruction::I64AtomicStore8(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicStore16, memarg) => we::Instruction::I64AtomicStore16(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicStore32, memarg) => we::Instruction::I64AtomicStore32(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwAdd, memarg) => we::Instruction::I32AtomicRmwAdd(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwAdd, memarg) => we::Instruction::I64AtomicRmwAdd(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8AddU, memarg) => we::Instruction::I32AtomicRmw8AddU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16AddU, memarg) => we::Instruction::I32AtomicRmw16AddU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8AddU, memarg) => we::Instruction::I64AtomicRmw8AddU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16AddU, memarg) => we::Instruction::I64AtomicRmw16AddU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32AddU, memarg) => we::Instruction::I64AtomicRmw32AddU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwSub, memarg) => we::Instruction::I32AtomicRmwSub(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwSub, memarg) => we::Instruction::I64AtomicRmwSub(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8SubU, memarg) => we::Instruction::I32AtomicRmw8SubU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16SubU, memarg) => we::Instruction::I32AtomicRmw16SubU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8SubU, memarg) => we::Instruction::I64AtomicRmw8SubU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16SubU, memarg) => we::Instruction::I64AtomicRmw16SubU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32SubU, memarg) => we::Instruction::I64AtomicRmw32SubU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwAnd, memarg) => we::Instruction::I32AtomicRmwAnd(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwAnd, memarg) => we::Instruction::I64AtomicRmwAnd(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8AndU, memarg) => we::Instruction::I32AtomicRmw8AndU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16AndU, memarg) => we::Instruction::I32AtomicRmw16AndU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8AndU, memarg) => we::Instruction::I64AtomicRmw8AndU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16AndU, memarg) => we::Instruction::I64AtomicRmw16AndU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32AndU, memarg) => we::Instruction::I64AtomicRmw32AndU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwOr, memarg) => we::Instruction::I32AtomicRmwOr(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwOr, memarg) => we::Instruction::I64AtomicRmwOr(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8OrU, memarg) => we::Instruction::I32AtomicRmw8OrU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16OrU, memarg) => we::Instruction::I32AtomicRmw16OrU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8OrU, memarg) => we::Instruction::I64AtomicRmw8OrU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16OrU, memarg) => we::Instruction::I64AtomicRmw16OrU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32OrU, memarg) => we::Instruction::I64AtomicRmw32OrU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwXor, memarg) => we::Instruction::I32AtomicRmwXor(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwXor, memarg) => we::Instruction::I64AtomicRmwXor(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8XorU, memarg) => we::Instruction::I32AtomicRmw8XorU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16XorU, memarg) => we::Instruction::I32AtomicRmw16XorU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8XorU, memarg) => we::Instruction::I64AtomicRmw8XorU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16XorU, memarg) => we::Instruction::I64AtomicRmw16XorU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32XorU, memarg) => we::Instruction::I64AtomicRmw32XorU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwXchg, memarg) => we::Instruction::I32AtomicRmwXchg(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwXchg, memarg) => we::Instruction::I64AtomicRmwXchg(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8XchgU, memarg) => we::Instruction::I32AtomicRmw8XchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16XchgU, memarg) => we::Instruction::I32AtomicRmw16XchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8XchgU, memarg) => we::Instruction::I64AtomicRmw8XchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16XchgU, memarg) => we::Instruction::I64AtomicRmw16XchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32XchgU, memarg) => we::Instruction::I64AtomicRmw32XchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmwCmpxchg, memarg) => we::Instruction::I32AtomicRmwCmpxchg(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmwCmpxchg, memarg) => we::Instruction::I64AtomicRmwCmpxchg(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw8CmpxchgU, memarg) => we::Instruction::I32AtomicRmw8CmpxchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I32AtomicRmw16CmpxchgU, memarg) => we::Instruction::I32AtomicRmw16CmpxchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw8CmpxchgU, memarg) => we::Instruction::I64AtomicRmw8CmpxchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw16CmpxchgU, memarg) => we::Instruction::I64AtomicRmw16CmpxchgU(memarg.into()),
+        Instr::Atomic(AtomicOp::I64AtomicRmw32CmpxchgU, memarg) => we::Instruction::I64AtomicRmw32CmpxchgU(memarg.into()),
+        Instr::AtomicFence => we::Instruction::AtomicFence,
 
         Instr::MemorySize(memory_idx) => we::Instruction::MemorySize(state.map_memory_idx(memory_idx)?.to_u32()),
         Instr::MemoryGrow(memory_idx) => we::Instruction::MemoryGrow(state.map_memory_idx(memory_idx)?.to_u32()),
 
+        Instr::MemoryInit(data_idx, memory_idx) => we::Instruction::MemoryInit {
+            mem: state.map_memory_idx(memory_idx)?.to_u32(),
+            data_index: data_idx.to_u32(),
+        },
+        Instr::DataDrop(data_idx) => we::Instruction::DataDrop(data_idx.to_u32()),
+        Instr::MemoryCopy { dst, src } => we::Instruction::MemoryCopy {
+            src_mem: state.map_memory_idx(src)?.to_u32(),
+            dst_mem: state.map_memory_idx(dst)?.to_u32(),
+        },
+        Instr::MemoryFill(memory_idx) => we::Instruction::MemoryFill(state.map_memory_idx(memory_idx)?.to_u32()),
+        Instr::TableInit(elem_idx, table_idx) => we::Instruction::TableInit {
+            elem_index: elem_idx.to_u32(),
+            table: state.map_table_idx(table_idx)?.to_u32(),
+        },
+        Instr::ElemDrop(elem_idx) => we::Instruction::ElemDrop(elem_idx.to_u32()),
+        Instr::TableCopy { dst, src } => we::Instruction::TableCopy {
+            src_table: state.map_table_idx(src)?.to_u32(),
+            dst_table: state.map_table_idx(dst)?.to_u32(),
+        },
+
         Instr::Const(Val::I32(value)) => we::Instruction::I32Const(value),
         Instr::Const(Val::I64(value)) => we::Instruction::I64Const(value),
         Instr::Const(Val::F32(value)) => we::Instruction::F32Const(value.into_inner()),
         Instr::Const(Val::F64(value)) => we::Instruction::F64Const(value.into_inner()),
+        Instr::Const(Val::V128(bytes)) => we::Instruction::V128Const(i128::from_le_bytes(bytes)),
+
+        Instr::RefNull(ty) => we::Instruction::RefNull(ty.into()),
+        Instr::RefIsNull => we::Instruction::RefIsNull,
+        Instr::RefFunc(function_idx) => we::Instruction::RefFunc(state.map_function_idx(function_idx)?.to_u32()),
 
         Instr::Unary(UnaryOp::I32Eqz) => we::Instruction::I32Eqz,
         Instr::Unary(UnaryOp::I64Eqz) => we::Instruction::I64Eqz,
@@ -536,12 +801,20 @@ fn encode_instruction(
         Instr::Unary(UnaryOp::I32TruncF32U) => we::Instruction::I32TruncF32U,
         Instr::Unary(UnaryOp::I32TruncF64S) => we::Instruction::I32TruncF64S,
         Instr::Unary(UnaryOp::I32TruncF64U) => we::Instruction::I32TruncF64U,
+        Instr::Unary(UnaryOp::I32TruncSatF32S) => we::Instruction::I32TruncSatF32S,
+        Instr::Unary(UnaryOp::I32TruncSatF32U) => we::Instruction::I32TruncSatF32U,
+        Instr::Unary(UnaryOp::I32TruncSatF64S) => we::Instruction::I32TruncSatF64S,
+        Instr::Unary(UnaryOp::I32TruncSatF64U) => we::Instruction::I32TruncSatF64U,
         Instr::Unary(UnaryOp::I64ExtendI32S) => we::Instruction::I64ExtendI32S,
         Instr::Unary(UnaryOp::I64ExtendI32U) => we::Instruction::I64ExtendI32U,
         Instr::Unary(UnaryOp::I64TruncF32S) => we::Instruction::I64TruncF32S,
         Instr::Unary(UnaryOp::I64TruncF32U) => we::Instruction::I64TruncF32U,
         Instr::Unary(UnaryOp::I64TruncF64S) => we::Instruction::I64TruncF64S,
         Instr::Unary(UnaryOp::I64TruncF64U) => we::Instruction::I64TruncF64U,
+        Instr::Unary(UnaryOp::I64TruncSatF32S) => we::Instruction::I64TruncSatF32S,
+        Instr::Unary(UnaryOp::I64TruncSatF32U) => we::Instruction::I64TruncSatF32U,
+        Instr::Unary(UnaryOp::I64TruncSatF64S) => we::Instruction::I64TruncSatF64S,
+        Instr::Unary(UnaryOp::I64TruncSatF64U) => we::Instruction::I64TruncSatF64U,
         Instr::Unary(UnaryOp::F32ConvertI32S) => we::Instruction::F32ConvertI32S,
         Instr::Unary(UnaryOp::F32ConvertI32U) => we::Instruction::F32ConvertI32U,
         Instr::Unary(UnaryOp::F32ConvertI64S) => we::Instruction::F32ConvertI64S,
@@ -556,6 +829,76 @@ fn encode_instruction(
         Instr::Unary(UnaryOp::I64ReinterpretF64) => we::Instruction::I64ReinterpretF64,
         Instr::Unary(UnaryOp::F32ReinterpretI32) => we::Instruction::F32ReinterpretI32,
         Instr::Unary(UnaryOp::F64ReinterpretI64) => we::Instruction::F64ReinterpretI64,
+        Instr::Unary(UnaryOp::I32Extend8S) => we::Instruction::I32Extend8S,
+        Instr::Unary(UnaryOp::I32Extend16S) => we::Instruction::I32Extend16S,
+        Instr::Unary(UnaryOp::I64Extend8S) => we::Instruction::I64Extend8S,
+        Instr::Unary(UnaryOp::I64Extend16S) => we::Instruction::I64Extend16S,
+        Instr::Unary(UnaryOp::I64Extend32S) => we::Instruction::I64Extend32S,
+        Instr::Unary(UnaryOp::I8x16Splat) => we::Instruction::I8x16Splat,
+        Instr::Unary(UnaryOp::I16x8Splat) => we::Instruction::I16x8Splat,
+        Instr::Unary(UnaryOp::I32x4Splat) => we::Instruction::I32x4Splat,
+        Instr::Unary(UnaryOp::I64x2Splat) => we::Instruction::I64x2Splat,
+        Instr::Unary(UnaryOp::F32x4Splat) => we::Instruction::F32x4Splat,
+        Instr::Unary(UnaryOp::F64x2Splat) => we::Instruction::F64x2Splat,
+        Instr::Unary(UnaryOp::V128Not) => we::Instruction::V128Not,
+        Instr::Unary(UnaryOp::V128AnyTrue) => we::Instruction::V128AnyTrue,
+        Instr::Unary(UnaryOp::I8x16Abs) => we::Instruction::I8x16Abs,
+        Instr::Unary(UnaryOp::I8x16Neg) => we::Instruction::I8x16Neg,
+        Instr::Unary(UnaryOp::I8x16Popcnt) => we::Instruction::I8x16Popcnt,
+        Instr::Unary(UnaryOp::I8x16AllTrue) => we::Instruction::I8x16AllTrue,
+        Instr::Unary(UnaryOp::I8x16Bitmask) => we::Instruction::I8x16Bitmask,
+        Instr::Unary(UnaryOp::I16x8ExtAddPairwiseI8x16S) => we::Instruction::I16x8ExtAddPairwiseI8x16S,
+        Instr::Unary(UnaryOp::I16x8ExtAddPairwiseI8x16U) => we::Instruction::I16x8ExtAddPairwiseI8x16U,
+        Instr::Unary(UnaryOp::I16x8Abs) => we::Instruction::I16x8Abs,
+        Instr::Unary(UnaryOp::I16x8Neg) => we::Instruction::I16x8Neg,
+        Instr::Unary(UnaryOp::I16x8AllTrue) => we::Instruction::I16x8AllTrue,
+        Instr::Unary(UnaryOp::I16x8Bitmask) => we::Instruction::I16x8Bitmask,
+        Instr::Unary(UnaryOp::I16x8ExtendLowI8x16S) => we::Instruction::I16x8ExtendLowI8x16S,
+        Instr::Unary(UnaryOp::I16x8ExtendHighI8x16S) => we::Instruction::I16x8ExtendHighI8x16S,
+        Instr::Unary(UnaryOp::I16x8ExtendLowI8x16U) => we::Instruction::I16x8ExtendLowI8x16U,
+        Instr::Unary(UnaryOp::I16x8ExtendHighI8x16U) => we::Instruction::I16x8ExtendHighI8x16U,
+        Instr::Unary(UnaryOp::I32x4ExtAddPairwiseI16x8S) => we::Instruction::I32x4ExtAddPairwiseI16x8S,
+        Instr::Unary(UnaryOp::I32x4ExtAddPairwiseI16x8U) => we::Instruction::I32x4ExtAddPairwiseI16x8U,
+        Instr::Unary(UnaryOp::I32x4Abs) => we::Instruction::I32x4Abs,
+        Instr::Unary(UnaryOp::I32x4Neg) => we::Instruction::I32x4Neg,
+        Instr::Unary(UnaryOp::I32x4AllTrue) => we::Instruction::I32x4AllTrue,
+        Instr::Unary(UnaryOp::I32x4Bitmask) => we::Instruction::I32x4Bitmask,
+        Instr::Unary(UnaryOp::I32x4ExtendLowI16x8S) => we::Instruction::I32x4ExtendLowI16x8S,
+        Instr::Unary(UnaryOp::I32x4ExtendHighI16x8S) => we::Instruction::I32x4ExtendHighI16x8S,
+        Instr::Unary(UnaryOp::I32x4ExtendLowI16x8U) => we::Instruction::I32x4ExtendLowI16x8U,
+        Instr::Unary(UnaryOp::I32x4ExtendHighI16x8U) => we::Instruction::I32x4ExtendHighI16x8U,
+        Instr::Unary(UnaryOp::I64x2Abs) => we::Instruction::I64x2Abs,
+        Instr::Unary(UnaryOp::I64x2Neg) => we::Instruction::I64x2Neg,
+        Instr::Unary(UnaryOp::I64x2AllTrue) => we::Instruction::I64x2AllTrue,
+        Instr::Unary(UnaryOp::I64x2Bitmask) => we::Instruction::I64x2Bitmask,
+        Instr::Unary(UnaryOp::I64x2ExtendLowI32x4S) => we::Instruction::I64x2ExtendLowI32x4S,
+        Instr::Unary(UnaryOp::I64x2ExtendHighI32x4S) => we::Instruction::I64x2ExtendHighI32x4S,
+        Instr::Unary(UnaryOp::I64x2ExtendLowI32x4U) => we::Instruction::I64x2ExtendLowI32x4U,
+        Instr::Unary(UnaryOp::I64x2ExtendHighI32x4U) => we::Instruction::I64x2ExtendHighI32x4U,
+        Instr::Unary(UnaryOp::F32x4Ceil) => we::Instruction::F32x4Ceil,
+        Instr::Unary(UnaryOp::F32x4Floor) => we::Instruction::F32x4Floor,
+        Instr::Unary(UnaryOp::F32x4Trunc) => we::Instruction::F32x4Trunc,
+        Instr::Unary(UnaryOp::F32x4Nearest) => we::Instruction::F32x4Nearest,
+        Instr::Unary(UnaryOp::F32x4Abs) => we::Instruction::F32x4Abs,
+        Instr::Unary(UnaryOp::F32x4Neg) => we::Instruction::F32x4Neg,
+        Instr::Unary(UnaryOp::F32x4Sqrt) => we::Instruction::F32x4Sqrt,
+        Instr::Unary(UnaryOp::F64x2Ceil) => we::Instruction::F64x2Ceil,
+        Instr::Unary(UnaryOp::F64x2Floor) => we::Instruction::F64x2Floor,
+        Instr::Unary(UnaryOp::F64x2Trunc) => we::Instruction::F64x2Trunc,
+        Instr::Unary(UnaryOp::F64x2Nearest) => we::Instruction::F64x2Nearest,
+        Instr::Unary(UnaryOp::F64x2Abs) => we::Instruction::F64x2Abs,
+        Instr::Unary(UnaryOp::F64x2Neg) => we::Instruction::F64x2Neg,
+        Instr::Unary(UnaryOp::F64x2Sqrt) => we::Instruction::F64x2Sqrt,
+        Instr::Unary(UnaryOp::I32x4TruncSatF32x4S) => we::Instruction::I32x4TruncSatF32x4S,
+        Instr::Unary(UnaryOp::I32x4TruncSatF32x4U) => we::Instruction::I32x4TruncSatF32x4U,
+        Instr::Unary(UnaryOp::F32x4ConvertI32x4S) => we::Instruction::F32x4ConvertI32x4S,
+        Instr::Unary(UnaryOp::F32x4ConvertI32x4U) => we::Instruction::F32x4ConvertI32x4U,
+        Instr::Unary(UnaryOp::I32x4TruncSatF64x2SZero) => we::Instruction::I32x4TruncSatF64x2SZero,
+        Instr::Unary(UnaryOp::I32x4TruncSatF64x2UZero) => we::Instruction::I32x4TruncSatF64x2UZero,
+        Instr::Unary(UnaryOp::F64x2ConvertLowI32x4S) => we::Instruction::F64x2ConvertLowI32x4S,
+        Instr::Unary(UnaryOp::F64x2ConvertLowI32x4U) => we::Instruction::F64x2ConvertLowI32x4U,
+        Instr::Unary(UnaryOp::F32x4DemoteF64x2Zero) => we::Instruction::F32x4DemoteF64x2Zero,
+        Instr::Unary(UnaryOp::F64x2PromoteLowF32x4) => we::Instruction::F64x2PromoteLowF32x4,
 
         Instr::Binary(BinaryOp::I32Eq) => we::Instruction::I32Eq,
         Instr::Binary(BinaryOp::I32Ne) => we::Instruction::I32Ne,
@@ -633,9 +976,174 @@ fn encode_instruction(
         Instr::Binary(BinaryOp::F64Min) => we::Instruction::F64Min,
         Instr::Binary(BinaryOp::F64Max) => we::Instruction::F64Max,
         Instr::Binary(BinaryOp::F64Copysign) => we::Instruction::F64Copysign,
+        Instr::Binary(BinaryOp::I8x16Swizzle) => we::Instruction::I8x16Swizzle,
+        Instr::Binary(BinaryOp::I8x16Eq) => we::Instruction::I8x16Eq,
+        Instr::Binary(BinaryOp::I8x16Ne) => we::Instruction::I8x16Ne,
+        Instr::Binary(BinaryOp::I8x16LtS) => we::Instruction::I8x16LtS,
+        Instr::Binary(BinaryOp::I8x16LtU) => we::Instruction::I8x16LtU,
+        Instr::Binary(BinaryOp::I8x16GtS) => we::Instruction::I8x16GtS,
+        Instr::Binary(BinaryOp::I8x16GtU) => we::Instruction::I8x16GtU,
+        Instr::Binary(BinaryOp::I8x16LeS) => we::Instruction::I8x16LeS,
+        Instr::Binary(BinaryOp::I8x16LeU) => we::Instruction::I8x16LeU,
+        Instr::Binary(BinaryOp::I8x16GeS) => we::Instruction::I8x16GeS,
+        Instr::Binary(BinaryOp::I8x16GeU) => we::Instruction::I8x16GeU,
+        Instr::Binary(BinaryOp::I16x8Eq) => we::Instruction::I16x8Eq,
+        Instr::Binary(BinaryOp::I16x8Ne) => we::Instruction::I16x8Ne,
+        Instr::Binary(BinaryOp::I16x8LtS) => we::Instruction::I16x8LtS,
+        Instr::Binary(BinaryOp::I16x8LtU) => we::Instruction::I16x8LtU,
+        Instr::Binary(BinaryOp::I16x8GtS) => we::Instruction::I16x8GtS,
+        Instr::Binary(BinaryOp::I16x8GtU) => we::Instruction::I16x8GtU,
+        Instr::Binary(BinaryOp::I16x8LeS) => we::Instruction::I16x8LeS,
+        Instr::Binary(BinaryOp::I16x8LeU) => we::Instruction::I16x8LeU,
+        Instr::Binary(BinaryOp::I16x8GeS) => we::Instruction::I16x8GeS,
+        Instr::Binary(BinaryOp::I16x8GeU) => we::Instruction::I16x8GeU,
+        Instr::Binary(BinaryOp::I32x4Eq) => we::Instruction::I32x4Eq,
+        Instr::Binary(BinaryOp::I32x4Ne) => we::Instruction::I32x4Ne,
+        Instr::Binary(BinaryOp::I32x4LtS) => we::Instruction::I32x4LtS,
+        Instr::Binary(BinaryOp::I32x4LtU) => we::Instruction::I32x4LtU,
+        Instr::Binary(BinaryOp::I32x4GtS) => we::Instruction::I32x4GtS,
+        Instr::Binary(BinaryOp::I32x4GtU) => we::Instruction::I32x4GtU,
+        Instr::Binary(BinaryOp::I32x4LeS) => we::Instruction::I32x4LeS,
+        Instr::Binary(BinaryOp::I32x4LeU) => we::Instruction::I32x4LeU,
+        Instr::Binary(BinaryOp::I32x4GeS) => we::Instruction::I32x4GeS,
+        Instr::Binary(BinaryOp::I32x4GeU) => we::Instruction::I32x4GeU,
+        Instr::Binary(BinaryOp::I64x2Eq) => we::Instruction::I64x2Eq,
+        Instr::Binary(BinaryOp::I64x2Ne) => we::Instruction::I64x2Ne,
+        Instr::Binary(BinaryOp::I64x2LtS) => we::Instruction::I64x2LtS,
+        Instr::Binary(BinaryOp::I64x2GtS) => we::Instruction::I64x2GtS,
+        Instr::Binary(BinaryOp::I64x2LeS) => we::Instruction::I64x2LeS,
+        Instr::Binary(BinaryOp::I64x2GeS) => we::Instruction::I64x2GeS,
+        Instr::Binary(BinaryOp::F32x4Eq) => we::Instruction::F32x4Eq,
+        Instr::Binary(BinaryOp::F32x4Ne) => we::Instruction::F32x4Ne,
+        Instr::Binary(BinaryOp::F32x4Lt) => we::Instruction::F32x4Lt,
+        Instr::Binary(BinaryOp::F32x4Gt) => we::Instruction::F32x4Gt,
+        Instr::Binary(BinaryOp::F32x4Le) => we::Instruction::F32x4Le,
+        Instr::Binary(BinaryOp::F32x4Ge) => we::Instruction::F32x4Ge,
+        Instr::Binary(BinaryOp::F64x2Eq) => we::Instruction::F64x2Eq,
+        Instr::Binary(BinaryOp::F64x2Ne) => we::Instruction::F64x2Ne,
+        Instr::Binary(BinaryOp::F64x2Lt) => we::Instruction::F64x2Lt,
+        Instr::Binary(BinaryOp::F64x2Gt) => we::Instruction::F64x2Gt,
+        Instr::Binary(BinaryOp::F64x2Le) => we::Instruction::F64x2Le,
+        Instr::Binary(BinaryOp::F64x2Ge) => we::Instruction::F64x2Ge,
+        Instr::Binary(BinaryOp::V128And) => we::Instruction::V128And,
+        Instr::Binary(BinaryOp::V128AndNot) => we::Instruction::V128AndNot,
+        Instr::Binary(BinaryOp::V128Or) => we::Instruction::V128Or,
+        Instr::Binary(BinaryOp::V128Xor) => we::Instruction::V128Xor,
+        Instr::Binary(BinaryOp::I8x16NarrowI16x8S) => we::Instruction::I8x16NarrowI16x8S,
+        Instr::Binary(BinaryOp::I8x16NarrowI16x8U) => we::Instruction::I8x16NarrowI16x8U,
+        Instr::Binary(BinaryOp::I8x16Shl) => we::Instruction::I8x16Shl,
+        Instr::Binary(BinaryOp::I8x16ShrS) => we::Instruction::I8x16ShrS,
+        Instr::Binary(BinaryOp::I8x16ShrU) => we::Instruction::I8x16ShrU,
+        Instr::Binary(BinaryOp::I8x16Add) => we::Instruction::I8x16Add,
+        Instr::Binary(BinaryOp::I8x16AddSatS) => we::Instruction::I8x16AddSatS,
+        Instr::Binary(BinaryOp::I8x16AddSatU) => we::Instruction::I8x16AddSatU,
+        Instr::Binary(BinaryOp::I8x16Sub) => we::Instruction::I8x16Sub,
+        Instr::Binary(BinaryOp::I8x16SubSatS) => we::Instruction::I8x16SubSatS,
+        Instr::Binary(BinaryOp::I8x16SubSatU) => we::Instruction::I8x16SubSatU,
+        Instr::Binary(BinaryOp::I8x16MinS) => we::Instruction::I8x16MinS,
+        Instr::Binary(BinaryOp::I8x16MinU) => we::Instruction::I8x16MinU,
+        Instr::Binary(BinaryOp::I8x16MaxS) => we::Instruction::I8x16MaxS,
+        Instr::Binary(BinaryOp::I8x16MaxU) => we::Instruction::I8x16MaxU,
+        Instr::Binary(BinaryOp::I8x16AvgrU) => we::Instruction::I8x16AvgrU,
+        Instr::Binary(BinaryOp::I16x8Q15MulrSatS) => we::Instruction::I16x8Q15MulrSatS,
+        Instr::Binary(BinaryOp::I16x8NarrowI32x4S) => we::Instruction::I16x8NarrowI32x4S,
+        Instr::Binary(BinaryOp::I16x8NarrowI32x4U) => we::Instruction::I16x8NarrowI32x4U,
+        Instr::Binary(BinaryOp::I16x8Shl) => we::Instruction::I16x8Shl,
+        Instr::Binary(BinaryOp::I16x8ShrS) => we::Instruction::I16x8ShrS,
+        Instr::Binary(BinaryOp::I16x8ShrU) => we::Instruction::I16x8ShrU,
+        Instr::Binary(BinaryOp::I16x8Add) => we::Instruction::I16x8Add,
+        Instr::Binary(BinaryOp::I16x8AddSatS) => we::Instruction::I16x8AddSatS,
+        Instr::Binary(BinaryOp::I16x8AddSatU) => we::Instruction::I16x8AddSatU,
+        Instr::Binary(BinaryOp::I16x8Sub) => we::Instruction::I16x8Sub,
+        Instr::Binary(BinaryOp::I16x8SubSatS) => we::Instruction::I16x8SubSatS,
+        Instr::Binary(BinaryOp::I16x8SubSatU) => we::Instruction::I16x8SubSatU,
+        Instr::Binary(BinaryOp::I16x8Mul) => we::Instruction::I16x8Mul,
+        Instr::Binary(BinaryOp::I16x8MinS) => we::Instruction::I16x8MinS,
+        Instr::Binary(BinaryOp::I16x8MinU) => we::Instruction::I16x8MinU,
+        Instr::Binary(BinaryOp::I16x8MaxS) => we::Instruction::I16x8MaxS,
+        Instr::Binary(BinaryOp::I16x8MaxU) => we::Instruction::I16x8MaxU,
+        Instr::Binary(BinaryOp::I16x8AvgrU) => we::Instruction::I16x8AvgrU,
+        Instr::Binary(BinaryOp::I16x8ExtMulLowI8x16S) => we::Instruction::I16x8ExtMulLowI8x16S,
+        Instr::Binary(BinaryOp::I16x8ExtMulHighI8x16S) => we::Instruction::I16x8ExtMulHighI8x16S,
+        Instr::Binary(BinaryOp::I16x8ExtMulLowI8x16U) => we::Instruction::I16x8ExtMulLowI8x16U,
+        Instr::Binary(BinaryOp::I16x8ExtMulHighI8x16U) => we::Instruction::I16x8ExtMulHighI8x16U,
+        Instr::Binary(BinaryOp::I32x4Shl) => we::Instruction::I32x4Shl,
+        Instr::Binary(BinaryOp::I32x4ShrS) => we::Instruction::I32x4ShrS,
+        Instr::Binary(BinaryOp::I32x4ShrU) => we::Instruction::I32x4ShrU,
+        Instr::Binary(BinaryOp::I32x4Add) => we::Instruction::I32x4Add,
+        Instr::Binary(BinaryOp::I32x4Sub) => we::Instruction::I32x4Sub,
+        Instr::Binary(BinaryOp::I32x4Mul) => we::Instruction::I32x4Mul,
+        Instr::Binary(BinaryOp::I32x4MinS) => we::Instruction::I32x4MinS,
+        Instr::Binary(BinaryOp::I32x4MinU) => we::Instruction::I32x4MinU,
+        Instr::Binary(BinaryOp::I32x4MaxS) => we::Instruction::I32x4MaxS,
+        Instr::Binary(BinaryOp::I32x4MaxU) => we::Instruction::I32x4MaxU,
+        Instr::Binary(BinaryOp::I32x4DotI16x8S) => we::Instruction::I32x4DotI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulLowI16x8S) => we::Instruction::I32x4ExtMulLowI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulHighI16x8S) => we::Instruction::I32x4ExtMulHighI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulLowI16x8U) => we::Instruction::I32x4ExtMulLowI16x8U,
+        Instr::Binary(BinaryOp::I32x4ExtMulHighI16x8U) => we::Instruction::I32x4ExtMulHighI16x8U,
+        Instr::Binary(BinaryOp::I64x2Shl) => we::Instruction::I64x2Shl,
+        Instr::Binary(BinaryOp::I64x2ShrS) => we::Instruction::I64x2ShrS,
+        Instr::Binary(BinaryOp::I64x2ShrU) => we::Instruction::I64x2ShrU,
+        Instr::Binary(BinaryOp::I64x2Add) => we::Instruction::I64x2Add,
+        Instr::Binary(BinaryOp::I64x2Sub) => we::Instruction::I64x2Sub,
+        Instr::Binary(BinaryOp::I64x2Mul) => we::Instruction::I64x2Mul,
+        Instr::Binary(BinaryOp::I64x2ExtMulLowI32x4S) => we::Instruction::I64x2ExtMulLowI32x4S,
+        Instr::Binary(BinaryOp::I64x2ExtMulHighI32x4S) => we::Instruction::I64x2ExtMulHighI32x4S,
+        Instr::Binary(BinaryOp::I64x2ExtMulLowI32x4U) => we::Instruction::I64x2ExtMulLowI32x4U,
+        Instr::Binary(BinaryOp::I64x2ExtMulHighI32x4U) => we::Instruction::I64x2ExtMulHighI32x4U,
+        Instr::Binary(BinaryOp::F32x4Add) => we::Instruction::F32x4Add,
+        Instr::Binary(BinaryOp::F32x4Sub) => we::Instruction::F32x4Sub,
+        Instr::Binary(BinaryOp::F32x4Mul) => we::Instruction::F32x4Mul,
+        Instr::Binary(BinaryOp::F32x4Div) => we::Instruction::F32x4Div,
+        Instr::Binary(BinaryOp::F32x4Min) => we::Instruction::F32x4Min,
+        Instr::Binary(BinaryOp::F32x4Max) => we::Instruction::F32x4Max,
+        Instr::Binary(BinaryOp::F32x4PMin) => we::Instruction::F32x4PMin,
+        Instr::Binary(BinaryOp::F32x4PMax) => we::Instruction::F32x4PMax,
+        Instr::Binary(BinaryOp::F64x2Add) => we::Instruction::F64x2Add,
+        Instr::Binary(BinaryOp::F64x2Sub) => we::Instruction::F64x2Sub,
+        Instr::Binary(BinaryOp::F64x2Mul) => we::Instruction::F64x2Mul,
+        Instr::Binary(BinaryOp::F64x2Div) => we::Instruction::F64x2Div,
+        Instr::Binary(BinaryOp::F64x2Min) => we::Instruction::F64x2Min,
+        Instr::Binary(BinaryOp::F64x2Max) => we::Instruction::F64x2Max,
+        Instr::Binary(BinaryOp::F64x2PMin) => we::Instruction::F64x2PMin,
+        Instr::Binary(BinaryOp::F64x2PMax) => we::Instruction::F64x2PMax,
+
+        Instr::Lane(LaneOp::I8x16ExtractLaneS, lane) => we::Instruction::I8x16ExtractLaneS(lane),
+        Instr::Lane(LaneOp::I8x16ExtractLaneU, lane) => we::Instruction::I8x16ExtractLaneU(lane),
+        Instr::Lane(LaneOp::I8x16ReplaceLane, lane) => we::Instruction::I8x16ReplaceLane(lane),
+        Instr::Lane(LaneOp::I16x8ExtractLaneS, lane) => we::Instruction::I16x8ExtractLaneS(lane),
+        Instr::Lane(LaneOp::I16x8ExtractLaneU, lane) => we::Instruction::I16x8ExtractLaneU(lane),
+        Instr::Lane(LaneOp::I16x8ReplaceLane, lane) => we::Instruction::I16x8ReplaceLane(lane),
+        Instr::Lane(LaneOp::I32x4ExtractLane, lane) => we::Instruction::I32x4ExtractLane(lane),
+        Instr::Lane(LaneOp::I32x4ReplaceLane, lane) => we::Instruction::I32x4ReplaceLane(lane),
+        Instr::Lane(LaneOp::I64x2ExtractLane, lane) => we::Instruction::I64x2ExtractLane(lane),
+        Instr::Lane(LaneOp::I64x2ReplaceLane, lane) => we::Instruction::I64x2ReplaceLane(lane),
+        Instr::Lane(LaneOp::F32x4ExtractLane, lane) => we::Instruction::F32x4ExtractLane(lane),
+        Instr::Lane(LaneOp::F32x4ReplaceLane, lane) => we::Instruction::F32x4ReplaceLane(lane),
+        Instr::Lane(LaneOp::F64x2ExtractLane, lane) => we::Instruction::F64x2ExtractLane(lane),
+        Instr::Lane(LaneOp::F64x2ReplaceLane, lane) => we::Instruction::F64x2ReplaceLane(lane),
+        Instr::I8x16Shuffle(lanes) => we::Instruction::I8x16Shuffle(lanes),
+        Instr::V128Bitselect => we::Instruction::V128Bitselect,
+        // Cannot be represented as a single instruction, see `encode_code`.
+        Instr::Unparsed(_) | Instr::Unsupported(..) => return Err(EncodeError::message("unparsed instructions are only allowed in function bodies".to_string())),
     })
 }
 
+pub(crate) fn encode_producers(producers: &Producers) -> Vec<u8> {
+    let mut content = Vec::new();
+    producers.fields.len().encode(&mut content);
+    for field in &producers.fields {
+        field.name.encode(&mut content);
+        field.values.len().encode(&mut content);
+        for value in &field.values {
+            value.name.encode(&mut content);
+            value.version.encode(&mut content);
+        }
+    }
+    content
+}
+
 fn encode_names(
     module: &Module,
     state: &EncodeState,
@@ -645,6 +1153,7 @@ fn encode_names(
     // lazily initialize on access. Then, write them only if they are not `None`.
     let mut functions_subsection: Option<we::NameMap> = None;
     let mut locals_subsection: Option<we::IndirectNameMap> = None;
+    let mut labels_subsection: Option<we::IndirectNameMap> = None;
     for (hl_function_idx, function) in module.functions() {
         let ll_function_idx = state.map_function_idx(hl_function_idx)?.to_u32();
 
@@ -667,8 +1176,38 @@ fn encode_names(
                 .get_or_insert_with(Default::default)
                 .append(ll_function_idx, &local_names);
         }
+
+        if let Some(code) = function.code() {
+            if let Some(label_names) = name_map(code.label_names.iter().map(|(idx, name)| (*idx, name.as_str()))) {
+                labels_subsection
+                    .get_or_insert_with(Default::default)
+                    .append(ll_function_idx, &label_names);
+            }
+        }
     }
 
+    let types_idx = state.types_idx.read().unwrap();
+    let types_subsection = name_map(module.type_names.iter().filter_map(|(type_, name)| {
+        // Types that are not used anywhere in the module were not encoded, so drop their name.
+        types_idx.get(type_).map(|ll_type_idx| (ll_type_idx.to_u32(), name.as_str()))
+    }));
+    let tables_subsection = name_map(module.tables().filter_map(|(idx, table)| {
+        Some((state.map_table_idx(idx).ok()?.to_u32(), table.name.as_deref()?))
+    }));
+    let memories_subsection = name_map(module.memories().filter_map(|(idx, memory)| {
+        Some((state.map_memory_idx(idx).ok()?.to_u32(), memory.name.as_deref()?))
+    }));
+    let globals_subsection = name_map(module.globals().filter_map(|(idx, global)| {
+        Some((state.map_global_idx(idx).ok()?.to_u32(), global.name.as_deref()?))
+    }));
+    // Element and data segments are not re-indexed, so their high-level index is the low-level one.
+    let elements_subsection = name_map(module.elements().filter_map(|(idx, element)| {
+        Some((idx.to_u32(), element.name.as_deref()?))
+    }));
+    let data_subsection = name_map(module.data.iter().enumerate().filter_map(|(idx, data)| {
+        Some((u32::try_from(idx).ok()?, data.name.as_deref()?))
+    }));
+
     let mut name_section: Option<we::NameSection> = None;
     if let Some(module_name) = &module.name {
         name_section
@@ -685,10 +1224,62 @@ fn encode_names(
             .get_or_insert_with(Default::default)
             .locals(locals_subsection);
     }
+    // The remaining subsections are from the extended name section proposal, and must come in
+    // this order, see https://github.com/WebAssembly/extended-name-section.
+    if let Some(labels_subsection) = &labels_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .labels(labels_subsection);
+    }
+    if let Some(types_subsection) = &types_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .types(types_subsection);
+    }
+    if let Some(tables_subsection) = &tables_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .tables(tables_subsection);
+    }
+    if let Some(memories_subsection) = &memories_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .memories(memories_subsection);
+    }
+    if let Some(globals_subsection) = &globals_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .globals(globals_subsection);
+    }
+    if let Some(elements_subsection) = &elements_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .elements(elements_subsection);
+    }
+    if let Some(data_subsection) = &data_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .data(data_subsection);
+    }
 
     Ok(name_section)
 }
 
+/// Returns `None` if there are no names, such that no empty name subsection is written.
+fn name_map<'a>(names: impl Iterator<Item = (u32, &'a str)>) -> Option<we::NameMap> {
+    // Name maps must be sorted by (low-level) index.
+    let mut names: Vec<_> = names.collect();
+    names.sort_unstable_by_key(|(idx, _name)| *idx);
+    if names.is_empty() {
+        return None;
+    }
+    let mut name_map = we::NameMap::new();
+    for (idx, name) in names {
+        name_map.append(idx, name);
+    }
+    Some(name_map)
+}
+
 fn encode_block_type(func_or_block_ty: FunctionType, state: &EncodeState) -> we::BlockType {
     match (func_or_block_ty.inputs(), func_or_block_ty.results()) {
         // Prefer the more compact inline encoding for Wasm MVP block types.
@@ -713,32 +1304,34 @@ impl From<GlobalType> for we::GlobalType {
     }
 }
 
-impl From<Limits> for we::TableType {
-    fn from(limits: Limits) -> Self {
+impl From<&Table> for we::TableType {
+    fn from(table: &Table) -> Self {
         Self {
-            element_type: we::ValType::FuncRef,
-            minimum: limits.initial_size,
-            maximum: limits.max_size,
+            element_type: table.ref_type.into(),
+            // Table sizes are always 32-bit, unlike the sizes of 64-bit memories.
+            minimum: table.limits.initial_size.try_into().expect("table size should fit in 32 bits"),
+            maximum: table.limits.max_size.map(|size| size.try_into().expect("table size should fit in 32 bits")),
         }
     }
 }
 
-impl From<Limits> for we::MemoryType {
-    fn from(limits: Limits) -> Self {
+impl From<&Memory> for we::MemoryType {
+    fn from(memory: &Memory) -> Self {
         Self {
-            minimum: limits
-                .initial_size
-                .try_into()
-                .expect("u32 to u64 should always succeed"),
-            maximum: limits
-                .max_size
-                .map(|u32| u32.try_into().expect("u32 to u64 should always succeed")),
-            memory64: false,
-            shared: false,
+            minimum: memory.limits.initial_size,
+            maximum: memory.limits.max_size,
+            memory64: memory.index_type == IndexType::I64,
+            shared: memory.shared,
         }
     }
 }
 
+impl From<RefType> for we::ValType {
+    fn from(hl_ref_type: RefType) -> Self {
+        ValType::from(hl_ref_type).into()
+    }
+}
+
 impl From<ValType> for we::ValType {
     fn from(hl_val_type: ValType) -> Self {
         use ValType::*;
@@ -747,6 +1340,9 @@ impl From<ValType> for we::ValType {
             I64 => we::ValType::I64,
             F32 => we::ValType::F32,
             F64 => we::ValType::F64,
+            V128 => we::ValType::V128,
+            FuncRef => we::ValType::FuncRef,
+            ExternRef => we::ValType::ExternRef,
         }
     }
 }
@@ -754,10 +1350,7 @@ impl From<ValType> for we::ValType {
 impl From<Memarg> for we::MemArg {
     fn from(hl_memarg: Memarg) -> Self {
         Self {
-            offset: hl_memarg
-                .offset
-                .try_into()
-                .expect("u32 to u64 should always succeed"),
+            offset: hl_memarg.offset,
             align: hl_memarg.alignment_exp.into(),
             memory_index: 0,
         }
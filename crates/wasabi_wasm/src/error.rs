@@ -1,6 +1,7 @@
 //! Typed errors and warnings when parsing/encoding of modules.
 
 use crate::extensions::WasmExtension;
+use crate::SectionId;
 
 /// Used only for errors (not recoverable, i.e., parsing stops and does not return an AST).
 #[derive(Debug, thiserror::Error)]
@@ -40,6 +41,91 @@ pub enum ParseIssue {
         extension: WasmExtension,
     },
 
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: duplicate {:?} section", offset, section)]
+    DuplicateSection {
+        offset: usize,
+        section: SectionId,
+    },
+
+    /// Only returned when parsing with a strict section order, see
+    /// `ParseOptions::strict_section_order`.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: {:?} section must come before {:?} section", offset, section, previous)]
+    SectionOutOfOrder {
+        offset: usize,
+        section: SectionId,
+        previous: SectionId,
+    },
+
+    /// A type index was used (e.g., by a function import), but no type section came before.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: {} references a type, but there is no type section before it", offset, referenced_by)]
+    MissingTypeSection {
+        offset: usize,
+        referenced_by: &'static str,
+    },
+
+    /// An active element segment initializes a table whose type is not a supertype of the
+    /// segment's type, e.g., `externref` elements in a `funcref` table.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: element segment of type {} does not fit into table of type {}", offset, element_type, table_type)]
+    ElementTypeMismatch {
+        offset: usize,
+        element_type: crate::RefType,
+        table_type: crate::RefType,
+    },
+
+    /// The maximum size of a table or memory is smaller than its initial size.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: maximum size {} is smaller than initial size {}", offset, max, min)]
+    InvalidLimits {
+        offset: usize,
+        min: u64,
+        max: u64,
+    },
+
+    /// The initial or maximum size of a memory is larger than the address space, i.e., more than
+    /// 65536 pages (4 GiB) for 32-bit memories or 2^48 pages for 64-bit memories.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: memory size of {} pages exceeds the limit of {} pages", offset, pages, max_pages)]
+    MemoryTooLarge {
+        offset: usize,
+        pages: u64,
+        max_pages: u64,
+    },
+
+    /// Only a warning when parsing with `ParseOptions::lossy_names`, otherwise names that are
+    /// not valid UTF-8 are a `Wasmparser` error. `bytes` is the original name, `name` the
+    /// lossily converted one that is used in the module instead.
+    #[error("name at offset 0x{:x} is not valid UTF-8, using {:?} instead", offset, name)]
+    InvalidUtf8Name {
+        offset: usize,
+        bytes: Vec<u8>,
+        name: String,
+    },
+
+    /// A function body could not be decoded, e.g., because of a malformed LEB128 or a truncated
+    /// instruction. `offset` is the absolute offset of the error in the binary, `body_offset` the
+    /// offset where the body starts (such that their difference is the position within the body).
+    #[error("error decoding body of function {} at offset 0x{:x} (0x{:x} into the body): {}", func.to_u32(), offset, offset - body_offset, source.message())]
+    BodyDecode {
+        func: crate::Idx<crate::Function>,
+        offset: usize,
+        body_offset: usize,
+        #[source]
+        source: wasmparser::BinaryReaderError,
+    },
+
+    /// A count or index does not fit into `usize`, which can only happen on 16-bit targets.
+    #[error("error parsing WebAssembly binary at offset 0x{:x}: {} is too large for this platform", offset, value)]
+    TooLarge {
+        offset: usize,
+        value: u32,
+    },
+
+    /// Line and column are 1-based.
+    #[error("error parsing WebAssembly text at line {}, column {}: {}", line, column, message)]
+    Text {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
@@ -58,12 +144,59 @@ impl ParseIssue {
         ParseIssue::Unsupported { offset, extension }
     }
 
+    pub fn duplicate_section(offset: usize, section: SectionId) -> Self {
+        ParseIssue::DuplicateSection { offset, section }
+    }
+
+    pub fn section_out_of_order(offset: usize, section: SectionId, previous: SectionId) -> Self {
+        ParseIssue::SectionOutOfOrder { offset, section, previous }
+    }
+
+    pub fn missing_type_section(offset: usize, referenced_by: &'static str) -> Self {
+        ParseIssue::MissingTypeSection { offset, referenced_by }
+    }
+
+    pub fn invalid_limits(offset: usize, min: u64, max: u64) -> Self {
+        ParseIssue::InvalidLimits { offset, min, max }
+    }
+
+    pub fn memory_too_large(offset: usize, pages: u64, max_pages: u64) -> Self {
+        ParseIssue::MemoryTooLarge { offset, pages, max_pages }
+    }
+
+    pub fn element_type_mismatch(offset: usize, element_type: crate::RefType, table_type: crate::RefType) -> Self {
+        ParseIssue::ElementTypeMismatch { offset, element_type, table_type }
+    }
+
+    pub fn invalid_utf8_name(offset: usize, bytes: Vec<u8>, name: String) -> Self {
+        ParseIssue::InvalidUtf8Name { offset, bytes, name }
+    }
+
+    pub fn body_decode(func: crate::Idx<crate::Function>, body_offset: usize, source: wasmparser::BinaryReaderError) -> Self {
+        ParseIssue::BodyDecode { func, offset: source.offset(), body_offset, source }
+    }
+
+    pub fn too_large(offset: usize, value: u32) -> Self {
+        ParseIssue::TooLarge { offset, value }
+    }
+
     pub fn offset(&self) -> Option<usize> {
         match self {
             ParseIssue::Wasmparser(err) => Some(err.offset()),
             ParseIssue::Message { offset, .. } => Some(*offset),
             ParseIssue::Index { offset, .. } => Some(*offset),
             ParseIssue::Unsupported { offset, .. } => Some(*offset),
+            ParseIssue::DuplicateSection { offset, .. } => Some(*offset),
+            ParseIssue::SectionOutOfOrder { offset, .. } => Some(*offset),
+            ParseIssue::MissingTypeSection { offset, .. } => Some(*offset),
+            ParseIssue::ElementTypeMismatch { offset, .. } => Some(*offset),
+            ParseIssue::InvalidLimits { offset, .. } => Some(*offset),
+            ParseIssue::MemoryTooLarge { offset, .. } => Some(*offset),
+            ParseIssue::InvalidUtf8Name { offset, .. } => Some(*offset),
+            ParseIssue::TooLarge { offset, .. } => Some(*offset),
+            ParseIssue::BodyDecode { offset, .. } => Some(*offset),
+            // Not an offset in the binary, see `line` and `column` instead.
+            ParseIssue::Text { .. } => None,
             ParseIssue::Io(_) => None,
         }
     }
@@ -77,6 +210,57 @@ impl ParseError {
     pub fn offset(&self) -> Option<usize> {
         self.0.offset()
     }
+
+    /// The underlying issue, e.g., to match on the kind of error.
+    pub fn issue(&self) -> &ParseIssue {
+        &self.0
+    }
+
+    pub fn into_issue(self) -> ParseIssue {
+        *self.0
+    }
+
+    /// The error message, followed by a hexdump of the bytes around the error offset in `source`
+    /// (the binary that failed to parse), with a caret below the offending byte. Without an offset
+    /// (e.g., for I/O errors) or if it is outside of `source`, this is just the error message.
+    pub fn render(&self, source: &[u8]) -> String {
+        use std::fmt::Write;
+
+        const BYTES_PER_LINE: usize = 16;
+        let mut out = self.to_string();
+        let Some(offset) = self.offset().filter(|&offset| offset <= source.len()) else {
+            return out;
+        };
+        // One line of context before and after the line with the error.
+        let error_line = offset / BYTES_PER_LINE;
+        for line in error_line.saturating_sub(1)..=error_line + 1 {
+            let start = line * BYTES_PER_LINE;
+            let bytes = source.get(start..source.len().min(start + BYTES_PER_LINE)).unwrap_or_default();
+            if bytes.is_empty() && line != error_line {
+                continue;
+            }
+            write!(out, "\n{start:08x}:").unwrap();
+            for byte in bytes {
+                write!(out, " {byte:02x}").unwrap();
+            }
+            if line == error_line {
+                // Each byte takes three characters, after the offset and colon.
+                write!(out, "\n{}^^", " ".repeat(10 + 3 * (offset - start))).unwrap();
+                if offset == source.len() {
+                    out.push_str(" (end of input)");
+                }
+            }
+        }
+        out
+    }
+}
+
+#[test]
+fn parse_error_is_send_and_sync() {
+    // Required for returning errors from the parallel (rayon) parsing of function bodies.
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ParseError>();
+    assert_send_sync::<ParseIssue>();
 }
 
 // Allow conversion of everything that can be converted into a `ParseIssue`
@@ -135,3 +319,162 @@ where
         EncodeError(Box::new(err.into()))
     }
 }
+
+/// Editing a function body would split a block from its `end` (or `else`), see
+/// `Code::insert_instrs`, `Code::remove_range`, and `Code::map_instrs`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("editing instructions would change the block structure at instruction #{}: {}", instr_idx, message)]
+pub struct BlockStructureError {
+    /// Index in the function body, or in the inserted instructions for `Code::insert_instrs`, or
+    /// in the new function body for `Code::map_instrs`.
+    pub instr_idx: usize,
+    pub message: &'static str,
+}
+
+/// The control-flow graph of a function body could not be built, see `cfg::build_cfg`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("cannot build control-flow graph at instruction #{}: {}", instr_idx, message)]
+pub struct CfgError {
+    pub instr_idx: usize,
+    pub message: &'static str,
+}
+
+/// A constant expression (e.g., the offset of a data segment or the initializer of a global)
+/// could not be evaluated statically, see `eval_const_expr`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ConstEvalError {
+    #[error("instruction #{} ({}) is not allowed in constant expressions", instr_idx, instr)]
+    NonConstant {
+        instr_idx: usize,
+        instr: &'static str,
+    },
+
+    #[error("instruction #{} ({}) produces a reference, which cannot be represented as a value", instr_idx, instr)]
+    Reference {
+        instr_idx: usize,
+        instr: &'static str,
+    },
+
+    /// The value of an imported global is only known at instantiation time.
+    #[error("global {} is imported, its value is only known at instantiation", .0.to_u32())]
+    ImportedGlobal(crate::Idx<crate::Global>),
+
+    #[error("global {} is mutable, so it cannot be used in constant expressions", .0.to_u32())]
+    MutableGlobal(crate::Idx<crate::Global>),
+
+    #[error("global {} does not exist", .0.to_u32())]
+    InvalidGlobal(crate::Idx<crate::Global>),
+
+    #[error("table {} does not exist", .0.to_u32())]
+    InvalidTable(crate::Idx<crate::Table>),
+
+    #[error("memory {} does not exist", .0.to_u32())]
+    InvalidMemory(crate::Idx<crate::Memory>),
+
+    /// Constant expressions may only refer to imported globals, see the validation rules at
+    /// https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+    #[error("global {} is defined in the module, but only imported globals can be used in constant expressions", .0.to_u32())]
+    NonImportedGlobalInInit(crate::Idx<crate::Global>),
+
+    #[error("constant expression must produce exactly one value and end with `end`")]
+    Malformed,
+
+    /// See `Module::initial_memory_bytes`.
+    #[error("offset of data segment {} is not a valid 32-bit address", .0.to_u32())]
+    InvalidDataOffset(crate::Idx<crate::Data>),
+
+    /// See `Module::resolved_table_elements`.
+    #[error("offset of element segment {} is not a valid 32-bit table index", .0.to_u32())]
+    InvalidElementOffset(crate::Idx<crate::Element>),
+
+    /// See `Module::resolved_table_elements`.
+    #[error("item #{} of element segment {} is neither `ref.func` nor `ref.null`", item_idx, .element.to_u32())]
+    UnresolvedElementItem {
+        element: crate::Idx<crate::Element>,
+        item_idx: usize,
+    },
+}
+
+/// Two modules could not be linked, see `link::link`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LinkError {
+    #[error("main module has no function import {}.{}", .0.module, .0.name)]
+    ImportNotFound(crate::link::ImportRef),
+
+    #[error("library module has no function export {}", .0.name)]
+    ExportNotFound(crate::link::ExportRef),
+
+    #[error("function import {}.{} has type {}, but export {} has type {}", .import.module, .import.name, import_type, .export.name, export_type)]
+    TypeMismatch {
+        import: crate::link::ImportRef,
+        import_type: crate::FunctionType,
+        export: crate::link::ExportRef,
+        export_type: crate::FunctionType,
+    },
+
+    #[error("both modules have an export named {}", .0)]
+    DuplicateExport(String),
+
+    /// Merging tables, memories, or start functions is not supported (yet), so at most one of
+    /// the modules may have them.
+    #[error("cannot link modules that both have a {}", .0)]
+    Unsupported(&'static str),
+
+    /// The functions of one of the modules could not be renumbered, e.g., because of placeholders.
+    #[error(transparent)]
+    Renumber(#[from] RenumberError),
+}
+
+/// Functions could not be renumbered, e.g., when inserting or removing functions, see
+/// `Module::insert_imported_function` and `Module::remove_unreachable_functions`.
+/// The module is left unchanged in that case.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RenumberError {
+    /// The function indices in the raw bytes of `Instr::Unparsed` and `Instr::Unsupported` (see
+    /// `OnUnsupported`) are unknown, so they can neither be updated nor analyzed.
+    #[error("function {} contains placeholders for unparsed instructions, whose function indices cannot be updated", .0.to_u32())]
+    Placeholder(crate::Idx<crate::Function>),
+
+    #[error("reference to function {}, which does not exist (anymore)", .0.to_u32())]
+    InvalidFunction(crate::Idx<crate::Function>),
+}
+
+/// A reference between module-level entities (outside of function bodies) is invalid, see
+/// `Module::check_references`. (References in instructions are checked by `validate::validate`.)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RefError {
+    #[error("start function {} does not exist", .0.to_u32())]
+    InvalidStart(crate::Idx<crate::Function>),
+
+    #[error("start function {} has type {}, but must have type [] -> []", .0.to_u32(), .1)]
+    StartType(crate::Idx<crate::Function>, crate::FunctionType),
+
+    #[error("export name {} is not unique", .0)]
+    DuplicateExport(String),
+
+    #[error("element segment {} refers to non-existing table {}", .element.to_u32(), .table.to_u32())]
+    InvalidElementTable {
+        element: crate::Idx<crate::Element>,
+        table: crate::Idx<crate::Table>,
+    },
+
+    #[error("element segment {} of type {} initializes table {} of type {}", .element.to_u32(), .element_type.to_str(), .table.to_u32(), .table_type.to_str())]
+    ElementTypeMismatch {
+        element: crate::Idx<crate::Element>,
+        element_type: crate::RefType,
+        table: crate::Idx<crate::Table>,
+        table_type: crate::RefType,
+    },
+
+    #[error("element segment {} refers to non-existing function {}", .element.to_u32(), .function.to_u32())]
+    InvalidElementFunction {
+        element: crate::Idx<crate::Element>,
+        function: crate::Idx<crate::Function>,
+    },
+
+    #[error("data segment {} refers to non-existing memory {}", .data.to_u32(), .memory.to_u32())]
+    InvalidDataMemory {
+        data: crate::Idx<crate::Data>,
+        memory: crate::Idx<crate::Memory>,
+    },
+}
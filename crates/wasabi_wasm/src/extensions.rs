@@ -1,5 +1,6 @@
 /// See https://webassembly.org/roadmap/ and https://github.com/WebAssembly/proposals.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WasmExtension {
     // Extensions that are already standardized and merged into WebAssembly 1.1:
     NontrappingFloatToInt,
@@ -45,6 +46,54 @@ impl WasmExtension {
         }
     }
 
+    /// Stable identifier in kebab-case, e.g., for serializing analysis results.
+    /// Mostly the name of the proposal repository (see `url()`), e.g., "bulk-memory-operations".
+    pub fn id(self) -> &'static str {
+        use WasmExtension::*;
+        match self {
+            NontrappingFloatToInt => "nontrapping-float-to-int-conversions",
+            SignExtensionOps => "sign-extension-ops",
+            MultiValue => "multi-value",
+            ReferenceTypes => "reference-types",
+            BulkMemoryOperations => "bulk-memory-operations",
+            Simd => "simd",
+
+            ThreadsAtomics => "threads",
+            RelaxedSimd => "relaxed-simd",
+            Memory64 => "memory64",
+            ExceptionHandling => "exception-handling",
+            ExtendedNameSection => "extended-name-section",
+            TailCalls => "tail-call",
+            TypeImports => "type-imports",
+            MultiMemory => "multi-memory",
+            ComponentModel => "component-model",
+        }
+    }
+
+    /// Inverse of `id()`.
+    pub fn from_id(id: &str) -> Option<Self> {
+        use WasmExtension::*;
+        Some(match id {
+            "nontrapping-float-to-int-conversions" => NontrappingFloatToInt,
+            "sign-extension-ops" => SignExtensionOps,
+            "multi-value" => MultiValue,
+            "reference-types" => ReferenceTypes,
+            "bulk-memory-operations" => BulkMemoryOperations,
+            "simd" => Simd,
+
+            "threads" => ThreadsAtomics,
+            "relaxed-simd" => RelaxedSimd,
+            "memory64" => Memory64,
+            "exception-handling" => ExceptionHandling,
+            "extended-name-section" => ExtendedNameSection,
+            "tail-call" => TailCalls,
+            "type-imports" => TypeImports,
+            "multi-memory" => MultiMemory,
+            "component-model" => ComponentModel,
+            _ => return None,
+        })
+    }
+
     #[rustfmt::skip]
     pub fn url(self) -> &'static str {
         use WasmExtension::*;
@@ -68,3 +117,12 @@ impl WasmExtension {
         }
     }
 }
+
+/// Parses the identifier returned by `WasmExtension::id()`.
+impl std::str::FromStr for WasmExtension {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_id(s).ok_or(())
+    }
+}
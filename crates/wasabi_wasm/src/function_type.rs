@@ -2,7 +2,7 @@
 //! representation for them.
 //! The goals are:
 //! - Use at most 32 bits of memory, because they are frequently part of larger
-//! AST data types, e.g., in functions and instructions.
+//!   AST data types, e.g., in functions and instructions.
 //! - Should be cheap to compare for equality, just a single `u32` comparison.
 //! - Should be cheap to copy, ideally just a memcpy (Rust: Copy trait)
 //! - Should be cheap to create, which is espaclly common in parsing and type checking.
@@ -126,7 +126,7 @@ impl FunctionType {
 
 impl PartialOrd for FunctionType {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        (self.inputs(), self.results()).partial_cmp(&(other.inputs(), other.results()))
+        Some(self.cmp(other))
     }
 }
 
@@ -148,17 +148,43 @@ impl fmt::Display for FunctionType {
     }
 }
 
+// The internal representation (Gödel number or arena id) is only valid in the current process, so
+// (de)serialize the text representation instead, e.g., `[i32, i32] -> [i64]`. Being a string, it
+// can also be used as a map key in JSON, see `Module::type_names`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FunctionType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FunctionType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let str = String::deserialize(deserializer)?;
+        str.parse().map_err(|()| serde::de::Error::custom(format!("invalid function type '{str}'")))
+    }
+}
+
+/// Parses the format of `Display`, e.g., `[i32, i32] -> [i64]`, but also accepts types separated by
+/// whitespace instead of commas (`[i32 i32] -> [i64]`), parentheses instead of brackets
+/// (`(i32, i32) -> i64`), and omitting the brackets altogether (`i32 i32 -> i64`, `-> i64`), e.g.,
+/// for filtering functions by type on the command line.
 impl FromStr for FunctionType {
     type Err = ();
 
     fn from_str(str: &str) -> Result<Self, Self::Err> {
-        fn trim_filter(s: &str) -> Option<&str> {
+        fn parse_val_types(s: &str) -> Result<Vec<ValType>, ()> {
             let s = s.trim();
-            if s.is_empty() {
-                None
-            } else {
-                Some(s)
-            }
+            let s = match (s.strip_prefix('['), s.strip_prefix('(')) {
+                (Some(rest), _) => rest.strip_suffix(']').ok_or(())?,
+                (_, Some(rest)) => rest.strip_suffix(')').ok_or(())?,
+                (None, None) => s,
+            };
+            s.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(ValType::from_str)
+                .collect()
         }
 
         // Split by the arrow.
@@ -170,24 +196,27 @@ impl FromStr for FunctionType {
             return Err(());
         }
 
-        // Split individual types by comma, and remove brackets.
-        let params = params
-            .trim()
-            .strip_prefix('[').ok_or(())?
-            .strip_suffix(']').ok_or(())?
-            .split(',')
-            .filter_map(trim_filter)
-            .map(ValType::from_str)
-            .collect::<Result<Vec<_>, _>>()?;
-        let results = results
-            .trim()
-            .strip_prefix('[').ok_or(())?
-            .strip_suffix(']').ok_or(())?
-            .split(',')
-            .filter_map(trim_filter)
-            .map(ValType::from_str)
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(FunctionType::new(&params, &results))
+        Ok(FunctionType::new(&parse_val_types(params)?, &parse_val_types(results)?))
+    }
+}
+
+#[test]
+fn function_types_are_parsed_in_all_notations() {
+    use ValType::*;
+    let ty = FunctionType::new(&[I32, I32], &[I64]);
+    for str in ["[i32, i32] -> [i64]", "[i32 i32] -> [i64]", "(i32, i32) -> i64", "i32 i32->i64", " [ i32,i32 ] -> ( i64 ) "] {
+        assert_eq!(str.parse(), Ok(ty), "{str}");
+    }
+    assert_eq!(ty.to_string().parse(), Ok(ty));
+
+    let empty = FunctionType::empty();
+    assert_eq!(empty.to_string(), "[] -> []");
+    for str in ["[] -> []", "() -> ()", "->", " -> [] "] {
+        assert_eq!(str.parse(), Ok(empty), "{str}");
+    }
+
+    for str in ["[i32] [i64]", "[i32 -> i64", "(i32] -> []", "[i32] -> [i64] -> []", "[i33] -> []", "[i32;] -> []"] {
+        assert_eq!(str.parse::<FunctionType>(), Err(()), "{str}");
     }
 }
 
@@ -209,6 +238,9 @@ const fn val_type_to_goedel_number(val_type: ValType) -> usize {
         ValType::I64 => 1,
         ValType::F32 => 2,
         ValType::F64 => 3,
+        ValType::V128 => 4,
+        ValType::FuncRef => 5,
+        ValType::ExternRef => 6,
     }
 }
 
@@ -218,12 +250,15 @@ const fn goedel_number_to_val_type(goedel_number: usize) -> Option<ValType> {
         1 => Some(ValType::I64),
         2 => Some(ValType::F32),
         3 => Some(ValType::F64),
+        4 => Some(ValType::V128),
+        5 => Some(ValType::FuncRef),
+        6 => Some(ValType::ExternRef),
         _ => None,
     }
 }
 
 // Determined by the number of variants of `ValType`.
-const VAL_TYPE_MAX_GOEDEL_NUMBER: usize = 3;
+const VAL_TYPE_MAX_GOEDEL_NUMBER: usize = 6;
 
 #[allow(unused)]
 const fn val_type_seq_max_goedel_number(max_seq_len: u32) -> usize {
@@ -240,11 +275,13 @@ const fn val_type_seq_max_goedel_number(max_seq_len: u32) -> usize {
 fn test_goedel_number_constants() {
     assert_eq!(val_type_to_goedel_number(ValType::I32), 0);
     assert_eq!(val_type_to_goedel_number(ValType::F64), 3);
+    assert_eq!(val_type_to_goedel_number(ValType::V128), 4);
+    assert_eq!(val_type_to_goedel_number(ValType::ExternRef), 6);
     assert_eq!(val_type_seq_max_goedel_number(0), 0);
-    assert_eq!(val_type_seq_max_goedel_number(1), 4);
-    assert_eq!(val_type_seq_max_goedel_number(2), 20);
-    assert_eq!(val_type_seq_max_goedel_number(3), 84);
-    assert_eq!(val_type_seq_max_goedel_number(4), 340);
+    assert_eq!(val_type_seq_max_goedel_number(1), 7);
+    assert_eq!(val_type_seq_max_goedel_number(2), 56);
+    assert_eq!(val_type_seq_max_goedel_number(3), 399);
+    assert_eq!(val_type_seq_max_goedel_number(4), 2800);
 }
 
 fn val_type_seq_to_goedel_number(seq: impl IntoIterator<Item=ValType>) -> Option<usize> {
@@ -262,7 +299,7 @@ fn val_type_seq_to_goedel_number(seq: impl IntoIterator<Item=ValType>) -> Option
 fn test_val_type_seq_to_goedel_number() {
     assert_eq!(val_type_seq_to_goedel_number([]), Some(0));
     assert_eq!(val_type_seq_to_goedel_number([ValType::I32]), Some(1));
-    assert_eq!(val_type_seq_to_goedel_number([ValType::I32, ValType::I32]), Some(5));
+    assert_eq!(val_type_seq_to_goedel_number([ValType::I32, ValType::I32]), Some(8));
 }
 
 // Reverse direction: Gödel number to slice.
@@ -318,7 +355,7 @@ fn goedel_number_to_val_type_seq(mut goedel_number: usize) -> Vec<ValType> {
 fn test_goedel_number_to_val_type_seq() {
     assert_eq!(goedel_number_to_val_type_seq(0), vec![]);
     assert_eq!(goedel_number_to_val_type_seq(1), vec![ValType::I32]);
-    assert_eq!(goedel_number_to_val_type_seq(5), vec![ValType::I32, ValType::I32]);
+    assert_eq!(goedel_number_to_val_type_seq(8), vec![ValType::I32, ValType::I32]);
 }
 
 #[test]
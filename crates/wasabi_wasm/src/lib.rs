@@ -7,11 +7,24 @@ mod error;
 // Export error types directly under the crate.
 pub use crate::error::*;
 
+pub mod callgraph;
+pub mod cfg;
+pub mod link;
+pub mod stats;
 pub mod types;
+pub mod validate;
+pub mod visit;
+pub mod wat;
 
+mod const_eval;
+pub use crate::const_eval::eval_const_expr;
 mod encode;
 mod extensions;
+// Export such that users can name the extensions, e.g., in `ParseIssue::Unsupported`.
+pub use crate::extensions::WasmExtension;
 mod parse;
+pub use crate::parse::detect_extensions;
+pub use crate::parse::{default_instr_capacity, OnUnsupported, ParseOptions, ThreadPool, MAX_PREALLOCATED_INSTRS};
 
 #[cfg(test)]
 mod tests;
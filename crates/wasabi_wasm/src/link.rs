@@ -0,0 +1,212 @@
+//! Static linking of two modules, e.g., to bundle a library with the module that uses it.
+//!
+//! Linking concatenates the functions, globals, tables, memories, tags, and element and data segments of
+//! both modules (the ones of the library after the ones of the main module) and renumbers all
+//! references to them. Then, the function imports of the main module given in `resolve` are
+//! replaced by the corresponding exported functions of the library.
+//!
+//! Only function imports can be resolved so far. Tables and memories are not merged, so linking
+//! fails if both modules have (defined or imported) tables, or both have memories, or both have a
+//! start function. All other imports (including those of the library) remain imports.
+//!
+//! Example: Link a module that imports `add` with a library that implements it.
+//! ```
+//! # #[cfg(feature = "wat")] {
+//! use wasabi_wasm::Module;
+//! use wasabi_wasm::link::{link, ExportRef, ImportRef};
+//!
+//! let main = Module::from_wat(r#"(module
+//!     (import "lib" "add" (func $add (param i32 i32) (result i32)))
+//!     (func (export "main") (result i32)
+//!         i32.const 1
+//!         i32.const 2
+//!         call $add))"#).unwrap();
+//! let lib = Module::from_wat(r#"(module
+//!     (func (export "add") (param i32 i32) (result i32)
+//!         local.get 0
+//!         local.get 1
+//!         i32.add))"#).unwrap();
+//!
+//! let linked = link(main, lib, &[(ImportRef::new("lib", "add"), ExportRef::new("add"))]).unwrap();
+//! assert_eq!(linked.functions.len(), 2);
+//! assert!(linked.functions().all(|(_, function)| function.is_defined()));
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{DataMode, ElementItems, ElementMode, Function, Idx, ImportOrPresent, Instr, LinkError, Module};
+
+/// A function import of the main module, by module and field name.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ImportRef {
+    pub module: String,
+    pub name: String,
+}
+
+impl ImportRef {
+    pub fn new(module: impl Into<String>, name: impl Into<String>) -> Self {
+        ImportRef { module: module.into(), name: name.into() }
+    }
+}
+
+/// A function export of the library module, by name.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ExportRef {
+    pub name: String,
+}
+
+impl ExportRef {
+    pub fn new(name: impl Into<String>) -> Self {
+        ExportRef { name: name.into() }
+    }
+}
+
+/// Links `lib` into `main`, replacing each function import of `main` in `resolve` with the
+/// exported function of `lib` it is paired with (which must have the same type). If `main`
+/// imports the same function more than once, all of those imports are resolved.
+///
+/// The exports of both modules are kept, so their names must be distinct. Exports of resolved
+/// imports of `main` (i.e., re-exports) are moved to the library function that replaces them. The
+/// name of the
+/// module and the custom (and unknown) sections are those of `main`, i.e., custom sections of `lib`
+/// (which may refer to its original indices, e.g., debug info) are dropped.
+///
+/// Function bodies with placeholders (see `OnUnsupported`) are rejected in both modules, since the
+/// indices in their raw bytes cannot be updated.
+pub fn link(mut main: Module, mut lib: Module, resolve: &[(ImportRef, ExportRef)]) -> Result<Module, LinkError> {
+    main.check_no_placeholders()?;
+    lib.check_no_placeholders()?;
+    if !main.tables.is_empty() && !lib.tables.is_empty() {
+        return Err(LinkError::Unsupported("table"));
+    }
+    if !main.memories.is_empty() && !lib.memories.is_empty() {
+        return Err(LinkError::Unsupported("memory"));
+    }
+    if main.start.is_some() && lib.start.is_some() {
+        return Err(LinkError::Unsupported("start function"));
+    }
+
+    let main_exports: BTreeSet<&String> = export_names(&main).collect();
+    if let Some(duplicate) = export_names(&lib).find(|name| main_exports.contains(name)) {
+        return Err(LinkError::DuplicateExport(duplicate.clone()));
+    }
+
+    // Maps resolved imports of `main` to the exported function of `lib` (in their original index spaces).
+    let mut resolved: BTreeMap<Idx<Function>, Idx<Function>> = BTreeMap::new();
+    for (import, export) in resolve {
+        let (export_idx, export_function) = lib.function_by_export(&export.name)
+            .ok_or_else(|| LinkError::ExportNotFound(export.clone()))?;
+        let mut found = false;
+        for (import_idx, import_function) in main.functions() {
+            if import_function.import() != Some((import.module.as_str(), import.name.as_str())) {
+                continue;
+            }
+            if import_function.type_ != export_function.type_ {
+                return Err(LinkError::TypeMismatch {
+                    import: import.clone(),
+                    import_type: import_function.type_,
+                    export: export.clone(),
+                    export_type: export_function.type_,
+                });
+            }
+            resolved.insert(import_idx, export_idx);
+            found = true;
+        }
+        if !found {
+            return Err(LinkError::ImportNotFound(import.clone()));
+        }
+    }
+
+    // Functions of `lib` come after the unresolved functions of `main`.
+    let mut main_function_map = Vec::with_capacity(main.functions.len());
+    let mut kept = 0usize;
+    for (idx, _) in main.functions() {
+        if resolved.contains_key(&idx) {
+            main_function_map.push(None);
+        } else {
+            main_function_map.push(Some(kept));
+            kept += 1;
+        }
+    }
+    let lib_function_offset = kept;
+    main.remap_function_indices(|idx| {
+        let new_idx = match main_function_map.get(idx.to_usize())? {
+            Some(new_idx) => *new_idx,
+            None => lib_function_offset + resolved[&idx].to_usize(),
+        };
+        Some(new_idx.into())
+    })?;
+    let lib_function_count = lib.functions.len();
+    lib.remap_function_indices(|idx| (idx.to_usize() < lib_function_count).then(|| (lib_function_offset + idx.to_usize()).into()))?;
+    shift_indices(&mut lib, main.globals.len(), main.elements.len(), main.data.len());
+
+    for (import_idx, export_idx) in &resolved {
+        let export = std::mem::take(&mut main.functions[import_idx.to_usize()].export);
+        lib.functions[export_idx.to_usize()].export.extend(export);
+    }
+    let mut idx = 0;
+    main.functions.retain(|_| {
+        idx += 1;
+        main_function_map[idx - 1].is_some()
+    });
+    main.functions.append(&mut lib.functions);
+    main.globals.append(&mut lib.globals);
+    main.tables.append(&mut lib.tables);
+    main.memories.append(&mut lib.memories);
+    main.tags.append(&mut lib.tags);
+    main.elements.append(&mut lib.elements);
+    main.data.append(&mut lib.data);
+    main.data_count_section |= lib.data_count_section;
+    main.start = main.start.or(lib.start);
+    for (type_, name) in lib.type_names {
+        main.type_names.entry(type_).or_insert(name);
+    }
+    for extension in lib.metadata.used_extensions() {
+        main.metadata.add_used_extension(extension);
+    }
+    Ok(main)
+}
+
+fn export_names(module: &Module) -> impl Iterator<Item = &String> {
+    let functions = module.functions.iter().flat_map(|function| &function.export);
+    let globals = module.globals.iter().flat_map(|global| &global.export);
+    let tables = module.tables.iter().flat_map(|table| &table.export);
+    let memories = module.memories.iter().flat_map(|memory| &memory.export);
+    let tags = module.tags.iter().flat_map(|tag| &tag.export);
+    functions.chain(globals).chain(tables).chain(memories).chain(tags)
+}
+
+/// Adds the given offsets to all references to globals, element segments, and data segments, i.e.,
+/// in function bodies, global initializers, and segment offsets and items.
+fn shift_indices(module: &mut Module, globals: usize, elements: usize, data: usize) {
+    let shift_instr = |instr: &mut Instr| match instr {
+        Instr::Global(_, global) => *global = (global.to_usize() + globals).into(),
+        Instr::TableInit(element, _) | Instr::ElemDrop(element) => *element = (element.to_usize() + elements).into(),
+        Instr::MemoryInit(data_, _) | Instr::DataDrop(data_) => *data_ = (data_.to_usize() + data).into(),
+        _ => {}
+    };
+    for function in &mut module.functions {
+        if let Some(instrs) = function.instrs_mut() {
+            instrs.iter_mut().for_each(shift_instr);
+        }
+    }
+    for global in &mut module.globals {
+        if let ImportOrPresent::Present(init) = &mut global.init {
+            init.iter_mut().for_each(shift_instr);
+        }
+    }
+    for element in &mut module.elements {
+        if let ElementMode::Active { offset, .. } = &mut element.mode {
+            offset.iter_mut().for_each(shift_instr);
+        }
+        if let ElementItems::Expressions(exprs) = &mut element.items {
+            exprs.iter_mut().flatten().for_each(shift_instr);
+        }
+    }
+    for data in &mut module.data {
+        if let DataMode::Active { offset, .. } = &mut data.mode {
+            offset.iter_mut().for_each(shift_instr);
+        }
+    }
+}
@@ -1,10 +1,14 @@
 //! Code for parsing the WebAssembly binary format to our AST.
 //! Uses `wasmparser` crate for the actual low-level work.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryInto;
+use std::io;
+use std::io::Read;
 use std::sync::RwLock;
 
 use ordered_float::OrderedFloat;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
 use smallvec::SmallVec;
@@ -13,30 +17,389 @@ use wasmparser as wp;
 use crate::extensions::WasmExtension;
 use crate::*;
 
-// The streaming API of wasmparser is a bit cumbersome, so implement reading
-// from bytes fully resident in memory first.
-// TODO Add a second API from streaming sources, i.e., `io::Read` like here:
-// https://docs.rs/wasmparser/latest/wasmparser/struct.Parser.html#examples
-pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), ParseError> {
-    let mut warnings = Vec::new();
+/// Assembles the text format to a binary, with the error location (if any) as line and column.
+#[cfg(feature = "wat")]
+pub fn wat_to_bytes(text: &str) -> Result<Vec<u8>, ParseError> {
+    let to_issue = |err: wast::Error| {
+        let (line, column) = err.span().linecol_in(text);
+        ParseIssue::Text {
+            line: line + 1,
+            column: column + 1,
+            message: err.message(),
+        }
+    };
+    let buffer = wast::parser::ParseBuffer::new(text).map_err(to_issue)?;
+    let mut wat: wast::Wat = wast::parser::parse(&buffer).map_err(to_issue)?;
+    Ok(wat.encode().map_err(to_issue)?)
+}
+
+/// Optional checks or information during parsing, see `Module::from_bytes_with_options`.
+/// All disabled by default, except that function bodies and segments are parsed in parallel.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions<'a> {
+    /// If true, returns an error if the non-custom sections are not in the order mandated by the
+    /// spec (e.g., type before import before function section). If false, sections may appear in
+    /// any order (as long as, e.g., the memory section still comes before exports of memories),
+    /// like in previous versions.
+    pub strict_section_order: bool,
+    /// Record the byte offset of every instruction in `Offsets::functions_instrs`, e.g., to map a
+    /// program counter to an instruction with `Offsets::instr_at`. Costs one `usize` per instruction.
+    pub instr_offsets: bool,
+    /// If false, function bodies and element and data segments are parsed one after the other on
+    /// the calling thread, e.g., inside libraries that manage their own concurrency, or on
+    /// targets without threads. Without the `parallel` feature, this is always the case.
+    pub parallel: bool,
+    /// Parse function bodies and element and data segments in this thread pool instead of the
+    /// global one of `rayon`. Ignored if not `parallel`.
+    pub thread_pool: Option<&'a ThreadPool>,
+    /// What to do with function bodies that contain instructions of unsupported extensions.
+    pub on_unsupported: OnUnsupported,
+    /// If true, import and export names that are not valid UTF-8 (which some toolchains produce)
+    /// are converted lossily, i.e., invalid sequences are replaced by U+FFFD, and a
+    /// `ParseIssue::InvalidUtf8Name` with the original bytes is added as a warning. Otherwise,
+    /// such names are an error. (Note that the encoded module then contains the converted names.)
+    /// Invalid names in the name section always just cause it to be kept as a raw custom section.
+    pub lossy_names: bool,
+    /// How many instructions to pre-allocate for a function body of the given size in bytes,
+    /// see `default_instr_capacity`. The result is bounded by the body size, since every
+    /// instruction takes at least one byte.
+    pub instr_capacity: fn(usize) -> usize,
+}
+
+#[cfg(feature = "parallel")]
+pub use rayon::ThreadPool;
+
+/// Stand-in for `rayon::ThreadPool` without the `parallel` feature, such that `ParseOptions` has
+/// the same fields either way. Cannot be constructed, i.e., `ParseOptions::thread_pool` is `None`.
+#[cfg(not(feature = "parallel"))]
+#[derive(Debug)]
+pub enum ThreadPool {}
+
+/// See `ParseOptions::on_unsupported`. Only function bodies are affected, unsupported extensions
+/// in other sections (e.g., the sections of the component model) are always an error.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum OnUnsupported {
+    /// Abort parsing with a `ParseIssue::Unsupported` error.
+    #[default]
+    Error,
+    /// Replace the function body with just `unreachable` (keeping the locals), and add the
+    /// `ParseIssue::Unsupported` as a warning.
+    SkipBody,
+    /// Replace the function body with `Instr::Unparsed`, which contains the original bytes of the
+    /// body, such that it is encoded unchanged. Also adds the issue as a warning.
+    /// Note that indices (e.g., of functions) in the raw bytes are not updated when encoding, so
+    /// the index spaces of the module should not be changed.
+    Placeholder,
+    /// Parse the function body as usual, but replace each instruction of an unsupported extension
+    /// (e.g., `try`, `catch`, and `throw` from exception handling) with `Instr::Unsupported`,
+    /// which contains its original bytes. Adds the first issue of each body as a warning.
+    /// Functions with such placeholders can be counted and encoded unchanged, but not analyzed
+    /// (e.g., type checked), since the block structure and types of the instructions are unknown.
+    /// The caveat about indices of `Placeholder` applies as well. If something other than an
+    /// instruction is unsupported (e.g., the type of a local), falls back to `Placeholder`.
+    PlaceholderInstrs,
+}
+
+impl Default for ParseOptions<'_> {
+    fn default() -> Self {
+        Self {
+            strict_section_order: false,
+            instr_offsets: false,
+            parallel: true,
+            thread_pool: None,
+            on_unsupported: OnUnsupported::Error,
+            lossy_names: false,
+            instr_capacity: default_instr_capacity,
+        }
+    }
+}
+
+/// Maximum number of instructions that `default_instr_capacity` pre-allocates for a function body,
+/// such that a single huge body does not cause a giant speculative allocation up front.
+pub const MAX_PREALLOCATED_INSTRS: usize = 1 << 16;
+
+/// Assumes two bytes per instruction (see `parse_body`), but at most `MAX_PREALLOCATED_INSTRS`.
+/// Beyond that, the instructions vector grows as needed, which is amortized anyway.
+pub fn default_instr_capacity(body_byte_size: usize) -> usize {
+    (body_byte_size / 2).min(MAX_PREALLOCATED_INSTRS)
+}
+
+// Reading from bytes fully resident in memory allows to parse the code section in parallel.
+// See `parse_module_streaming` for parsing from an `io::Read` source with bounded memory.
+pub fn parse_module(bytes: &[u8], options: ParseOptions) -> Result<(Module, Offsets, ParseWarnings), ParseError> {
+    let mut parser = ModuleParser::new(options.strict_section_order);
 
-    // The final module to return.
-    let mut module = Module::default();
-
-    // State during module parsing.
-    let mut types = Types::none();
-    let mut imported_function_count = 0;
-    let mut current_code_index = 0;
-    let mut section_offsets = Vec::with_capacity(16);
-    let mut function_offsets = Vec::new();
     // Put the function bodies in their own vector, such that parallel processing of the
     // code section doesn't require synchronization on the shared `module` variable.
     let mut function_bodies = Vec::new();
-    let mut code_entries_count = 0;
-    let metadata = RwLock::new(ModuleMetadata::default());
 
     for payload in wp::Parser::new(0).parse_all(bytes) {
         match payload? {
+            wp::Payload::CodeSectionEntry(mut body) => {
+                let (func_idx, last_code_entry) = parser.code_entry(&mut body);
+                function_bodies.push((func_idx, body));
+
+                if last_code_entry {
+                    // Parse and convert to high-level instructions in parallel (unless disabled).
+                    // Do not short-circuit on the first error (e.g., by collecting into a
+                    // `Result<Vec<_>, _>`), because which thread fails first is nondeterministic.
+                    // Instead, all bodies are parsed and collected in order (which `collect` into
+                    // a `Vec` guarantees for the indexed `into_par_iter`), such that the error of the
+                    // function with the lowest index is reported below.
+                    let parse = |(func_idx, body): (u32, wp::FunctionBody)| {
+                        (func_idx, body.range().start, parse_body_or_fallback(body, &parser.types, &parser.metadata, options))
+                    };
+                    let function_bodies = map_maybe_parallel(std::mem::take(&mut function_bodies), &options, parse);
+                    // Attach the converted function bodies to the function definitions (not parallel).
+                    for (func_idx, offset, code) in function_bodies {
+                        let (code, instr_offsets, warning) = code.map_err(|err| body_decode_error(err, func_idx, offset))?;
+                        parser.function_mut(func_idx, offset)?.code = ImportOrPresent::Present(code);
+                        parser.warnings.extend(warning);
+                        if let Some(instr_offsets) = instr_offsets {
+                            parser.instr_offsets.push((func_idx.into(), instr_offsets));
+                        }
+                    }
+                }
+            }
+            payload => {
+                if let wp::Payload::CodeSectionStart { count, ref range, .. } = payload {
+                    function_bodies.reserve_exact(bounded_capacity(count, range));
+                }
+                parser.parse_payload(payload, &options, Some(bytes))?;
+            }
+        }
+    }
+
+    Ok(parser.finish())
+}
+
+/// Parses only the "interface" of a module, see `Module::parse_headers_only`.
+/// Function bodies, data segments, and custom sections (including names) are skipped.
+pub fn parse_module_headers(bytes: &[u8]) -> Result<ModuleHeaders, ParseError> {
+    let mut parser = ModuleParser::new(false);
+    for payload in wp::Parser::new(0).parse_all(bytes) {
+        match payload? {
+            wp::Payload::CodeSectionStart { .. }
+            | wp::Payload::CodeSectionEntry(_)
+            | wp::Payload::DataSection(_)
+            | wp::Payload::CustomSection(_) => {}
+            payload => parser.parse_payload(payload, &ParseOptions::default(), None)?,
+        }
+    }
+
+    let module = parser.module;
+    Ok(ModuleHeaders {
+        types: parser.types.0.unwrap_or_default(),
+        functions: module.functions,
+        tables: module.tables,
+        memories: module.memories,
+        globals: module.globals,
+        start: module.start,
+    })
+}
+
+/// Returns only the custom sections, see `Module::parse_custom_sections`.
+/// The code section is skipped as a whole, all other sections are only read, not converted.
+pub fn parse_custom_sections(bytes: &[u8]) -> Result<Vec<CustomSectionRef<'_>>, ParseError> {
+    let mut parser = wp::Parser::new(0);
+    let mut offset = 0;
+    let mut sections = Vec::new();
+    loop {
+        // All bytes are available upfront, so wasmparser returns an error instead of needing more data.
+        let wp::Chunk::Parsed { consumed, payload } = parser.parse(&bytes[offset..], true)? else {
+            unreachable!("parser should not need more data at the end of the input")
+        };
+        offset += consumed;
+        match payload {
+            wp::Payload::Version { encoding: wp::Encoding::Component, .. } => {
+                Err(ParseIssue::unsupported(0, WasmExtension::ComponentModel))?
+            }
+            wp::Payload::CustomSection(reader) => sections.push(CustomSectionRef {
+                name: reader.name(),
+                content: reader.data(),
+                offset: reader.data_offset(),
+            }),
+            // Skip over function bodies at once, instead of yielding them one by one.
+            wp::Payload::CodeSectionStart { size, range, .. } => {
+                parser.skip_section();
+                let section_end = offset.checked_add(u32_to_usize(size, offset)?);
+                match section_end {
+                    Some(section_end) if section_end <= bytes.len() => offset = section_end,
+                    _ => Err(ParseIssue::message(range.start, "code section extends beyond the end of the binary", None))?,
+                }
+            }
+            wp::Payload::End(_) => return Ok(sections),
+            _ => {}
+        }
+    }
+}
+
+/// Parses a module from a streaming source, without reading it fully into memory first.
+/// Function bodies are parsed one at a time (i.e., not in parallel) and yielded as
+/// `SectionEvent::FunctionBody`, such that only a single one has to be resident in memory.
+pub fn parse_module_streaming<R: io::Read>(reader: R) -> StreamingParser<R> {
+    StreamingParser {
+        reader,
+        buffer: Vec::new(),
+        eof: false,
+        wp_parser: wp::Parser::new(0),
+        parser: Some(ModuleParser::new(false)),
+    }
+}
+
+/// Iterator over the sections and function bodies of a module, see `Module::parse_streaming`.
+pub struct StreamingParser<R> {
+    reader: R,
+    // Bytes that were read from `reader`, but not yet consumed by `wp_parser`.
+    buffer: Vec<u8>,
+    eof: bool,
+    wp_parser: wp::Parser,
+    // Taken when the module is complete or after an error, after which the iterator is exhausted.
+    parser: Option<ModuleParser>,
+}
+
+impl<R: io::Read> Iterator for StreamingParser<R> {
+    type Item = Result<SectionEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parser.as_ref()?;
+        let event = self.next_event();
+        if event.is_err() {
+            self.parser = None;
+        }
+        Some(event)
+    }
+}
+
+impl<R: io::Read> StreamingParser<R> {
+    fn next_event(&mut self) -> Result<SectionEvent, ParseError> {
+        loop {
+            let parser = self.parser.as_mut().expect("iterator should not be exhausted yet");
+            let (consumed, event) = match self.wp_parser.parse(&self.buffer, self.eof)? {
+                wp::Chunk::NeedMoreData(hint) => {
+                    let read = (&mut self.reader).take(hint).read_to_end(&mut self.buffer)?;
+                    self.eof = (read as u64) < hint;
+                    continue;
+                }
+                wp::Chunk::Parsed { consumed, payload } => {
+                    let event = match payload {
+                        wp::Payload::CodeSectionEntry(mut body) => {
+                            let offset = body.range().start;
+                            let (idx, _last_code_entry) = parser.code_entry(&mut body);
+                            let (code, _, _) = parse_body(body, &parser.types, &parser.metadata, default_instr_capacity, false, false)
+                                .map_err(|err| body_decode_error(err, idx, offset))?;
+                            let function = parser.function_mut(idx, offset)?;
+                            // Keep the (small) locals, such that names from the name section, which comes
+                            // after the code section, can still be attached to them.
+                            function.code = ImportOrPresent::Present(Code {
+                                locals: code.locals.clone(),
+                                ..Code::new()
+                            });
+                            let type_ = function.type_;
+                            Some(SectionEvent::FunctionBody { idx: idx.into(), offset, type_, code })
+                        }
+                        wp::Payload::End(_) => {
+                            let (module, offsets, warnings) = self.parser.take().expect("checked above").finish();
+                            Some(SectionEvent::End { module, offsets, warnings })
+                        }
+                        payload => {
+                            let section_count = parser.section_offsets.len();
+                            parser.parse_payload(payload, &ParseOptions::default(), None)?;
+                            // Only report actual sections, not, e.g., the version header.
+                            parser.section_offsets[section_count..]
+                                .last()
+                                .map(|(id, offset)| SectionEvent::Section { id: id.clone(), offset: *offset })
+                        }
+                    };
+                    (consumed, event)
+                }
+            };
+            self.buffer.drain(..consumed);
+            if let Some(event) = event {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// State during module parsing, shared between in-memory and streaming parsing.
+struct ModuleParser {
+    // The final module to return.
+    module: Module,
+    warnings: ParseWarnings,
+
+    types: Types,
+    imported_function_count: u32,
+    current_code_index: u32,
+    code_entries_count: u32,
+    // From the data count section, to check it against the data section.
+    data_count: Option<u32>,
+    section_offsets: Vec<(SectionId, usize)>,
+    function_offsets: Vec<(Idx<Function>, usize)>,
+    // Only filled if requested, see `ParseOptions::instr_offsets`.
+    instr_offsets: Vec<(Idx<Function>, Vec<usize>)>,
+    metadata: RwLock<ModuleMetadata>,
+    strict_section_order: bool,
+}
+
+impl ModuleParser {
+    fn new(strict_section_order: bool) -> Self {
+        ModuleParser {
+            module: Module::default(),
+            warnings: Vec::new(),
+            types: Types::none(),
+            imported_function_count: 0,
+            current_code_index: 0,
+            code_entries_count: 0,
+            data_count: None,
+            section_offsets: Vec::with_capacity(16),
+            function_offsets: Vec::new(),
+            instr_offsets: Vec::new(),
+            metadata: RwLock::new(ModuleMetadata::default()),
+            strict_section_order,
+        }
+    }
+
+    /// Registers a code section entry, but doesn't parse it yet (such that the caller can do
+    /// that in parallel). Returns the function index and whether this was the last code entry.
+    fn code_entry(&mut self, body: &mut wp::FunctionBody) -> (u32, bool) {
+        // Memory offsets are only 64-bit for 64-bit memories, and wasmparser needs to know upfront.
+        body.allow_memarg64(self.module.memories.iter().any(|memory| memory.index_type == IndexType::I64));
+
+        let func_index = self.imported_function_count + self.current_code_index;
+        self.function_offsets.push((func_index.into(), body.range().start));
+        self.current_code_index += 1;
+        (func_index, self.current_code_index == self.code_entries_count)
+    }
+
+    fn function_mut(&mut self, func_idx: u32, offset: usize) -> Result<&mut Function, ParseIssue> {
+        self.module
+            .functions
+            .get_mut(u32_to_usize(func_idx, offset)?)
+            .ok_or_else(|| ParseIssue::index(offset, func_idx, "function"))
+    }
+
+    /// Parses all payloads except for function bodies, see `code_entry`.
+    /// If `bytes` is given (the whole module, which wasmparser's section readers do not expose),
+    /// and `options.lossy_names` is set, import and export names are read from it lossily.
+    fn parse_payload(&mut self, payload: wp::Payload, options: &ParseOptions, bytes: Option<&[u8]>) -> Result<(), ParseError> {
+        let lossy_names = bytes.filter(|_| options.lossy_names);
+        let ModuleParser {
+            module,
+            warnings,
+            types,
+            imported_function_count,
+            current_code_index: _,
+            code_entries_count,
+            section_offsets,
+            function_offsets,
+            instr_offsets: _,
+            data_count: expected_data_count,
+            metadata,
+            strict_section_order,
+        } = self;
+
+        match payload {
             wp::Payload::Version { num: _, encoding, range: _ } => {
                 // The version number is checked by wasmparser to always be 1.
                 match encoding {
@@ -50,9 +413,9 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 // This is the offset AFTER the section tag and size in bytes,
                 // but BEFORE the number of elements in the section.
                 let type_offset = reader.range().start;
-                section_offsets.push((SectionId::Type, type_offset));
+                add_section_offset(section_offsets, SectionId::Type, type_offset, *strict_section_order)?;
 
-                types.new_type_section(reader.count(), type_offset)?;
+                types.new_type_section(reader.count(), reader.range())?;
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, wp::Type::Func(type_)) = elem?;
@@ -61,22 +424,27 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 }
             }
             wp::Payload::ImportSection(reader) => {
-                section_offsets.push((SectionId::Import, reader.range().start));
-
-                for elem in reader.into_iter_with_offsets() {
-                    let (import_offset, import) = elem?;
-
-                    let import_module = import.module.to_string();
-                    let import_name = import.name.to_string();
-
-                    match import.ty {
+                add_section_offset(section_offsets, SectionId::Import, reader.range().start, *strict_section_order)?;
+
+                let imports = match lossy_names {
+                    Some(bytes) => read_imports_lossy(&bytes[reader.range()], reader.range().start, warnings)?,
+                    None => reader
+                        .into_iter_with_offsets()
+                        .map(|elem| {
+                            let (offset, import) = elem?;
+                            Ok((offset, import.module.to_string(), import.name.to_string(), import.ty))
+                        })
+                        .collect::<Result<Vec<_>, ParseError>>()?,
+                };
+                for (import_offset, import_module, import_name, import_ty) in imports {
+                    match import_ty {
                         wp::TypeRef::Func(ty_index) => {
-                            imported_function_count += 1;
+                            *imported_function_count += 1;
                             module.functions.push(Function::new_imported(
                                 // The `import_offset` is not actually the offset of the type index,
                                 // but wasmparser doesn't offer a way to get the latter.
                                 // This slightly misattributes potential errors, namely to the beginning of the import.
-                                types.get(ty_index, import_offset)?,
+                                types.get(ty_index, import_offset, "function import")?,
                                 import_module,
                                 import_name,
                                 Vec::new(),
@@ -86,98 +454,110 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                             // Same issue regarding `import_offset`.
                             Global::new_imported(parse_global_ty(ty, import_offset)?, import_module, import_name),
                         ),
-                        wp::TypeRef::Table(ty) => module.tables.push(
+                        wp::TypeRef::Table(ty) => {
                             // Same issue regarding `import_offset`.
-                            Table::new_imported(parse_table_ty(ty, import_offset)?, import_module, import_name),
-                        ),
+                            let (limits, ref_type) = parse_table_ty(ty, import_offset)?;
+                            module.tables.push(Table::new_imported(limits, ref_type, import_module, import_name))
+                        }
                         wp::TypeRef::Memory(ty) => {
                             // Same issue regarding `import_offset`.
-                            module.memories.push(Memory::new_imported(
-                                parse_memory_ty(ty, import_offset)?,
-                                import_module,
-                                import_name,
-                            ))
+                            let mut memory = parse_memory_ty(ty, import_offset, metadata)?;
+                            memory.import = Some((import_module, import_name));
+                            module.memories.push(memory)
                         }
-                        wp::TypeRef::Tag(_) => {
+                        wp::TypeRef::Tag(ty) => {
+                            add_used_extension(metadata, WasmExtension::ExceptionHandling);
                             // Same issue regarding `import_offset`.
-                            Err(ParseIssue::unsupported(import_offset, WasmExtension::ExceptionHandling))?
+                            let type_ = types.get(ty.func_type_idx, import_offset, "tag import")?;
+                            module.tags.push(Tag::new_imported(type_, import_module, import_name))
                         }
                     }
                 }
             }
             wp::Payload::FunctionSection(reader) => {
-                section_offsets.push((SectionId::Function, reader.range().start));
+                add_section_offset(section_offsets, SectionId::Function, reader.range().start, *strict_section_order)?;
 
                 let function_count = reader.count();
-                module.functions.reserve(u32_to_usize(function_count));
+                module.functions.reserve(bounded_capacity(function_count, &reader.range()));
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, type_index) = elem?;
-                    let type_ = types.get(type_index, offset)?;
+                    let type_ = types.get(type_index, offset, "function")?;
                     // Fill in the code of the function later with the code section.
                     module.functions.push(Function::new(type_, Code::new(), Vec::new()));
                 }
             }
             wp::Payload::TableSection(reader) => {
-                section_offsets.push((SectionId::Table, reader.range().start));
+                add_section_offset(section_offsets, SectionId::Table, reader.range().start, *strict_section_order)?;
 
                 let table_count = reader.count();
-                module.tables.reserve(u32_to_usize(table_count));
+                module.tables.reserve(bounded_capacity(table_count, &reader.range()));
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, table_ty) = elem?;
-                    let table_ty = parse_table_ty(table_ty, offset)?;
+                    let (limits, ref_type) = parse_table_ty(table_ty, offset)?;
                     // Fill in the elements of the table later with the element section.
-                    module.tables.push(Table::new(table_ty));
+                    module.tables.push(Table::new(limits, ref_type));
                 }
             }
             wp::Payload::MemorySection(reader) => {
-                section_offsets.push((SectionId::Memory, reader.range().start));
+                add_section_offset(section_offsets, SectionId::Memory, reader.range().start, *strict_section_order)?;
 
                 let memory_count = reader.count();
-                module.memories.reserve(u32_to_usize(memory_count));
+                module.memories.reserve(bounded_capacity(memory_count, &reader.range()));
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, memory_ty) = elem?;
-                    let memory_ty = parse_memory_ty(memory_ty, offset)?;
                     // Fill in the data of the memory later with the data section.
-                    module.memories.push(Memory::new(memory_ty));
+                    module.memories.push(parse_memory_ty(memory_ty, offset, metadata)?);
+                }
+            }
+            wp::Payload::TagSection(reader) => {
+                add_section_offset(section_offsets, SectionId::Tag, reader.range().start, *strict_section_order)?;
+                add_used_extension(metadata, WasmExtension::ExceptionHandling);
+
+                let tag_count = reader.count();
+                module.tags.reserve(bounded_capacity(tag_count, &reader.range()));
+
+                for elem in reader.into_iter_with_offsets() {
+                    let (offset, tag_ty) = elem?;
+                    let type_ = types.get(tag_ty.func_type_idx, offset, "tag")?;
+                    module.tags.push(Tag::new(type_));
                 }
             }
-            wp::Payload::TagSection(reader) => Err(ParseIssue::unsupported(reader.range().start, WasmExtension::ExceptionHandling))?,
             wp::Payload::GlobalSection(reader) => {
-                section_offsets.push((SectionId::Global, reader.range().start));
+                add_section_offset(section_offsets, SectionId::Global, reader.range().start, *strict_section_order)?;
 
                 let global_count = reader.count();
-                module.globals.reserve(u32_to_usize(global_count));
+                module.globals.reserve(bounded_capacity(global_count, &reader.range()));
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, global) = elem?;
                     let type_ = parse_global_ty(global.ty, offset)?;
 
-                    // Most initialization expressions have just a constant and the end instruction.
-                    let mut init = Vec::with_capacity(2);
-                    for op in global.init_expr.get_operators_reader() {
-                        // The `offset` will be slightly off, because it points to the beginning of the
-                        // whole global entry, not the initialization expression.
-                        init.push(parse_instr(op?, offset, &types, &metadata)?)
-                    }
+                    let init = parse_const_expr(global.init_expr, types, metadata)?;
 
                     module.globals.push(Global::new(type_, init));
                 }
             }
             wp::Payload::ExportSection(reader) => {
-                section_offsets.push((SectionId::Export, reader.range().start));
-
-                for elem in reader.into_iter_with_offsets() {
-                    let (export_offset, export) = elem?;
-
-                    let name = export.name.to_string();
-                    let index_u32 = export.index;
-                    let index = u32_to_usize(export.index);
+                add_section_offset(section_offsets, SectionId::Export, reader.range().start, *strict_section_order)?;
+
+                let exports = match lossy_names {
+                    Some(bytes) => read_exports_lossy(&bytes[reader.range()], reader.range().start, warnings)?,
+                    None => reader
+                        .into_iter_with_offsets()
+                        .map(|elem| {
+                            let (offset, export) = elem?;
+                            Ok((offset, export.name.to_string(), export.kind, export.index))
+                        })
+                        .collect::<Result<Vec<_>, ParseError>>()?,
+                };
+                for (export_offset, name, kind, index_u32) in exports {
+                    let index = u32_to_usize(index_u32, export_offset)?;
 
                     use wp::ExternalKind;
-                    match export.kind {
+                    match kind {
                         ExternalKind::Func => module
                             .functions
                             .get_mut(index)
@@ -208,145 +588,72 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                             .ok_or_else(|| ParseIssue::index(export_offset, index_u32, "global"))?
                             .export
                             .push(name),
-                        ExternalKind::Tag => {
+                        ExternalKind::Tag => module
+                            .tags
+                            .get_mut(index)
                             // Same issue regarding `export_offset`.
-                            Err(ParseIssue::unsupported(export_offset, WasmExtension::ExceptionHandling))?
-                        }
+                            .ok_or_else(|| ParseIssue::index(export_offset, index_u32, "tag"))?
+                            .export
+                            .push(name),
                     };
                 }
             }
             wp::Payload::StartSection { func, range } => {
-                section_offsets.push((SectionId::Start, range.start));
-
-                let prev_start = std::mem::replace(&mut module.start, Some(func.into()));
-                if prev_start.is_some() {
-                    Err(ParseIssue::message(range.start, "duplicate start section", None))?
-                }
+                add_section_offset(section_offsets, SectionId::Start, range.start, *strict_section_order)?;
+                module.start = Some(func.into());
             }
             wp::Payload::ElementSection(reader) => {
-                section_offsets.push((SectionId::Element, reader.range().start));
-
-                for elem in reader.into_iter_with_offsets() {
-                    let (element_offset, element) = elem?;
-                    parse_elem_ty(element.ty, element_offset)?;
-
-                    let items = match element.items {
-                        wp::ElementItems::Functions(items_reader) => {
-                            items_reader.into_iter()
-                                .map(|func_idx| func_idx.map(
-                                    |func_idx| u32_to_usize(func_idx).into()))
-                                .collect::<Result<Vec<Idx<Function>>, _>>()?
-                        },
-                        wp::ElementItems::Expressions(reader) => Err(ParseIssue::unsupported(reader.original_position(), WasmExtension::ReferenceTypes))?,
-                    };
-
-                    match element.kind {
-                        wp::ElementKind::Active {
-                            table_index,
-                            offset_expr,
-                        } => {
-                            let table = module
-                                .tables
-                                .get_mut(u32_to_usize(table_index))
-                                .ok_or_else(|| ParseIssue::index(element_offset, table_index, "table"))?;
-
-                            // Most offset expressions are just a constant and the end instruction.
-                            let mut offset_instrs = Vec::with_capacity(2);
-                            for op_offset in offset_expr.get_operators_reader().into_iter_with_offsets() {
-                                let (op, offset) = op_offset?;
-                                offset_instrs.push(parse_instr(op, offset, &types, &metadata)?)
-                            }
-
-                            table.elements.push(Element {
-                                offset: offset_instrs,
-                                functions: items,
-                            })
-                        }
-                        wp::ElementKind::Passive => {
-                            Err(ParseIssue::unsupported(element_offset, WasmExtension::BulkMemoryOperations))?
-                        }
-                        wp::ElementKind::Declared => {
-                            Err(ParseIssue::unsupported(element_offset, WasmExtension::ReferenceTypes))?
-                        }
-                    }
-                }
+                add_section_offset(section_offsets, SectionId::Element, reader.range().start, *strict_section_order)?;
+
+                // Only read the raw segments serially, but convert them in parallel (like
+                // function bodies), since modules may contain tens of thousands of segments.
+                let elements = reader.into_iter_with_offsets().collect::<Result<Vec<_>, _>>()?;
+                let table_types: Vec<RefType> = module.tables.iter().map(|table| table.ref_type).collect();
+                let elements = map_maybe_parallel(elements, options, |(element_offset, element)| {
+                    parse_element(element, element_offset, &table_types, types, metadata)
+                });
+                let elements = elements.into_iter().collect::<Result<Vec<_>, _>>()?;
+                module.elements.extend(elements);
             }
-            wp::Payload::DataCountSection { count: _, range } => {
-                Err(ParseIssue::unsupported(range.start, WasmExtension::BulkMemoryOperations))?
+            wp::Payload::DataCountSection { count, range } => {
+                add_section_offset(section_offsets, SectionId::DataCount, range.start, *strict_section_order)?;
+
+                add_used_extension(metadata, WasmExtension::BulkMemoryOperations);
+                module.data_count_section = true;
+                *expected_data_count = Some(count);
             }
             wp::Payload::DataSection(reader) => {
-                section_offsets.push((SectionId::Data, reader.range().start));
-
-                for elem in reader.into_iter_with_offsets() {
-                    let (data_offset, data) = elem?;
-
-                    match data.kind {
-                        wp::DataKind::Active {
-                            memory_index,
-                            offset_expr,
-                        } => {
-                            let memory = module
-                                .memories
-                                .get_mut(u32_to_usize(memory_index))
-                                .ok_or_else(|| ParseIssue::index(data_offset, memory_index, "memory"))?;
-
-                            // Most offset expressions are just a constant and the end instruction.
-                            let mut offset_instrs = Vec::with_capacity(2);
-                            for op_offset in offset_expr.get_operators_reader().into_iter_with_offsets() {
-                                let (op, offset) = op_offset?;
-                                offset_instrs.push(parse_instr(op, offset, &types, &metadata)?)
-                            }
+                add_section_offset(section_offsets, SectionId::Data, reader.range().start, *strict_section_order)?;
 
-                            memory.data.push(Data {
-                                offset: offset_instrs,
-                                bytes: data.data.to_vec(),
-                            })
-                        }
-                        wp::DataKind::Passive => {
-                            Err(ParseIssue::unsupported(data_offset, WasmExtension::BulkMemoryOperations))?
-                        }
+                let data_count = reader.count();
+                if let Some(expected_count) = *expected_data_count {
+                    if data_count != expected_count {
+                        Err(ParseIssue::message(reader.range().start, "data count section and data section have inconsistent lengths", None))?
                     }
                 }
+
+                // Parallel for the same reason as element segments above. This also copies the
+                // (potentially large) data bytes in parallel.
+                let data = reader.into_iter_with_offsets().collect::<Result<Vec<_>, _>>()?;
+                let memory_count = module.memories.len();
+                let data = map_maybe_parallel(data, options, |(data_offset, data)| {
+                    parse_data(data, data_offset, memory_count, types, metadata)
+                });
+                let data = data.into_iter().collect::<Result<Vec<_>, _>>()?;
+                module.data.extend(data);
             }
             wp::Payload::CodeSectionStart {
                 count,
                 range,
                 size: _,
             } => {
-                section_offsets.push((SectionId::Code, range.start));
+                add_section_offset(section_offsets, SectionId::Code, range.start, *strict_section_order)?;
 
-                function_offsets.reserve_exact(u32_to_usize(count));
-                function_bodies.reserve_exact(u32_to_usize(count));
+                function_offsets.reserve_exact(bounded_capacity(count, &range));
 
-                code_entries_count = count;
-            }
-            wp::Payload::CodeSectionEntry(body) => {
-                let func_index = imported_function_count + current_code_index;
-
-                function_offsets.push((func_index.into(), body.range().start));
-                function_bodies.push((func_index, body));
-
-                current_code_index += 1;
-
-                let last_code_entry = current_code_index == code_entries_count;
-                if last_code_entry {
-                    // Parse and convert to high-level instructions in parallel.
-                    let function_bodies = function_bodies
-                        .par_drain(..)
-                        .map(|(func_idx, body)| {
-                            (func_idx, body.range().start, parse_body(body, &types, &metadata))
-                        })
-                        .collect::<Vec<_>>();
-                    // Attach the converted function bodies to the function definitions (not parallel).
-                    for (func_idx, offset, code) in function_bodies {
-                        let function = module
-                            .functions
-                            .get_mut(u32_to_usize(func_idx))
-                            .ok_or_else(|| ParseIssue::index(offset, func_idx, "function"))?;
-                        function.code = ImportOrPresent::Present(code?);
-                    }
-                }
+                *code_entries_count = count;
             }
+            wp::Payload::CodeSectionEntry(_) => unreachable!("function bodies are parsed by the caller, see `ModuleParser::code_entry`"),
             wp::Payload::CustomSection(reader) => {
                 let name = reader.name().to_string();
                 let previous_section_id = section_offsets
@@ -360,12 +667,15 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 if name == "name" {
                     // If parts of the name section cannot be parsed, collect the issue as a warning and abort parsing the
                     // name section, but produce an AST for the rest of the module.
-                    match parse_name_custom_section(reader.data(), reader.data_offset(), &mut warnings, &mut module) {
+                    match parse_name_custom_section(reader.data(), reader.data_offset(), types, warnings, module) {
                         Ok(()) => {
                             // All the names got inserted into the AST, so no need to add a custom section.
-                            continue;
+                            return Ok(());
                         }
                         Err(name_parsing_aborted) => {
+                            // Names that were parsed before the error are also contained in the raw custom
+                            // section below, so remove them to avoid encoding them twice.
+                            module.clear_names();
                             warnings.push(ParseIssue::Message { 
                                 offset: custom_section_start_offset, 
                                 message: "could not parse name section, adding it as a raw (unparsed) custom section...",
@@ -394,45 +704,187 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
             wp::Payload::ComponentStartSection { start:_, range } => Err(ParseIssue::unsupported(range.start, WasmExtension::ComponentModel))?,
             wp::Payload::ComponentImportSection(reader) => Err(ParseIssue::unsupported(reader.range().start, WasmExtension::ComponentModel))?,
             wp::Payload::ComponentExportSection(reader) => Err(ParseIssue::unsupported(reader.range().start, WasmExtension::ComponentModel))?,
-            wp::Payload::UnknownSection {
-                id: _,
-                contents: _,
-                range,
-            } => Err(ParseIssue::message(range.start, "unknown section", None))?,
+            wp::Payload::UnknownSection { id, contents, range } => {
+                let previous_section = section_offsets
+                    .last()
+                    .map(|(section, _offset)| section)
+                    .cloned();
+                // Like custom sections, unknown sections may appear anywhere, since their
+                // position in the order of sections is not known.
+                section_offsets.push((SectionId::Unknown(id), range.start));
+                module.unknown_sections.push(RawUnknownSection {
+                    id,
+                    content: contents.to_vec(),
+                    previous_section,
+                });
+            }
             wp::Payload::End(_offset_bytes) => {
                 // I don't understand what this end marker is for?
                 // If the module ended (i.e., the input buffer is exhausted),
                 // there is just no more payload following, isn't there?
             }
         }
-    }
-
-    let offsets = Offsets {
-        sections: section_offsets,
-        functions_code: function_offsets,
-    };
 
-    module.metadata = metadata.into_inner().unwrap();
+        Ok(())
+    }
 
-    Ok((module, offsets, warnings))
+    fn finish(self) -> (Module, Offsets, ParseWarnings) {
+        // Code entries and their instructions are processed in order of function indices, which
+        // the lookups in `Offsets` rely on.
+        debug_assert!(self.function_offsets.windows(2).all(|w| w[0].0 < w[1].0));
+        debug_assert!(self.instr_offsets.windows(2).all(|w| w[0].0 < w[1].0));
+        let offsets = Offsets {
+            sections: self.section_offsets,
+            functions_code: self.function_offsets,
+            functions_instrs: self.instr_offsets,
+        };
+
+        let mut module = self.module;
+        module.metadata = self.metadata.into_inner().unwrap();
+        module.metadata.set_section_order(offsets.sections.iter().map(|(section, _offset)| section.clone()).collect());
+
+        (module, offsets, self.warnings)
+    }
 }
 
-fn parse_body(
+/// Also returns the offsets of all instructions (plus the end of the body), if `record_instr_offsets`.
+/// Like `parse_body`, but if the body contains unsupported instructions, replaces it according to
+/// `options.on_unsupported` and returns the issue as a warning.
+#[allow(clippy::type_complexity)]
+fn parse_body_or_fallback(
     body: wp::FunctionBody,
     types: &Types,
     metadata: &RwLock<ModuleMetadata>,
-) -> Result<Code, ParseError> {
+    options: ParseOptions,
+) -> Result<(Code, Option<Vec<usize>>, Option<ParseIssue>), ParseError> {
+    let placeholder_instrs = options.on_unsupported == OnUnsupported::PlaceholderInstrs;
+    let err = match parse_body(body.clone(), types, metadata, options.instr_capacity, options.instr_offsets, placeholder_instrs) {
+        Ok((code, instr_offsets, warning)) => return Ok((code, instr_offsets, warning)),
+        Err(err) if options.on_unsupported != OnUnsupported::Error && matches!(err.issue(), ParseIssue::Unsupported { .. }) => err,
+        Err(err) => return Err(err),
+    };
+
+    let operators_reader = body.get_operators_reader()?;
+    let start_offset = operators_reader.original_position();
+    let mut reader = operators_reader.get_binary_reader();
+    // Everything except the final `end`, which is kept as a regular instruction.
+    let bytes = reader.read_bytes(reader.bytes_remaining().saturating_sub(1))?;
+    let end_offset = reader.original_position();
+    let first_instr = match options.on_unsupported {
+        OnUnsupported::Error => unreachable!("errors are returned above"),
+        OnUnsupported::SkipBody => Instr::Unreachable,
+        OnUnsupported::Placeholder | OnUnsupported::PlaceholderInstrs => Instr::Unparsed(bytes.to_vec()),
+    };
+    if reader.read_u8()? != 0x0b {
+        Err(ParseIssue::message(end_offset, "function body must end with `end`", None))?
+    }
+
+    let code = Code {
+        locals: parse_locals(&body)?,
+        body: vec![first_instr, Instr::End],
+        label_names: BTreeMap::new(),
+    };
+    let instr_offsets = options.instr_offsets.then(|| vec![start_offset, end_offset, body.range().end]);
+    Ok((code, instr_offsets, Some(err.into_issue())))
+}
+
+/// Like iterating over a `wp::ImportSectionReader`, but with lossy names, see
+/// `ParseOptions::lossy_names`. `section` are the contents of the section at `offset`.
+#[allow(clippy::type_complexity)]
+fn read_imports_lossy(
+    section: &[u8],
+    offset: usize,
+    warnings: &mut ParseWarnings,
+) -> Result<Vec<(usize, String, String, wp::TypeRef)>, ParseError> {
+    let mut reader = wp::BinaryReader::new_with_offset(section, offset);
+    let count = reader.read_var_u32()?;
+    let mut imports = Vec::with_capacity(bounded_capacity(count, &reader.range()));
+    for _ in 0..count {
+        let import_offset = reader.original_position();
+        let module = read_name_lossy(&mut reader, warnings)?;
+        let name = read_name_lossy(&mut reader, warnings)?;
+        imports.push((import_offset, module, name, reader.read()?));
+    }
+    if !reader.eof() {
+        Err(ParseIssue::message(reader.original_position(), "unexpected content at the end of the import section", None))?
+    }
+    Ok(imports)
+}
+
+/// Like iterating over a `wp::ExportSectionReader`, but with lossy names, see
+/// `ParseOptions::lossy_names`. `section` are the contents of the section at `offset`.
+fn read_exports_lossy(
+    section: &[u8],
+    offset: usize,
+    warnings: &mut ParseWarnings,
+) -> Result<Vec<(usize, String, wp::ExternalKind, u32)>, ParseError> {
+    let mut reader = wp::BinaryReader::new_with_offset(section, offset);
+    let count = reader.read_var_u32()?;
+    let mut exports = Vec::with_capacity(bounded_capacity(count, &reader.range()));
+    for _ in 0..count {
+        let export_offset = reader.original_position();
+        let name = read_name_lossy(&mut reader, warnings)?;
+        exports.push((export_offset, name, reader.read()?, reader.read_var_u32()?));
+    }
+    if !reader.eof() {
+        Err(ParseIssue::message(reader.original_position(), "unexpected content at the end of the export section", None))?
+    }
+    Ok(exports)
+}
+
+/// Reads a name, replacing invalid UTF-8 with U+FFFD and adding a warning in that case.
+fn read_name_lossy(reader: &mut wp::BinaryReader, warnings: &mut ParseWarnings) -> Result<String, ParseError> {
+    let offset = reader.original_position();
+    let len = reader.read_var_u32()?;
+    let bytes = reader.read_bytes(u32_to_usize(len, offset)?)?;
+    let name = String::from_utf8_lossy(bytes).into_owned();
+    if std::str::from_utf8(bytes).is_err() {
+        warnings.push(ParseIssue::invalid_utf8_name(offset, bytes.to_vec(), name.clone()));
+    }
+    Ok(name)
+}
+
+/// Attaches the function index to low-level decoding errors (e.g., a malformed LEB128) in the body
+/// starting at `body_offset`, see `ParseIssue::BodyDecode`. Other errors are returned unchanged.
+fn body_decode_error(err: ParseError, func_idx: u32, body_offset: usize) -> ParseError {
+    match err.into_issue() {
+        ParseIssue::Wasmparser(source) => ParseIssue::body_decode(func_idx.into(), body_offset, source).into(),
+        issue => issue.into(),
+    }
+}
+
+fn parse_locals(body: &wp::FunctionBody) -> Result<Vec<Local>, ParseError> {
     let mut locals_reader = body.get_locals_reader()?;
     let mut offset = locals_reader.original_position();
     // Pre-allocate: There are at least as many locals as there are _unique_ local types.
-    let mut locals = Vec::with_capacity(u32_to_usize(locals_reader.get_count()));
+    let mut locals = Vec::with_capacity(bounded_capacity(locals_reader.get_count(), &body.range()));
     for _ in 0..locals_reader.get_count() {
         let (count, type_) = locals_reader.read()?;
-        let count = u32_to_usize(count);
+        let count = u32_to_usize(count, offset)?;
+        // The count is not bounded by the size of the binary, so check it before allocating.
+        if locals.len() + count > MAX_LOCALS {
+            Err(ParseIssue::message(offset, "too many locals in function", None))?
+        }
         let type_ = parse_val_ty(type_, offset)?;
-        locals.extend(std::iter::repeat(Local::new(type_)).take(count));
+        locals.extend(std::iter::repeat_n(Local::new(type_), count));
         offset = locals_reader.original_position();
     }
+    Ok(locals)
+}
+
+/// If `placeholder_instrs`, instructions of unsupported extensions are replaced by
+/// `Instr::Unsupported` and the first such issue is returned as a warning, see
+/// `OnUnsupported::PlaceholderInstrs`.
+#[allow(clippy::type_complexity)]
+fn parse_body(
+    body: wp::FunctionBody,
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+    instr_capacity: fn(usize) -> usize,
+    record_instr_offsets: bool,
+    placeholder_instrs: bool,
+) -> Result<(Code, Option<Vec<usize>>, Option<ParseIssue>), ParseError> {
+    let locals = parse_locals(&body)?;
 
     // Pre-allocate: We don't know the exact number of instructions yet,
     // but there are typically one or two bytes per instruction.
@@ -463,21 +915,156 @@ fn parse_body(
     // parallel parsing is 700% slower with the Windows 10 system allocator, and
     // encoding is 50% slower vs. mimalloc.
     // Since the performance is not improved on Linux, just enable it on Windows.
+    // The heuristic can be changed with `ParseOptions::instr_capacity`, e.g., for modules with
+    // unusually dense or sparse code, but is always bounded like `bounded_capacity`.
     let body_byte_size = body.range().end - body.range().start;
-    let approx_instr_count = body_byte_size / 2;
+    let approx_instr_count = instr_capacity(body_byte_size).min(body_byte_size);
     let mut instrs = Vec::with_capacity(approx_instr_count);
 
-    for op_offset in body.get_operators_reader()?.into_iter_with_offsets() {
-        let (op, offset) = op_offset?;
-        instrs.push(parse_instr(op, offset, types, metadata)?);
+    let mut instr_offsets = Vec::new();
+    let mut warning = None;
+    let mut reader = body.get_operators_reader()?;
+    let start_offset = reader.original_position();
+    while !reader.eof() {
+        let (op, offset) = reader.read_with_offset()?;
+        let instr = match parse_instr(op, offset, types, metadata) {
+            Ok(instr) => instr,
+            Err(err) => match err.issue() {
+                ParseIssue::Unsupported { extension, .. } if placeholder_instrs => {
+                    let extension = *extension;
+                    // The reader is now at the end of the instruction, so read it again as raw bytes.
+                    let mut bytes_reader = body.get_operators_reader()?.get_binary_reader();
+                    bytes_reader.read_bytes(offset - start_offset)?;
+                    let bytes = bytes_reader.read_bytes(reader.original_position() - offset)?;
+                    warning.get_or_insert(err.into_issue());
+                    Instr::Unsupported(extension, bytes.to_vec())
+                }
+                _ => return Err(err),
+            },
+        };
+        instrs.push(instr);
+        if record_instr_offsets {
+            instr_offsets.push(offset);
+        }
     }
 
-    Ok(Code {
+    let code = Code {
         locals,
         body: instrs,
+        label_names: BTreeMap::new(),
+    };
+    let instr_offsets = record_instr_offsets.then(|| {
+        instr_offsets.push(body.range().end);
+        instr_offsets
+    });
+    Ok((code, instr_offsets, warning))
+}
+
+fn parse_element(
+    element: wp::Element,
+    element_offset: usize,
+    table_types: &[RefType],
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+) -> Result<Element, ParseError> {
+    let ref_type = parse_elem_ty(element.ty, element_offset)?;
+
+    let items = match element.items {
+        wp::ElementItems::Functions(items_reader) => {
+            ElementItems::Functions(items_reader.into_iter()
+                .map(|func_idx| Ok(u32_to_usize(func_idx?, element_offset)?.into()))
+                .collect::<Result<Vec<Idx<Function>>, ParseError>>()?)
+        },
+        wp::ElementItems::Expressions(items_reader) => {
+            add_used_extension(metadata, WasmExtension::ReferenceTypes);
+            let mut items = Vec::with_capacity(bounded_capacity(items_reader.count(), &items_reader.range()));
+            for item in items_reader {
+                items.push(parse_const_expr(item?, types, metadata)?);
+            }
+            ElementItems::Expressions(items)
+        }
+    };
+
+    let mode = match element.kind {
+        wp::ElementKind::Active {
+            table_index,
+            offset_expr,
+        } => {
+            let table_type = *table_types.get(u32_to_usize(table_index, element_offset)?)
+                .ok_or_else(|| ParseIssue::index(element_offset, table_index, "table"))?;
+            if !ref_type.is_subtype_of(table_type) {
+                Err(ParseIssue::element_type_mismatch(element_offset, ref_type, table_type))?
+            }
+            ElementMode::Active {
+                table: table_index.into(),
+                offset: parse_const_expr(offset_expr, types, metadata)?,
+            }
+        }
+        wp::ElementKind::Passive => {
+            add_used_extension(metadata, WasmExtension::BulkMemoryOperations);
+            ElementMode::Passive
+        }
+        wp::ElementKind::Declared => {
+            add_used_extension(metadata, WasmExtension::ReferenceTypes);
+            ElementMode::Declared
+        }
+    };
+
+    Ok(Element {
+        mode,
+        ref_type,
+        items,
+        name: None,
+    })
+}
+
+fn parse_data(
+    data: wp::Data,
+    data_offset: usize,
+    memory_count: usize,
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+) -> Result<Data, ParseError> {
+    let mode = match data.kind {
+        wp::DataKind::Active {
+            memory_index,
+            offset_expr,
+        } => {
+            if u32_to_usize(memory_index, data_offset)? >= memory_count {
+                Err(ParseIssue::index(data_offset, memory_index, "memory"))?
+            }
+            DataMode::Active {
+                memory: memory_index.into(),
+                offset: parse_const_expr(offset_expr, types, metadata)?,
+            }
+        }
+        wp::DataKind::Passive => {
+            add_used_extension(metadata, WasmExtension::BulkMemoryOperations);
+            DataMode::Passive
+        }
+    };
+
+    Ok(Data {
+        mode,
+        bytes: data.data.to_vec(),
+        name: None,
     })
 }
 
+fn parse_const_expr(
+    expr: wp::ConstExpr,
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+) -> Result<Expr, ParseError> {
+    // Most constant expressions (e.g., offsets) are just a constant and the end instruction.
+    let mut instrs = Vec::with_capacity(2);
+    for op_offset in expr.get_operators_reader().into_iter_with_offsets() {
+        let (op, offset) = op_offset?;
+        instrs.push(parse_instr(op, offset, types, metadata)?)
+    }
+    Ok(instrs)
+}
+
 fn parse_instr(
     op: wp::Operator,
     offset: usize,
@@ -486,13 +1073,20 @@ fn parse_instr(
 ) -> Result<Instr, ParseError> {
     use crate::Instr::*;
     use wp::Operator as wp;
+
+    // Record uses of (supported) extensions in the module metadata. Unsupported instructions
+    // return an error below anyway, so it does not matter that those are recorded as well.
+    for extension in required_extensions(&op) {
+        add_used_extension(metadata, extension);
+    }
+
     Ok(match op {
         wp::Unreachable => Unreachable,
         wp::Nop => Nop,
 
-        wp::Block { blockty } => Block(parse_block_ty(blockty, offset + 1, types, metadata)?),
-        wp::Loop { blockty } => Loop(parse_block_ty(blockty, offset + 1, types, metadata)?),
-        wp::If { blockty } => If(parse_block_ty(blockty, offset + 1, types, metadata)?),
+        wp::Block { blockty } => Block(parse_block_ty(blockty, offset + 1, types)?),
+        wp::Loop { blockty } => Loop(parse_block_ty(blockty, offset + 1, types)?),
+        wp::If { blockty } => If(parse_block_ty(blockty, offset + 1, types)?),
         wp::Else => Else,
         wp::End => End,
 
@@ -509,7 +1103,8 @@ fn parse_instr(
         wp::BrIf { relative_depth } => BrIf(Label::from(relative_depth)),
         wp::BrTable { targets } => {
             let default = Label::from(targets.default());
-            let mut table = Vec::with_capacity(u32_to_usize(targets.len()));
+            // The number of targets is bounded by wasmparser (to the maximum size of a function).
+            let mut table = Vec::with_capacity(u32_to_usize(targets.len(), offset)?);
             for target in targets.targets() {
                 table.push(Label::from(target?))
             }
@@ -526,19 +1121,21 @@ fn parse_instr(
                 Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?
             }
             assert!(table_byte == 0, "not sure which extension this is");
-            CallIndirect(types.get(type_index, offset + 1)?, 0usize.into())
+            CallIndirect(types.get(type_index, offset + 1, "call_indirect")?, 0usize.into())
         }
 
-        wp::ReturnCall { function_index: _ }
-        | wp::ReturnCallIndirect {
-            type_index: _,
-            table_index: _,
-        } => Err(ParseIssue::unsupported(offset, WasmExtension::TailCalls))?,
+        wp::ReturnCall { function_index } => ReturnCall(function_index.into()),
+        wp::ReturnCallIndirect { type_index, table_index } => {
+            if table_index != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?
+            }
+            ReturnCallIndirect(types.get(type_index, offset + 1, "return_call_indirect")?, 0usize.into())
+        }
 
         wp::Drop => Drop,
-        wp::Select => Select,
+        wp::Select => Select(None),
 
-        wp::TypedSelect { ty: _ } => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
+        wp::TypedSelect { ty } => Select(Some(parse_val_ty(ty, offset + 1)?)),
 
         wp::LocalGet { local_index } => Local(LocalOp::Get, local_index.into()),
         wp::LocalSet { local_index } => Local(LocalOp::Set, local_index.into()),
@@ -594,9 +1191,9 @@ fn parse_instr(
         wp::F32Const { value } => Const(Val::F32(OrderedFloat(f32::from_bits(value.bits())))),
         wp::F64Const { value } => Const(Val::F64(OrderedFloat(f64::from_bits(value.bits())))),
 
-        wp::RefNull { ty: _ } | wp::RefIsNull | wp::RefFunc { function_index: _ } => {
-            Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?
-        }
+        wp::RefNull { ty } => RefNull(parse_ref_ty(ty, offset + 1)?),
+        wp::RefIsNull => RefIsNull,
+        wp::RefFunc { function_index } => RefFunc(function_index.into()),
 
         wp::I32Eqz => Unary(UnaryOp::I32Eqz),
         wp::I64Eqz => Unary(UnaryOp::I64Eqz),
@@ -625,12 +1222,20 @@ fn parse_instr(
         wp::I32TruncF32U => Unary(UnaryOp::I32TruncF32U),
         wp::I32TruncF64S => Unary(UnaryOp::I32TruncF64S),
         wp::I32TruncF64U => Unary(UnaryOp::I32TruncF64U),
+        wp::I32TruncSatF32S => Unary(UnaryOp::I32TruncSatF32S),
+        wp::I32TruncSatF32U => Unary(UnaryOp::I32TruncSatF32U),
+        wp::I32TruncSatF64S => Unary(UnaryOp::I32TruncSatF64S),
+        wp::I32TruncSatF64U => Unary(UnaryOp::I32TruncSatF64U),
         wp::I64ExtendI32S => Unary(UnaryOp::I64ExtendI32S),
         wp::I64ExtendI32U => Unary(UnaryOp::I64ExtendI32U),
         wp::I64TruncF32S => Unary(UnaryOp::I64TruncF32S),
         wp::I64TruncF32U => Unary(UnaryOp::I64TruncF32U),
         wp::I64TruncF64S => Unary(UnaryOp::I64TruncF64S),
         wp::I64TruncF64U => Unary(UnaryOp::I64TruncF64U),
+        wp::I64TruncSatF32S => Unary(UnaryOp::I64TruncSatF32S),
+        wp::I64TruncSatF32U => Unary(UnaryOp::I64TruncSatF32U),
+        wp::I64TruncSatF64S => Unary(UnaryOp::I64TruncSatF64S),
+        wp::I64TruncSatF64U => Unary(UnaryOp::I64TruncSatF64U),
         wp::F32ConvertI32S => Unary(UnaryOp::F32ConvertI32S),
         wp::F32ConvertI32U => Unary(UnaryOp::F32ConvertI32U),
         wp::F32ConvertI64S => Unary(UnaryOp::F32ConvertI64S),
@@ -723,28 +1328,44 @@ fn parse_instr(
         wp::F64Max => Binary(BinaryOp::F64Max),
         wp::F64Copysign => Binary(BinaryOp::F64Copysign),
 
-        wp::I32Extend8S
-        | wp::I32Extend16S
-        | wp::I64Extend8S
-        | wp::I64Extend16S
-        | wp::I64Extend32S => Err(ParseIssue::unsupported(offset, WasmExtension::SignExtensionOps))?,
-
-        wp::I32TruncSatF32S
-        | wp::I32TruncSatF32U
-        | wp::I32TruncSatF64S
-        | wp::I32TruncSatF64U
-        | wp::I64TruncSatF32S
-        | wp::I64TruncSatF32U
-        | wp::I64TruncSatF64S
-        | wp::I64TruncSatF64U => Err(ParseIssue::unsupported(offset, WasmExtension::NontrappingFloatToInt))?,
-
-        wp::MemoryInit { data_index: _, mem: _ }
-        | wp::DataDrop { data_index: _ }
-        | wp::MemoryCopy { dst_mem: _, src_mem: _ }
-        | wp::MemoryFill { mem: _ }
-        | wp::TableInit { elem_index: _, table: _ }
-        | wp::ElemDrop { elem_index: _ }
-        | wp::TableCopy { dst_table: _, src_table: _ } => Err(ParseIssue::unsupported(offset, WasmExtension::BulkMemoryOperations))?,
+        wp::I32Extend8S => Unary(UnaryOp::I32Extend8S),
+        wp::I32Extend16S => Unary(UnaryOp::I32Extend16S),
+        wp::I64Extend8S => Unary(UnaryOp::I64Extend8S),
+        wp::I64Extend16S => Unary(UnaryOp::I64Extend16S),
+        wp::I64Extend32S => Unary(UnaryOp::I64Extend32S),
+
+        wp::MemoryInit { data_index, mem } => {
+            if mem != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::MultiMemory))?
+            }
+            MemoryInit(data_index.into(), 0u32.into())
+        }
+        wp::DataDrop { data_index } => DataDrop(data_index.into()),
+        wp::MemoryCopy { dst_mem, src_mem } => {
+            if dst_mem != 0 || src_mem != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::MultiMemory))?
+            }
+            MemoryCopy { dst: 0u32.into(), src: 0u32.into() }
+        }
+        wp::MemoryFill { mem } => {
+            if mem != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::MultiMemory))?
+            }
+            MemoryFill(0u32.into())
+        }
+        wp::TableInit { elem_index, table } => {
+            if table != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?
+            }
+            TableInit(elem_index.into(), 0u32.into())
+        }
+        wp::ElemDrop { elem_index } => ElemDrop(elem_index.into()),
+        wp::TableCopy { dst_table, src_table } => {
+            if dst_table != 0 || src_table != 0 {
+                Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?
+            }
+            TableCopy { dst: 0u32.into(), src: 0u32.into() }
+        }
 
         wp::TableFill { table: _ } => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
 
@@ -753,314 +1374,317 @@ fn parse_instr(
         | wp::TableGrow { table: _ }
         | wp::TableSize { table: _ } => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
 
-        wp::MemoryAtomicNotify { memarg: _ }
-        | wp::MemoryAtomicWait32 { memarg: _ }
-        | wp::MemoryAtomicWait64 { memarg: _ }
-        | wp::AtomicFence
-        | wp::I32AtomicLoad { memarg: _ }
-        | wp::I64AtomicLoad { memarg: _ }
-        | wp::I32AtomicLoad8U { memarg: _ }
-        | wp::I32AtomicLoad16U { memarg: _ }
-        | wp::I64AtomicLoad8U { memarg: _ }
-        | wp::I64AtomicLoad16U { memarg: _ }
-        | wp::I64AtomicLoad32U { memarg: _ }
-        | wp::I32AtomicStore { memarg: _ }
-        | wp::I64AtomicStore { memarg: _ }
-        | wp::I32AtomicStore8 { memarg: _ }
-        | wp::I32AtomicStore16 { memarg: _ }
-        | wp::I64AtomicStore8 { memarg: _ }
-        | wp::I64AtomicStore16 { memarg: _ }
-        | wp::I64AtomicStore32 { memarg: _ }
-        | wp::I32AtomicRmwAdd { memarg: _ }
-        | wp::I64AtomicRmwAdd { memarg: _ }
-        | wp::I32AtomicRmw8AddU { memarg: _ }
-        | wp::I32AtomicRmw16AddU { memarg: _ }
-        | wp::I64AtomicRmw8AddU { memarg: _ }
-        | wp::I64AtomicRmw16AddU { memarg: _ }
-        | wp::I64AtomicRmw32AddU { memarg: _ }
-        | wp::I32AtomicRmwSub { memarg: _ }
-        | wp::I64AtomicRmwSub { memarg: _ }
-        | wp::I32AtomicRmw8SubU { memarg: _ }
-        | wp::I32AtomicRmw16SubU { memarg: _ }
-        | wp::I64AtomicRmw8SubU { memarg: _ }
-        | wp::I64AtomicRmw16SubU { memarg: _ }
-        | wp::I64AtomicRmw32SubU { memarg: _ }
-        | wp::I32AtomicRmwAnd { memarg: _ }
-        | wp::I64AtomicRmwAnd { memarg: _ }
-        | wp::I32AtomicRmw8AndU { memarg: _ }
-        | wp::I32AtomicRmw16AndU { memarg: _ }
-        | wp::I64AtomicRmw8AndU { memarg: _ }
-        | wp::I64AtomicRmw16AndU { memarg: _ }
-        | wp::I64AtomicRmw32AndU { memarg: _ }
-        | wp::I32AtomicRmwOr { memarg: _ }
-        | wp::I64AtomicRmwOr { memarg: _ }
-        | wp::I32AtomicRmw8OrU { memarg: _ }
-        | wp::I32AtomicRmw16OrU { memarg: _ }
-        | wp::I64AtomicRmw8OrU { memarg: _ }
-        | wp::I64AtomicRmw16OrU { memarg: _ }
-        | wp::I64AtomicRmw32OrU { memarg: _ }
-        | wp::I32AtomicRmwXor { memarg: _ }
-        | wp::I64AtomicRmwXor { memarg: _ }
-        | wp::I32AtomicRmw8XorU { memarg: _ }
-        | wp::I32AtomicRmw16XorU { memarg: _ }
-        | wp::I64AtomicRmw8XorU { memarg: _ }
-        | wp::I64AtomicRmw16XorU { memarg: _ }
-        | wp::I64AtomicRmw32XorU { memarg: _ }
-        | wp::I32AtomicRmwXchg { memarg: _ }
-        | wp::I64AtomicRmwXchg { memarg: _ }
-        | wp::I32AtomicRmw8XchgU { memarg: _ }
-        | wp::I32AtomicRmw16XchgU { memarg: _ }
-        | wp::I64AtomicRmw8XchgU { memarg: _ }
-        | wp::I64AtomicRmw16XchgU { memarg: _ }
-        | wp::I64AtomicRmw32XchgU { memarg: _ }
-        | wp::I32AtomicRmwCmpxchg { memarg: _ }
-        | wp::I64AtomicRmwCmpxchg { memarg: _ }
-        | wp::I32AtomicRmw8CmpxchgU { memarg: _ }
-        | wp::I32AtomicRmw16CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw8CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw16CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw32CmpxchgU { memarg: _ } => {
-            Err(ParseIssue::unsupported(offset, WasmExtension::ThreadsAtomics))?
-        }
-
-        wp::V128Load { memarg: _ }
-        | wp::V128Load8x8S { memarg: _ }
-        | wp::V128Load8x8U { memarg: _ }
-        | wp::V128Load16x4S { memarg: _ }
-        | wp::V128Load16x4U { memarg: _ }
-        | wp::V128Load32x2S { memarg: _ }
-        | wp::V128Load32x2U { memarg: _ }
-        | wp::V128Load8Splat { memarg: _ }
-        | wp::V128Load16Splat { memarg: _ }
-        | wp::V128Load32Splat { memarg: _ }
-        | wp::V128Load64Splat { memarg: _ }
-        | wp::V128Load32Zero { memarg: _ }
-        | wp::V128Load64Zero { memarg: _ }
-        | wp::V128Store { memarg: _ }
-        | wp::V128Load8Lane { memarg: _, lane: _ }
-        | wp::V128Load16Lane { memarg: _, lane: _ }
-        | wp::V128Load32Lane { memarg: _, lane: _ }
-        | wp::V128Load64Lane { memarg: _, lane: _ }
-        | wp::V128Store8Lane { memarg: _, lane: _ }
-        | wp::V128Store16Lane { memarg: _, lane: _ }
-        | wp::V128Store32Lane { memarg: _, lane: _ }
-        | wp::V128Store64Lane { memarg: _, lane: _ }
-        | wp::V128Const { value: _ }
-        | wp::I8x16Shuffle { lanes: _ }
-        | wp::I8x16ExtractLaneS { lane: _ }
-        | wp::I8x16ExtractLaneU { lane: _ }
-        | wp::I8x16ReplaceLane { lane: _ }
-        | wp::I16x8ExtractLaneS { lane: _ }
-        | wp::I16x8ExtractLaneU { lane: _ }
-        | wp::I16x8ReplaceLane { lane: _ }
-        | wp::I32x4ExtractLane { lane: _ }
-        | wp::I32x4ReplaceLane { lane: _ }
-        | wp::I64x2ExtractLane { lane: _ }
-        | wp::I64x2ReplaceLane { lane: _ }
-        | wp::F32x4ExtractLane { lane: _ }
-        | wp::F32x4ReplaceLane { lane: _ }
-        | wp::F64x2ExtractLane { lane: _ }
-        | wp::F64x2ReplaceLane { lane: _ }
-        | wp::I8x16Swizzle
-        | wp::I8x16Splat
-        | wp::I16x8Splat
-        | wp::I32x4Splat
-        | wp::I64x2Splat
-        | wp::F32x4Splat
-        | wp::F64x2Splat
-        | wp::I8x16Eq
-        | wp::I8x16Ne
-        | wp::I8x16LtS
-        | wp::I8x16LtU
-        | wp::I8x16GtS
-        | wp::I8x16GtU
-        | wp::I8x16LeS
-        | wp::I8x16LeU
-        | wp::I8x16GeS
-        | wp::I8x16GeU
-        | wp::I16x8Eq
-        | wp::I16x8Ne
-        | wp::I16x8LtS
-        | wp::I16x8LtU
-        | wp::I16x8GtS
-        | wp::I16x8GtU
-        | wp::I16x8LeS
-        | wp::I16x8LeU
-        | wp::I16x8GeS
-        | wp::I16x8GeU
-        | wp::I32x4Eq
-        | wp::I32x4Ne
-        | wp::I32x4LtS
-        | wp::I32x4LtU
-        | wp::I32x4GtS
-        | wp::I32x4GtU
-        | wp::I32x4LeS
-        | wp::I32x4LeU
-        | wp::I32x4GeS
-        | wp::I32x4GeU
-        | wp::I64x2Eq
-        | wp::I64x2Ne
-        | wp::I64x2LtS
-        | wp::I64x2GtS
-        | wp::I64x2LeS
-        | wp::I64x2GeS
-        | wp::F32x4Eq
-        | wp::F32x4Ne
-        | wp::F32x4Lt
-        | wp::F32x4Gt
-        | wp::F32x4Le
-        | wp::F32x4Ge
-        | wp::F64x2Eq
-        | wp::F64x2Ne
-        | wp::F64x2Lt
-        | wp::F64x2Gt
-        | wp::F64x2Le
-        | wp::F64x2Ge
-        | wp::V128Not
-        | wp::V128And
-        | wp::V128AndNot
-        | wp::V128Or
-        | wp::V128Xor
-        | wp::V128Bitselect
-        | wp::V128AnyTrue
-        | wp::I8x16Abs
-        | wp::I8x16Neg
-        | wp::I8x16Popcnt
-        | wp::I8x16AllTrue
-        | wp::I8x16Bitmask
-        | wp::I8x16NarrowI16x8S
-        | wp::I8x16NarrowI16x8U
-        | wp::I8x16Shl
-        | wp::I8x16ShrS
-        | wp::I8x16ShrU
-        | wp::I8x16Add
-        | wp::I8x16AddSatS
-        | wp::I8x16AddSatU
-        | wp::I8x16Sub
-        | wp::I8x16SubSatS
-        | wp::I8x16SubSatU
-        | wp::I8x16MinS
-        | wp::I8x16MinU
-        | wp::I8x16MaxS
-        | wp::I8x16MaxU
-        | wp::I8x16AvgrU
-        | wp::I16x8ExtAddPairwiseI8x16S
-        | wp::I16x8ExtAddPairwiseI8x16U
-        | wp::I16x8Abs
-        | wp::I16x8Neg
-        | wp::I16x8Q15MulrSatS
-        | wp::I16x8AllTrue
-        | wp::I16x8Bitmask
-        | wp::I16x8NarrowI32x4S
-        | wp::I16x8NarrowI32x4U
-        | wp::I16x8ExtendLowI8x16S
-        | wp::I16x8ExtendHighI8x16S
-        | wp::I16x8ExtendLowI8x16U
-        | wp::I16x8ExtendHighI8x16U
-        | wp::I16x8Shl
-        | wp::I16x8ShrS
-        | wp::I16x8ShrU
-        | wp::I16x8Add
-        | wp::I16x8AddSatS
-        | wp::I16x8AddSatU
-        | wp::I16x8Sub
-        | wp::I16x8SubSatS
-        | wp::I16x8SubSatU
-        | wp::I16x8Mul
-        | wp::I16x8MinS
-        | wp::I16x8MinU
-        | wp::I16x8MaxS
-        | wp::I16x8MaxU
-        | wp::I16x8AvgrU
-        | wp::I16x8ExtMulLowI8x16S
-        | wp::I16x8ExtMulHighI8x16S
-        | wp::I16x8ExtMulLowI8x16U
-        | wp::I16x8ExtMulHighI8x16U
-        | wp::I32x4ExtAddPairwiseI16x8S
-        | wp::I32x4ExtAddPairwiseI16x8U
-        | wp::I32x4Abs
-        | wp::I32x4Neg
-        | wp::I32x4AllTrue
-        | wp::I32x4Bitmask
-        | wp::I32x4ExtendLowI16x8S
-        | wp::I32x4ExtendHighI16x8S
-        | wp::I32x4ExtendLowI16x8U
-        | wp::I32x4ExtendHighI16x8U
-        | wp::I32x4Shl
-        | wp::I32x4ShrS
-        | wp::I32x4ShrU
-        | wp::I32x4Add
-        | wp::I32x4Sub
-        | wp::I32x4Mul
-        | wp::I32x4MinS
-        | wp::I32x4MinU
-        | wp::I32x4MaxS
-        | wp::I32x4MaxU
-        | wp::I32x4DotI16x8S
-        | wp::I32x4ExtMulLowI16x8S
-        | wp::I32x4ExtMulHighI16x8S
-        | wp::I32x4ExtMulLowI16x8U
-        | wp::I32x4ExtMulHighI16x8U
-        | wp::I64x2Abs
-        | wp::I64x2Neg
-        | wp::I64x2AllTrue
-        | wp::I64x2Bitmask
-        | wp::I64x2ExtendLowI32x4S
-        | wp::I64x2ExtendHighI32x4S
-        | wp::I64x2ExtendLowI32x4U
-        | wp::I64x2ExtendHighI32x4U
-        | wp::I64x2Shl
-        | wp::I64x2ShrS
-        | wp::I64x2ShrU
-        | wp::I64x2Add
-        | wp::I64x2Sub
-        | wp::I64x2Mul
-        | wp::I64x2ExtMulLowI32x4S
-        | wp::I64x2ExtMulHighI32x4S
-        | wp::I64x2ExtMulLowI32x4U
-        | wp::I64x2ExtMulHighI32x4U
-        | wp::F32x4Ceil
-        | wp::F32x4Floor
-        | wp::F32x4Trunc
-        | wp::F32x4Nearest
-        | wp::F32x4Abs
-        | wp::F32x4Neg
-        | wp::F32x4Sqrt
-        | wp::F32x4Add
-        | wp::F32x4Sub
-        | wp::F32x4Mul
-        | wp::F32x4Div
-        | wp::F32x4Min
-        | wp::F32x4Max
-        | wp::F32x4PMin
-        | wp::F32x4PMax
-        | wp::F64x2Ceil
-        | wp::F64x2Floor
-        | wp::F64x2Trunc
-        | wp::F64x2Nearest
-        | wp::F64x2Abs
-        | wp::F64x2Neg
-        | wp::F64x2Sqrt
-        | wp::F64x2Add
-        | wp::F64x2Sub
-        | wp::F64x2Mul
-        | wp::F64x2Div
-        | wp::F64x2Min
-        | wp::F64x2Max
-        | wp::F64x2PMin
-        | wp::F64x2PMax
-        | wp::I32x4TruncSatF32x4S
-        | wp::I32x4TruncSatF32x4U
-        | wp::F32x4ConvertI32x4S
-        | wp::F32x4ConvertI32x4U
-        | wp::I32x4TruncSatF64x2SZero
-        | wp::I32x4TruncSatF64x2UZero
-        | wp::F64x2ConvertLowI32x4S
-        | wp::F64x2ConvertLowI32x4U
-        | wp::F32x4DemoteF64x2Zero
-        | wp::F64x2PromoteLowF32x4 => Err(ParseIssue::unsupported(offset, WasmExtension::Simd))?,
-
-        | wp::I8x16RelaxedSwizzle
+        // Threads and atomics.
+        wp::MemoryAtomicNotify { memarg } => Atomic(AtomicOp::MemoryAtomicNotify, parse_memarg(memarg, offset + 1)?),
+        wp::MemoryAtomicWait32 { memarg } => Atomic(AtomicOp::MemoryAtomicWait32, parse_memarg(memarg, offset + 1)?),
+        wp::MemoryAtomicWait64 { memarg } => Atomic(AtomicOp::MemoryAtomicWait64, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicLoad { memarg } => Atomic(AtomicOp::I32AtomicLoad, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicLoad { memarg } => Atomic(AtomicOp::I64AtomicLoad, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicLoad8U { memarg } => Atomic(AtomicOp::I32AtomicLoad8U, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicLoad16U { memarg } => Atomic(AtomicOp::I32AtomicLoad16U, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicLoad8U { memarg } => Atomic(AtomicOp::I64AtomicLoad8U, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicLoad16U { memarg } => Atomic(AtomicOp::I64AtomicLoad16U, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicLoad32U { memarg } => Atomic(AtomicOp::I64AtomicLoad32U, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicStore { memarg } => Atomic(AtomicOp::I32AtomicStore, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicStore { memarg } => Atomic(AtomicOp::I64AtomicStore, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicStore8 { memarg } => Atomic(AtomicOp::I32AtomicStore8, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicStore16 { memarg } => Atomic(AtomicOp::I32AtomicStore16, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicStore8 { memarg } => Atomic(AtomicOp::I64AtomicStore8, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicStore16 { memarg } => Atomic(AtomicOp::I64AtomicStore16, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicStore32 { memarg } => Atomic(AtomicOp::I64AtomicStore32, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwAdd { memarg } => Atomic(AtomicOp::I32AtomicRmwAdd, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwAdd { memarg } => Atomic(AtomicOp::I64AtomicRmwAdd, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8AddU { memarg } => Atomic(AtomicOp::I32AtomicRmw8AddU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16AddU { memarg } => Atomic(AtomicOp::I32AtomicRmw16AddU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8AddU { memarg } => Atomic(AtomicOp::I64AtomicRmw8AddU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16AddU { memarg } => Atomic(AtomicOp::I64AtomicRmw16AddU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32AddU { memarg } => Atomic(AtomicOp::I64AtomicRmw32AddU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwSub { memarg } => Atomic(AtomicOp::I32AtomicRmwSub, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwSub { memarg } => Atomic(AtomicOp::I64AtomicRmwSub, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8SubU { memarg } => Atomic(AtomicOp::I32AtomicRmw8SubU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16SubU { memarg } => Atomic(AtomicOp::I32AtomicRmw16SubU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8SubU { memarg } => Atomic(AtomicOp::I64AtomicRmw8SubU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16SubU { memarg } => Atomic(AtomicOp::I64AtomicRmw16SubU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32SubU { memarg } => Atomic(AtomicOp::I64AtomicRmw32SubU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwAnd { memarg } => Atomic(AtomicOp::I32AtomicRmwAnd, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwAnd { memarg } => Atomic(AtomicOp::I64AtomicRmwAnd, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8AndU { memarg } => Atomic(AtomicOp::I32AtomicRmw8AndU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16AndU { memarg } => Atomic(AtomicOp::I32AtomicRmw16AndU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8AndU { memarg } => Atomic(AtomicOp::I64AtomicRmw8AndU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16AndU { memarg } => Atomic(AtomicOp::I64AtomicRmw16AndU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32AndU { memarg } => Atomic(AtomicOp::I64AtomicRmw32AndU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwOr { memarg } => Atomic(AtomicOp::I32AtomicRmwOr, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwOr { memarg } => Atomic(AtomicOp::I64AtomicRmwOr, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8OrU { memarg } => Atomic(AtomicOp::I32AtomicRmw8OrU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16OrU { memarg } => Atomic(AtomicOp::I32AtomicRmw16OrU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8OrU { memarg } => Atomic(AtomicOp::I64AtomicRmw8OrU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16OrU { memarg } => Atomic(AtomicOp::I64AtomicRmw16OrU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32OrU { memarg } => Atomic(AtomicOp::I64AtomicRmw32OrU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwXor { memarg } => Atomic(AtomicOp::I32AtomicRmwXor, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwXor { memarg } => Atomic(AtomicOp::I64AtomicRmwXor, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8XorU { memarg } => Atomic(AtomicOp::I32AtomicRmw8XorU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16XorU { memarg } => Atomic(AtomicOp::I32AtomicRmw16XorU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8XorU { memarg } => Atomic(AtomicOp::I64AtomicRmw8XorU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16XorU { memarg } => Atomic(AtomicOp::I64AtomicRmw16XorU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32XorU { memarg } => Atomic(AtomicOp::I64AtomicRmw32XorU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwXchg { memarg } => Atomic(AtomicOp::I32AtomicRmwXchg, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwXchg { memarg } => Atomic(AtomicOp::I64AtomicRmwXchg, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8XchgU { memarg } => Atomic(AtomicOp::I32AtomicRmw8XchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16XchgU { memarg } => Atomic(AtomicOp::I32AtomicRmw16XchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8XchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw8XchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16XchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw16XchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32XchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw32XchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmwCmpxchg { memarg } => Atomic(AtomicOp::I32AtomicRmwCmpxchg, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmwCmpxchg { memarg } => Atomic(AtomicOp::I64AtomicRmwCmpxchg, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw8CmpxchgU { memarg } => Atomic(AtomicOp::I32AtomicRmw8CmpxchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I32AtomicRmw16CmpxchgU { memarg } => Atomic(AtomicOp::I32AtomicRmw16CmpxchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw8CmpxchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw8CmpxchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw16CmpxchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw16CmpxchgU, parse_memarg(memarg, offset + 1)?),
+        wp::I64AtomicRmw32CmpxchgU { memarg } => Atomic(AtomicOp::I64AtomicRmw32CmpxchgU, parse_memarg(memarg, offset + 1)?),
+        wp::AtomicFence => AtomicFence,
+
+        // SIMD instructions.
+        wp::V128Load { memarg } => Load(LoadOp::V128Load, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load8x8S { memarg } => Load(LoadOp::V128Load8x8S, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load8x8U { memarg } => Load(LoadOp::V128Load8x8U, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load16x4S { memarg } => Load(LoadOp::V128Load16x4S, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load16x4U { memarg } => Load(LoadOp::V128Load16x4U, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load32x2S { memarg } => Load(LoadOp::V128Load32x2S, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load32x2U { memarg } => Load(LoadOp::V128Load32x2U, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load8Splat { memarg } => Load(LoadOp::V128Load8Splat, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load16Splat { memarg } => Load(LoadOp::V128Load16Splat, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load32Splat { memarg } => Load(LoadOp::V128Load32Splat, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load64Splat { memarg } => Load(LoadOp::V128Load64Splat, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load32Zero { memarg } => Load(LoadOp::V128Load32Zero, parse_memarg(memarg, offset + 1)?),
+        wp::V128Load64Zero { memarg } => Load(LoadOp::V128Load64Zero, parse_memarg(memarg, offset + 1)?),
+        wp::V128Store { memarg } => Store(StoreOp::V128Store, parse_memarg(memarg, offset + 1)?),
+        // Lane indices are already checked against the lane count by wasmparser's binary reader.
+        wp::V128Load8Lane { memarg, lane } => LoadLane(LoadLaneOp::V128Load8Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Load16Lane { memarg, lane } => LoadLane(LoadLaneOp::V128Load16Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Load32Lane { memarg, lane } => LoadLane(LoadLaneOp::V128Load32Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Load64Lane { memarg, lane } => LoadLane(LoadLaneOp::V128Load64Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Store8Lane { memarg, lane } => StoreLane(StoreLaneOp::V128Store8Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Store16Lane { memarg, lane } => StoreLane(StoreLaneOp::V128Store16Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Store32Lane { memarg, lane } => StoreLane(StoreLaneOp::V128Store32Lane, parse_memarg(memarg, offset + 1)?, lane),
+        wp::V128Store64Lane { memarg, lane } => StoreLane(StoreLaneOp::V128Store64Lane, parse_memarg(memarg, offset + 1)?, lane),
+
+        wp::V128Const { value } => Const(Val::V128(*value.bytes())),
+        wp::I8x16Shuffle { lanes } => I8x16Shuffle(lanes),
+        wp::V128Bitselect => V128Bitselect,
+
+        wp::I8x16ExtractLaneS { lane } => Lane(LaneOp::I8x16ExtractLaneS, lane),
+        wp::I8x16ExtractLaneU { lane } => Lane(LaneOp::I8x16ExtractLaneU, lane),
+        wp::I8x16ReplaceLane { lane } => Lane(LaneOp::I8x16ReplaceLane, lane),
+        wp::I16x8ExtractLaneS { lane } => Lane(LaneOp::I16x8ExtractLaneS, lane),
+        wp::I16x8ExtractLaneU { lane } => Lane(LaneOp::I16x8ExtractLaneU, lane),
+        wp::I16x8ReplaceLane { lane } => Lane(LaneOp::I16x8ReplaceLane, lane),
+        wp::I32x4ExtractLane { lane } => Lane(LaneOp::I32x4ExtractLane, lane),
+        wp::I32x4ReplaceLane { lane } => Lane(LaneOp::I32x4ReplaceLane, lane),
+        wp::I64x2ExtractLane { lane } => Lane(LaneOp::I64x2ExtractLane, lane),
+        wp::I64x2ReplaceLane { lane } => Lane(LaneOp::I64x2ReplaceLane, lane),
+        wp::F32x4ExtractLane { lane } => Lane(LaneOp::F32x4ExtractLane, lane),
+        wp::F32x4ReplaceLane { lane } => Lane(LaneOp::F32x4ReplaceLane, lane),
+        wp::F64x2ExtractLane { lane } => Lane(LaneOp::F64x2ExtractLane, lane),
+        wp::F64x2ReplaceLane { lane } => Lane(LaneOp::F64x2ReplaceLane, lane),
+        wp::I8x16Splat => Unary(UnaryOp::I8x16Splat),
+        wp::I16x8Splat => Unary(UnaryOp::I16x8Splat),
+        wp::I32x4Splat => Unary(UnaryOp::I32x4Splat),
+        wp::I64x2Splat => Unary(UnaryOp::I64x2Splat),
+        wp::F32x4Splat => Unary(UnaryOp::F32x4Splat),
+        wp::F64x2Splat => Unary(UnaryOp::F64x2Splat),
+        wp::V128Not => Unary(UnaryOp::V128Not),
+        wp::V128AnyTrue => Unary(UnaryOp::V128AnyTrue),
+        wp::I8x16Abs => Unary(UnaryOp::I8x16Abs),
+        wp::I8x16Neg => Unary(UnaryOp::I8x16Neg),
+        wp::I8x16Popcnt => Unary(UnaryOp::I8x16Popcnt),
+        wp::I8x16AllTrue => Unary(UnaryOp::I8x16AllTrue),
+        wp::I8x16Bitmask => Unary(UnaryOp::I8x16Bitmask),
+        wp::I16x8ExtAddPairwiseI8x16S => Unary(UnaryOp::I16x8ExtAddPairwiseI8x16S),
+        wp::I16x8ExtAddPairwiseI8x16U => Unary(UnaryOp::I16x8ExtAddPairwiseI8x16U),
+        wp::I16x8Abs => Unary(UnaryOp::I16x8Abs),
+        wp::I16x8Neg => Unary(UnaryOp::I16x8Neg),
+        wp::I16x8AllTrue => Unary(UnaryOp::I16x8AllTrue),
+        wp::I16x8Bitmask => Unary(UnaryOp::I16x8Bitmask),
+        wp::I16x8ExtendLowI8x16S => Unary(UnaryOp::I16x8ExtendLowI8x16S),
+        wp::I16x8ExtendHighI8x16S => Unary(UnaryOp::I16x8ExtendHighI8x16S),
+        wp::I16x8ExtendLowI8x16U => Unary(UnaryOp::I16x8ExtendLowI8x16U),
+        wp::I16x8ExtendHighI8x16U => Unary(UnaryOp::I16x8ExtendHighI8x16U),
+        wp::I32x4ExtAddPairwiseI16x8S => Unary(UnaryOp::I32x4ExtAddPairwiseI16x8S),
+        wp::I32x4ExtAddPairwiseI16x8U => Unary(UnaryOp::I32x4ExtAddPairwiseI16x8U),
+        wp::I32x4Abs => Unary(UnaryOp::I32x4Abs),
+        wp::I32x4Neg => Unary(UnaryOp::I32x4Neg),
+        wp::I32x4AllTrue => Unary(UnaryOp::I32x4AllTrue),
+        wp::I32x4Bitmask => Unary(UnaryOp::I32x4Bitmask),
+        wp::I32x4ExtendLowI16x8S => Unary(UnaryOp::I32x4ExtendLowI16x8S),
+        wp::I32x4ExtendHighI16x8S => Unary(UnaryOp::I32x4ExtendHighI16x8S),
+        wp::I32x4ExtendLowI16x8U => Unary(UnaryOp::I32x4ExtendLowI16x8U),
+        wp::I32x4ExtendHighI16x8U => Unary(UnaryOp::I32x4ExtendHighI16x8U),
+        wp::I64x2Abs => Unary(UnaryOp::I64x2Abs),
+        wp::I64x2Neg => Unary(UnaryOp::I64x2Neg),
+        wp::I64x2AllTrue => Unary(UnaryOp::I64x2AllTrue),
+        wp::I64x2Bitmask => Unary(UnaryOp::I64x2Bitmask),
+        wp::I64x2ExtendLowI32x4S => Unary(UnaryOp::I64x2ExtendLowI32x4S),
+        wp::I64x2ExtendHighI32x4S => Unary(UnaryOp::I64x2ExtendHighI32x4S),
+        wp::I64x2ExtendLowI32x4U => Unary(UnaryOp::I64x2ExtendLowI32x4U),
+        wp::I64x2ExtendHighI32x4U => Unary(UnaryOp::I64x2ExtendHighI32x4U),
+        wp::F32x4Ceil => Unary(UnaryOp::F32x4Ceil),
+        wp::F32x4Floor => Unary(UnaryOp::F32x4Floor),
+        wp::F32x4Trunc => Unary(UnaryOp::F32x4Trunc),
+        wp::F32x4Nearest => Unary(UnaryOp::F32x4Nearest),
+        wp::F32x4Abs => Unary(UnaryOp::F32x4Abs),
+        wp::F32x4Neg => Unary(UnaryOp::F32x4Neg),
+        wp::F32x4Sqrt => Unary(UnaryOp::F32x4Sqrt),
+        wp::F64x2Ceil => Unary(UnaryOp::F64x2Ceil),
+        wp::F64x2Floor => Unary(UnaryOp::F64x2Floor),
+        wp::F64x2Trunc => Unary(UnaryOp::F64x2Trunc),
+        wp::F64x2Nearest => Unary(UnaryOp::F64x2Nearest),
+        wp::F64x2Abs => Unary(UnaryOp::F64x2Abs),
+        wp::F64x2Neg => Unary(UnaryOp::F64x2Neg),
+        wp::F64x2Sqrt => Unary(UnaryOp::F64x2Sqrt),
+        wp::I32x4TruncSatF32x4S => Unary(UnaryOp::I32x4TruncSatF32x4S),
+        wp::I32x4TruncSatF32x4U => Unary(UnaryOp::I32x4TruncSatF32x4U),
+        wp::F32x4ConvertI32x4S => Unary(UnaryOp::F32x4ConvertI32x4S),
+        wp::F32x4ConvertI32x4U => Unary(UnaryOp::F32x4ConvertI32x4U),
+        wp::I32x4TruncSatF64x2SZero => Unary(UnaryOp::I32x4TruncSatF64x2SZero),
+        wp::I32x4TruncSatF64x2UZero => Unary(UnaryOp::I32x4TruncSatF64x2UZero),
+        wp::F64x2ConvertLowI32x4S => Unary(UnaryOp::F64x2ConvertLowI32x4S),
+        wp::F64x2ConvertLowI32x4U => Unary(UnaryOp::F64x2ConvertLowI32x4U),
+        wp::F32x4DemoteF64x2Zero => Unary(UnaryOp::F32x4DemoteF64x2Zero),
+        wp::F64x2PromoteLowF32x4 => Unary(UnaryOp::F64x2PromoteLowF32x4),
+        wp::I8x16Swizzle => Binary(BinaryOp::I8x16Swizzle),
+        wp::I8x16Eq => Binary(BinaryOp::I8x16Eq),
+        wp::I8x16Ne => Binary(BinaryOp::I8x16Ne),
+        wp::I8x16LtS => Binary(BinaryOp::I8x16LtS),
+        wp::I8x16LtU => Binary(BinaryOp::I8x16LtU),
+        wp::I8x16GtS => Binary(BinaryOp::I8x16GtS),
+        wp::I8x16GtU => Binary(BinaryOp::I8x16GtU),
+        wp::I8x16LeS => Binary(BinaryOp::I8x16LeS),
+        wp::I8x16LeU => Binary(BinaryOp::I8x16LeU),
+        wp::I8x16GeS => Binary(BinaryOp::I8x16GeS),
+        wp::I8x16GeU => Binary(BinaryOp::I8x16GeU),
+        wp::I16x8Eq => Binary(BinaryOp::I16x8Eq),
+        wp::I16x8Ne => Binary(BinaryOp::I16x8Ne),
+        wp::I16x8LtS => Binary(BinaryOp::I16x8LtS),
+        wp::I16x8LtU => Binary(BinaryOp::I16x8LtU),
+        wp::I16x8GtS => Binary(BinaryOp::I16x8GtS),
+        wp::I16x8GtU => Binary(BinaryOp::I16x8GtU),
+        wp::I16x8LeS => Binary(BinaryOp::I16x8LeS),
+        wp::I16x8LeU => Binary(BinaryOp::I16x8LeU),
+        wp::I16x8GeS => Binary(BinaryOp::I16x8GeS),
+        wp::I16x8GeU => Binary(BinaryOp::I16x8GeU),
+        wp::I32x4Eq => Binary(BinaryOp::I32x4Eq),
+        wp::I32x4Ne => Binary(BinaryOp::I32x4Ne),
+        wp::I32x4LtS => Binary(BinaryOp::I32x4LtS),
+        wp::I32x4LtU => Binary(BinaryOp::I32x4LtU),
+        wp::I32x4GtS => Binary(BinaryOp::I32x4GtS),
+        wp::I32x4GtU => Binary(BinaryOp::I32x4GtU),
+        wp::I32x4LeS => Binary(BinaryOp::I32x4LeS),
+        wp::I32x4LeU => Binary(BinaryOp::I32x4LeU),
+        wp::I32x4GeS => Binary(BinaryOp::I32x4GeS),
+        wp::I32x4GeU => Binary(BinaryOp::I32x4GeU),
+        wp::I64x2Eq => Binary(BinaryOp::I64x2Eq),
+        wp::I64x2Ne => Binary(BinaryOp::I64x2Ne),
+        wp::I64x2LtS => Binary(BinaryOp::I64x2LtS),
+        wp::I64x2GtS => Binary(BinaryOp::I64x2GtS),
+        wp::I64x2LeS => Binary(BinaryOp::I64x2LeS),
+        wp::I64x2GeS => Binary(BinaryOp::I64x2GeS),
+        wp::F32x4Eq => Binary(BinaryOp::F32x4Eq),
+        wp::F32x4Ne => Binary(BinaryOp::F32x4Ne),
+        wp::F32x4Lt => Binary(BinaryOp::F32x4Lt),
+        wp::F32x4Gt => Binary(BinaryOp::F32x4Gt),
+        wp::F32x4Le => Binary(BinaryOp::F32x4Le),
+        wp::F32x4Ge => Binary(BinaryOp::F32x4Ge),
+        wp::F64x2Eq => Binary(BinaryOp::F64x2Eq),
+        wp::F64x2Ne => Binary(BinaryOp::F64x2Ne),
+        wp::F64x2Lt => Binary(BinaryOp::F64x2Lt),
+        wp::F64x2Gt => Binary(BinaryOp::F64x2Gt),
+        wp::F64x2Le => Binary(BinaryOp::F64x2Le),
+        wp::F64x2Ge => Binary(BinaryOp::F64x2Ge),
+        wp::V128And => Binary(BinaryOp::V128And),
+        wp::V128AndNot => Binary(BinaryOp::V128AndNot),
+        wp::V128Or => Binary(BinaryOp::V128Or),
+        wp::V128Xor => Binary(BinaryOp::V128Xor),
+        wp::I8x16NarrowI16x8S => Binary(BinaryOp::I8x16NarrowI16x8S),
+        wp::I8x16NarrowI16x8U => Binary(BinaryOp::I8x16NarrowI16x8U),
+        wp::I8x16Shl => Binary(BinaryOp::I8x16Shl),
+        wp::I8x16ShrS => Binary(BinaryOp::I8x16ShrS),
+        wp::I8x16ShrU => Binary(BinaryOp::I8x16ShrU),
+        wp::I8x16Add => Binary(BinaryOp::I8x16Add),
+        wp::I8x16AddSatS => Binary(BinaryOp::I8x16AddSatS),
+        wp::I8x16AddSatU => Binary(BinaryOp::I8x16AddSatU),
+        wp::I8x16Sub => Binary(BinaryOp::I8x16Sub),
+        wp::I8x16SubSatS => Binary(BinaryOp::I8x16SubSatS),
+        wp::I8x16SubSatU => Binary(BinaryOp::I8x16SubSatU),
+        wp::I8x16MinS => Binary(BinaryOp::I8x16MinS),
+        wp::I8x16MinU => Binary(BinaryOp::I8x16MinU),
+        wp::I8x16MaxS => Binary(BinaryOp::I8x16MaxS),
+        wp::I8x16MaxU => Binary(BinaryOp::I8x16MaxU),
+        wp::I8x16AvgrU => Binary(BinaryOp::I8x16AvgrU),
+        wp::I16x8Q15MulrSatS => Binary(BinaryOp::I16x8Q15MulrSatS),
+        wp::I16x8NarrowI32x4S => Binary(BinaryOp::I16x8NarrowI32x4S),
+        wp::I16x8NarrowI32x4U => Binary(BinaryOp::I16x8NarrowI32x4U),
+        wp::I16x8Shl => Binary(BinaryOp::I16x8Shl),
+        wp::I16x8ShrS => Binary(BinaryOp::I16x8ShrS),
+        wp::I16x8ShrU => Binary(BinaryOp::I16x8ShrU),
+        wp::I16x8Add => Binary(BinaryOp::I16x8Add),
+        wp::I16x8AddSatS => Binary(BinaryOp::I16x8AddSatS),
+        wp::I16x8AddSatU => Binary(BinaryOp::I16x8AddSatU),
+        wp::I16x8Sub => Binary(BinaryOp::I16x8Sub),
+        wp::I16x8SubSatS => Binary(BinaryOp::I16x8SubSatS),
+        wp::I16x8SubSatU => Binary(BinaryOp::I16x8SubSatU),
+        wp::I16x8Mul => Binary(BinaryOp::I16x8Mul),
+        wp::I16x8MinS => Binary(BinaryOp::I16x8MinS),
+        wp::I16x8MinU => Binary(BinaryOp::I16x8MinU),
+        wp::I16x8MaxS => Binary(BinaryOp::I16x8MaxS),
+        wp::I16x8MaxU => Binary(BinaryOp::I16x8MaxU),
+        wp::I16x8AvgrU => Binary(BinaryOp::I16x8AvgrU),
+        wp::I16x8ExtMulLowI8x16S => Binary(BinaryOp::I16x8ExtMulLowI8x16S),
+        wp::I16x8ExtMulHighI8x16S => Binary(BinaryOp::I16x8ExtMulHighI8x16S),
+        wp::I16x8ExtMulLowI8x16U => Binary(BinaryOp::I16x8ExtMulLowI8x16U),
+        wp::I16x8ExtMulHighI8x16U => Binary(BinaryOp::I16x8ExtMulHighI8x16U),
+        wp::I32x4Shl => Binary(BinaryOp::I32x4Shl),
+        wp::I32x4ShrS => Binary(BinaryOp::I32x4ShrS),
+        wp::I32x4ShrU => Binary(BinaryOp::I32x4ShrU),
+        wp::I32x4Add => Binary(BinaryOp::I32x4Add),
+        wp::I32x4Sub => Binary(BinaryOp::I32x4Sub),
+        wp::I32x4Mul => Binary(BinaryOp::I32x4Mul),
+        wp::I32x4MinS => Binary(BinaryOp::I32x4MinS),
+        wp::I32x4MinU => Binary(BinaryOp::I32x4MinU),
+        wp::I32x4MaxS => Binary(BinaryOp::I32x4MaxS),
+        wp::I32x4MaxU => Binary(BinaryOp::I32x4MaxU),
+        wp::I32x4DotI16x8S => Binary(BinaryOp::I32x4DotI16x8S),
+        wp::I32x4ExtMulLowI16x8S => Binary(BinaryOp::I32x4ExtMulLowI16x8S),
+        wp::I32x4ExtMulHighI16x8S => Binary(BinaryOp::I32x4ExtMulHighI16x8S),
+        wp::I32x4ExtMulLowI16x8U => Binary(BinaryOp::I32x4ExtMulLowI16x8U),
+        wp::I32x4ExtMulHighI16x8U => Binary(BinaryOp::I32x4ExtMulHighI16x8U),
+        wp::I64x2Shl => Binary(BinaryOp::I64x2Shl),
+        wp::I64x2ShrS => Binary(BinaryOp::I64x2ShrS),
+        wp::I64x2ShrU => Binary(BinaryOp::I64x2ShrU),
+        wp::I64x2Add => Binary(BinaryOp::I64x2Add),
+        wp::I64x2Sub => Binary(BinaryOp::I64x2Sub),
+        wp::I64x2Mul => Binary(BinaryOp::I64x2Mul),
+        wp::I64x2ExtMulLowI32x4S => Binary(BinaryOp::I64x2ExtMulLowI32x4S),
+        wp::I64x2ExtMulHighI32x4S => Binary(BinaryOp::I64x2ExtMulHighI32x4S),
+        wp::I64x2ExtMulLowI32x4U => Binary(BinaryOp::I64x2ExtMulLowI32x4U),
+        wp::I64x2ExtMulHighI32x4U => Binary(BinaryOp::I64x2ExtMulHighI32x4U),
+        wp::F32x4Add => Binary(BinaryOp::F32x4Add),
+        wp::F32x4Sub => Binary(BinaryOp::F32x4Sub),
+        wp::F32x4Mul => Binary(BinaryOp::F32x4Mul),
+        wp::F32x4Div => Binary(BinaryOp::F32x4Div),
+        wp::F32x4Min => Binary(BinaryOp::F32x4Min),
+        wp::F32x4Max => Binary(BinaryOp::F32x4Max),
+        wp::F32x4PMin => Binary(BinaryOp::F32x4PMin),
+        wp::F32x4PMax => Binary(BinaryOp::F32x4PMax),
+        wp::F64x2Add => Binary(BinaryOp::F64x2Add),
+        wp::F64x2Sub => Binary(BinaryOp::F64x2Sub),
+        wp::F64x2Mul => Binary(BinaryOp::F64x2Mul),
+        wp::F64x2Div => Binary(BinaryOp::F64x2Div),
+        wp::F64x2Min => Binary(BinaryOp::F64x2Min),
+        wp::F64x2Max => Binary(BinaryOp::F64x2Max),
+        wp::F64x2PMin => Binary(BinaryOp::F64x2PMin),
+        wp::F64x2PMax => Binary(BinaryOp::F64x2PMax),
+
+        wp::I8x16RelaxedSwizzle
         | wp::I32x4RelaxedTruncSatF32x4S
         | wp::I32x4RelaxedTruncSatF32x4U
         | wp::I32x4RelaxedTruncSatF64x2SZero
@@ -1084,53 +1708,320 @@ fn parse_instr(
     })
 }
 
+/// Cheap in the common case that the extension was already recorded, because it only takes the
+/// read lock, which avoids contention when parsing function bodies in parallel.
+fn add_used_extension(metadata: &RwLock<ModuleMetadata>, extension: WasmExtension) {
+    if !metadata.read().unwrap().used_extensions().any(|used| used == extension) {
+        metadata.write().unwrap().add_used_extension(extension);
+    }
+}
+
+/// The extensions (beyond the MVP) that a single instruction requires, including those implied
+/// by its immediates (e.g., a non-zero memory index requires multi-memory).
+/// This is the single source of truth for both `parse_instr` and `detect_extensions`.
+fn required_extensions(op: &wp::Operator) -> impl Iterator<Item = WasmExtension> {
+    use wp::Operator as wp;
+
+    // Generate the mapping from operator to proposal with wasmparser's own list of operators,
+    // such that we cannot forget any (e.g., one of the many SIMD instructions).
+    macro_rules! proposal_extension {
+        (mvp) => { None };
+        (exceptions) => { Some(WasmExtension::ExceptionHandling) };
+        (tail_call) => { Some(WasmExtension::TailCalls) };
+        (reference_types) => { Some(WasmExtension::ReferenceTypes) };
+        (sign_extension) => { Some(WasmExtension::SignExtensionOps) };
+        (saturating_float_to_int) => { Some(WasmExtension::NontrappingFloatToInt) };
+        (bulk_memory) => { Some(WasmExtension::BulkMemoryOperations) };
+        (threads) => { Some(WasmExtension::ThreadsAtomics) };
+        (simd) => { Some(WasmExtension::Simd) };
+        (relaxed_simd) => { Some(WasmExtension::RelaxedSimd) };
+    }
+    macro_rules! define_proposal_of {
+        ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*) => {
+            fn proposal_of(op: &wp) -> Option<WasmExtension> {
+                match op {
+                    $( wp::$op { .. } => proposal_extension!($proposal), )*
+                }
+            }
+        }
+    }
+    wasmparser::for_each_operator!(define_proposal_of);
+
+    let proposal = match op {
+        // wasmparser (still) lists relaxed SIMD instructions as part of the SIMD proposal.
+        wp::I8x16RelaxedSwizzle
+        | wp::I32x4RelaxedTruncSatF32x4S
+        | wp::I32x4RelaxedTruncSatF32x4U
+        | wp::I32x4RelaxedTruncSatF64x2SZero
+        | wp::I32x4RelaxedTruncSatF64x2UZero
+        | wp::F32x4RelaxedFma
+        | wp::F32x4RelaxedFnma
+        | wp::F64x2RelaxedFma
+        | wp::F64x2RelaxedFnma
+        | wp::I8x16RelaxedLaneselect
+        | wp::I16x8RelaxedLaneselect
+        | wp::I32x4RelaxedLaneselect
+        | wp::I64x2RelaxedLaneselect
+        | wp::F32x4RelaxedMin
+        | wp::F32x4RelaxedMax
+        | wp::F64x2RelaxedMin
+        | wp::F64x2RelaxedMax
+        | wp::I16x8RelaxedQ15mulrS
+        | wp::I16x8DotI8x16I7x16S
+        | wp::I32x4DotI8x16I7x16AddS
+        | wp::F32x4RelaxedDotBf16x8AddF32x4 => Some(WasmExtension::RelaxedSimd),
+        op => proposal_of(op),
+    };
+
+    let immediates = match *op {
+        wp::Block { blockty: wasmparser::BlockType::FuncType(_) }
+        | wp::Loop { blockty: wasmparser::BlockType::FuncType(_) }
+        | wp::If { blockty: wasmparser::BlockType::FuncType(_) } => Some(WasmExtension::MultiValue),
+        wp::CallIndirect { table_index, .. } | wp::ReturnCallIndirect { table_index, .. } if table_index != 0 => {
+            Some(WasmExtension::ReferenceTypes)
+        }
+        wp::TableInit { table, .. } if table != 0 => Some(WasmExtension::ReferenceTypes),
+        wp::TableCopy { dst_table, src_table } if dst_table != 0 || src_table != 0 => Some(WasmExtension::ReferenceTypes),
+        wp::MemorySize { mem, .. }
+        | wp::MemoryGrow { mem, .. }
+        | wp::MemoryInit { mem, .. }
+        | wp::MemoryFill { mem } if mem != 0 => Some(WasmExtension::MultiMemory),
+        wp::MemoryCopy { dst_mem, src_mem } if dst_mem != 0 || src_mem != 0 => Some(WasmExtension::MultiMemory),
+        _ => None,
+    };
+
+    proposal.into_iter().chain(immediates)
+}
+
+/// Offsets in errors are relative to the start of `content`.
+pub(crate) fn parse_producers(content: &[u8]) -> Result<Producers, ParseError> {
+    let reader = wp::ProducersSectionReader::new(content, 0)?;
+    let mut fields = Vec::with_capacity(bounded_capacity(reader.count(), &reader.range()));
+    for field in reader {
+        let field = field?;
+        let values = field.values
+            .into_iter()
+            .map(|value| value.map(|value| ProducerValue {
+                name: value.name.to_string(),
+                version: value.version.to_string(),
+            }))
+            .collect::<Result<Vec<_>, _>>()?;
+        fields.push(ProducersField {
+            name: field.name.to_string(),
+            values,
+        });
+    }
+    Ok(Producers { fields })
+}
+
+/// Scans a binary for all extensions that it uses, without converting it to our AST and without
+/// failing on the first unsupported extension (unlike `Module::from_bytes`).
+/// Useful for triaging a corpus of binaries by feature usage.
+///
+/// Only fails if the binary is malformed. Note that some extensions can only be detected
+/// approximately, e.g., multi-memory only from the number of memories and memory indices of
+/// non-load/store instructions.
+pub fn detect_extensions(bytes: &[u8]) -> Result<BTreeSet<WasmExtension>, ParseError> {
+    let mut extensions = BTreeSet::new();
+    let mut table_count = 0;
+    let mut memory_count = 0;
+
+    let mut add_memory_ty = |extensions: &mut BTreeSet<WasmExtension>, ty: wp::MemoryType| {
+        memory_count += 1;
+        if ty.memory64 {
+            extensions.insert(WasmExtension::Memory64);
+        }
+        if ty.shared {
+            extensions.insert(WasmExtension::ThreadsAtomics);
+        }
+    };
+    let add_const_expr = |extensions: &mut BTreeSet<WasmExtension>, expr: wp::ConstExpr| -> Result<(), ParseError> {
+        for op in expr.get_operators_reader() {
+            extensions.extend(required_extensions(&op?));
+        }
+        Ok(())
+    };
+
+    for payload in wp::Parser::new(0).parse_all(bytes) {
+        match payload? {
+            wp::Payload::Version { encoding: wp::Encoding::Component, .. }
+            | wp::Payload::ComponentSection { .. }
+            | wp::Payload::InstanceSection(_)
+            | wp::Payload::CoreTypeSection(_)
+            | wp::Payload::ComponentInstanceSection(_)
+            | wp::Payload::ComponentAliasSection(_)
+            | wp::Payload::ComponentTypeSection(_)
+            | wp::Payload::ComponentCanonicalSection(_)
+            | wp::Payload::ComponentStartSection { .. }
+            | wp::Payload::ComponentImportSection(_)
+            | wp::Payload::ComponentExportSection(_) => {
+                extensions.insert(WasmExtension::ComponentModel);
+            }
+            wp::Payload::TypeSection(reader) => {
+                for type_ in reader {
+                    let wp::Type::Func(type_) = type_?;
+                    if type_.results().len() > 1 {
+                        extensions.insert(WasmExtension::MultiValue);
+                    }
+                }
+            }
+            wp::Payload::ImportSection(reader) => {
+                for import in reader {
+                    match import?.ty {
+                        wp::TypeRef::Func(_) | wp::TypeRef::Global(_) => {}
+                        wp::TypeRef::Table(_) => table_count += 1,
+                        wp::TypeRef::Memory(ty) => add_memory_ty(&mut extensions, ty),
+                        wp::TypeRef::Tag(_) => {
+                            extensions.insert(WasmExtension::ExceptionHandling);
+                        }
+                    }
+                }
+            }
+            wp::Payload::TableSection(reader) => table_count += reader.count(),
+            wp::Payload::MemorySection(reader) => {
+                for ty in reader {
+                    add_memory_ty(&mut extensions, ty?);
+                }
+            }
+            wp::Payload::TagSection(_) => {
+                extensions.insert(WasmExtension::ExceptionHandling);
+            }
+            wp::Payload::GlobalSection(reader) => {
+                for global in reader {
+                    add_const_expr(&mut extensions, global?.init_expr)?;
+                }
+            }
+            wp::Payload::ElementSection(reader) => {
+                for element in reader {
+                    let element = element?;
+                    match element.kind {
+                        wp::ElementKind::Active { table_index, offset_expr } => {
+                            if table_index != 0 {
+                                extensions.insert(WasmExtension::ReferenceTypes);
+                            }
+                            add_const_expr(&mut extensions, offset_expr)?;
+                        }
+                        wp::ElementKind::Passive => {
+                            extensions.insert(WasmExtension::BulkMemoryOperations);
+                        }
+                        wp::ElementKind::Declared => {
+                            extensions.insert(WasmExtension::ReferenceTypes);
+                        }
+                    }
+                    if let wp::ElementItems::Expressions(items) = element.items {
+                        extensions.insert(WasmExtension::ReferenceTypes);
+                        for item in items {
+                            add_const_expr(&mut extensions, item?)?;
+                        }
+                    }
+                }
+            }
+            wp::Payload::DataCountSection { .. } => {
+                extensions.insert(WasmExtension::BulkMemoryOperations);
+            }
+            wp::Payload::DataSection(reader) => {
+                for data in reader {
+                    match data?.kind {
+                        wp::DataKind::Active { memory_index, offset_expr } => {
+                            if memory_index != 0 {
+                                extensions.insert(WasmExtension::MultiMemory);
+                            }
+                            add_const_expr(&mut extensions, offset_expr)?;
+                        }
+                        wp::DataKind::Passive => {
+                            extensions.insert(WasmExtension::BulkMemoryOperations);
+                        }
+                    }
+                }
+            }
+            wp::Payload::CodeSectionEntry(mut body) => {
+                // The memory section comes before the code section, so this is known here already.
+                body.allow_memarg64(extensions.contains(&WasmExtension::Memory64));
+                for op in body.get_operators_reader()? {
+                    extensions.extend(required_extensions(&op?));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if table_count > 1 {
+        extensions.insert(WasmExtension::ReferenceTypes);
+    }
+    if memory_count > 1 {
+        extensions.insert(WasmExtension::MultiMemory);
+    }
+
+    Ok(extensions)
+}
+
 fn parse_memarg(memarg: wp::MemArg, parser_offset: usize) -> Result<Memarg, ParseError> {
     if memarg.memory != 0 {
         Err(ParseIssue::unsupported(parser_offset, WasmExtension::MultiMemory))?
     }
-    let offset: u32 = memarg
-        .offset
-        .try_into()
-        .map_err(|_| ParseIssue::unsupported(parser_offset, WasmExtension::Memory64))?;
+    // Offsets beyond 32 bits are only valid for 64-bit memories, which wasmparser checks already.
     Ok(Memarg {
         alignment_exp: memarg.align,
-        offset,
+        offset: memarg.offset,
     })
 }
 
-fn parse_memory_ty(ty: wp::MemoryType, offset: usize) -> Result<Limits, ParseError> {
-    if ty.memory64 {
-        Err(ParseIssue::unsupported(offset, WasmExtension::Memory64))?
-    }
+fn parse_memory_ty(ty: wp::MemoryType, offset: usize, metadata: &RwLock<ModuleMetadata>) -> Result<Memory, ParseError> {
+    let index_type = if ty.memory64 {
+        add_used_extension(metadata, WasmExtension::Memory64);
+        IndexType::I64
+    } else {
+        IndexType::I32
+    };
     if ty.shared {
-        Err(ParseIssue::unsupported(offset, WasmExtension::ThreadsAtomics))?
+        add_used_extension(metadata, WasmExtension::ThreadsAtomics);
     }
-    Ok(Limits {
-        initial_size: ty
-            .initial
-            .try_into()
-            .expect("guaranteed u32 by wasmparser if !memory64"),
-        max_size: ty
-            .maximum
-            .map(|u| u.try_into().expect("guaranteed u32 by wasmparser if !memory64")),
-    })
+    let limits = parse_limits(ty.initial, ty.maximum, offset)?;
+    // Pages are 64 KiB, so the whole address space is 2^16 pages for 32-bit memories and 2^48
+    // pages for 64-bit memories.
+    let max_pages = match index_type {
+        IndexType::I32 => 1 << 16,
+        IndexType::I64 => 1 << 48,
+    };
+    let pages = limits.max_size.unwrap_or(limits.initial_size);
+    if pages > max_pages {
+        Err(ParseIssue::memory_too_large(offset, pages, max_pages))?
+    }
+    let mut memory = Memory::new(limits);
+    memory.index_type = index_type;
+    memory.shared = ty.shared;
+    Ok(memory)
 }
 
-fn parse_table_ty(ty: wp::TableType, offset: usize) -> Result<Limits, ParseError> {
-    parse_elem_ty(ty.element_type, offset)?;
-    Ok(Limits {
-        initial_size: ty.initial,
-        max_size: ty.maximum,
-    })
+fn parse_table_ty(ty: wp::TableType, offset: usize) -> Result<(Limits, RefType), ParseError> {
+    let ref_type = parse_elem_ty(ty.element_type, offset)?;
+    let limits = parse_limits(ty.initial.into(), ty.maximum.map(u64::from), offset)?;
+    Ok((limits, ref_type))
 }
 
-fn parse_elem_ty(ty: wp::ValType, offset: usize) -> Result<(), ParseError> {
+fn parse_limits(initial_size: u64, max_size: Option<u64>, offset: usize) -> Result<Limits, ParseError> {
+    if let Some(max_size) = max_size {
+        if max_size < initial_size {
+            Err(ParseIssue::invalid_limits(offset, initial_size, max_size))?
+        }
+    }
+    Ok(Limits { initial_size, max_size })
+}
+
+fn parse_elem_ty(ty: wp::ValType, offset: usize) -> Result<RefType, ParseError> {
     use wp::ValType::*;
     match ty {
         I32 | I64 | F32 | F64 => Err(ParseIssue::message(offset, "only reftypes, not value types are allowed as table elements", None))?,
         V128 => Err(ParseIssue::message(offset, "only reftypes, not value types are allowed as table elements", None))?,
-        FuncRef => Ok(()),
-        ExternRef => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
+        FuncRef | ExternRef => parse_ref_ty(ty, offset),
+    }
+}
+
+fn parse_ref_ty(ty: wp::ValType, offset: usize) -> Result<RefType, ParseError> {
+    match ty {
+        wp::ValType::FuncRef => Ok(RefType::FuncRef),
+        wp::ValType::ExternRef => Ok(RefType::ExternRef),
+        _ => Err(ParseIssue::message(offset, "expected a reference type", None))?,
     }
 }
 
@@ -1138,16 +2029,12 @@ fn parse_block_ty(
     ty: wp::BlockType,
     offset: usize,
     types: &Types,
-    metadata: &RwLock<ModuleMetadata>,
 ) -> Result<FunctionType, ParseError> {
     use wp::BlockType::*;
     match ty {
         Empty => Ok(FunctionType::empty()),
         Type(ty) => Ok(FunctionType::new(&[], &[parse_val_ty(ty, offset)?])),
-        FuncType(type_idx) => {
-            metadata.write().unwrap().add_used_extension(WasmExtension::MultiValue);
-            types.get(type_idx, offset)
-        }
+        FuncType(type_idx) => types.get(type_idx, offset, "block type"),
     }
 }
 
@@ -1179,16 +2066,18 @@ fn parse_global_ty(ty: wp::GlobalType, offset: usize) -> Result<GlobalType, Pars
     ))
 }
 
-fn parse_val_ty(ty: wp::ValType, offset: usize) -> Result<ValType, ParseError> {
-    match ty {
-        wp::ValType::I32 => Ok(ValType::I32),
-        wp::ValType::I64 => Ok(ValType::I64),
-        wp::ValType::F32 => Ok(ValType::F32),
-        wp::ValType::F64 => Ok(ValType::F64),
-        wp::ValType::V128 => Err(ParseIssue::unsupported(offset, WasmExtension::Simd))?,
-        wp::ValType::FuncRef => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
-        wp::ValType::ExternRef => Err(ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes))?,
-    }
+// Returns a `Result` even though all value types of the current `wasmparser` version are supported,
+// such that future value types (e.g., from the GC proposal) can be rejected with a proper offset.
+fn parse_val_ty(ty: wp::ValType, _offset: usize) -> Result<ValType, ParseError> {
+    Ok(match ty {
+        wp::ValType::I32 => ValType::I32,
+        wp::ValType::I64 => ValType::I64,
+        wp::ValType::F32 => ValType::F32,
+        wp::ValType::F64 => ValType::F64,
+        wp::ValType::V128 => ValType::V128,
+        wp::ValType::FuncRef => ValType::FuncRef,
+        wp::ValType::ExternRef => ValType::ExternRef,
+    })
 }
 
 // The difference between `warnings` and returning a `Err(ParseIssue)` is that the latter will abort
@@ -1196,6 +2085,7 @@ fn parse_val_ty(ty: wp::ValType, offset: usize) -> Result<ValType, ParseError> {
 fn parse_name_custom_section(
     data: &[u8],
     data_offset: usize,
+    types: &Types,
     warnings: &mut Vec<ParseIssue>,
     module: &mut Module,
 ) -> Result<(), ParseIssue> {
@@ -1213,7 +2103,7 @@ fn parse_name_custom_section(
                     let (offset, wp::Naming { index: function_index, name }) = elem?;
                     module
                         .functions
-                        .get_mut(u32_to_usize(function_index))
+                        .get_mut(u32_to_usize(function_index, offset)?)
                         .ok_or_else(|| ParseIssue::index(offset, function_index, "function"))?
                         .name = Some(name.to_string());
                 }
@@ -1223,7 +2113,7 @@ fn parse_name_custom_section(
                     let (offset, wp::IndirectNaming { index: function_index, names }) = elem?;
                     let function = module
                         .functions
-                        .get_mut(u32_to_usize(function_index))
+                        .get_mut(u32_to_usize(function_index, offset)?)
                         .ok_or_else(|| ParseIssue::index(offset, function_index, "function"))?;
 
                     for elem in names.into_iter_with_offsets() {
@@ -1231,7 +2121,7 @@ fn parse_name_custom_section(
 
                         // TODO Refactor param_or_local_name to return a `Result`
                         // instead of checking the index beforehand.
-                        if local_index as usize >= (function.param_count() + function.local_count()) {
+                        if u32_to_usize(local_index, offset)? >= (function.param_count() + function.local_count()) {
                             warnings.push(ParseIssue::index(offset, local_index, "local"));
                         } else {
                             *function.param_or_local_name_mut(local_index.into()) =
@@ -1240,17 +2130,41 @@ fn parse_name_custom_section(
                     }
                 }
             }
-            Name::Label(name_map) => {
-                warnings.push(ParseIssue::unsupported(name_map.range().start, WasmExtension::ExtendedNameSection))
+            Name::Label(indirect_name_map) => {
+                for elem in indirect_name_map.into_iter_with_offsets() {
+                    let (offset, wp::IndirectNaming { index: function_index, names }) = elem?;
+                    let code = module
+                        .functions
+                        .get_mut(u32_to_usize(function_index, offset)?)
+                        .ok_or_else(|| ParseIssue::index(offset, function_index, "function"))?
+                        .code_mut();
+                    let Some(code) = code else {
+                        warnings.push(ParseIssue::message(offset, "name section: label names for imported function", None));
+                        continue;
+                    };
+
+                    for elem in names.into_iter_with_offsets() {
+                        let (_offset, wp::Naming { index: label_index, name }) = elem?;
+                        code.label_names.insert(label_index, name.to_string());
+                    }
+                }
             }
-            Name::Type(name_map)
-            | Name::Table(name_map)
-            | Name::Memory(name_map)
-            | Name::Global(name_map)
-            | Name::Element(name_map)
-            | Name::Data(name_map) => {
-                warnings.push(ParseIssue::unsupported(name_map.range().start, WasmExtension::ExtendedNameSection))
+            Name::Type(name_map) => {
+                for elem in name_map.into_iter_with_offsets() {
+                    let (offset, wp::Naming { index: type_index, name }) = elem?;
+                    match types.get(type_index, offset, "name section") {
+                        Ok(type_) => {
+                            module.type_names.insert(type_, name.to_string());
+                        }
+                        Err(_) => warnings.push(ParseIssue::index(offset, type_index, "type")),
+                    }
+                }
             }
+            Name::Table(name_map) => parse_name_map(name_map, &mut module.tables, "table", |table| &mut table.name)?,
+            Name::Memory(name_map) => parse_name_map(name_map, &mut module.memories, "memory", |memory| &mut memory.name)?,
+            Name::Global(name_map) => parse_name_map(name_map, &mut module.globals, "global", |global| &mut global.name)?,
+            Name::Element(name_map) => parse_name_map(name_map, &mut module.elements, "element", |element| &mut element.name)?,
+            Name::Data(name_map) => parse_name_map(name_map, &mut module.data, "data", |data| &mut data.name)?,
             | Name::Unknown {
                 ty: _,
                 data: _,
@@ -1262,6 +2176,24 @@ fn parse_name_custom_section(
     Ok(())
 }
 
+/// Assigns the names of a name subsection with a simple (non-indirect) name map, e.g., for
+/// tables or globals, to the corresponding AST elements.
+fn parse_name_map<T>(
+    name_map: wp::NameMap,
+    elements: &mut [T],
+    index_space: &'static str,
+    name_of: impl Fn(&mut T) -> &mut Option<String>,
+) -> Result<(), ParseIssue> {
+    for elem in name_map.into_iter_with_offsets() {
+        let (offset, wp::Naming { index, name }) = elem?;
+        let element = elements
+            .get_mut(u32_to_usize(index, offset)?)
+            .ok_or_else(|| ParseIssue::index(offset, index, index_space))?;
+        *name_of(element) = Some(name.to_string());
+    }
+    Ok(())
+}
+
 // Wrapper for type map, to offer some convenience like:
 // - u32 indices (which we get from wasmparser) instead of usize (which Vec expects)
 // - checking that type section exists only a single time and type index is valid
@@ -1277,11 +2209,11 @@ impl Types {
     pub fn new_type_section(
         &mut self,
         count: u32,
-        type_section_offset: usize,
+        type_section_range: std::ops::Range<usize>,
     ) -> Result<(), ParseError> {
-        let prev_state = self.0.replace(Vec::with_capacity(u32_to_usize(count)));
+        let prev_state = self.0.replace(Vec::with_capacity(bounded_capacity(count, &type_section_range)));
         match prev_state {
-            Some(_) => Err(ParseIssue::message(type_section_offset, "duplicate type section", None))?,
+            Some(_) => Err(ParseIssue::duplicate_section(type_section_range.start, SectionId::Type))?,
             None => Ok(()),
         }
     }
@@ -1293,18 +2225,82 @@ impl Types {
             .push(ty);
     }
 
-    pub fn get(&self, index: u32, index_offset: usize) -> Result<FunctionType, ParseError> {
-        Ok(self
+    /// Returns the type by value, which is cheap because `FunctionType` is a 4-byte `Copy` handle
+    /// (see `function_type.rs`), i.e., this never allocates, even for many `call_indirect`s.
+    /// `referenced_by` describes what needs the type (e.g., "function import") for the error
+    /// if there is no (preceding) type section at all.
+    pub fn get(&self, index: u32, index_offset: usize, referenced_by: &'static str) -> Result<FunctionType, ParseError> {
+        let types = self
             .0
             .as_deref()
-            // No type section == empty type vector.
-            .unwrap_or(&[])
-            .get(u32_to_usize(index))
-            .cloned()
+            .ok_or_else(|| ParseIssue::missing_type_section(index_offset, referenced_by))?;
+        Ok(types
+            .get(u32_to_usize(index, index_offset)?)
+            .copied()
             .ok_or_else(|| ParseIssue::index(index_offset, index, "type"))?)
     }
 }
 
-fn u32_to_usize(u: u32) -> usize {
-    u.try_into().expect("u32 to usize should always succeed")
+/// Records the offset of a non-custom section, or returns an error if the section appeared before,
+/// since the spec allows at most one of each (unlike custom sections). (Otherwise the second
+/// section would, e.g., append functions or overwrite the start function, which is not the module
+/// the producer intended.)
+/// If `strict_section_order`, also returns an error if the section comes after a section that
+/// should follow it, see https://webassembly.github.io/spec/core/binary/modules.html#binary-module
+fn add_section_offset(
+    section_offsets: &mut Vec<(SectionId, usize)>,
+    section: SectionId,
+    offset: usize,
+    strict_section_order: bool,
+) -> Result<(), ParseIssue> {
+    if section_offsets.iter().any(|(prev, _)| *prev == section) {
+        return Err(ParseIssue::duplicate_section(offset, section));
+    }
+    if strict_section_order {
+        // The order of `SectionId` follows the binary format, custom sections can appear anywhere.
+        let previous = section_offsets.iter()
+            .rev()
+            .map(|(prev, _)| prev)
+            .find(|prev| !matches!(prev, SectionId::Custom(_) | SectionId::Unknown(_)));
+        if let Some(previous) = previous {
+            if *previous > section {
+                return Err(ParseIssue::section_out_of_order(offset, section, previous.clone()));
+            }
+        }
+    }
+    section_offsets.push((section, offset));
+    Ok(())
+}
+
+/// Applies `f` to all `items`, in parallel unless disabled by `options.parallel`, and in
+/// `options.thread_pool` if given. The results are in the order of `items` either way.
+#[cfg(feature = "parallel")]
+fn map_maybe_parallel<T: Send, R: Send>(items: Vec<T>, options: &ParseOptions, f: impl Fn(T) -> R + Sync + Send) -> Vec<R> {
+    match (options.parallel, options.thread_pool) {
+        (false, _) => items.into_iter().map(f).collect(),
+        (true, None) => items.into_par_iter().map(f).collect(),
+        (true, Some(pool)) => pool.install(|| items.into_par_iter().map(f).collect()),
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_maybe_parallel<T, R>(items: Vec<T>, _options: &ParseOptions, f: impl Fn(T) -> R) -> Vec<R> {
+    items.into_iter().map(f).collect()
+}
+
+/// Maximum number of (non-parameter) locals per function, the same limit as in `wasmparser`'s
+/// validator and in engines, see https://webassembly.github.io/spec/js-api/#limits.
+const MAX_LOCALS: usize = 50_000;
+
+/// Capacity for pre-allocating `count` entries of a section (or similar), where `count` is read
+/// from the untrusted binary. Every entry takes at least one byte, so `count` can be bounded by the
+/// size of the section, such that a tiny (malicious) binary cannot make us allocate gigabytes.
+fn bounded_capacity(count: u32, range: &std::ops::Range<usize>) -> usize {
+    // A count that does not even fit into `usize` is certainly larger than the range.
+    usize::try_from(count).map_or(range.len(), |count| count.min(range.len()))
+}
+
+/// Always succeeds on 32- and 64-bit targets, but not on 16-bit targets.
+fn u32_to_usize(u: u32, offset: usize) -> Result<usize, ParseIssue> {
+    u.try_into().map_err(|_| ParseIssue::too_large(offset, u))
 }
@@ -0,0 +1,244 @@
+//! Simple statistics over the instructions of a module, e.g., for profiling real-world binaries.
+
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{Function, Instr, Module, ValType, WasmExtension};
+
+/// Overview of a module, e.g., for printing basic information about a binary, see `Module::summary`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ModuleSummary {
+    pub functions: EntityCounts,
+    pub globals: EntityCounts,
+    pub tables: EntityCounts,
+    pub memories: EntityCounts,
+    pub has_start: bool,
+    /// Over all function bodies, including the final `end` of each.
+    pub instr_count: usize,
+    /// See `ModuleMetadata::used_extensions`, i.e., empty if the module was not parsed.
+    pub extensions: BTreeSet<WasmExtension>,
+}
+
+/// How many functions, globals, tables, or memories a module imports, defines, and exports.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct EntityCounts {
+    pub imported: usize,
+    pub defined: usize,
+    /// Number of export names, so an entity that is exported under two names counts twice.
+    pub exported: usize,
+}
+
+impl EntityCounts {
+    fn count<'a>(entities: impl Iterator<Item = (bool, &'a [String])>) -> Self {
+        let mut counts = EntityCounts::default();
+        for (imported, export) in entities {
+            if imported {
+                counts.imported += 1;
+            } else {
+                counts.defined += 1;
+            }
+            counts.exported += export.len();
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.imported + self.defined
+    }
+}
+
+impl Module {
+    pub fn summary(&self) -> ModuleSummary {
+        ModuleSummary {
+            functions: EntityCounts::count(self.functions.iter().map(|f| (f.is_imported(), f.export.as_slice()))),
+            globals: EntityCounts::count(self.globals.iter().map(|g| (g.is_imported(), g.export.as_slice()))),
+            tables: EntityCounts::count(self.tables.iter().map(|t| (t.is_imported(), t.export.as_slice()))),
+            memories: EntityCounts::count(self.memories.iter().map(|m| (m.is_imported(), m.export.as_slice()))),
+            has_start: self.start.is_some(),
+            instr_count: self.functions.iter().map(|function| function.instr_count()).sum(),
+            extensions: self.metadata.used_extensions().collect(),
+        }
+    }
+}
+
+/// Coarse kind of an instruction, roughly following the instruction categories in the spec:
+/// https://webassembly.github.io/spec/core/syntax/instructions.html
+/// except that calls are separate from other control instructions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum InstrCategory {
+    /// Blocks, branches, `return`, `unreachable`, and `nop`.
+    Control,
+    /// `call` and `call_indirect`, including their tail call variants.
+    Call,
+    /// `drop` and `select`.
+    Parametric,
+    /// Accesses of locals and globals.
+    Variable,
+    /// Bulk table operations.
+    Table,
+    /// Loads and stores, including SIMD lane and atomic accesses, i.e., all instructions with a
+    /// `Memarg`, see `Instr::memory_access`.
+    MemoryAccess,
+    /// Other memory instructions: `memory.size`, `memory.grow`, bulk memory operations, and
+    /// `atomic.fence`.
+    Memory,
+    /// `*.const` instructions, including `v128.const`.
+    Const,
+    /// Scalar (non-SIMD) operations.
+    Numeric,
+    /// Reference types: `ref.null`, `ref.is_null`, and `ref.func`.
+    Reference,
+    /// SIMD operations (except for loads and stores, which are `MemoryAccess`).
+    Vector,
+    /// Placeholder for unsupported instructions, see `Instr::Unparsed` and `Instr::Unsupported`.
+    Unparsed,
+}
+
+impl Instr {
+    pub fn category(&self) -> InstrCategory {
+        use Instr::*;
+        match self {
+            Unreachable | Nop | Block(_) | Loop(_) | If(_) | Else | End | Br(_) | BrIf(_) | BrTable { .. } | Return => InstrCategory::Control,
+            Call(_) | CallIndirect(..) | ReturnCall(_) | ReturnCallIndirect(..) => InstrCategory::Call,
+            Drop | Select(_) => InstrCategory::Parametric,
+            Local(..) | Global(..) => InstrCategory::Variable,
+            TableInit(..) | ElemDrop(_) | TableCopy { .. } => InstrCategory::Table,
+            Load(..) | Store(..) | LoadLane(..) | StoreLane(..) | Atomic(..) => InstrCategory::MemoryAccess,
+            AtomicFence | MemorySize(_) | MemoryGrow(_) | MemoryInit(..) | DataDrop(_) | MemoryCopy { .. } | MemoryFill(_) => InstrCategory::Memory,
+            RefNull(_) | RefIsNull | RefFunc(_) => InstrCategory::Reference,
+            Unary(op) if op.to_type().inputs().contains(&ValType::V128) || op.to_type().results().contains(&ValType::V128) => InstrCategory::Vector,
+            Binary(op) if op.to_type().inputs().contains(&ValType::V128) => InstrCategory::Vector,
+            Const(_) => InstrCategory::Const,
+            Unary(_) | Binary(_) => InstrCategory::Numeric,
+            Lane(..) | I8x16Shuffle(_) | V128Bitselect => InstrCategory::Vector,
+            Unparsed(_) | Unsupported(..) => InstrCategory::Unparsed,
+        }
+    }
+}
+
+/// Counts how often each instruction (by its name, see `Instr::to_name()`) appears in all
+/// function bodies of the module. Functions are processed in parallel (with the `parallel` feature).
+pub fn opcode_histogram(module: &Module) -> HashMap<&'static str, u64> {
+    histogram(module, Instr::to_name)
+}
+
+/// Like `opcode_histogram`, but counts per instruction category instead.
+pub fn category_histogram(module: &Module) -> HashMap<InstrCategory, u64> {
+    histogram(module, Instr::category)
+}
+
+fn histogram<K: Eq + Hash + Send>(module: &Module, key: impl Fn(&Instr) -> K + Sync) -> HashMap<K, u64> {
+    let function_counts = |function: &Function| {
+        let mut counts = HashMap::new();
+        for instr in function.instrs() {
+            *counts.entry(key(instr)).or_insert(0) += 1;
+        }
+        counts
+    };
+    let merge = |mut counts: HashMap<K, u64>, other_counts: HashMap<K, u64>| {
+        for (key, count) in other_counts {
+            *counts.entry(key).or_insert(0) += count;
+        }
+        counts
+    };
+
+    #[cfg(feature = "parallel")]
+    let counts = module.functions.par_iter().map(function_counts).reduce(HashMap::new, merge);
+    #[cfg(not(feature = "parallel"))]
+    let counts = module.functions.iter().map(function_counts).fold(HashMap::new(), merge);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BinaryOp;
+    use crate::FunctionType;
+    use crate::Idx;
+    use crate::LocalOp;
+    use crate::Val;
+
+    use super::*;
+
+    #[test]
+    fn counts_across_all_functions() {
+        let mut module = Module::new();
+        module.add_function(FunctionType::new(&[ValType::I32], &[ValType::I32]), vec![], vec![
+            Instr::Local(LocalOp::Get, Idx::from(0u32)),
+            Instr::Const(Val::I32(1)),
+            Instr::Binary(BinaryOp::I32Add),
+            Instr::End,
+        ]);
+        module.add_function(FunctionType::new(&[], &[ValType::I32]), vec![], vec![
+            Instr::Const(Val::I32(2)),
+            Instr::Call(Idx::from(0u32)),
+            Instr::End,
+        ]);
+
+        let opcodes = opcode_histogram(&module);
+        assert_eq!(opcodes.len(), 5);
+        assert_eq!(opcodes["i32.const"], 2);
+        assert_eq!(opcodes["end"], 2);
+        assert_eq!(opcodes["local.get"], 1);
+        assert_eq!(opcodes.values().sum::<u64>(), 7);
+
+        let categories = category_histogram(&module);
+        assert_eq!(categories[&InstrCategory::Const], 2);
+        assert_eq!(categories[&InstrCategory::Numeric], 1);
+        assert_eq!(categories[&InstrCategory::Control], 2);
+        assert_eq!(categories[&InstrCategory::Variable], 1);
+        assert_eq!(categories[&InstrCategory::Call], 1);
+    }
+
+    #[cfg(feature = "wat")]
+    #[test]
+    fn summary_counts_imports_definitions_and_exports() {
+        let module = Module::from_wat(r#"(module
+            (import "env" "f" (func))
+            (import "env" "g" (global i32))
+            (memory (export "mem") (export "memory") 1)
+            (global (mut i64) (i64.const 0))
+            (func $start
+                call 0)
+            (func (export "run") (result i32)
+                i32.const 0
+                i32.const 1
+                i32.add
+                i32.extend8_s)
+            (start $start))"#).unwrap();
+
+        let summary = module.summary();
+        assert_eq!(summary.functions, EntityCounts { imported: 1, defined: 2, exported: 1 });
+        assert_eq!(summary.functions.total(), 3);
+        assert_eq!(summary.globals, EntityCounts { imported: 1, defined: 1, exported: 0 });
+        assert_eq!(summary.tables, EntityCounts::default());
+        assert_eq!(summary.memories, EntityCounts { imported: 0, defined: 1, exported: 2 });
+        assert!(summary.has_start);
+        assert_eq!(summary.instr_count, 7);
+        assert_eq!(summary.extensions, BTreeSet::from([WasmExtension::SignExtensionOps]));
+    }
+
+    #[test]
+    fn simd_instructions_are_vector_category() {
+        assert_eq!(Instr::Const(Val::V128([0; 16])).category(), InstrCategory::Const);
+        assert_eq!(Instr::Binary(BinaryOp::I32x4Add).category(), InstrCategory::Vector);
+        assert_eq!(Instr::Binary(BinaryOp::I32Add).category(), InstrCategory::Numeric);
+    }
+
+    #[test]
+    fn memory_accesses_are_separate_from_other_memory_instructions() {
+        use std::str::FromStr;
+        for (instr, category) in [
+            ("i64.load8_u", InstrCategory::MemoryAccess),
+            ("memory.atomic.notify", InstrCategory::MemoryAccess),
+            ("memory.grow", InstrCategory::Memory),
+            ("atomic.fence", InstrCategory::Memory),
+        ] {
+            let instr = Instr::from_str(instr).unwrap();
+            assert_eq!(instr.category(), category);
+            assert_eq!(instr.is_memory_access(), category == InstrCategory::MemoryAccess);
+        }
+    }
+}
@@ -1,28 +1,54 @@
+#[cfg(feature = "wat")]
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt::Write;
 use std::fs;
+use std::str::FromStr;
 
 use dashmap::DashMap;
 
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
 use test_utilities::*;
 
 use crate::types::TypeChecker;
+use crate::validate::validate;
 use crate::*;
 
 const NAME_SECTION_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/name-section/wabt-tests/names.wasm";
 const BANANABREAD_REAL_WORLD_TEST_BINARY: &str = "../../test-inputs/real-world-binaries/bananabread/bb.wasm";
+const SIMD_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/simd/build/simd.wat.wasm";
+const REFERENCE_TYPES_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/reference-types/build/reference-types.wat.wasm";
+const ELEMENT_SEGMENTS_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/element-segments/build/element-segments.wat.wasm";
+const BULK_MEMORY_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/bulk-memory/build/bulk-memory.wat.wasm";
+const SIGN_EXTENSION_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/sign-extension/build/sign-extension.wat.wasm";
+const NONTRAPPING_FLOAT_TO_INT_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/nontrapping-float-to-int/build/nontrapping-float-to-int.wat.wasm";
+const MULTI_VALUE_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/multi-value/build/multi-value.wat.wasm";
+const EXTENDED_NAME_SECTION_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/extended-name-section/build/names.wat.wasm";
 
-// Removed this test, because when changing to wasmparser,
-// we did not port over the low-level parsing of the extended name section.
-// const WASM_TEST_INPUT_EXTENDED_NAMES_SECTION: &str = "../../test-inputs/wasm-feature-tests/name-section/extended-name-section/vuln.wasm";
+const EMSCRIPTEN_EXTENDED_NAME_SECTION_TEST_BINARY: &str = "../../test-inputs/wasm-feature-tests/name-section/extended-name-section/vuln.wasm";
+
+/// Encodes and decodes `module` again, which should give the same module.
+fn assert_roundtrips(module: &Module) {
+    let module_roundtrip = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(module, &module_roundtrip);
+}
+
+/// Number of entries in the type section of the binary, or `None` if there is no type section.
+fn type_count(bytes: &[u8]) -> Option<u32> {
+    wasmparser::Parser::new(0).parse_all(bytes)
+        .find_map(|payload| match payload.unwrap() {
+            wasmparser::Payload::TypeSection(reader) => Some(reader.count()),
+            _ => None,
+        })
+}
 
 #[test]
 fn collect_all_function_types_in_test_set() {
     let type_count = DashMap::new();
     for_each_valid_wasm_binary_in_test_set(|path| {
-        let (module, _, _) = Module::from_file(path)
+        let module = Module::from_file(path)
             .unwrap_or_else(|err| panic!("Could not parse valid binary '{}': {err}", path.display()));
 
         for func in module.functions {
@@ -47,8 +73,11 @@ fn collect_all_function_types_in_test_set() {
     fs::write("../../test-outputs/collect-types/function_type_count.csv", output_contents).unwrap();
 
     let val_type_seq_count = DashMap::new();
-    type_count
-        .par_iter()
+    #[cfg(feature = "parallel")]
+    let type_count_iter = type_count.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let type_count_iter = type_count.iter();
+    type_count_iter
         .for_each(|(func_ty, count)| {
             *val_type_seq_count.entry(func_ty.inputs()).or_insert(0) += count;
             *val_type_seq_count.entry(func_ty.results()).or_insert(0) += count;
@@ -67,7 +96,7 @@ fn collect_all_function_types_in_test_set() {
 #[test]
 fn roundtrip_produces_same_module_ast() {
     for_each_valid_wasm_binary_in_test_set(|path| {
-        let (module, _offsets, warnings) = Module::from_file(path)
+        let (module, _offsets, warnings) = Module::from_file_with_offsets(path)
             .unwrap_or_else(|err| panic!("Could not parse valid binary '{}': {err}", path.display()));
         if !warnings.is_empty() {
             eprintln!("Warnings parsing '{}': {:#?}", path.display(), warnings);
@@ -76,7 +105,7 @@ fn roundtrip_produces_same_module_ast() {
         let bytes = module.to_bytes()
             .unwrap_or_else(|err| panic!("Could not encode valid binary '{}': {err}", path.display()));
 
-        let (module_roundtrip, _, _) = Module::from_bytes(&bytes).unwrap();
+        let module_roundtrip = Module::from_bytes(&bytes).unwrap();
         assert_eq!(module, module_roundtrip, "Roundtrip failed for binary '{}'", path.display());
     });
 }
@@ -84,7 +113,7 @@ fn roundtrip_produces_same_module_ast() {
 #[test]
 fn type_checking_valid_files() {
     for_each_valid_wasm_binary_in_test_set(|path| {
-        let (module, _, _) = Module::from_file(path)
+        let module = Module::from_file(path)
             .unwrap_or_else(|err| panic!("Could not parse valid binary '{}': {err}", path.display()));
         
         TypeChecker::check_module(&module)
@@ -92,10 +121,21 @@ fn type_checking_valid_files() {
     });
 }
 
+#[test]
+fn validating_valid_files() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let module = Module::from_file(path)
+            .unwrap_or_else(|err| panic!("Could not parse valid binary '{}': {err}", path.display()));
+
+        validate(&module)
+            .unwrap_or_else(|err| panic!("Valid binary '{}' should validate, but did not: {err}", path.display()));
+    });
+}
+
 #[test]
 fn decode_encode_is_valid_wasm() {
     for_each_valid_wasm_binary_in_test_set(|path| {
-        let (module, _, _) = Module::from_file(path)
+        let module = Module::from_file(path)
             .unwrap_or_else(|err| panic!("Could not parse valid binary '{}': {err}", path.display()));
 
         let output_path = &output_file(path, "encode").unwrap();
@@ -107,28 +147,1726 @@ fn decode_encode_is_valid_wasm() {
     });
 }
 
+#[test]
+fn simd_instructions_are_parsed_and_roundtrip() {
+    let module = Module::from_file(SIMD_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::Simd]);
+
+    let instrs = module.functions[0].instrs().iter().map(|instr| instr.to_string()).collect::<Vec<_>>();
+    assert_eq!(instrs, vec![
+        "local.get 0",
+        "local.get 0",
+        "v128.load",
+        "v128.const i8x16 1 0 0 0 2 0 0 0 3 0 0 0 4 0 0 0",
+        "i32x4.add",
+        "local.get 0",
+        "i32.const 42",
+        "i32x4.splat",
+        "v128.load8_lane 3",
+        "i8x16.shuffle 0 1 2 3 4 5 6 7 16 17 18 19 20 21 22 23",
+        "v128.store offset=16",
+        "local.get 0",
+        "v128.load offset=16",
+        "v128.const i8x16 0 0 0 0 0 0 0 0 255 255 255 255 255 255 255 255",
+        "v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15",
+        "i32.const 1",
+        "i8x16.shl",
+        "v128.bitselect",
+        "i32x4.extract_lane 2",
+        "end",
+    ]);
+
+    // Instructions with arguments can also be parsed back from their text representation.
+    for instr in module.functions[0].instrs() {
+        if instr.to_string().contains(' ') {
+            assert_eq!(&Instr::from_str(&instr.to_string()).unwrap(), instr);
+        }
+    }
+
+    assert_roundtrips(&module);
+    TypeChecker::check_module(&module).unwrap();
+}
+
+#[test]
+fn reference_types_are_parsed_and_roundtrip() {
+    let module = Module::from_file(REFERENCE_TYPES_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::ReferenceTypes]);
+
+    assert_eq!(module.globals[0].type_, GlobalType(ValType::ExternRef, Mutability::Const));
+    assert_eq!(module.globals[0].import(), Some(("env", "ref")));
+    assert_eq!(module.globals[1].init(), Some(&vec![Instr::RefFunc(0u32.into()), Instr::End]));
+    assert_eq!(module.tables[0].ref_type, RefType::ExternRef);
+
+    let instrs = module.functions().flat_map(|(_, function)| function.instrs()).map(|instr| instr.to_string()).collect::<Vec<_>>();
+    assert_eq!(instrs, vec![
+        "global.get 0",
+        "ref.is_null",
+        "end",
+        "ref.null funcref",
+        "global.get 1",
+        "local.get 0",
+        "select funcref",
+        "end",
+    ]);
+    for instr in ["ref.null funcref", "ref.func 0", "select funcref"] {
+        assert_eq!(Instr::from_str(instr).unwrap().to_string(), instr);
+    }
+
+    assert_roundtrips(&module);
+    TypeChecker::check_module(&module).unwrap();
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn typed_select_with_numeric_type_is_parsed_and_roundtrips() {
+    let module = Module::from_wat(r#"(module
+        (func (param f64 f64 i32) (result f64)
+            local.get 0
+            local.get 1
+            local.get 2
+            select (result f64))
+    )"#).unwrap();
+    assert_eq!(module.functions[0].instrs()[3], Instr::Select(Some(ValType::F64)));
+    assert_eq!(Instr::Select(Some(ValType::F64)).to_string(), "select f64");
+    TypeChecker::check_module(&module).unwrap();
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn passive_and_declared_element_segments_are_parsed_and_roundtrip() {
+    let module = Module::from_file(ELEMENT_SEGMENTS_TEST_BINARY).unwrap();
+    let used_extensions = module.metadata.used_extensions().collect::<Vec<_>>();
+    assert!(used_extensions.contains(&WasmExtension::BulkMemoryOperations));
+    assert!(used_extensions.contains(&WasmExtension::ReferenceTypes));
+
+    assert_eq!(module.elements.len(), 4);
+    assert_eq!(module.elements[0].mode, ElementMode::Active { table: 0u32.into(), offset: vec![Instr::Const(Val::I32(0)), Instr::End] });
+    assert_eq!(module.elements[1], Element {
+        mode: ElementMode::Passive,
+        ref_type: RefType::FuncRef,
+        items: ElementItems::Functions(vec![0u32.into(), 1u32.into()]),
+        name: Some("passive".to_string()),
+    });
+    assert_eq!(module.elements[2].mode, ElementMode::Declared);
+    assert_eq!(module.elements[2].items, ElementItems::Functions(vec![2u32.into()]));
+    assert_eq!(module.elements[3], Element {
+        mode: ElementMode::Active { table: 1u32.into(), offset: vec![Instr::Const(Val::I32(0)), Instr::End] },
+        ref_type: RefType::ExternRef,
+        items: ElementItems::Expressions(vec![vec![Instr::RefNull(RefType::ExternRef), Instr::End]]),
+        name: None,
+    });
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn bulk_memory_instructions_are_parsed_and_roundtrip() {
+    let module = Module::from_file(BULK_MEMORY_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::BulkMemoryOperations]);
+    assert!(module.data_count_section);
+    assert_eq!(module.data[1], Data { mode: DataMode::Passive, bytes: b"passive".to_vec(), name: Some("passive".to_string()) });
+
+    let instrs = module.functions[0].instrs();
+    assert!(instrs.contains(&Instr::MemoryCopy { dst: 0u32.into(), src: 0u32.into() }));
+    assert!(instrs.contains(&Instr::MemoryInit(1u32.into(), 0u32.into())));
+    assert!(instrs.contains(&Instr::DataDrop(1u32.into())));
+    assert!(instrs.contains(&Instr::TableInit(0u32.into(), 0u32.into())));
+    for instr in ["memory.init 1", "data.drop 1", "table.init 0", "elem.drop 0"] {
+        assert_eq!(Instr::from_str(instr).unwrap().to_string(), instr);
+    }
+
+    assert_roundtrips(&module);
+    TypeChecker::check_module(&module).unwrap();
+}
+
+#[test]
+fn sign_extension_ops_are_parsed_and_roundtrip() {
+    let module = Module::from_file(SIGN_EXTENSION_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::SignExtensionOps]);
+
+    let instrs = module.functions().flat_map(|(_, function)| function.instrs()).map(|instr| instr.to_string()).collect::<Vec<_>>();
+    assert_eq!(instrs, vec![
+        "local.get 0",
+        "i32.extend8_s",
+        "i32.extend16_s",
+        "end",
+        "local.get 0",
+        "i64.extend8_s",
+        "i64.extend16_s",
+        "i64.extend32_s",
+        "end",
+    ]);
+    for instr in ["i32.extend8_s", "i64.extend32_s"] {
+        assert_eq!(Instr::from_str(instr).unwrap().to_string(), instr);
+    }
+    TypeChecker::check_module(&module).unwrap();
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn nontrapping_float_to_int_conversions_are_parsed_and_roundtrip() {
+    let module = Module::from_file(NONTRAPPING_FLOAT_TO_INT_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::NontrappingFloatToInt]);
+
+    let instrs = module.functions().flat_map(|(_, function)| function.instrs()).map(|instr| instr.to_string()).collect::<Vec<_>>();
+    assert_eq!(instrs, vec![
+        "local.get 0",
+        "i32.trunc_sat_f64_s",
+        "end",
+        "local.get 0",
+        "i64.trunc_sat_f32_u",
+        "end",
+    ]);
+    // Same stack effect as the trapping versions.
+    assert_eq!(UnaryOp::I32TruncSatF64S.to_type(), UnaryOp::I32TruncF64S.to_type());
+    TypeChecker::check_module(&module).unwrap();
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn multi_value_block_types_are_parsed_and_roundtrip() {
+    use ValType::*;
+
+    let module = Module::from_file(MULTI_VALUE_TEST_BINARY).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::MultiValue]);
+
+    let block_types = module
+        .functions()
+        .flat_map(|(_, function)| function.instrs())
+        .filter_map(|instr| match instr {
+            Instr::Block(ty) | Instr::Loop(ty) | Instr::If(ty) => Some(*ty),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(block_types, vec![
+        FunctionType::new(&[I32], &[I32, I32]),
+        FunctionType::new(&[I32], &[I32]),
+        FunctionType::new(&[I64, I64], &[I64, I64]),
+    ]);
+    TypeChecker::check_module(&module).unwrap();
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn const_expr_with_multiple_instructions_roundtrips() {
+    // E.g., as allowed by the extended constant expressions proposal.
+    let mut module = Module::new();
+    module.add_global(ValType::I32, Mutability::Const, vec![
+        Instr::Const(Val::I32(1)),
+        Instr::Const(Val::I32(2)),
+        Instr::Binary(BinaryOp::I32Add),
+        Instr::End,
+    ]);
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn duplicate_start_section_is_typed_error() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: [] -> []
+        0x03, 0x02, 0x01, 0x00, // function section: one function of type 0
+        0x08, 0x01, 0x00, // start section: function 0
+        0x08, 0x01, 0x00, // (duplicate) start section: function 0
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::DuplicateSection { section: SectionId::Start, .. }), "{err}");
+    // Offset of the section contents, i.e., after the section id and size.
+    assert_eq!(err.offset(), Some(23));
+}
+
+#[test]
+fn duplicate_export_section_is_typed_error() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x05, 0x03, 0x01, 0x00, 0x01, // memory section: one memory with min size 1
+        0x07, 0x05, 0x01, 0x01, b'a', 0x02, 0x00, // export section: memory 0 as "a"
+        0x07, 0x05, 0x01, 0x01, b'b', 0x02, 0x00, // (duplicate) export section: memory 0 as "b"
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::DuplicateSection { section: SectionId::Export, .. }), "{err}");
+    assert_eq!(err.offset(), Some(22));
+}
+
+#[test]
+fn function_import_without_type_section_is_typed_error() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x02, 0x07, 0x01, 0x01, b'm', 0x01, b'f', 0x00, 0x00, // import section: function "m" "f" of type 0
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::MissingTypeSection { referenced_by: "function import", .. }), "{err}");
+    assert_eq!(err.offset(), Some(11));
+}
+
+#[test]
+fn section_order_is_only_checked_if_strict() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x00, 0x02, 0x01, b'c', // custom section "c" (allowed anywhere)
+        0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section: immutable i32 global
+        0x00, 0x02, 0x01, b'c', // custom section "c" (allowed anywhere)
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section (must come before the global section)
+    ];
+    let strict = ParseOptions { strict_section_order: true, ..ParseOptions::default() };
+    let (module, _, _) = Module::from_bytes_with_options(&bytes, ParseOptions::default()).unwrap();
+    assert_eq!(module.globals.len(), 1);
+    assert_eq!(module.custom_sections.len(), 2);
+
+    let err = Module::from_bytes_with_options(&bytes, strict).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::SectionOutOfOrder { section: SectionId::Type, previous: SectionId::Global, .. }), "{err}");
+    assert_eq!(err.offset(), Some(26));
+
+    // Correctly ordered modules are accepted either way.
+    let bytes = fs::read(BULK_MEMORY_TEST_BINARY).unwrap();
+    assert_eq!(Module::from_bytes_with_options(&bytes, strict).unwrap().0, Module::from_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn extended_name_section_is_parsed_and_roundtrips() {
+    let (module, _, warnings) = Module::from_file_with_offsets(EXTENDED_NAME_SECTION_TEST_BINARY).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+    // All names were parsed into the AST, so no raw name section should remain.
+    assert!(module.custom_sections.is_empty());
+
+    assert_eq!(module.name.as_deref(), Some("names"));
+    assert_eq!(module.type_names.get(&FunctionType::new(&[], &[])).map(String::as_str), Some("void"));
+    assert_eq!(module.tables[0].name.as_deref(), Some("imported_table"));
+    assert_eq!(module.memories[0].name.as_deref(), Some("mem"));
+    assert_eq!(module.globals[0].name.as_deref(), Some("counter"));
+    assert_eq!(module.elements[0].name.as_deref(), Some("elements"));
+    assert_eq!(module.data[0].name.as_deref(), Some("bytes"));
+
+    let function = &module.functions[0];
+    assert_eq!(function.name.as_deref(), Some("f"));
+    assert_eq!(function.param_or_local_name(0u32.into()), Some("l"));
+    let label_names = &function.code().unwrap().label_names;
+    assert_eq!(label_names.get(&0).map(String::as_str), Some("outer"));
+    assert_eq!(label_names.get(&1).map(String::as_str), Some("inner"));
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn emscripten_extended_name_section_is_parsed() {
+    // Global and data segment names (in subsections of the extended name section), next to DWARF.
+    let (module, _, warnings) = Module::from_file_with_offsets(EMSCRIPTEN_EXTENDED_NAME_SECTION_TEST_BINARY).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert_eq!(module.globals[0].name.as_deref(), Some("__stack_pointer"));
+    assert_eq!(module.data[0].name.as_deref(), Some(".rodata"));
+    assert_eq!(module.functions[0].name.as_deref(), Some("__wasi_fd_seek"));
+    assert!(module.has_debug_info());
+    assert_eq!(module.debug_sections().count(), module.custom_sections.len());
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn malformed_name_section_is_kept_as_raw_custom_section() {
+    let name_section_content = [
+        0x04, b'n', b'a', b'm', b'e', // custom section name
+        0x00, 0x02, 0x01, b'm', // module name subsection: "m"
+        0x01, 0x04, 0x01, 0x00, 0x03, b'f', // function names subsection: name of function 0 is truncated
+    ];
+    let mut bytes = vec![
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: [] -> []
+        0x03, 0x02, 0x01, 0x00, // function section: one function of type 0
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section: empty body
+        0x00, name_section_content.len() as u8, // custom section header
+    ];
+    bytes.extend_from_slice(&name_section_content);
+
+    let (module, _, warnings) = Module::from_bytes_with_offsets(&bytes).unwrap();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    // The module name was parsed before the error, but should not be duplicated in the AST.
+    assert_eq!(module.name, None);
+    assert_eq!(module.custom_sections.len(), 1);
+    assert_eq!(module.custom_sections[0].name, "name");
+    assert_eq!(module.custom_sections[0].content, &name_section_content[5..]);
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn value_types_where_reference_types_are_expected_are_errors() {
+    // Try every possible byte as the element type of a table, none of them should panic.
+    for type_byte in 0x00..=0xff {
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+            0x04, 0x04, 0x01, type_byte, 0x00, 0x01, // table section: one table with min size 1
+        ];
+        let result = Module::from_bytes(&bytes);
+        match type_byte {
+            0x70 | 0x6f => assert!(result.is_ok(), "{type_byte:#x}: {result:?}"),
+            _ => assert!(result.is_err(), "{type_byte:#x}: {result:?}"),
+        }
+    }
+
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x06, 0x06, 0x01, 0x70, 0x00, // global section: one immutable funcref global
+        0xd0, 0x7f, 0x0b, // init expression: ref.null i32, end
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::Message { .. }), "{err}");
+    // Offset of the type immediate of `ref.null`.
+    assert_eq!(err.offset(), Some(14));
+}
+
+#[test]
+fn huge_counts_in_tiny_binaries_do_not_allocate() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: [] -> []
+        0x03, 0x05, 0xff, 0xff, 0xff, 0xff, 0x0f, // function section: claims u32::MAX functions
+    ];
+    assert!(Module::from_bytes(&bytes).is_err());
+
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: [] -> []
+        0x03, 0x02, 0x01, 0x00, // function section: one function of type 0
+        0x0a, 0x0a, 0x01, 0x08, // code section: one body of 8 bytes
+        0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, // u32::MAX i32 locals
+        0x0b, // end
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(err.to_string().contains("too many locals"), "{err}");
+}
+
+#[test]
+fn instruction_errors_have_operator_offset() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x06, 0x09, 0x01, 0x7f, 0x00, // global section: one immutable i32 global
+        0x41, 0x00, 0xfc, 0x10, 0x00, 0x0b, // init expression: i32.const 0, table.size 0, end
+    ];
+    let err = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::Unsupported { extension: WasmExtension::ReferenceTypes, .. }), "{err}");
+    // Offset of the offending `table.size`, not of the whole global entry.
+    assert_eq!(err.offset(), Some(15));
+    assert!(err.to_string().contains("at offset 0xf"), "{err}");
+}
+
+#[test]
+fn unsupported_function_bodies_can_be_skipped_or_kept_as_placeholder() {
+    let wasm = ::wat::parse_str(r#"(module
+        (func (result i32)
+            i32.const 1)
+        (func (param v128 v128 v128) (result v128)
+            local.get 0
+            local.get 1
+            local.get 2
+            f32x4.relaxed_madd)
+        (func (local i32)
+            nop)
+    )"#).unwrap();
+    let err = Module::from_bytes(&wasm).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::Unsupported { extension: WasmExtension::RelaxedSimd, .. }), "{err}");
+
+    let options = |on_unsupported| ParseOptions { on_unsupported, ..ParseOptions::default() };
+    let (module, _, warnings) = Module::from_bytes_with_options(&wasm, options(OnUnsupported::Placeholder)).unwrap();
+    assert!(matches!(warnings.as_slice(), [ParseIssue::Unsupported { extension: WasmExtension::RelaxedSimd, .. }]), "{warnings:?}");
+    assert_eq!(module.functions[0].instrs(), [Instr::Const(Val::I32(1)), Instr::End]);
+    assert!(matches!(module.functions[1].instrs(), [Instr::Unparsed(bytes), Instr::End] if bytes.len() == 9), "{:?}", module.functions[1].instrs());
+    assert_eq!(module.functions[2].instrs(), [Instr::Nop, Instr::End]);
+    assert_eq!(module.functions[2].code().unwrap().locals.len(), 1);
+    assert_eq!(module.to_bytes().unwrap(), wasm);
+    assert_eq!(validate(&module), Ok(()));
+
+    let (module, _, warnings) = Module::from_bytes_with_options(&wasm, options(OnUnsupported::SkipBody)).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(module.functions[1].instrs(), [Instr::Unreachable, Instr::End]);
+    assert_eq!(validate(&module), Ok(()));
+}
+
+#[test]
+fn unsupported_instructions_can_be_kept_as_placeholders() {
+    let wasm = ::wat::parse_str(r#"(module
+        (func (result i32)
+            try
+                nop
+            catch_all
+                nop
+            end
+            i32.const 1)
+        (func (result i32)
+            i32.const 2)
+    )"#).unwrap();
+    let err = Module::from_bytes(&wasm).unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::Unsupported { extension: WasmExtension::ExceptionHandling, .. }), "{err}");
+
+    let options = ParseOptions { on_unsupported: OnUnsupported::PlaceholderInstrs, ..ParseOptions::default() };
+    let (module, _, warnings) = Module::from_bytes_with_options(&wasm, options).unwrap();
+    assert!(matches!(warnings.as_slice(), [ParseIssue::Unsupported { extension: WasmExtension::ExceptionHandling, .. }]), "{warnings:?}");
+    use WasmExtension::ExceptionHandling;
+    assert_eq!(module.functions[0].instrs(), [
+        // `try` with empty block type, `catch_all`.
+        Instr::Unsupported(ExceptionHandling, vec![0x06, 0x40]),
+        Instr::Nop,
+        Instr::Unsupported(ExceptionHandling, vec![0x19]),
+        Instr::Nop,
+        Instr::End,
+        Instr::Const(Val::I32(1)),
+        Instr::End,
+    ]);
+    assert_eq!(module.functions[1].instrs(), [Instr::Const(Val::I32(2)), Instr::End]);
+    assert_eq!(module.functions[0].instrs().iter().filter(|instr| instr.category() == crate::stats::InstrCategory::Unparsed).count(), 2);
+    assert_eq!(module.to_bytes().unwrap(), wasm);
+
+    // The function with placeholders cannot be analyzed, but the others can.
+    assert!(TypeChecker::check_function(&module.functions[0], &module).is_err());
+    assert!(TypeChecker::check_function(&module.functions[1], &module).is_ok());
+}
+
+#[test]
+fn extension_ids_roundtrip() {
+    use WasmExtension::*;
+    let all = [
+        NontrappingFloatToInt, SignExtensionOps, MultiValue, ReferenceTypes, BulkMemoryOperations, Simd,
+        ThreadsAtomics, RelaxedSimd, Memory64, ExceptionHandling, ExtendedNameSection, TailCalls, TypeImports, MultiMemory, ComponentModel,
+    ];
+    for extension in all {
+        assert_eq!(WasmExtension::from_id(extension.id()), Some(extension));
+        assert_eq!(extension.id().parse(), Ok(extension));
+        assert!(extension.url().contains(extension.id()), "{extension:?}");
+    }
+    assert_eq!(BulkMemoryOperations.id(), "bulk-memory-operations");
+    assert_eq!(WasmExtension::from_id("bulk memory operations"), None);
+    assert_eq!("".parse::<WasmExtension>(), Err(()));
+}
+
+#[test]
+fn detect_extensions_agrees_with_parsing() {
+    for path in [SIMD_TEST_BINARY, REFERENCE_TYPES_TEST_BINARY, BULK_MEMORY_TEST_BINARY, SIGN_EXTENSION_TEST_BINARY, NONTRAPPING_FLOAT_TO_INT_TEST_BINARY, MULTI_VALUE_TEST_BINARY] {
+        let bytes = fs::read(path).unwrap();
+        let module = Module::from_bytes(&bytes).unwrap();
+        let detected = detect_extensions(&bytes).unwrap();
+        assert_eq!(detected, module.metadata.used_extensions().collect(), "{path}");
+    }
+}
+
+#[test]
+fn detect_extensions_does_not_stop_at_unsupported_ones() {
+    let bytes = ::wat::parse_str(r#"
+        (module
+            (memory 1 1 shared)
+            (table 1 funcref)
+            (func $f (result i32)
+                atomic.fence
+                i32.const 0
+                i32.extend8_s
+                table.size 0
+                i32.add
+                return_call $f))"#).unwrap();
+    assert!(Module::from_bytes(&bytes).is_err());
+    assert_eq!(detect_extensions(&bytes).unwrap(), [
+        WasmExtension::SignExtensionOps,
+        WasmExtension::ThreadsAtomics,
+        WasmExtension::ReferenceTypes,
+        WasmExtension::TailCalls,
+    ].into_iter().collect());
+
+    assert!(detect_extensions(&[0x00, 0x61, 0x73]).is_err());
+}
+
+#[test]
+fn many_segments_keep_their_order() {
+    let segments = (0..1000).map(|i| format!("(data (i32.const {i}) \"{i}\") (elem (i32.const {i}) func {})", i % 2)).collect::<String>();
+    let bytes = ::wat::parse_str(format!("(module (memory 1) (table 1000 funcref) (func) (func) {segments})")).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert_eq!(module.data.len(), 1000);
+    assert_eq!(module.elements.len(), 1000);
+    for (i, (data, element)) in module.data.iter().zip(&module.elements).enumerate() {
+        assert_eq!(data.bytes, i.to_string().as_bytes());
+        assert_eq!(element.items, ElementItems::Functions(vec![(i % 2).into()]));
+    }
+    assert_roundtrips(&module);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn memory64_is_parsed_and_roundtrips() {
+    let bytes = ::wat::parse_str(r#"
+        (module
+            (memory i64 1 65536)
+            (func (param i64) (result i32)
+                local.get 0
+                i32.load offset=0x1_0000_0000
+                memory.size
+                memory.grow
+                i32.wrap_i64
+                i32.add))"#).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert_eq!(module.memories[0].index_type, IndexType::I64);
+    assert_eq!(module.memories[0].limits, Limits { initial_size: 1, max_size: Some(65536) });
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::Memory64]);
+    let code = module.functions[0].code().unwrap();
+    assert!(matches!(code.body[1], Instr::Load(LoadOp::I32Load, Memarg { offset: 0x1_0000_0000, .. })));
+    assert_eq!(code.body[2].memory_type(&module), Some(FunctionType::new(&[], &[ValType::I64])));
+    assert_eq!(validate(&module), Ok(()));
+
+    assert_roundtrips(&module);
+    // The text format adds symbolic names, so only compare the relevant parts.
+    let module_from_wat = Module::from_wat(&wat::module_to_wat(&module)).unwrap();
+    assert_eq!(module_from_wat.memories[0].index_type, IndexType::I64);
+    assert_eq!(module_from_wat.functions[0].code(), module.functions[0].code());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn atomics_are_parsed_and_roundtrip() {
+    let bytes = ::wat::parse_str(r#"
+        (module
+            (memory 1 1 shared)
+            (func (param i32) (result i32)
+                local.get 0
+                i32.const 1
+                i32.atomic.rmw.add offset=4
+                atomic.fence
+                local.get 0
+                i64.const 0
+                i64.const 1
+                i64.atomic.rmw8.cmpxchg_u
+                i32.wrap_i64
+                i32.add))"#).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert!(module.memories[0].shared);
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::ThreadsAtomics]);
+    let body = &module.functions[0].code().unwrap().body;
+    assert_eq!(body[2], Instr::Atomic(AtomicOp::I32AtomicRmwAdd, Memarg { alignment_exp: 2, offset: 4 }));
+    assert_eq!(body[3], Instr::AtomicFence);
+    assert_eq!(body[7], Instr::Atomic(AtomicOp::I64AtomicRmw8CmpxchgU, Memarg { alignment_exp: 0, offset: 0 }));
+    assert_eq!(body[2].to_string(), "i32.atomic.rmw.add offset=4");
+    assert_eq!(Instr::from_str("i32.atomic.rmw.add offset=4"), Ok(body[2].clone()));
+    assert_eq!(validate(&module), Ok(()));
+
+    assert_roundtrips(&module);
+    let module_from_wat = Module::from_wat(&wat::module_to_wat(&module)).unwrap();
+    assert!(module_from_wat.memories[0].shared);
+    assert_eq!(module_from_wat.functions[0].code(), module.functions[0].code());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn tail_calls_are_parsed_and_roundtrip() {
+    let bytes = ::wat::parse_str(r#"
+        (module
+            (type $t (func (param i64 i64) (result i64)))
+            (table 1 funcref)
+            (elem (i32.const 0) $fac_acc)
+            (func $fac_acc (type $t)
+                local.get 0
+                i64.eqz
+                if
+                    local.get 1
+                    return
+                end
+                local.get 0
+                i64.const 1
+                i64.sub
+                local.get 0
+                local.get 1
+                i64.mul
+                return_call $fac_acc)
+            (func (param i64) (result i64)
+                local.get 0
+                i64.const 1
+                i32.const 0
+                return_call_indirect (type $t)))"#).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert_eq!(module.metadata.used_extensions().collect::<Vec<_>>(), vec![WasmExtension::TailCalls]);
+    let fac_acc_ty = module.functions[0].type_;
+    let body = &module.functions[0].code().unwrap().body;
+    assert_eq!(body[body.len() - 2], Instr::ReturnCall(0u32.into()));
+    let body = &module.functions[1].code().unwrap().body;
+    assert_eq!(body[body.len() - 2], Instr::ReturnCallIndirect(fac_acc_ty, 0u32.into()));
+    assert_eq!(body[body.len() - 2].to_string(), "return_call_indirect [i64, i64] -> [i64]");
+    assert_eq!(validate(&module), Ok(()));
+
+    assert_roundtrips(&module);
+    let module_from_wat = Module::from_wat(&wat::module_to_wat(&module)).unwrap();
+    for (function_from_wat, function) in module_from_wat.functions.iter().zip(&module.functions) {
+        assert_eq!(function_from_wat.code().unwrap().body, function.code().unwrap().body);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn module_roundtrips_through_json() {
+    for path in [NAME_SECTION_TEST_BINARY, MULTI_VALUE_TEST_BINARY, ELEMENT_SEGMENTS_TEST_BINARY, BULK_MEMORY_TEST_BINARY, SIMD_TEST_BINARY] {
+        let module = Module::from_file(path).unwrap();
+        let json = serde_json::to_string(&module).unwrap();
+        let module_from_json: Module = serde_json::from_str(&json).unwrap();
+        assert_eq!(module_from_json, module, "{path}");
+        assert_eq!(module_from_json.metadata.section_order(), module.metadata.section_order(), "{path}");
+    }
+}
+
+#[test]
+fn structurally_eq_ignores_names_and_custom_section_order() {
+    let module = Module::from_bytes(&::wat::parse_str(r#"
+        (module $m
+            (@custom "first" "1")
+            (func $f (param $p i32) (local $l i64)
+                block $b
+                end)
+            (@custom "second" "2"))"#).unwrap()).unwrap();
+    let module_without_names = Module::from_bytes(&::wat::parse_str(r#"
+        (module
+            (@custom "second" "2")
+            (@custom "first" (after func) "1")
+            (func (param i32) (local i64)
+                block
+                end))"#).unwrap()).unwrap();
+    assert_ne!(module, module_without_names);
+    assert!(module.structurally_eq(&module_without_names));
+    assert!(module_without_names.structurally_eq(&module));
+
+    let mut module_changed = module_without_names.clone();
+    module_changed.functions[0].code_mut().unwrap().body.insert(0, Instr::Nop);
+    assert!(!module.structurally_eq(&module_changed));
+    let mut module_changed = module_without_names;
+    module_changed.custom_sections[0].content.push(0);
+    assert!(!module.structurally_eq(&module_changed));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn disassemble_indents_by_block_nesting() {
+    let module = Module::from_wat(r#"
+        (module
+            (func (param i32) (result i32)
+                block (result i32)
+                    local.get 0
+                    if
+                        call 0
+                        drop
+                    else
+                        nop
+                    end
+                    i32.const 42
+                end))"#).unwrap();
+    assert_eq!(module.functions[0].code().unwrap().disassemble(), "\
+block [] -> [i32]
+  local.get 0
+  if [] -> []
+    call 0
+    drop
+  else
+    nop
+  end
+  i32.const 42
+end
+end
+");
+}
+
+#[test]
+fn instr_offsets_map_byte_offsets_to_instructions() {
+    let bytes = ::wat::parse_str("(module (func) (func (result i32) i32.const 1 i32.const 2 i32.add))").unwrap();
+    let (_, offsets, _) = Module::from_bytes_with_offsets(&bytes).unwrap();
+    assert!(offsets.functions_instrs.is_empty());
+    assert_eq!(offsets.instr_at(1u32.into(), offsets.functions_code[1].1 + 1), None);
+
+    let (module, offsets, _) = Module::from_bytes_with_options(&bytes, ParseOptions { instr_offsets: true, ..ParseOptions::default() }).unwrap();
+    assert_eq!(module, Module::from_bytes(&bytes).unwrap());
+    let func = Idx::from(1u32);
+    // The body starts with the local declarations (a single 0x00 byte for no locals).
+    let body = offsets.function_idx_to_offset(func).unwrap();
+    assert_eq!(offsets.functions_instrs[1], (func, vec![body + 1, body + 3, body + 5, body + 6, body + 7]));
+    assert_eq!(offsets.instr_at(func, body), None);
+    assert_eq!(offsets.instr_at(func, body + 1), Some(0));
+    // Offsets within the immediate of an instruction belong to it.
+    assert_eq!(offsets.instr_at(func, body + 4), Some(1));
+    assert_eq!(offsets.instr_at(func, body + 6), Some(3));
+    assert_eq!(offsets.instr_at(func, body + 7), None);
+}
+
+#[test]
+fn added_functions_reuse_types_and_imports_are_encoded_first() {
+    let mut module = Module::new();
+    let ty = FunctionType::new(&[ValType::I32], &[ValType::I32]);
+    let defined = module.add_function(ty, vec![], vec![Instr::Local(LocalOp::Get, 0u32.into()), Instr::End]);
+    let import = module.add_function_import(ty, "env".to_string(), "hook".to_string());
+    let caller = module.add_function(ty, vec![ValType::I64], vec![
+        Instr::Local(LocalOp::Get, 0u32.into()),
+        Instr::Call(import),
+        Instr::Call(defined),
+        Instr::End,
+    ]);
+    module.functions[caller.to_usize()].export.push("caller".to_string());
+    assert_eq!(validate(&module), Ok(()));
+
+    let bytes = module.to_bytes().unwrap();
+    // All three functions share a single type.
+    assert_eq!(type_count(&bytes), Some(1));
+    let encoded = Module::from_bytes(&bytes).unwrap();
+    assert_eq!(encoded.functions[0].import(), Some(("env", "hook")));
+    assert_eq!(encoded.functions[2].export, vec!["caller".to_string()]);
+    assert_eq!(encoded.functions[2].instrs()[1..3], [Instr::Call(0u32.into()), Instr::Call(1u32.into())]);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn insert_and_remove_instrs_keep_block_structure() {
+    let mut module = Module::from_wat(r#"
+        (module
+            (func (param i32)
+                block $outer
+                    block $inner
+                        local.get 0
+                        br_if $outer
+                    end
+                end
+                block $after
+                end))"#).unwrap();
+    let code = module.functions[0].code_mut().unwrap();
+    let label_names = |code: &Code| code.label_names.values().cloned().collect::<Vec<_>>();
+    assert_eq!(label_names(code), ["outer", "inner", "after"]);
+
+    // Insert a nested block inside `$inner`, with a branch to `$outer` from inside it.
+    let inserted = [
+        Instr::Block(FunctionType::empty()),
+        Instr::Local(LocalOp::Get, 0u32.into()),
+        Instr::BrIf(Label::from(2u32)),
+        Instr::End,
+    ];
+    code.insert_instrs(3, &inserted).unwrap();
+    assert_eq!(code.body[7], Instr::BrIf(Label::from(1u32)));
+    assert_eq!(code.label_names.get(&1).map(String::as_str), Some("inner"));
+    assert_eq!(code.label_names.get(&3).map(String::as_str), Some("after"));
+    assert_eq!(validate(&module), Ok(()));
+
+    // Edits that would split blocks are rejected and don't change the body.
+    let code = module.functions[0].code_mut().unwrap();
+    let body_before = code.body.clone();
+    assert_eq!(code.insert_instrs(3, &[Instr::End]).unwrap_err().instr_idx, 0);
+    assert_eq!(code.insert_instrs(3, &[Instr::Block(FunctionType::empty())]).unwrap_err().instr_idx, 1);
+    assert!(code.insert_instrs(3, &[Instr::Loop(FunctionType::empty()), Instr::Else, Instr::End]).is_err());
+    assert_eq!(code.remove_range(3..9).unwrap_err().instr_idx, 8);
+    assert!(code.remove_range(12..13).is_err());
+    assert_eq!(code.body, body_before);
+
+    assert_eq!(code.remove_range(3..7).unwrap(), inserted);
+    assert_eq!(label_names(code), ["outer", "inner", "after"]);
+    code.remove_range(6..8).unwrap();
+    assert_eq!(label_names(code), ["outer", "inner"]);
+    assert_eq!(validate(&module), Ok(()));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn instrument_calls_passes_encoded_callee_indices() {
+    let mut module = Module::from_wat(r#"
+        (module
+            (import "env" "imported" (func $imported))
+            (table 2 funcref)
+            (elem (i32.const 0) $callee $main)
+            (func $callee (export "callee"))
+            (func $main
+                call $callee
+                call $imported
+                i32.const 1
+                call_indirect)
+            (start $main))"#).unwrap();
+    let hook = module.insert_imported_function(FunctionType::new(&[ValType::I32], &[]), "hooks".to_string(), "call".to_string()).unwrap();
+    assert_eq!(hook, Idx::from(1u32));
+    module.instrument_calls(hook);
+    assert_eq!(validate(&module), Ok(()));
+
+    // The hook was inserted right after the existing import, so all other indices shifted already.
+    let encoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(encoded.functions, module.functions);
+    let names = encoded.functions.iter().map(|function| function.name.as_deref()).collect::<Vec<_>>();
+    assert_eq!(names, [Some("imported"), None, Some("callee"), Some("main")]);
+    assert_eq!(encoded.functions[1].import(), Some(("hooks", "call")));
+    assert_eq!(encoded.functions[2].export, ["callee"]);
+    assert_eq!(encoded.start, Some(3u32.into()));
+    assert_eq!(encoded.elements[0].items, ElementItems::Functions(vec![2u32.into(), 3u32.into()]));
+
+    let hook = Instr::Call(1u32.into());
+    assert_eq!(encoded.functions[3].instrs(), [
+        Instr::Const(Val::I32(2)), hook.clone(), Instr::Call(2u32.into()),
+        Instr::Const(Val::I32(0)), hook.clone(), Instr::Call(0u32.into()),
+        Instr::Const(Val::I32(1)),
+        Instr::Const(Val::I32(-1)), hook, Instr::CallIndirect(FunctionType::empty(), 0u32.into()),
+        Instr::End,
+    ]);
+
+    // Appended hooks are called with the indices of the encoded binary as well.
+    let mut module = Module::from_wat("(module (func $f call $f))").unwrap();
+    let hook = module.add_function_import(FunctionType::new(&[ValType::I32], &[]), "hooks".to_string(), "call".to_string());
+    module.instrument_calls(hook);
+    let encoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(encoded.functions[1].instrs(), [Instr::Const(Val::I32(1)), Instr::Call(0u32.into()), Instr::Call(1u32.into()), Instr::End]);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn insert_imported_function_updates_all_references() {
+    let mut module = Module::from_wat(r#"
+        (module
+            (import "env" "imported" (func $imported))
+            (table 4 funcref)
+            (global $g funcref (ref.func $callee))
+            (elem (i32.const 0) $callee $main)
+            (elem (i32.const 2) funcref (ref.func $main) (ref.null func))
+            (func $callee (export "callee"))
+            (func $main
+                call $callee
+                call $imported
+                ref.func $callee
+                drop
+                return_call $main)
+            (start $main))"#).unwrap();
+    let new = module.insert_imported_function(FunctionType::new(&[ValType::I32], &[]), "hooks".to_string(), "new".to_string()).unwrap();
+    assert_eq!(new, Idx::from(1u32));
+    assert_eq!(module.functions.len(), 4);
+    assert_eq!(validate(&module), Ok(()));
+
+    let name = |idx: Idx<Function>| module.function(idx).name.as_deref();
+    assert_eq!(module.functions[2].name.as_deref(), Some("callee"));
+    assert_eq!(module.functions[2].export, ["callee"]);
+    assert_eq!(name(module.start.unwrap()), Some("main"));
+    assert_eq!(module.globals[0].init.clone(), ImportOrPresent::Present(vec![Instr::RefFunc(2u32.into()), Instr::End]));
+    assert_eq!(module.elements[0].items, ElementItems::Functions(vec![2u32.into(), 3u32.into()]));
+    assert_eq!(module.elements[1].items, ElementItems::Expressions(vec![
+        vec![Instr::RefFunc(3u32.into()), Instr::End],
+        vec![Instr::RefNull(RefType::FuncRef), Instr::End],
+    ]));
+    assert_eq!(module.functions[3].instrs(), [
+        Instr::Call(2u32.into()),
+        Instr::Call(0u32.into()),
+        Instr::RefFunc(2u32.into()),
+        Instr::Drop,
+        Instr::ReturnCall(3u32.into()),
+        Instr::End,
+    ]);
+
+    // Since the import was inserted after the existing one, the encoder does not renumber anything.
+    let encoded = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(encoded.functions, module.functions);
+    assert_eq!(encoded.elements, module.elements);
+}
+
+#[test]
+fn insert_imported_function_rejects_placeholders() {
+    let mut module = Module::new();
+    // `call 0` in the raw bytes would have to be renumbered.
+    module.add_function(FunctionType::empty(), vec![], vec![Instr::Unparsed(vec![0x10, 0x00]), Instr::End]);
+    let before = module.clone();
+    assert_eq!(
+        module.insert_imported_function(FunctionType::empty(), "env".to_string(), "f".to_string()),
+        Err(RenumberError::Placeholder(0u32.into()))
+    );
+    assert_eq!(module, before);
+}
+
+#[test]
+fn unreachable_functions_are_removed_and_references_renumbered() {
+    let wasm = ::wat::parse_str(r#"
+        (module
+            (import "env" "unused" (func $unused))
+            (table 1 funcref)
+            (elem (i32.const 0) $in_table)
+            (func $dead
+                call $also_dead)
+            (func $main (export "main")
+                call $helper
+                i32.const 0
+                call_indirect)
+            (func $also_dead)
+            (func $helper
+                return_call $main)
+            (func $in_table))"#).unwrap();
+    let (mut module, offsets, _) = Module::from_bytes_with_offsets(&wasm).unwrap();
+    let size_before = module.to_bytes().unwrap().len();
+
+    let removed = module.remove_unreachable_functions().unwrap();
+    assert_eq!(removed.functions, [Idx::from(1u32), Idx::from(3u32)]);
+    assert_eq!(removed.instr_count, 3);
+    assert_eq!(validate(&module), Ok(()));
+    // Bodies of `$dead` (size, no locals, call, end) and `$also_dead` (size, no locals, end).
+    let byte_size = removed.byte_size(&offsets.size_profile(wasm.len()));
+    assert_eq!(byte_size, 5 + 3);
+    assert!(module.to_bytes().unwrap().len() <= size_before - byte_size);
+
+    let names = module.functions.iter().map(|function| function.name.as_deref().unwrap()).collect::<Vec<_>>();
+    assert_eq!(names, ["unused", "main", "helper", "in_table"]);
+    assert_eq!(module.functions[1].instrs()[0], Instr::Call(2u32.into()));
+    assert_eq!(module.functions[2].instrs()[0], Instr::ReturnCall(1u32.into()));
+    assert_eq!(module.elements[0].items, ElementItems::Functions(vec![3u32.into()]));
+
+    // Nothing left to remove.
+    assert_eq!(module.remove_unreachable_functions(), Ok(RemovedFunctions::default()));
+}
+
+#[test]
+fn remove_unreachable_functions_rejects_placeholders_and_invalid_references() {
+    let mut module = Module::new();
+    let main = module.add_function(FunctionType::empty(), vec![], vec![Instr::Call(7u32.into()), Instr::End]);
+    module.functions[main.to_usize()].export.push("main".to_string());
+    module.add_function(FunctionType::empty(), vec![], vec![Instr::End]);
+    let before = module.clone();
+    assert_eq!(module.remove_unreachable_functions(), Err(RenumberError::InvalidFunction(7u32.into())));
+    assert_eq!(module, before);
+
+    // The only call to function 1 is hidden in the raw bytes of the placeholder.
+    module.functions[main.to_usize()].code_mut().unwrap().body = vec![Instr::Unparsed(vec![0x10, 0x01]), Instr::End];
+    let before = module.clone();
+    assert_eq!(module.remove_unreachable_functions(), Err(RenumberError::Placeholder(main)));
+    assert_eq!(module, before);
+}
+
+#[test]
+fn identical_types_are_deduplicated_when_encoding() {
+    let bytes = ::wat::parse_str(r#"
+        (module
+            (type (func (param i32)))
+            (type (func (param i32)))
+            (type (func (param i32)))
+            (type (func (result f64)))
+            (table 1 funcref)
+            (func (type 0)
+                local.get 0
+                i32.const 0
+                call_indirect (type 1))
+            (func (type 2)))"#).unwrap();
+    assert_eq!(type_count(&bytes), Some(4));
+
+    let module = Module::from_bytes(&bytes).unwrap();
+    let ty = FunctionType::new(&[ValType::I32], &[]);
+    assert_eq!(module.functions[0].type_, ty);
+    assert_eq!(module.functions[0].instrs()[2], Instr::CallIndirect(ty, 0u32.into()));
+    assert_eq!(module.functions[1].type_, ty);
+    assert_eq!(type_count(&module.to_bytes().unwrap()), Some(1));
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn consecutive_custom_sections_roundtrip_in_order() {
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    // Name section with only a module name "m".
+    const NAME: [u8; 11] = [0x00, 0x09, 0x04, b'n', b'a', b'm', b'e', 0x00, 0x02, 0x01, b'm'];
+    // Producers section with zero fields.
+    const PRODUCERS: [u8; 13] = [0x00, 0x0b, 0x09, b'p', b'r', b'o', b'd', b'u', b'c', b'e', b'r', b's', 0x00];
+
+    for sections in [[&NAME[..], &PRODUCERS[..]], [&PRODUCERS[..], &NAME[..]]] {
+        let bytes = [&HEADER[..], sections[0], sections[1]].concat();
+        let module = Module::from_bytes(&bytes).unwrap();
+        assert_eq!(module.name.as_deref(), Some("m"));
+        assert_eq!(module.to_bytes().unwrap(), bytes);
+    }
+}
+
+#[test]
+fn unknown_sections_roundtrip_unchanged() {
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+        0x20, 0x03, 0xab, 0xcd, 0xef, // section with (made-up) id 0x20
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+        0x21, 0x00, // another unknown section, empty
+    ];
+    let (module, offsets, _) = Module::from_bytes_with_offsets(&bytes).unwrap();
+    assert_eq!(module.unknown_sections, [
+        RawUnknownSection { id: 0x20, content: vec![0xab, 0xcd, 0xef], previous_section: Some(SectionId::Type) },
+        RawUnknownSection { id: 0x21, content: vec![], previous_section: Some(SectionId::Code) },
+    ]);
+    assert_eq!(offsets.section_offset(&SectionId::Unknown(0x20)), Some(16));
+    assert_eq!(module.to_bytes().unwrap(), bytes);
+
+    // Strict section order does not apply to unknown sections.
+    let strict = ParseOptions { strict_section_order: true, ..ParseOptions::default() };
+    assert!(Module::from_bytes_with_options(&bytes, strict).is_ok());
+}
+
+#[test]
+fn removing_custom_sections_keeps_order_of_remaining_sections() {
+    let wasm = ::wat::parse_str(r#"(module
+        (@custom "a" (after code) "A")
+        (@custom "b" (after code) "B")
+        (@custom "c" (after code) "C")
+        (func $f)
+        (@custom "d" (after data) "D")
+    )"#).unwrap();
+    let mut module = Module::from_bytes(&wasm).unwrap();
+    // `wat` always puts the name section last, so add another custom section after it.
+    module.custom_sections.push(RawCustomSection {
+        name: "e".to_string(),
+        content: b"E".to_vec(),
+        previous_section: Some(SectionId::Custom("name".to_string())),
+    });
+    let mut module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    let names = |module: &Module| module.custom_sections.iter().map(|custom| custom.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&module), ["a", "b", "c", "d", "e"]);
+    assert_eq!(module.custom_sections[4].previous_section, Some(SectionId::Custom("name".to_string())));
+
+    let removed = module.remove_custom_section("b").unwrap();
+    assert_eq!(removed.content, b"B");
+    assert!(module.remove_custom_section("b").is_none());
+    assert_eq!(module.custom_sections[1].previous_section, Some(SectionId::Custom("a".to_string())));
+
+    module.retain_custom_sections(|name| name != "a");
+    assert_eq!(names(&module), ["c", "d", "e"]);
+    assert_eq!(module.custom_sections[0].previous_section, Some(SectionId::Code));
+
+    // Without names, the section after the name section moves to the name section's position.
+    module.strip_names();
+    assert_eq!(module.functions[0].name, None);
+    assert_eq!(module.custom_sections[2].previous_section, Some(SectionId::Custom("d".to_string())));
+
+    // The remaining sections are all encoded, at the right position.
+    let reparsed = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.custom_sections, module.custom_sections);
+    assert_eq!(
+        reparsed.metadata.section_order(),
+        [SectionId::Type, SectionId::Function, SectionId::Code, SectionId::Custom("c".to_string()), SectionId::Custom("d".to_string()), SectionId::Custom("e".to_string())]
+    );
+}
+
+#[test]
+fn size_profile_sums_to_binary_length() {
+    let wasm = ::wat::parse_str("(module (func) (func (local i32) i32.const 0 drop))").unwrap();
+    let (_, offsets, _) = Module::from_bytes_with_offsets(&wasm).unwrap();
+    let profile = offsets.size_profile(wasm.len());
+    assert_eq!(profile.header, 8);
+    // Both functions have type [] -> [], so the type section has a single entry.
+    assert_eq!(profile.section_size(&SectionId::Type), Some(6));
+    assert_eq!(profile.section_size(&SectionId::Function), Some(5));
+    assert_eq!(profile.functions, [(0usize.into(), 3), (1usize.into(), 8)]);
+    assert_eq!(profile.section_size(&SectionId::Code), Some(1 + 1 + 1 + 3 + 8));
+    assert_eq!(profile.total(), wasm.len());
+
+    for path in [NAME_SECTION_TEST_BINARY, SIMD_TEST_BINARY, BULK_MEMORY_TEST_BINARY] {
+        let bytes = fs::read(path).unwrap();
+        let (_, offsets, _) = Module::from_bytes_with_offsets(&bytes).unwrap();
+        let profile = offsets.size_profile(bytes.len());
+        assert_eq!(profile.total(), bytes.len(), "{path}");
+        let code_size = profile.section_size(&SectionId::Code).unwrap_or(0);
+        let functions_size: usize = profile.functions.iter().map(|(_, size)| size).sum();
+        assert!(functions_size < code_size, "{path}");
+    }
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn local_type_resolves_params_and_declared_locals() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "imported" (func (param i64)))
+        (func (param i32 f32) (local f64 i64))
+    )"#).unwrap();
+
+    let imported = module.function(0usize.into());
+    assert_eq!((imported.param_count(), imported.local_count()), (1, 0));
+    assert_eq!(imported.local_type(0usize.into()), Some(ValType::I64));
+    assert_eq!(imported.local_type(1usize.into()), None);
+
+    let function = module.function(1usize.into());
+    assert_eq!((function.param_count(), function.local_count()), (2, 2));
+    let types: Vec<_> = (0..5usize).map(|idx| function.local_type(idx.into())).collect();
+    assert_eq!(types, [Some(ValType::I32), Some(ValType::F32), Some(ValType::F64), Some(ValType::I64), None]);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn const_exprs_are_evaluated_statically() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "imported" (global i32))
+        (global $a i64 (i64.const -7))
+        (global $b i64 (global.get $a))
+        (global $mutable (mut f32) (f32.const 1.5))
+        (global $ref funcref (ref.null func))
+        (memory 1)
+        (data (i32.const 16) "x")
+        (data (global.get 0) "y")
+    )"#).unwrap();
+
+    let init = |idx: usize| module.globals[idx].init().unwrap();
+    assert_eq!(eval_const_expr(init(1), &module), Ok(Val::I64(-7)));
+    assert_eq!(eval_const_expr(init(2), &module), Err(ConstEvalError::NonImportedGlobalInInit(1u32.into())));
+    assert_eq!(eval_const_expr(init(3), &module), Ok(Val::F32(1.5.into())));
+    assert_eq!(eval_const_expr(init(4), &module), Err(ConstEvalError::Reference { instr_idx: 0, instr: "ref.null" }));
+
+    let data_offset = |idx: usize| match &module.data[idx].mode {
+        DataMode::Active { offset, .. } => offset.clone(),
+        DataMode::Passive => unreachable!(),
+    };
+    assert_eq!(eval_const_expr(&data_offset(0), &module), Ok(Val::I32(16)));
+    assert_eq!(eval_const_expr(&data_offset(1), &module), Err(ConstEvalError::ImportedGlobal(0u32.into())));
+
+    let get_mutable = [Instr::Global(GlobalOp::Get, 3u32.into()), Instr::End];
+    assert_eq!(eval_const_expr(&get_mutable, &module), Err(ConstEvalError::MutableGlobal(3u32.into())));
+    let non_constant = [Instr::Const(Val::I32(1)), Instr::Const(Val::I32(2)), Instr::Binary(BinaryOp::I32Add), Instr::End];
+    assert_eq!(eval_const_expr(&non_constant, &module), Err(ConstEvalError::NonConstant { instr_idx: 2, instr: "i32.add" }));
+    assert_eq!(eval_const_expr(&[Instr::Const(Val::I32(1))], &module), Err(ConstEvalError::Malformed));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn initial_memory_bytes_are_resolved_from_active_data_segments() {
+    let module = Module::from_wat(r#"(module
+        (memory 1)
+        (data (i32.const 1024) "world")
+        (data "passive")
+        (data (i32.const 8) "hello")
+    )"#).unwrap();
+    let initial_bytes = module.initial_memory_bytes(0u32.into()).unwrap();
+    assert_eq!(initial_bytes, [(8, &b"hello"[..]), (1024, &b"world"[..])]);
+    assert_eq!(module.initial_memory_bytes(1u32.into()), Err(ConstEvalError::InvalidMemory(1u32.into())));
+
+    // Later segments overwrite earlier ones where they overlap.
+    let module = Module::from_wat(r#"(module
+        (memory 1)
+        (data (i32.const 8) "hello")
+        (data (i32.const 12) "world")
+        (data (i32.const 0) "0123456789")
+        (data (i32.const 100) "a")
+        (data (i32.const 99) "xyz")
+    )"#).unwrap();
+    assert_eq!(
+        module.initial_memory_bytes(0u32.into()).unwrap(),
+        [(0, &b"0123456789"[..]), (10, &b"ll"[..]), (12, &b"world"[..]), (99, &b"xyz"[..])]
+    );
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn table_slots_are_resolved_from_active_element_segments() {
+    let module = Module::from_wat(r#"(module
+        (table 8 funcref)
+        (func $a) (func $b) (func $c)
+        (elem (i32.const 0) $a $b $c)
+        (elem (i32.const 2) func $a)
+        (elem (table 0) (i32.const 1) funcref (ref.null func) (ref.func $b))
+        (elem func $c)
+    )"#).unwrap();
+    let slots = module.resolved_table_elements(0u32.into()).unwrap();
+    assert_eq!(slots, BTreeMap::from([(0, 0u32.into()), (2, 1u32.into())]));
+
+    let module = Module::from_wat(r#"(module
+        (global $f funcref (ref.null func))
+        (table 1 funcref)
+        (elem (i32.const 0) funcref (global.get $f))
+    )"#).unwrap();
+    assert_eq!(
+        module.resolved_table_elements(0u32.into()),
+        Err(ConstEvalError::UnresolvedElementItem { element: 0u32.into(), item_idx: 0 })
+    );
+    assert_eq!(module.resolved_table_elements(1u32.into()), Err(ConstEvalError::InvalidTable(1u32.into())));
+}
+
+#[test]
+fn instrs_are_classified() {
+    let classes = |instr: &str| {
+        let instr = Instr::from_str(instr).unwrap();
+        [
+            instr.is_control(),
+            instr.is_call(),
+            instr.is_parametric(),
+            instr.is_variable_access(),
+            instr.is_memory_access(),
+            instr.is_numeric(),
+            instr.is_const(),
+        ]
+    };
+    assert_eq!(classes("br 0"), [true, false, false, false, false, false, false]);
+    assert_eq!(classes("return_call 0"), [true, true, false, false, false, false, false]);
+    assert_eq!(classes("select"), [false, false, true, false, false, false, false]);
+    assert_eq!(classes("global.set 0"), [false, false, false, true, false, false, false]);
+    assert_eq!(classes("i64.load8_u"), [false, false, false, false, true, false, false]);
+    assert_eq!(classes("memory.grow"), [false; 7]);
+    assert_eq!(classes("f32.const 1.5"), [false, false, false, false, false, true, true]);
+    assert_eq!(classes("i32x4.add"), [false, false, false, false, false, true, false]);
+    assert_eq!(classes("ref.func 0"), [false; 7]);
+}
+
+#[test]
+fn memarg_alignment_is_compared_with_natural_alignment() {
+    assert_eq!(LoadOp::I32Load.natural_alignment_exp(), 2);
+    assert_eq!(LoadOp::I32Load8U.natural_alignment_exp(), 0);
+    assert_eq!(StoreOp::I64Store16.natural_alignment_exp(), 1);
+
+    let Instr::Load(op, memarg) = Instr::from_str("i32.load").unwrap() else { unreachable!() };
+    assert!(memarg.is_natural_alignment(op));
+    assert!(!memarg.is_over_aligned(op));
+
+    let Instr::Load(op, memarg) = Instr::from_str("i32.load align=1").unwrap() else { unreachable!() };
+    assert!(!memarg.is_natural_alignment(op));
+    assert!(!memarg.is_over_aligned(op));
+
+    let Instr::Store(op, memarg) = Instr::from_str("i32.store8 offset=4 align=2").unwrap() else { unreachable!() };
+    assert!(!memarg.is_natural_alignment(op));
+    assert!(memarg.is_over_aligned(op));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn memory_accesses_are_found_in_all_functions() {
+    let module = Module::from_wat(r#"(module
+        (memory 1)
+        (func (param i32) (result i32)
+            local.get 0
+            i32.load offset=8 align=1)
+        (func (param i32 v128)
+            local.get 0
+            i64.const 0
+            i64.store
+            local.get 0
+            local.get 1
+            v128.store8_lane 3)
+    )"#).unwrap();
+    let accesses: Vec<_> = module.memory_accesses().collect();
+    assert_eq!(accesses.iter().map(|access| (access.func.to_usize(), access.instr_idx, access.op)).collect::<Vec<_>>(), [
+        (0, 1, MemoryAccessOp::Load(LoadOp::I32Load)),
+        (1, 2, MemoryAccessOp::Store(StoreOp::I64Store)),
+        (1, 5, MemoryAccessOp::StoreLane(StoreLaneOp::V128Store8Lane)),
+    ]);
+    assert_eq!(accesses[0].memarg.offset, 8);
+
+    let misaligned: Vec<_> = module.memory_accesses()
+        .filter(|access| !access.memarg.is_natural_alignment(access.op))
+        .map(|access| access.op.to_name())
+        .collect();
+    assert_eq!(misaligned, ["i32.load"]);
+}
+
+#[test]
+fn error_of_lowest_invalid_function_is_reported() {
+    let mut wasm = ::wat::parse_str("(module (func nop) (func nop) (func nop) (func nop))").unwrap();
+    let (_, offsets, _) = Module::from_bytes_with_offsets(&wasm).unwrap();
+    // Replace the `nop` after the (empty) locals of the second and fourth function with an
+    // invalid opcode.
+    let nop_offset = |func: usize| offsets.function_idx_to_offset(func.into()).unwrap() + 1;
+    for func in [3, 1] {
+        assert_eq!(wasm[nop_offset(func)], 0x01);
+        wasm[nop_offset(func)] = 0xff;
+    }
+
+    // Parsing is parallel, so check that the result does not depend on thread scheduling.
+    for _ in 0..20 {
+        let error = Module::from_bytes(&wasm).unwrap_err();
+        assert_eq!(error.offset(), Some(nop_offset(1)));
+    }
+}
+
+#[test]
+fn serial_and_thread_pool_parse_equal_parallel_parse() {
+    let bytes = fs::read(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+    let (expected_module, expected_offsets, _) = Module::from_bytes_with_offsets(&bytes).unwrap();
+
+    let serial = ParseOptions { parallel: false, ..ParseOptions::default() };
+    let (module, offsets, _) = Module::from_bytes_with_options(&bytes, serial).unwrap();
+    assert_eq!(module, expected_module);
+    assert_eq!(offsets, expected_offsets);
+
+    #[cfg(feature = "parallel")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let own_pool = ParseOptions { thread_pool: Some(&pool), ..ParseOptions::default() };
+        let (module, offsets, _) = Module::from_bytes_with_options(&bytes, own_pool).unwrap();
+        assert_eq!(module, expected_module);
+        assert_eq!(offsets, expected_offsets);
+    }
+}
+
+#[test]
+fn segments_are_parsed_serially_if_requested() {
+    let bytes = ::wat::parse_str(r#"(module
+        (memory 1)
+        (table 2 funcref)
+        (func $f)
+        (elem (i32.const 0) func $f $f)
+        (elem func $f)
+        (data (i32.const 0) "hello")
+        (data "passive"))"#).unwrap();
+    let expected_module = Module::from_bytes(&bytes).unwrap();
+
+    let serial = ParseOptions { parallel: false, ..ParseOptions::default() };
+    let (module, _, _) = Module::from_bytes_with_options(&bytes, serial).unwrap();
+    assert_eq!(module, expected_module);
+    assert_eq!(module.elements.len(), 2);
+    assert_eq!(module.data.len(), 2);
+
+    #[cfg(feature = "parallel")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let own_pool = ParseOptions { thread_pool: Some(&pool), ..ParseOptions::default() };
+        let (module, _, _) = Module::from_bytes_with_options(&bytes, own_pool).unwrap();
+        assert_eq!(module, expected_module);
+    }
+}
+
+#[test]
+fn headers_only_parse_agrees_with_full_parse() {
+    let bytes = fs::read(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+    let headers = Module::parse_headers_only(&bytes).unwrap();
+    let mut module = Module::from_bytes(&bytes).unwrap();
+    // Custom sections, and thus names, are not parsed for the headers.
+    module.clear_names();
+
+    assert_eq!(headers.functions.len(), module.functions.len());
+    for (header, function) in headers.functions.iter().zip(&module.functions) {
+        assert_eq!(header.type_, function.type_);
+        assert_eq!(header.import(), function.import());
+        assert_eq!(header.export, function.export);
+        if header.import().is_none() {
+            assert!(header.instrs().is_empty());
+        }
+    }
+    assert_eq!(headers.tables, module.tables);
+    assert_eq!(headers.memories, module.memories);
+    assert_eq!(headers.globals, module.globals);
+    assert_eq!(headers.start, module.start);
+    assert!(headers.functions.iter().all(|function| headers.types.contains(&function.type_)));
+}
+
+#[test]
+fn streaming_parse_equals_in_memory_parse() {
+    for path in [NAME_SECTION_TEST_BINARY, BULK_MEMORY_TEST_BINARY, BANANABREAD_REAL_WORLD_TEST_BINARY] {
+        let (expected_module, expected_offsets, _) = Module::from_file_with_offsets(path).unwrap();
+
+        let mut section_ids = Vec::new();
+        let mut function_bodies = Vec::new();
+        let mut module_and_offsets = None;
+        for event in Module::parse_streaming(fs::File::open(path).unwrap()) {
+            match event.unwrap() {
+                SectionEvent::Section { id, offset: _ } => section_ids.push(id),
+                SectionEvent::FunctionBody { idx, offset: _, type_, code } => {
+                    assert_eq!(type_, expected_module.function(idx).type_);
+                    function_bodies.push((idx, code));
+                }
+                SectionEvent::End { module, offsets, warnings: _ } => module_and_offsets = Some((module, offsets)),
+            }
+        }
+
+        let (mut module, offsets) = module_and_offsets.expect("streaming parser should yield an end event");
+        for (idx, code) in function_bodies {
+            module.function_mut(idx).code_mut().unwrap().body = code.body;
+        }
+        assert_eq!(module, expected_module, "{path}");
+        assert_eq!(offsets, expected_offsets, "{path}");
+        let expected_section_ids = expected_offsets.sections.into_iter().map(|(id, _offset)| id).collect::<Vec<_>>();
+        assert_eq!(section_ids, expected_section_ids, "{path}");
+    }
+}
+
+#[test]
+fn streaming_parse_stops_after_error() {
+    let truncated_bytes: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, // type section, truncated
+    ];
+    let mut events = Module::parse_streaming(truncated_bytes);
+    assert!(events.next().unwrap().is_err());
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn visitor_counts_instructions_like_manual_loop() {
+    use crate::visit::{InstrLoc, Visitor};
+
+    #[derive(Default)]
+    struct CountCalls {
+        calls: usize,
+        last_loc: Option<InstrLoc>,
+    }
+    impl Visitor for CountCalls {
+        fn visit_instr(&mut self, instr: &Instr, loc: InstrLoc) {
+            if let Instr::Call(_) = instr {
+                self.calls += 1;
+            }
+            self.last_loc = Some(loc);
+        }
+    }
+
+    let module = Module::from_file(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+    let mut visitor = CountCalls::default();
+    module.visit(&mut visitor);
+
+    let expected_calls = module
+        .functions()
+        .flat_map(|(_, function)| function.instrs())
+        .filter(|instr| matches!(instr, Instr::Call(_)))
+        .count();
+    assert_eq!(visitor.calls, expected_calls);
+    let (last_function_idx, last_function) = module.functions().last().unwrap();
+    assert_eq!(visitor.last_loc, Some(InstrLoc {
+        function: last_function_idx,
+        instr: (last_function.instr_count() - 1).into(),
+    }));
+}
+
 // TODO: Also ensure that used_wasm_extensions(encode(decode(wasm))) <= used_wasm_extensions(wasm), i.e., that our
 // encoding does not introduce new extensions.
 
+#[test]
+fn wat_output_is_valid_and_reparses_to_same_code() {
+    for path in [
+        SIMD_TEST_BINARY,
+        REFERENCE_TYPES_TEST_BINARY,
+        ELEMENT_SEGMENTS_TEST_BINARY,
+        BULK_MEMORY_TEST_BINARY,
+        SIGN_EXTENSION_TEST_BINARY,
+        NONTRAPPING_FLOAT_TO_INT_TEST_BINARY,
+        MULTI_VALUE_TEST_BINARY,
+        EXTENDED_NAME_SECTION_TEST_BINARY,
+        NAME_SECTION_TEST_BINARY,
+    ] {
+        let module = Module::from_file(path).unwrap();
+        let wat = wat::module_to_wat(&module);
+        let bytes = ::wat::parse_str(&wat).unwrap_or_else(|err| panic!("invalid wat for '{path}': {err}\n{wat}"));
+        let module_reparsed = Module::from_bytes(&bytes).unwrap();
+
+        // Names and types are different (generated names, no type section), but code must be equal.
+        assert_eq!(module.functions.len(), module_reparsed.functions.len(), "{path}");
+        for (function, function_reparsed) in module.functions.iter().zip(&module_reparsed.functions) {
+            assert_eq!(function.type_, function_reparsed.type_, "{path}");
+            assert_eq!(function.instrs(), function_reparsed.instrs(), "{path}");
+        }
+        assert_eq!(module.globals.iter().map(|g| &g.init).collect::<Vec<_>>(), module_reparsed.globals.iter().map(|g| &g.init).collect::<Vec<_>>(), "{path}");
+        assert_eq!(module.elements.iter().map(|e| (&e.mode, &e.items)).collect::<Vec<_>>(), module_reparsed.elements.iter().map(|e| (&e.mode, &e.items)).collect::<Vec<_>>(), "{path}");
+        assert_eq!(module.data.iter().map(|d| (&d.mode, &d.bytes)).collect::<Vec<_>>(), module_reparsed.data.iter().map(|d| (&d.mode, &d.bytes)).collect::<Vec<_>>(), "{path}");
+    }
+}
+
+#[test]
+fn wat_output_uses_names_and_labels() {
+    let module = Module::from_file(EXTENDED_NAME_SECTION_TEST_BINARY).unwrap();
+    let wat = wat::module_to_wat(&module);
+    assert!(wat.contains("(func $f\n    (local $l i32)\n"), "{wat}");
+    assert!(wat.contains("    block $outer\n      loop $inner\n        global.get $counter\n        br_if $outer\n"), "{wat}");
+
+    // Without a name section, names are generated from indices.
+    let mut module = Module::new();
+    module.add_function(FunctionType::new(&[ValType::I32], &[]), vec![ValType::F64], vec![
+        Instr::Block(FunctionType::empty()),
+        Instr::Local(LocalOp::Get, 0u32.into()),
+        Instr::BrIf(Label::from(1u32)),
+        Instr::Br(Label::from(0u32)),
+        Instr::End,
+        Instr::End,
+    ]);
+    assert_eq!(wat::module_to_wat(&module), "\
+(module
+  (func $f0 (param $l0 i32)
+    (local $l1 f64)
+    block $b0
+      local.get $l0
+      br_if 1
+      br $b0
+    end
+  )
+)
+");
+}
+
+#[test]
+fn wat_output_prints_numeric_indices_for_invalid_references() {
+    // The parser accepts such a module, e.g., `(module (func call 5 local.get 3 global.get 1))`.
+    let mut module = Module::new();
+    module.add_function(FunctionType::empty(), vec![], vec![
+        Instr::Call(5u32.into()),
+        Instr::Local(LocalOp::Get, 3u32.into()),
+        Instr::Global(GlobalOp::Get, 1u32.into()),
+        Instr::End,
+    ]);
+    assert_eq!(wat::module_to_wat(&module), "\
+(module
+  (func $f0
+    call 5
+    local.get 3
+    global.get 1
+  )
+)
+");
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn module_from_wat() {
+    let module = Module::from_wat(r#"
+        (module
+          (func $add (export "add") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            i32.add))
+    "#).unwrap();
+    assert_eq!(module.functions[0].name.as_deref(), Some("add"));
+    assert_eq!(module.functions[0].export, vec!["add".to_string()]);
+    assert_eq!(module.functions[0].instrs().iter().map(Instr::to_string).collect::<Vec<_>>(), vec![
+        "local.get 0",
+        "local.get 1",
+        "i32.add",
+        "end",
+    ]);
+
+    // Errors from the text assembler report the (1-based) location.
+    let err = Module::from_wat("(module\n  (func\n    i32.foo))").unwrap_err();
+    assert!(matches!(err.issue(), ParseIssue::Text { line: 3, column: 5, .. }), "{err}");
+    assert!(err.to_string().starts_with("error parsing WebAssembly text at line 3, column 5:"), "{err}");
+}
+
+#[test]
+fn unusual_section_order_roundtrips_exactly() {
+    let custom_section = |name: &str, content: &[u8]| [&[0x00, (1 + name.len() + content.len()) as u8, name.len() as u8], name.as_bytes(), content].concat();
+    let binary = [
+        &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00][..],
+        // A custom section before all other sections.
+        &custom_section("first", &[1, 2, 3]),
+        // An empty type section.
+        &[0x01, 0x01, 0x00],
+        // The name section (with only a module name) before instead of after the data section.
+        &custom_section("name", &[0x00, 0x02, 0x01, b'm']),
+        // A memory section and an empty data section.
+        &[0x05, 0x03, 0x01, 0x00, 0x01],
+        &[0x0b, 0x01, 0x00],
+        &custom_section("last", &[]),
+    ].concat();
+
+    let module = Module::from_bytes(&binary).unwrap();
+    assert_eq!(module.name.as_deref(), Some("m"));
+    assert_eq!(module.metadata.section_order(), &[
+        SectionId::Custom("first".to_string()),
+        SectionId::Type,
+        SectionId::Custom("name".to_string()),
+        SectionId::Memory,
+        SectionId::Data,
+        SectionId::Custom("last".to_string()),
+    ]);
+    assert_eq!(module.to_bytes().unwrap(), binary);
+
+    // Custom sections after a removed custom section are appended at the end, instead of dropped.
+    let mut module = module;
+    module.custom_sections.remove(0);
+    let module_roundtrip = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(module_roundtrip.custom_sections.last().map(|custom| custom.name.as_str()), Some("last"));
+}
+
+#[test]
+fn instruction_names_can_be_parsed_back() {
+    for path in [SIMD_TEST_BINARY, SIGN_EXTENSION_TEST_BINARY, NONTRAPPING_FLOAT_TO_INT_TEST_BINARY, BANANABREAD_REAL_WORLD_TEST_BINARY] {
+        let module = Module::from_file(path).unwrap();
+        for (_, function) in module.functions() {
+            for instr in function.instrs() {
+                match instr {
+                    Instr::Unary(op) => assert_eq!(UnaryOp::from_str(instr.to_name()), Ok(*op)),
+                    Instr::Binary(op) => assert_eq!(BinaryOp::from_str(instr.to_name()), Ok(*op)),
+                    Instr::Load(op, _) => assert_eq!(LoadOp::from_str(instr.to_name()), Ok(*op)),
+                    Instr::Store(op, _) => assert_eq!(StoreOp::from_str(instr.to_name()), Ok(*op)),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
 #[test]
 fn section_offsets_like_objdump() {
     // Use a wasm file with a custom section for testing section offsets.
-    let (_module, offsets, _warnings) = Module::from_file(NAME_SECTION_TEST_BINARY).unwrap();
+    let (_module, offsets, _warnings) = Module::from_file_with_offsets(NAME_SECTION_TEST_BINARY).unwrap();
 
     // Expected values are taken from wasm-objdump output.
     assert_eq!(offsets.section_offsets(SectionId::Type), vec![0xa]);
     assert_eq!(offsets.section_offsets(SectionId::Function), vec![0x11]);
     assert_eq!(offsets.section_offsets(SectionId::Code), vec![0x15]);
     assert_eq!(offsets.section_offsets(SectionId::Custom("name".to_string())), vec![0x1f]);
+    assert_eq!(offsets.section_offset(&SectionId::Custom("name".to_string())), Some(0x1f));
+    assert_eq!(offsets.section_offset(&SectionId::Custom("producers".to_string())), None);
+    assert_eq!(offsets.section_offset(&SectionId::Data), None);
     // Also try the (only) function code offset, for completion.
     assert_eq!(offsets.function_idx_to_offset(Idx::from(0u32)), Some(0x17));
     assert_eq!(offsets.function_offset_to_idx(0x17), Some(Idx::from(0u32)));
 }
 
+#[test]
+fn offsets_map_byte_offsets_back_to_sections_and_functions() {
+    let bytes = ::wat::parse_str(r#"(module
+        (import "env" "f" (func))
+        (func) (func nop) (func nop nop))"#).unwrap();
+    let (_, offsets, _) = Module::from_bytes_with_offsets(&bytes).unwrap();
+    assert!(offsets.functions_code.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+
+    let code = offsets.section_offset(&SectionId::Code).unwrap();
+    assert_eq!(offsets.section_at(code), Some((&SectionId::Code, code)));
+    assert_eq!(offsets.section_at(code + 1), Some((&SectionId::Code, code)));
+    assert_eq!(offsets.section_at(0), None);
+
+    // The imported function has no body.
+    assert_eq!(offsets.function_idx_to_offset(Idx::from(0u32)), None);
+    assert_eq!(offsets.function_idx_to_offset(Idx::from(4u32)), None);
+    for (func, body) in offsets.functions_code.clone() {
+        assert_eq!(offsets.function_idx_to_offset(func), Some(body));
+        assert_eq!(offsets.function_offset_to_idx(body), Some(func));
+        assert_eq!(offsets.function_at(body), Some(func));
+        assert_eq!(offsets.function_at(body + 1), Some(func));
+    }
+    assert_eq!(offsets.function_at(code), None);
+}
+
+#[test]
+fn idx_helpers_and_ranges() {
+    let idx: Idx<Function> = Idx::new(3);
+    assert_eq!(idx.next(), Idx::from(4u32));
+    assert_eq!(IdxRange::new(idx, Idx::from(6u32)).collect::<Vec<_>>(), vec![Idx::from(3u32), Idx::from(4u32), Idx::from(5u32)]);
+    assert_eq!(IdxRange::new(idx, Idx::from(6u32)).next_back(), Some(Idx::from(5u32)));
+    assert_eq!(IdxRange::<Function>::new(idx, Idx::from(1u32)).len(), 0);
+
+    let mut module = Module::new();
+    assert_eq!(module.function_idxs().len(), 0);
+    module.add_function(FunctionType::empty(), vec![], vec![Instr::End]);
+    module.add_function(FunctionType::empty(), vec![], vec![Instr::End]);
+    module.add_global(ValType::I32, Mutability::Const, vec![Instr::Const(Val::I32(0)), Instr::End]);
+    assert_eq!(module.function_idxs().collect::<Vec<_>>(), module.functions().map(|(idx, _)| idx).collect::<Vec<_>>());
+    assert_eq!(module.global_idxs().collect::<Vec<_>>(), vec![Idx::<Global>::from(0u32)]);
+    assert_eq!(module.table_idxs().len() + module.memory_idxs().len(), 0);
+
+    // Iterating over indices allows mutating the module at the same time.
+    for idx in module.function_idxs() {
+        module.function_mut(idx).name = Some(format!("f{}", idx.to_u32()));
+    }
+    assert_eq!(module.functions[1].name.as_deref(), Some("f1"));
+}
+
+#[test]
+fn lookup_by_export_name_and_imports() {
+    let mut module = Module::new();
+    module.functions.push(Function::new_imported(FunctionType::empty(), "env".into(), "log".into(), Vec::new()));
+    let main = module.add_function(FunctionType::empty(), vec![], vec![Instr::End]);
+    module.function_mut(main).export = vec!["main".to_string(), "_start".to_string()];
+    module.globals.push(Global::new_imported(GlobalType(ValType::I32, Mutability::Const), "env".into(), "g".into()));
+    module.globals[0].export.push("g_reexported".to_string());
+    module.memories.push(Memory::new_imported(Limits { initial_size: 1, max_size: None }, "env".into(), "mem".into()));
+    module.tables.push(Table::new(Limits { initial_size: 1, max_size: None }, RefType::FuncRef));
+    module.tables[0].export.push("table".to_string());
+
+    // Entities with multiple export names can be found by either name.
+    assert_eq!(module.function_by_export("main").map(|(idx, _)| idx), Some(main));
+    assert_eq!(module.function_by_export("_start").map(|(idx, _)| idx), Some(main));
+    assert!(module.function_by_export("log").is_none());
+    assert_eq!(module.global_by_export("g_reexported").map(|(idx, _)| idx), Some(0u32.into()));
+    assert_eq!(module.table_by_export("table").map(|(idx, _)| idx), Some(0u32.into()));
+    assert!(module.memory_by_export("mem").is_none());
+
+    assert_eq!(module.imports().collect::<Vec<_>>(), vec![
+        ("env", "log", ImportKind::Function(0u32.into())),
+        ("env", "g", ImportKind::Global(0u32.into())),
+        ("env", "mem", ImportKind::Memory(0u32.into())),
+    ]);
+}
+
 #[test]
 fn code_offsets_like_objdump() {
     let (_module, offsets, _warnings) =
-        Module::from_file(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+        Module::from_file_with_offsets(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
 
     // Test first two and last two functions.
     // Expected values are taken from wasm-objdump output.
@@ -235,3 +1973,747 @@ fn error_offsets_correct() {
     ]].concat();
     assert_error_offset(invalid_instruction, 13);
 }
+
+#[cfg(feature = "wat")]
+#[test]
+fn link_resolves_function_imports_and_renumbers_library() {
+    use crate::link::{link, ExportRef, ImportRef};
+
+    let main = Module::from_wat(r#"(module
+        (import "env" "print" (func $print (param i32)))
+        (import "lib" "inc" (func $inc (param i32) (result i32)))
+        (global $g (mut i32) (i32.const 0))
+        (func (export "main")
+            global.get $g
+            call $inc
+            call $print))"#).unwrap();
+    let lib = Module::from_wat(r#"(module
+        (import "env" "abort" (func $abort))
+        (global $one i32 (i32.const 1))
+        (memory 1)
+        (data "lib")
+        (func $helper (result i32)
+            global.get $one)
+        (func (export "inc") (param i32) (result i32)
+            local.get 0
+            call $helper
+            i32.add))"#).unwrap();
+
+    let resolve = [(ImportRef::new("lib", "inc"), ExportRef::new("inc"))];
+    let linked = link(main.clone(), lib.clone(), &resolve).unwrap();
+    validate(&linked).unwrap();
+
+    // print, main, then all functions of lib (abort, helper, inc).
+    assert_eq!(linked.functions.len(), 5);
+    assert_eq!(linked.function(0u32.into()).import(), Some(("env", "print")));
+    assert_eq!(linked.function(2u32.into()).import(), Some(("env", "abort")));
+    let (main_idx, main_function) = linked.function_by_export("main").unwrap();
+    assert_eq!(main_idx, 1u32.into());
+    assert_eq!(main_function.instrs(), [
+        Instr::Global(GlobalOp::Get, 0u32.into()),
+        Instr::Call(4u32.into()),
+        Instr::Call(0u32.into()),
+        Instr::End,
+    ]);
+    assert_eq!(linked.function(4u32.into()).instrs()[1], Instr::Call(3u32.into()));
+    assert_eq!(linked.function(3u32.into()).instrs()[0], Instr::Global(GlobalOp::Get, 1u32.into()));
+    assert_eq!((linked.globals.len(), linked.memories.len(), linked.data.len()), (2, 1, 1));
+
+    assert_eq!(
+        link(main.clone(), lib.clone(), &[(ImportRef::new("lib", "dec"), ExportRef::new("inc"))]),
+        Err(LinkError::ImportNotFound(ImportRef::new("lib", "dec"))));
+    assert_eq!(
+        link(main.clone(), lib.clone(), &[(ImportRef::new("lib", "inc"), ExportRef::new("dec"))]),
+        Err(LinkError::ExportNotFound(ExportRef::new("dec"))));
+    assert!(matches!(
+        link(main.clone(), lib.clone(), &[(ImportRef::new("env", "print"), ExportRef::new("inc"))]),
+        Err(LinkError::TypeMismatch { .. })));
+    // Memories are not merged.
+    assert_eq!(link(lib.clone(), lib.clone(), &[]), Err(LinkError::Unsupported("memory")));
+
+    // Re-exported imports keep their export, now on the library function.
+    let mut reexport = main.clone();
+    reexport.functions[1].export.push("inc_reexport".to_string());
+    let linked = link(reexport.clone(), lib.clone(), &resolve).unwrap();
+    assert_eq!(linked.function(4u32.into()).export, ["inc", "inc_reexport"]);
+    validate(&linked).unwrap();
+
+    // Indices in placeholders cannot be renumbered.
+    let function = reexport.functions[2].code_mut().unwrap();
+    function.body = vec![Instr::Unparsed(vec![0x10, 0x01]), Instr::End];
+    assert_eq!(link(reexport, lib, &resolve), Err(LinkError::Renumber(RenumberError::Placeholder(2u32.into()))));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn check_references_reports_all_dangling_references() {
+    let mut module = Module::from_wat(r#"(module
+        (table 1 funcref)
+        (memory 1)
+        (elem (i32.const 0) $f)
+        (data (i32.const 0) "a")
+        (func $f (export "f"))
+        (start $f))"#).unwrap();
+    assert_eq!(module.check_references(), Ok(()));
+
+    // A dangling start index, e.g., after removing functions manually.
+    module.functions.clear();
+    assert_eq!(module.check_references(), Err(vec![
+        RefError::InvalidStart(0u32.into()),
+        RefError::InvalidElementFunction { element: 0u32.into(), function: 0u32.into() },
+    ]));
+
+    module.start = None;
+    module.elements[0].ref_type = RefType::ExternRef;
+    module.data[0].mode = DataMode::Active { memory: 1u32.into(), offset: vec![Instr::Const(Val::I32(0)), Instr::End] };
+    module.globals.push(Global { type_: GlobalType(ValType::I32, Mutability::Const), init: ImportOrPresent::Import("env".to_string(), "g".to_string()), export: vec!["m".to_string()], name: None });
+    module.memories[0].export.push("m".to_string());
+    assert_eq!(module.check_references(), Err(vec![
+        RefError::DuplicateExport("m".to_string()),
+        RefError::ElementTypeMismatch { element: 0u32.into(), element_type: RefType::ExternRef, table: 0u32.into(), table_type: RefType::FuncRef },
+        RefError::InvalidElementFunction { element: 0u32.into(), function: 0u32.into() },
+        RefError::InvalidDataMemory { data: 0u32.into(), memory: 1u32.into() },
+    ]));
+
+    // The start function must not have parameters or results.
+    let mut module = Module::from_wat("(module (func $f) (start $f))").unwrap();
+    module.function_mut(0u32.into()).type_ = FunctionType::new(&[ValType::I32], &[]);
+    assert_eq!(module.check_references(), Err(vec![RefError::StartType(0u32.into(), FunctionType::new(&[ValType::I32], &[]))]));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn element_segment_must_fit_table_type() {
+    let module = Module::from_wat(r#"(module
+        (table $funcs 1 funcref)
+        (table $externs 1 externref)
+        (elem (table $funcs) (i32.const 0) funcref (ref.null func))
+        (elem (table $externs) (i32.const 0) externref (ref.null extern)))"#).unwrap();
+    assert_eq!(module.elements[1].ref_type, RefType::ExternRef);
+    assert_eq!(module.check_references(), Ok(()));
+
+    let bytes = ::wat::parse_str(r#"(module
+        (table 1 funcref)
+        (elem (table 0) (i32.const 0) externref (ref.null extern)))"#).unwrap();
+    let error = Module::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(error.issue(), ParseIssue::ElementTypeMismatch { element_type: RefType::ExternRef, table_type: RefType::FuncRef, .. }), "{error}");
+}
+
+#[test]
+fn const_values_can_be_extracted_from_instructions() {
+    assert_eq!(Instr::Const(Val::I32(-1)).as_const(), Some(Val::I32(-1)));
+    assert_eq!(Instr::Const(Val::I32(-1)).as_const().and_then(|val| val.as_i32()), Some(-1));
+    assert_eq!(Instr::Nop.as_const(), None);
+    assert_eq!(Instr::RefNull(RefType::FuncRef).as_const(), None);
+
+    assert_eq!(Val::I64(1 << 40).as_i64(), Some(1 << 40));
+    assert_eq!(Val::F32(1.5.into()).as_f32(), Some(1.5));
+    assert_eq!(Val::F64(2.5.into()).as_f64(), Some(2.5));
+    assert_eq!(Val::I64(0).as_i32(), None);
+    assert_eq!(Val::I32(0).as_f32(), None);
+    assert_eq!(Val::V128([0; 16]).as_i64(), None);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn producers_section_is_parsed_and_extended() {
+    // Producers section with language Rust 1.70.0, followed by a data section.
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x00, 0x21, 0x09, b'p', b'r', b'o', b'd', b'u', b'c', b'e', b'r', b's',
+        0x01, 0x08, b'l', b'a', b'n', b'g', b'u', b'a', b'g', b'e',
+        0x01, 0x04, b'R', b'u', b's', b't', 0x06, b'1', b'.', b'7', b'0', b'.', b'0',
+        0x0b, 0x01, 0x00, // empty data section
+    ];
+    let mut module = Module::from_bytes(&bytes).unwrap();
+    assert_eq!(module.to_bytes().unwrap(), bytes);
+    let rust = ProducerValue { name: "Rust".to_string(), version: "1.70.0".to_string() };
+    assert_eq!(module.producers().unwrap().unwrap(), Producers {
+        fields: vec![ProducersField { name: "language".to_string(), values: vec![rust.clone()] }],
+    });
+
+    module.add_producer("processed-by", "wasabi", "0.1.0");
+    module.add_producer("processed-by", "wasabi", "0.2.0");
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(module.custom_sections.len(), 1);
+    // Still at the beginning of the binary.
+    assert_eq!(module.custom_sections[0].previous_section, None);
+    assert_eq!(module.producers().unwrap().unwrap().fields, [
+        ProducersField { name: "language".to_string(), values: vec![rust] },
+        ProducersField { name: "processed-by".to_string(), values: vec![ProducerValue { name: "wasabi".to_string(), version: "0.2.0".to_string() }] },
+    ]);
+
+    // Without a producers section, a new one is appended.
+    let mut module = Module::from_wat("(module (func))").unwrap();
+    assert!(module.producers().is_none());
+    module.add_producer("sdk", "wasabi", "0.2.0");
+    let bytes = module.to_bytes().unwrap();
+    assert!(bytes.ends_with(&Producers::to_bytes(&module.producers().unwrap().unwrap())));
+    assert_eq!(Module::from_bytes(&bytes).unwrap().producers().unwrap().unwrap().fields.len(), 1);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn debug_sections_are_found_by_name_and_kept() {
+    let bytes = ::wat::parse_str(r#"(module
+        (@custom ".debug_info" (after code) "\01\02")
+        (@custom "producers" (after code) "\00")
+        (@custom ".debug_line" (after code) "\03")
+        (func))"#).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert!(module.has_debug_info());
+    assert_eq!(module.debug_sections().collect::<Vec<_>>(), [(".debug_info", &[1, 2][..]), (".debug_line", &[3][..])]);
+    assert_eq!(module.to_bytes().unwrap(), bytes);
+
+    assert!(!Module::from_wat("(module (func))").unwrap().has_debug_info());
+}
+
+#[test]
+fn invalid_limits_are_rejected() {
+    let parse = |text: &str| Module::from_bytes(&::wat::parse_str(text).unwrap()).map_err(ParseError::into_issue);
+    assert!(matches!(parse("(module (memory 2 1))"), Err(ParseIssue::InvalidLimits { min: 2, max: 1, .. })));
+    assert!(matches!(parse("(module (table 2 1 funcref))"), Err(ParseIssue::InvalidLimits { min: 2, max: 1, .. })));
+    assert!(matches!(parse(r#"(module (import "env" "m" (memory 1 0)))"#), Err(ParseIssue::InvalidLimits { .. })));
+    assert!(matches!(parse("(module (memory 65537))"), Err(ParseIssue::MemoryTooLarge { pages: 65537, max_pages: 65536, .. })));
+    assert!(matches!(parse("(module (memory 1 65537))"), Err(ParseIssue::MemoryTooLarge { .. })));
+
+    assert!(parse("(module (memory 1 1) (table 0 0 funcref))").is_ok());
+    assert_eq!(parse("(module (memory 65536))").unwrap().memories[0].limits.initial_size, 65536);
+    assert!(parse("(module (memory i64 65537))").is_ok());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn global_init_values_resolve_through_imported_globals() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "base" (global $base i32))
+        (global $g i32 (global.get $base))
+        (global $counter (mut i32) (i32.const 5)))"#).unwrap();
+    let (base, g, counter) = (0u32.into(), 1u32.into(), 2u32.into());
+    let imported = |idx: Idx<Global>| (idx == base).then_some(Val::I32(1024));
+
+    assert_eq!(module.global_init_value(g, imported), Ok(Val::I32(1024)));
+    assert_eq!(module.global_init_value(base, imported), Ok(Val::I32(1024)));
+    assert_eq!(module.global_init_value(g, |_| None), Err(ConstEvalError::ImportedGlobal(base)));
+    // Unlike in `global.get`, the initial value of a mutable global is known.
+    assert_eq!(module.global_init_value(counter, |_| None), Ok(Val::I32(5)));
+    assert_eq!(module.global_init_value(3u32.into(), |_| None), Err(ConstEvalError::InvalidGlobal(3u32.into())));
+}
+
+#[test]
+fn locals_are_grouped_by_consecutive_type() {
+    let mut code = Code::new();
+    assert_eq!(code.locals_grouped(), []);
+    code.locals = [ValType::I32, ValType::I32, ValType::F64].into_iter().map(Local::new).collect();
+    assert_eq!(code.locals_grouped(), [(2, ValType::I32), (1, ValType::F64)]);
+    code.locals.push(Local::new(ValType::I32));
+    assert_eq!(code.locals_grouped(), [(2, ValType::I32), (1, ValType::F64), (1, ValType::I32)]);
+
+    // Groups in the binary are reproduced when encoding.
+    let wasm = ::wat::parse_str("(module (func (local i32 i32 f64 i32)))").unwrap();
+    let module = Module::from_bytes(&wasm).unwrap();
+    assert_eq!(module.functions[0].code().unwrap().locals_grouped().len(), 3);
+    assert_eq!(module.to_bytes().unwrap(), wasm);
+}
+
+#[test]
+fn non_utf8_names_are_converted_lossily_if_requested() {
+    #[rustfmt::skip]
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Memory section with one memory of 1 page.
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // Export section, exporting the memory as "f\xff".
+        0x07, 0x06, 0x01, 0x02, 0x66, 0xff, 0x02, 0x00,
+    ];
+    assert!(Module::from_bytes(&wasm).is_err());
+
+    let options = ParseOptions { lossy_names: true, ..ParseOptions::default() };
+    let (module, _offsets, warnings) = Module::from_bytes_with_options(&wasm, options).unwrap();
+    assert_eq!(module.memories[0].export, ["f\u{fffd}"]);
+    match warnings.as_slice() {
+        [ParseIssue::InvalidUtf8Name { offset, bytes, name }] => {
+            assert_eq!(*offset, 16);
+            assert_eq!(bytes, &[0x66, 0xff]);
+            assert_eq!(name, "f\u{fffd}");
+        }
+        _ => panic!("expected a single warning, got {warnings:?}"),
+    }
+
+    // Valid names are not affected.
+    let wasm = ::wat::parse_str(r#"(module (import "env" "f" (func)) (memory (export "m") 1))"#).unwrap();
+    let (module, _offsets, warnings) = Module::from_bytes_with_options(&wasm, options).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(module.functions[0].import(), Some(("env", "f")));
+    assert_eq!(module.to_bytes().unwrap(), wasm);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn mutable_iterators_yield_typed_indices() {
+    let mut module = Module::from_wat(r#"(module
+        (global i32 (i32.const 0))
+        (global i32 (i32.const 1))
+        (table 1 funcref)
+        (memory 1))"#).unwrap();
+    // Multiple memories are not supported when parsing, so add the second one manually.
+    module.memories.push(module.memories[0].clone());
+
+    for (idx, global) in module.globals_mut() {
+        global.export.push(format!("g{}", idx.to_u32()));
+    }
+    for (idx, table) in module.tables_mut() {
+        table.export.push(format!("t{}", idx.to_u32()));
+    }
+    for (idx, memory) in module.memories_mut() {
+        memory.export.push(format!("m{}", idx.to_u32()));
+    }
+    assert_eq!(module.global_by_export("g1").map(|(idx, _)| idx), Some(Idx::from(1u32)));
+    assert_eq!(module.table_by_export("t0").map(|(idx, _)| idx), Some(Idx::from(0u32)));
+    assert_eq!(module.memory_by_export("m1").map(|(idx, _)| idx), Some(Idx::from(1u32)));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn entities_know_whether_they_are_imported() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "f" (func))
+        (import "env" "g" (global i32))
+        (import "env" "t" (table 1 funcref))
+        (func)
+        (global i32 (i32.const 0))
+        (memory 1))"#).unwrap();
+    assert!(module.functions[0].is_imported());
+    assert!(module.functions[1].is_defined());
+    assert!(module.globals[0].is_imported());
+    assert!(module.globals[1].is_defined());
+    assert!(module.tables[0].is_imported());
+    assert!(!module.tables[0].is_defined());
+    assert!(module.memories[0].is_defined());
+    assert_eq!(module.tables[0].import(), Some(("env", "t")));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn select_type_depends_on_operands_and_annotation() {
+    let untyped = Instr::Select(None);
+    assert_eq!(untyped.select_type(ValType::F32, ValType::F32), Ok(FunctionType::new(&[ValType::F32, ValType::F32, ValType::I32], &[ValType::F32])));
+    assert!(untyped.select_type(ValType::I32, ValType::I64).is_err());
+    assert!(untyped.select_type(ValType::FuncRef, ValType::FuncRef).is_err());
+
+    let typed = Instr::Select(Some(ValType::FuncRef));
+    assert_eq!(typed.select_type(ValType::FuncRef, ValType::FuncRef).ok(), typed.simple_type());
+    assert!(typed.select_type(ValType::ExternRef, ValType::ExternRef).is_err());
+    assert!(Instr::Drop.select_type(ValType::I32, ValType::I32).is_err());
+
+    // The type checker applies the same rules.
+    let module = Module::from_wat(r#"(module
+        (func (param i64 i64 i32) (result i64)
+            local.get 0
+            local.get 1
+            local.get 2
+            select)
+        (func (param funcref funcref i32) (result funcref)
+            local.get 0
+            local.get 1
+            local.get 2
+            select (result funcref)))"#).unwrap();
+    TypeChecker::check_module(&module).unwrap();
+
+    let mut module = module;
+    module.functions[1].instrs_mut().unwrap()[3] = Instr::Select(None);
+    assert!(TypeChecker::check_module(&module).is_err());
+}
+
+#[test]
+fn custom_sections_can_be_borrowed_without_full_parse() {
+    let wasm = ::wat::parse_str(r#"(module
+        (@custom ".debug_info" (after code) "dwarf")
+        (@custom "meta" (before type) "\01\02")
+        (func (result i32)
+            i32.const 0))"#).unwrap();
+    let sections = Module::parse_custom_sections(&wasm).unwrap();
+    assert_eq!(sections.iter().map(|section| (section.name, section.content)).collect::<Vec<_>>(), [
+        ("meta", &[1u8, 2][..]),
+        (".debug_info", b"dwarf"),
+    ]);
+    for section in &sections {
+        assert_eq!(&wasm[section.offset..section.offset + section.content.len()], section.content);
+    }
+
+    let module = Module::from_bytes(&wasm).unwrap();
+    assert_eq!(module.custom_sections.len(), sections.len());
+    assert!(Module::parse_custom_sections(&wasm[..wasm.len() - 1]).is_err());
+}
+
+#[test]
+fn custom_sections_of_truncated_code_section_are_an_error() {
+    // Code section whose declared size (127 bytes) runs past the end of the binary.
+    let wasm = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x7f, 0x00];
+    let err = Module::parse_custom_sections(&wasm).unwrap_err();
+    assert_eq!(err.offset(), Some(10));
+    assert!(Module::from_bytes(&wasm).is_err());
+    assert!(Module::parse_headers_only(&wasm).is_err());
+}
+
+#[test]
+fn out_of_range_lane_indices_are_an_error() {
+    #[rustfmt::skip]
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Type section: [] -> []
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        // Function section
+        0x03, 0x02, 0x01, 0x00,
+        // Code section: v128.const 0, i32x4.extract_lane 4, drop
+        0x0a, 0x1a, 0x01, 0x18, 0x00,
+        0xfd, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xfd, 0x1b, 0x04,
+        0x1a, 0x0b,
+    ];
+    let err = Module::from_bytes(&wasm).unwrap_err();
+    // Points at the lane immediate, after the two bytes of the opcode.
+    assert_eq!(err.offset(), Some(43), "{err}");
+    assert!(err.to_string().contains("invalid lane index"), "{err}");
+
+    assert!(Instr::from_str("i32x4.extract_lane 3").is_ok());
+    assert!(Instr::from_str("i32x4.extract_lane 4").is_err());
+    assert!(Instr::from_str("v128.load64_lane 2").is_err());
+    assert!(Instr::from_str(&format!("i8x16.shuffle {}", "32 ".repeat(16))).is_err());
+}
+
+#[test]
+fn labels_are_resolved_to_their_target_instruction() {
+    use Instr::*;
+    let code = Code {
+        body: vec![
+            Block(FunctionType::empty()), // 0
+            Loop(FunctionType::empty()),  // 1
+            Block(FunctionType::empty()), // 2
+            Br(Label::from(2u32)),        // 3
+            Br(Label::from(1u32)),        // 4
+            Br(Label::from(0u32)),        // 5
+            End,                          // 6
+            Br(Label::from(2u32)),        // 7
+            End,                          // 8
+            End,                          // 9
+            Br(Label::from(0u32)),        // 10
+            End,                          // 11
+        ],
+        ..Code::new()
+    };
+    assert_eq!(code.resolve_label(3, Label::from(2u32)), Some(LabelTarget::End(9)));
+    assert_eq!(code.resolve_label(4, Label::from(1u32)), Some(LabelTarget::Loop(1)));
+    assert_eq!(code.resolve_label(5, Label::from(0u32)), Some(LabelTarget::End(6)));
+    // The outermost label is the function body.
+    assert_eq!(code.resolve_label(3, Label::from(3u32)), Some(LabelTarget::End(11)));
+    assert_eq!(code.resolve_label(7, Label::from(2u32)), Some(LabelTarget::End(11)));
+    assert_eq!(code.resolve_label(10, Label::from(0u32)), Some(LabelTarget::End(11)));
+
+    assert_eq!(code.resolve_label(3, Label::from(4u32)), None);
+    assert_eq!(code.resolve_label(12, Label::from(0u32)), None);
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn block_extent_matches_nested_ends() {
+    let module = Module::from_wat(r#"(module
+        (func (param i32)
+            block                 ;; 0
+                local.get 0       ;; 1
+                if                ;; 2
+                    loop          ;; 3
+                        local.get 0
+                        if        ;; 5
+                            nop
+                        else      ;; 7
+                            nop
+                        end       ;; 9
+                    end           ;; 10
+                else              ;; 11
+                    block         ;; 12
+                    end           ;; 13
+                end               ;; 14
+            end))                 ;; 15"#).unwrap();
+    let code = module.functions[0].code().unwrap();
+    assert_eq!(code.block_extent(0), Some(0..16));
+    assert_eq!(code.block_extent(2), Some(2..15));
+    assert_eq!(code.block_extent(3), Some(3..11));
+    assert_eq!(code.block_extent(5), Some(5..10));
+    assert_eq!(code.block_extent(12), Some(12..14));
+    assert_eq!(code.block_extent(1), None);
+    assert_eq!(code.block_extent(100), None);
+
+    assert_eq!(code.else_of(2), Some(11));
+    assert_eq!(code.else_of(5), Some(7));
+    assert_eq!(code.else_of(0), None);
+    assert_eq!(Code { body: vec![Instr::If(FunctionType::empty()), Instr::End], ..Code::new() }.else_of(0), None);
+}
+
+#[test]
+fn malformed_function_body_reports_function_and_position() {
+    #[rustfmt::skip]
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Type section with `[] -> []`, function section with two functions of that type.
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x03, 0x02, 0x00, 0x00,
+        // Code section: the first body is fine, the second has an overlong LEB128 for `i32.const`.
+        0x0a, 0x0e, 0x02,
+        0x02, 0x00, 0x0b,
+        0x09, 0x00, 0x41, 0x80, 0x80, 0x80, 0x80, 0x80, 0x1a, 0x0b,
+    ];
+    let err = Module::from_bytes(&wasm).unwrap_err();
+    match err.issue() {
+        ParseIssue::BodyDecode { func, offset, body_offset, source: _ } => {
+            assert_eq!(*func, Idx::from(1u32));
+            assert_eq!(*body_offset, 26);
+            assert!((28..34).contains(offset), "{offset}");
+        }
+        issue => panic!("expected body decode error, got {issue:?}"),
+    }
+    assert_eq!(err.offset(), Some(err.issue().offset().unwrap()));
+    assert!(err.to_string().contains("function 1"));
+
+    // Same for streaming parsing.
+    let err = Module::parse_streaming(wasm.as_slice()).find_map(Result::err).unwrap();
+    assert!(matches!(err.issue(), ParseIssue::BodyDecode { func, .. } if *func == Idx::from(1u32)));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn renamed_functions_and_exports_survive_encoding() {
+    let mut module = Module::from_wat(r#"(module
+        (func $f (export "f"))
+        (func (export "g"))
+        (memory (export "mem") 1))"#).unwrap();
+
+    module.set_function_name(Idx::from(0u32), "renamed");
+    module.set_function_name(Idx::from(1u32), "named");
+    assert!(module.rename_export("f", "new_f"));
+    assert!(module.rename_export("mem", "memory"));
+    assert!(!module.rename_export("does_not_exist", "x"));
+    assert!(!module.rename_export("g", "new_f"), "export names must stay unique");
+
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(module.functions[0].name.as_deref(), Some("renamed"));
+    assert_eq!(module.functions[1].name.as_deref(), Some("named"));
+    assert_eq!(module.function_by_export("new_f").map(|(idx, _)| idx), Some(Idx::from(0u32)));
+    assert_eq!(module.functions[1].export, ["g"]);
+    assert!(module.memory_by_export("memory").is_some());
+    assert!(module.function_by_export("f").is_none());
+
+    // Also works for modules without a name section.
+    let mut module = Module::from_wat("(module (func))").unwrap();
+    module.set_function_name(Idx::from(0u32), "f");
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(module.functions[0].name.as_deref(), Some("f"));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn shared_memories_are_parsed_and_encoded() {
+    let module = Module::from_wat("(module (memory 1 1 shared))").unwrap();
+    assert!(module.memories[0].shared);
+    assert!(module.uses_shared_memory());
+    assert!(module.metadata.used_extensions().any(|extension| extension == WasmExtension::ThreadsAtomics));
+    let module = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert!(module.memories[0].shared);
+
+    let module = Module::from_wat(r#"(module (import "env" "mem" (memory 1 2 shared)))"#).unwrap();
+    assert!(module.uses_shared_memory());
+    let module = Module::from_wat("(module (memory 1 1))").unwrap();
+    assert!(!module.uses_shared_memory());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn functions_are_found_by_type() {
+    let module = Module::from_wat(r#"(module
+        (type $unused (func (param i32) (result i32)))
+        (import "env" "f" (func (param i32) (result i32)))
+        (func (param i32) (result i32)
+            local.get 0)
+        (func (param i64) (result i32)
+            i32.const 0)
+        (func (type $unused)
+            local.get 0))"#).unwrap();
+    let i32_to_i32 = FunctionType::new(&[ValType::I32], &[ValType::I32]);
+    assert_eq!(module.functions_with_type(i32_to_i32), [Idx::from(0u32), Idx::from(1u32), Idx::from(3u32)]);
+    assert_eq!(module.functions_with_type(FunctionType::new(&[ValType::I64], &[ValType::I32])), [Idx::from(2u32)]);
+    assert!(module.functions_with_type(FunctionType::empty()).is_empty());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn parse_errors_are_rendered_with_context() {
+    #[rustfmt::skip]
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // Type section with one function type, followed by a function section.
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x03, 0x02, 0x01, 0x00,
+        // Code section with an invalid opcode in the only body.
+        0x0a, 0x05, 0x01, 0x03, 0x00, 0xff, 0x0b,
+    ];
+    let err = Module::from_bytes(&wasm).unwrap_err();
+    assert_eq!(err.render(&wasm), "\
+error decoding body of function 0 at offset 0x17 (0x1 into the body): illegal opcode: 0xff
+00000000: 00 61 73 6d 01 00 00 00 01 04 01 60 00 00 03 02
+00000010: 01 00 0a 05 01 03 00 ff 0b
+                               ^^");
+
+    // Without an offset, there is no context to show.
+    let err = Module::from_wat("(module (func (oops)))").unwrap_err();
+    assert_eq!(err.offset(), None);
+    assert_eq!(err.render(&[]), err.to_string());
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn atomic_wait_notify_and_fence_are_distinguished() {
+    let module = Module::from_wat(r#"(module
+        (memory 1 1 shared)
+        (func (param i32) (result i32)
+            local.get 0
+            i32.const 0
+            i64.const -1
+            memory.atomic.wait32 offset=8
+            atomic.fence
+            local.get 0
+            i32.const 1
+            memory.atomic.notify
+            i32.add))"#).unwrap();
+    let body = &module.functions[0].code().unwrap().body;
+    assert_eq!(body[3], Instr::Atomic(AtomicOp::MemoryAtomicWait32, Memarg { alignment_exp: 2, offset: 8 }));
+    assert_eq!(body[4], Instr::AtomicFence);
+    assert_eq!(body[7], Instr::Atomic(AtomicOp::MemoryAtomicNotify, Memarg { alignment_exp: 2, offset: 0 }));
+    assert_eq!(body[3].memory_access(), Some((MemoryAccessOp::Atomic(AtomicOp::MemoryAtomicWait32), Memarg { alignment_exp: 2, offset: 8 })));
+
+    assert!(AtomicOp::MemoryAtomicWait32.is_wait_or_notify());
+    assert!(AtomicOp::MemoryAtomicWait64.is_wait_or_notify());
+    assert!(AtomicOp::MemoryAtomicNotify.is_wait_or_notify());
+    assert!(!AtomicOp::I32AtomicRmwAdd.is_wait_or_notify());
+    assert_eq!(validate(&module), Ok(()));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn map_instrs_expands_and_rejects_unbalanced_results() {
+    let mut module = Module::from_wat(r#"(module
+        (func (param i32) (result i32)
+            block $outer
+                local.get 0
+                i32.const 1
+                i32.add
+                local.set 0
+            end
+            local.get 0
+            local.get 0
+            i32.add))"#).unwrap();
+    let code = module.functions[0].code_mut().unwrap();
+    code.label_names.insert(0, "outer".to_string());
+
+    code.map_instrs(|instr| match instr {
+        Instr::Binary(BinaryOp::I32Add) => vec![instr, Instr::Const(Val::I32(0)), Instr::Drop],
+        instr => vec![instr],
+    }).unwrap();
+    assert_eq!(code.body.iter().filter(|instr| **instr == Instr::Drop).count(), 2);
+    assert_eq!(&code.body[3..6], &[Instr::Binary(BinaryOp::I32Add), Instr::Const(Val::I32(0)), Instr::Drop]);
+    assert_eq!(code.label_names, BTreeMap::from([(0, "outer".to_string())]));
+
+    let before = code.clone();
+    let err = code.map_instrs(|instr| match instr {
+        Instr::End => vec![],
+        instr => vec![instr],
+    }).unwrap_err();
+    assert_eq!(err.message, "function body must end with end");
+    let err = code.map_instrs(|instr| match instr {
+        Instr::Block(_) => vec![],
+        instr => vec![instr],
+    }).unwrap_err();
+    assert_eq!(err.instr_idx, 6);
+    assert_eq!(*code, before);
+
+    assert_eq!(validate(&module), Ok(()));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn global_init_referencing_defined_global_is_rejected() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "base" (global $base i32))
+        (global $a i32 (i32.const 1))
+        (global $b i32 (global.get $a))
+        (global $c i32 (global.get $base)))"#).unwrap();
+    let (base, a, b, c) = (0u32.into(), 1u32.into(), 2u32.into(), 3u32.into());
+    let imported = |idx: Idx<Global>| (idx == base).then_some(Val::I32(7));
+
+    assert_eq!(module.global_init_value(a, imported), Ok(Val::I32(1)));
+    assert_eq!(module.global_init_value(b, imported), Err(ConstEvalError::NonImportedGlobalInInit(a)));
+    assert_eq!(eval_const_expr(module.globals[2].init().unwrap(), &module), Err(ConstEvalError::NonImportedGlobalInInit(a)));
+    assert_eq!(module.global_init_value(c, imported), Ok(Val::I32(7)));
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn tags_are_parsed_from_imports_and_tag_section() {
+    let module = Module::from_wat(r#"(module
+        (import "env" "error" (tag $error (param i32)))
+        (tag $local (export "local") (param i64 f32))
+        (tag))"#).unwrap();
+    assert_eq!(module.tags, [
+        Tag::new_imported(FunctionType::new(&[ValType::I32], &[]), "env".to_string(), "error".to_string()),
+        Tag { export: vec!["local".to_string()], ..Tag::new(FunctionType::new(&[ValType::I64, ValType::F32], &[])) },
+        Tag::new(FunctionType::new(&[], &[])),
+    ]);
+    assert!(module.tags[0].is_imported());
+    assert!(module.metadata.used_extensions().any(|extension| extension == WasmExtension::ExceptionHandling));
+
+    assert_roundtrips(&module);
+}
+
+#[test]
+fn instr_capacity_hint_is_bounded_by_body_size() {
+    assert_eq!(default_instr_capacity(100), 50);
+    assert_eq!(default_instr_capacity(usize::MAX), MAX_PREALLOCATED_INSTRS);
+
+    let wasm = ::wat::parse_str(r#"(module
+        (func (param i32) (result i32)
+            local.get 0
+            i32.const 1
+            i32.add))"#).unwrap();
+    let default = Module::from_bytes(&wasm).unwrap();
+    let hints: [fn(usize) -> usize; 2] = [|_| 0, |_| usize::MAX];
+    for instr_capacity in hints {
+        let options = ParseOptions { instr_capacity, ..ParseOptions::default() };
+        let (module, _, _) = Module::from_bytes_with_options(&wasm, options).unwrap();
+        assert_eq!(module, default);
+        // Even a hint of `usize::MAX` does not allocate more than one instruction per byte.
+        assert!(module.functions[0].code().unwrap().body.capacity() < wasm.len());
+    }
+}
+
+#[cfg(feature = "wat")]
+#[test]
+fn data_count_section_is_added_if_bodies_need_it() {
+    let mut module = Module::from_wat(r#"(module
+        (memory 1)
+        (data "passive")
+        (func))"#).unwrap();
+    assert!(!module.data_count_section);
+    assert!(wasmparser::validate(&module.to_bytes().unwrap()).is_ok());
+
+    // E.g., from instrumentation: `memory.init` is only valid with a data count section.
+    module.functions[0].code_mut().unwrap().body = vec![
+        Instr::Const(Val::I32(0)),
+        Instr::Const(Val::I32(0)),
+        Instr::Const(Val::I32(7)),
+        Instr::MemoryInit(0u32.into(), 0u32.into()),
+        Instr::DataDrop(0u32.into()),
+        Instr::End,
+    ];
+    let bytes = module.to_bytes().unwrap();
+    wasmparser::validate(&bytes).unwrap();
+    assert!(Module::from_bytes(&bytes).unwrap().data_count_section);
+}
@@ -17,26 +17,26 @@
 //! WebAssembly type checking and type inference can be though of in increasing levels of
 //! difficulty.
 //! - In the simplest case, the _instruction itself_ already gives you a precise type, e.g.,
-//! `i32.add` takes two `i32` value types as input and returns a single `i32`, the instruction type
-//! would be `[i32, i32] -> [i32]`.
+//!   `i32.add` takes two `i32` value types as input and returns a single `i32`, the instruction type
+//!   would be `[i32, i32] -> [i32]`.
 //! - Next, some instructions require additional _static context_ to infer the type. E.g., the
-//! return type of `local.get $l0` depends on the type of local `$0`, and the arguments and return
-//! value of `call f` depends on the called function `f`'s type.
+//!   return type of `local.get $l0` depends on the type of local `$0`, and the arguments and return
+//!   value of `call f` depends on the called function `f`'s type.
 //! - Some instructions are _value polymorphic_. That is, they can have several different types,
-//! depending on preceding instructions. E.g., the `drop` in `i32.const 0; drop` takes an `i32` as
-//! input, wheras the `drop` in `i64.const 0; drop` takes an `i64`. Type checking value polymorphic
-//! instructions requires modeling an abstract _type stack_, which models the implicit evaluation
-//! stack at the type level.
+//!   depending on preceding instructions. E.g., the `drop` in `i32.const 0; drop` takes an `i32` as
+//!   input, wheras the `drop` in `i64.const 0; drop` takes an `i64`. Type checking value polymorphic
+//!   instructions requires modeling an abstract _type stack_, which models the implicit evaluation
+//!   stack at the type level.
 //! - Then there are _blocks_ and _branches_, where the branch instruction's type depends on the
-//! targeted block. Blocks are nested and have their own "local" type stack, i.e., instructions
-//! inside a block cannot access values on the parent stack of their surrounding block.
-//! This requires modeling the type stack as a nested "stack of stacks".
+//!   targeted block. Blocks are nested and have their own "local" type stack, i.e., instructions
+//!   inside a block cannot access values on the parent stack of their surrounding block.
+//!   This requires modeling the type stack as a nested "stack of stacks".
 //! - Finally, there are instructions that are _stack polymorphic_. These can not only have one of
-//! several value types, but even a different _number_ of inputs and outputs. Stack-polymorphism
-//! only appears in instructions that leave the program in an _unreachable_ state, i.e., it is
-//! statically known that following instructions inside the current block will never be executed.
-//! For example any code after an unconditional branch `br` (which leaves the current block) or
-//! after an `unreachable` instruction (which terminates the program with an error) is unreachable.
+//!   several value types, but even a different _number_ of inputs and outputs. Stack-polymorphism
+//!   only appears in instructions that leave the program in an _unreachable_ state, i.e., it is
+//!   statically known that following instructions inside the current block will never be executed.
+//!   For example any code after an unconditional branch `br` (which leaves the current block) or
+//!   after an `unreachable` instruction (which terminates the program with an error) is unreachable.
 //!
 //! # Unreachable and stack-polymorphism
 //!
@@ -88,9 +88,9 @@
 //! Ideally, a type inference algorithm for WebAssembly would have all of the following three
 //! characteristics:
 //! - Streaming processing, i.e., it can always immediately produce an answer whenever we get the
-//! next instruction as input.
+//!   next instruction as input.
 //! - Fully concrete types in the surface language, i.e., it can assign/infer concrete instruction
-//! types that only contain "WebAssembly value types", not "unknown" (`?`) or other escape hatches.
+//!   types that only contain "WebAssembly value types", not "unknown" (`?`) or other escape hatches.
 //! - Assign a type for _all_ instructions in the program, not just a subset.
 //!
 //! With the current design of WebAssembly, there is no way to get all three at the same time.
@@ -106,11 +106,11 @@
 //! and all other comments in that issue thread.
 //! The reasons boil down to:
 //! - They wanted to do _some_ type checking for unreachable code (and not turn the type checker
-//! completely off), as to avoid security bugs in compilers generating native code from such
-//! "unchecked" input.
+//!   completely off), as to avoid security bugs in compilers generating native code from such
+//!   "unchecked" input.
 //! - They wanted to make sure any individually type-correct instruction sequence can be combined
-//! with another one, if their "input and output types" matched. This makes writing compilers
-//! easier that can glue together any individually to WebAssmebly translated code.
+//!   with another one, if their "input and output types" matched. This makes writing compilers
+//!   easier that can glue together any individually to WebAssmebly translated code.
 //!
 //! While I can sympathize with the first argument, I don't think the second argument justified the
 //! high cost in complexity, especially because all of this complexity is only there for code that
@@ -137,9 +137,9 @@
 //! Instead, we chose the following compromise:
 //! - Our algorithm is streaming.
 //! - We _type check_ all instructions, including unreachable code, as in the spec validation
-//! algorithm.
+//!   algorithm.
 //! - We assign concrete, simple types (i.e., using only WebAssembly core value types, not `?`) for
-//! all instructions that are reachable.
+//!   all instructions that are reachable.
 //! - For all unreachable instructions, we do _not_ assign an instruction type.
 //!
 // TODO Inspect V8, SpiderMonkey, JSC, Wasmtime, Wasmer, WABT, Binaryen, spec interpreter for how
@@ -162,14 +162,15 @@
 //!
 //! In terms of type checking implementations, see:
 //! - The reference interpreter, which assigns types, but in a richer type language:
-//! https://github.com/WebAssembly/spec/blob/master/interpreter/valid/valid.ml
+//!   https://github.com/WebAssembly/spec/blob/master/interpreter/valid/valid.ml
 //! - Conrad Watt's paper, page 9, right column:
-//! https://www.cl.cam.ac.uk/~caw77/papers/mechanising-and-verifying-the-webassembly-specification.pdf
+//!   https://www.cl.cam.ac.uk/~caw77/papers/mechanising-and-verifying-the-webassembly-specification.pdf
 //! - The instruction index in the specification: https://webassembly.github.io/spec/core/valid/instructions.html
-//! for a good overview of the typing rules for individual and sequences of instructions.
+//!   for a good overview of the typing rules for individual and sequences of instructions.
 //! - WABT type checker: https://github.com/WebAssembly/wabt/blob/main/src/type-checker.cc
-//! which follows the validation algorithm closely.
+//!   which follows the validation algorithm closely.
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 
@@ -346,9 +347,9 @@ impl TryFrom<StackType> for Vec<ValType> {
 /// A more fully-featured type checker might want to assign types also to the instructions that are
 /// in unreachable code, but those instruction types have two problems:
 /// 1. They cannot be expressed with the surface WebAssembly type syntax (i.e., `ValType`s),
-/// because of unconstrained types, i.e., they would be over `InferredValType`.
+///    because of unconstrained types, i.e., they would be over `InferredValType`.
 /// 2. They cannot be determined in a streaming fashion with O(1) memory and O(1) runtime, because
-/// it is only clear which values a stack-polymorphic instruction produces at the _end_ of blocks.
+///    it is only clear which values a stack-polymorphic instruction produces at the _end_ of blocks.
 ///
 /// For these two reasons, we restrict ourselves here to assign only types
 /// for reachable instructions.
@@ -452,7 +453,7 @@ impl std::error::Error for TypeError {}
 /// but differs in a couple of ways:
 /// - We produce types for each instruction, instead of only checking if they are valid.
 /// - Minor details, see below, e.g., merged value and control stack, nested stack instead of
-/// numerical stack height, precomputed label types.
+///   numerical stack height, precomputed label types.
 #[derive(Debug)]
 pub struct TypeChecker<'module> {
     /// For looking up the type of globals and functions.
@@ -567,6 +568,7 @@ impl<'module> TypeChecker<'module> {
                 Code {
                     locals: Vec::new(),
                     body: init.clone(),
+                    label_names: BTreeMap::new(),
                 },
                 Vec::new(),
             );
@@ -772,6 +774,18 @@ impl<'module> TypeChecker<'module> {
     }
 }
 
+/// A tail call returns the callee's results directly to the caller of `function`, so they must match.
+fn check_tail_call_results(callee_ty: FunctionType, function: &Function) -> Result<(), TypeError> {
+    if callee_ty.results() != function.type_.results() {
+        return Err(format!(
+            "tail call to function with results {:?}, but current function has results {:?}",
+            callee_ty.results(),
+            function.type_.results()
+        ).into());
+    }
+    Ok(())
+}
+
 #[inline(always)]
 fn check_instr(
     state: &mut TypeChecker,
@@ -791,8 +805,9 @@ fn check_instr(
         }
     };
 
-    // In the simple cases, we know the type from the instruction alone.
-    if let Some(ty) = instr.simple_type() {
+    // In the simple cases, we know the type from the instruction alone (or for memory
+    // instructions, additionally from the index type of the memory).
+    if let Some(ty) = instr.memory_type(module).or_else(|| instr.simple_type()) {
         state.pop_vals_expected(ty.inputs())?;
         state.push_vals(ty.results())?;
         return Ok(to_inferred_type(ty));
@@ -804,7 +819,8 @@ fn check_instr(
         // Instructions which are still monomorphic, but where we need additional information from
         // the context (current function, module, etc.) for typing.
         Local(op, idx) => {
-            let local_ty = function.param_or_local_type(*idx);
+            let local_ty = function.local_type(*idx)
+                .ok_or_else(|| TypeError::from(format!("local index {} out of range", idx.to_u32())))?;
             let op_ty = op.to_type(local_ty);
             state.pop_vals_expected(op_ty.inputs())?;
             state.push_vals(op_ty.results())?;
@@ -833,16 +849,35 @@ fn check_instr(
                 (Err(UnconstrainedTypeError), false) => unreachable!("unconstrained value type should never appear in reachable code"),
             }
         }
-        Select => {
+        Select(None) => {
             state.pop_val_expected(ValType::I32)?;
             let ty1 = state.pop_val()?;
             let ty2 = state.pop_val()?;
             let ty = ty1.join(ty2)
                 .ok_or_else(|| TypeError::from(format!("incompatible types {ty1} and {ty2} for select arguments")))?;
+            // Rejects reference types (which need a typed `select`), unless the type is unconstrained.
+            let select_type = ValType::try_from(ty).map(|ty| instr.select_type(ty, ty));
+            if let Ok(Err(err)) = select_type {
+                return Err(err);
+            }
             state.push_val(ty)?;
+            match (select_type, was_unreachable) {
+                (_, true) => InferredInstructionType::Unreachable,
+                (Ok(select_type), false) => InferredInstructionType::Reachable(select_type?),
+                (Err(UnconstrainedTypeError), false) => unreachable!("unconstrained value type should never appear in reachable code"),
+            }
+        }
+        RefIsNull => {
+            let ty = state.pop_val()?;
+            if let Ok(ty) = ValType::try_from(ty) {
+                if ty.to_ref_type().is_none() {
+                    return Err(TypeError::from(format!("expected reference type for ref.is_null, got {ty}")));
+                }
+            }
+            state.push_val(ValType::I32)?;
             match (ValType::try_from(ty), was_unreachable) {
                 (_, true) => InferredInstructionType::Unreachable,
-                (Ok(ty), false) => InferredInstructionType::Reachable(FunctionType::new(&[ValType::I32, ty, ty], &[ty])),
+                (Ok(ty), false) => InferredInstructionType::Reachable(FunctionType::new(&[ty], &[ValType::I32])),
                 (Err(UnconstrainedTypeError), false) => unreachable!("unconstrained value type should never appear in reachable code"),
             }
         }
@@ -941,6 +976,26 @@ fn check_instr(
             state.unreachable()?;
             to_inferred_type(FunctionType::new(tys, &[]))
         }
+        // Tail calls return the callee's results to our caller, so they must match our results.
+        ReturnCall(idx) => {
+            let callee_ty = module.function(*idx).type_;
+            check_tail_call_results(callee_ty, function)?;
+            state.pop_vals_expected(callee_ty.inputs())?;
+            state.unreachable()?;
+            to_inferred_type(FunctionType::new(callee_ty.inputs(), &[]))
+        }
+        ReturnCallIndirect(callee_ty, _) => {
+            check_tail_call_results(*callee_ty, function)?;
+            // Table index.
+            state.pop_val_expected(ValType::I32)?;
+            state.pop_vals_expected(callee_ty.inputs())?;
+            state.unreachable()?;
+            let input_tys = FunctionType::from_iter(
+                callee_ty.inputs().iter().copied().chain(std::iter::once(ValType::I32)),
+                std::iter::empty(),
+            );
+            to_inferred_type(input_tys)
+        }
 
         // The prototypical stack-polymorphic instruction.
         // Here you can see our type simplification: We return a concrete type
@@ -957,6 +1012,17 @@ fn check_instr(
             to_inferred_type(FunctionType::new(&[], &[]))
         }
 
+        // The instructions in the placeholder are unknown, so treat it like `unreachable`, such
+        // that the final `end` of the function accepts any results.
+        Unparsed(_) => {
+            state.unreachable()?;
+            to_inferred_type(FunctionType::new(&[], &[]))
+        }
+
+        // Unlike `Unparsed`, the instruction is only a part of the body, so the type of the
+        // following instructions and even the block structure (e.g., for `try`) is unknown.
+        Unsupported(extension, _) => return Err(TypeError::from(format!("cannot type check unsupported instruction of {}", extension.name()))),
+
         instr => unreachable!(
             "instruction {:?} should have been either be handled by `simple_type()` or the match above",
             instr
@@ -1028,6 +1094,7 @@ mod tests {
             Code {
                 locals: vec![crate::Local::new(F32)],
                 body: Vec::new(),
+                label_names: std::collections::BTreeMap::new(),
             },
             Vec::new(),
         )));
@@ -1226,4 +1293,37 @@ mod tests {
         assert_reachable_type(&mut type_checker, Const(Val::I64(0)), &[], &[I64]);
         assert_reachable_type(&mut type_checker, End, &[], &[I64]);
     }
+
+    #[test]
+    pub fn stack_effect_resolves_context_dependent_instructions() {
+        let mut module = crate::Module::new();
+        let callee = module.add_function(FunctionType::new(&[I32, F32], &[I64]), vec![], vec![End]);
+        let global = module.add_global(F64, crate::Mutability::Mut, vec![Const(Val::F64(0.0.into())), End]);
+        let function = Function::new(FunctionType::new(&[I64], &[F64]), Code {
+            locals: vec![crate::Local::new(F32)],
+            ..Code::new()
+        }, Vec::new());
+
+        let effect = |instr: Instr| instr.stack_effect(&function, &module);
+        assert_eq!(effect(Binary(I32Add)), Ok((vec![I32, I32], vec![I32])));
+        assert_eq!(effect(Local(LocalOp::Tee, Idx::from(1u32))), Ok((vec![F32], vec![F32])));
+        assert_eq!(effect(crate::Instr::Global(crate::GlobalOp::Set, global)), Ok((vec![F64], vec![])));
+        assert_eq!(effect(Call(callee)), Ok((vec![I32, F32], vec![I64])));
+        assert_eq!(effect(If(FunctionType::new(&[I64], &[I32]))), Ok((vec![I32, I64], vec![])));
+        assert_eq!(effect(Return), Ok((vec![F64], vec![])));
+        assert_eq!(effect(ReturnCall(callee)), Ok((vec![I32, F32], vec![])));
+        assert_eq!(effect(ReturnCallIndirect(FunctionType::new(&[F64], &[F64]), Idx::from(0u32))), Ok((vec![F64, I32], vec![])));
+        assert_eq!(effect(Unreachable), Ok((vec![], vec![])));
+
+        // Polymorphic or block-dependent instructions, and invalid indices are errors.
+        assert!(effect(Drop).is_err());
+        assert!(effect(End).is_err());
+        assert!(effect(Br(Label::from(0u32))).is_err());
+        assert!(effect(Local(LocalOp::Get, Idx::from(2u32))).is_err());
+        assert!(effect(Call(Idx::from(7u32))).is_err());
+        assert!(effect(ReturnCall(Idx::from(7u32))).is_err());
+        // Placeholders of unparsed instructions, see `OnUnsupported`.
+        assert!(effect(Unparsed(vec![0x01])).is_err());
+        assert!(effect(Unsupported(crate::WasmExtension::ExceptionHandling, vec![0x08, 0x00])).is_err());
+    }
 }
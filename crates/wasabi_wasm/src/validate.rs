@@ -0,0 +1,294 @@
+//! Validation of a (parsed or instrumented) module before encoding it.
+//!
+//! This checks that every function body is type-correct (using the abstract stack type checker in
+//! `types`) and that all indices referenced by instructions (functions, globals, locals, tables,
+//! memories, element and data segments, and branch labels) are in range. Unlike the type checker
+//! alone, out-of-range indices are reported as an error instead of panicking.
+//!
+//! Example: Check a module after instrumentation.
+//! ```
+//! use wasabi_wasm::{FunctionType, Instr, Module, ValType, Val};
+//! use wasabi_wasm::validate::validate;
+//!
+//! let mut module = Module::new();
+//! module.add_function(FunctionType::new(&[], &[ValType::I32]), vec![], vec![Instr::Const(Val::I32(0)), Instr::End]);
+//! assert!(validate(&module).is_ok());
+//!
+//! module.add_function(FunctionType::new(&[], &[ValType::I32]), vec![], vec![Instr::Const(Val::I64(0)), Instr::End]);
+//! assert!(validate(&module).is_err());
+//! ```
+
+use std::fmt;
+
+use crate::types::{StackType, TypeChecker};
+use crate::{Function, Idx, Instr, Module, ValType};
+
+/// Error during validation, with the location of the offending instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError(pub Box<ValidationErrorRepr>);
+
+// Boxed for the same reason as `TypeError`: keep `Result<(), ValidationError>` pointer-sized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrorRepr {
+    pub function_idx: Idx<Function>,
+    pub function_name: Option<String>,
+
+    /// Index of the offending instruction. One past the last instruction if the function body
+    /// ended without closing all blocks.
+    pub instruction_idx: Idx<Instr>,
+    pub instruction: Option<Instr>,
+
+    pub kind: ValidationErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// An index immediate of an instruction refers to a non-existing entity.
+    IndexOutOfBounds {
+        /// E.g., "function", "global", "local", or "label".
+        space: &'static str,
+        index: u32,
+        /// Number of entities in that index space (for labels: the current block nesting depth).
+        len: usize,
+    },
+    /// The instruction is not well-typed given the current operand stack.
+    Type {
+        message: String,
+        /// The input types of the instruction, if known from `Instr::stack_effect()`.
+        /// (E.g., for `i32.add`, but not for `drop` or `end`.)
+        expected: Option<Vec<ValType>>,
+        /// The operand stack of the current block right before the instruction.
+        actual: StackType,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "validation error, function #{}", self.0.function_idx.to_usize())?;
+        if let Some(function_name) = &self.0.function_name {
+            write!(f, " ({function_name})")?;
+        }
+        write!(f, ", instruction #{}", self.0.instruction_idx.to_usize())?;
+        if let Some(instruction) = &self.0.instruction {
+            write!(f, " ({instruction})")?;
+        }
+        match &self.0.kind {
+            ValidationErrorKind::IndexOutOfBounds { space, index, len } => {
+                write!(f, ": {space} index {index} out of bounds (only {len} in scope)")
+            }
+            ValidationErrorKind::Type { message, expected, actual } => {
+                write!(f, ": {message}")?;
+                if let Some(expected) = expected {
+                    write!(f, ", expected inputs {}", StackType::from(expected.clone()))?;
+                }
+                write!(f, ", actual stack {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Validates all function bodies of `module`, returning the first error found.
+pub fn validate(module: &Module) -> Result<(), ValidationError> {
+    for (function_idx, function) in module.functions() {
+        validate_function(function, function_idx, module)?;
+    }
+    Ok(())
+}
+
+fn validate_function(
+    function: &Function,
+    function_idx: Idx<Function>,
+    module: &Module,
+) -> Result<(), ValidationError> {
+    let code = match function.code() {
+        Some(code) => code,
+        None => return Ok(()),
+    };
+
+    let error = |instruction_idx: usize, instruction: Option<&Instr>, kind| {
+        ValidationError(Box::new(ValidationErrorRepr {
+            function_idx,
+            function_name: function.name.clone(),
+            instruction_idx: instruction_idx.into(),
+            instruction: instruction.cloned(),
+            kind,
+        }))
+    };
+
+    let mut type_checker = TypeChecker::begin_function(function, module);
+    // The implicit function block counts as a label target as well.
+    let mut block_depth = 1;
+    for (instr_idx, instr) in code.body.iter().enumerate() {
+        // Check indices first, since the type checker assumes they are valid (and panics otherwise).
+        check_indices(instr, function, module, block_depth)
+            .map_err(|kind| error(instr_idx, Some(instr), kind))?;
+
+        let stack_before = type_checker
+            .current_block_type_stack()
+            .unwrap_or(StackType::Reachable(Vec::new()));
+        type_checker.check_next_instr(instr).map_err(|e| {
+            error(instr_idx, Some(instr), ValidationErrorKind::Type {
+                message: e.0.message,
+                expected: instr.stack_effect(function, module).ok().map(|(inputs, _)| inputs),
+                actual: stack_before,
+            })
+        })?;
+
+        match instr {
+            Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => block_depth += 1,
+            Instr::End => block_depth -= 1,
+            _ => {}
+        }
+    }
+
+    if block_depth > 0 {
+        let actual = type_checker
+            .current_block_type_stack()
+            .unwrap_or(StackType::Reachable(Vec::new()));
+        return Err(error(code.body.len(), None, ValidationErrorKind::Type {
+            message: format!("function body ended without closing {block_depth} block(s)"),
+            expected: None,
+            actual,
+        }));
+    }
+
+    Ok(())
+}
+
+fn check_indices(
+    instr: &Instr,
+    function: &Function,
+    module: &Module,
+    block_depth: usize,
+) -> Result<(), ValidationErrorKind> {
+    fn check(space: &'static str, index: u32, len: usize) -> Result<(), ValidationErrorKind> {
+        if (index as usize) < len {
+            Ok(())
+        } else {
+            Err(ValidationErrorKind::IndexOutOfBounds { space, index, len })
+        }
+    }
+
+    let functions = module.functions.len();
+    let tables = module.tables.len();
+    let memories = module.memories.len();
+    let elements = module.elements.len();
+    let data = module.data.len();
+
+    use Instr::*;
+    match instr {
+        Br(label) | BrIf(label) => check("label", label.to_u32(), block_depth),
+        BrTable { table, default } => {
+            for label in table.iter().chain(std::iter::once(default)) {
+                check("label", label.to_u32(), block_depth)?;
+            }
+            Ok(())
+        }
+        Call(idx) | ReturnCall(idx) | RefFunc(idx) => check("function", idx.to_u32(), functions),
+        CallIndirect(_, table) | ReturnCallIndirect(_, table) => check("table", table.to_u32(), tables),
+        Local(_, idx) => check("local", idx.to_u32(), function.param_count() + function.local_count()),
+        Global(_, idx) => check("global", idx.to_u32(), module.globals.len()),
+        // Loads and stores implicitly refer to memory 0.
+        Load(..) | Store(..) | LoadLane(..) | StoreLane(..) | Atomic(..) => check("memory", 0, memories),
+        MemorySize(memory) | MemoryGrow(memory) | MemoryFill(memory) => {
+            check("memory", memory.to_u32(), memories)
+        }
+        MemoryCopy { dst, src } => {
+            check("memory", dst.to_u32(), memories)?;
+            check("memory", src.to_u32(), memories)
+        }
+        MemoryInit(segment, memory) => {
+            check("data", segment.to_u32(), data)?;
+            check("memory", memory.to_u32(), memories)
+        }
+        DataDrop(segment) => check("data", segment.to_u32(), data),
+        TableInit(segment, table) => {
+            check("element", segment.to_u32(), elements)?;
+            check("table", table.to_u32(), tables)
+        }
+        ElemDrop(segment) => check("element", segment.to_u32(), elements),
+        TableCopy { dst, src } => {
+            check("table", dst.to_u32(), tables)?;
+            check("table", src.to_u32(), tables)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BinaryOp;
+    use crate::FunctionType;
+    use crate::Label;
+    use crate::LocalOp;
+    use crate::Mutability;
+    use crate::Val;
+
+    use super::*;
+
+    fn module_with_body(type_: FunctionType, body: Vec<Instr>) -> Module {
+        let mut module = Module::new();
+        module.add_function(type_, vec![], body);
+        module
+    }
+
+    fn kind(result: Result<(), ValidationError>) -> ValidationErrorKind {
+        result.expect_err("expected validation error").0.kind
+    }
+
+    #[test]
+    fn valid_function_passes() {
+        let module = module_with_body(FunctionType::new(&[ValType::I32], &[ValType::I32]), vec![
+            Instr::Local(LocalOp::Get, 0u32.into()),
+            Instr::Const(Val::I32(1)),
+            Instr::Binary(BinaryOp::I32Add),
+            Instr::End,
+        ]);
+        assert_eq!(validate(&module), Ok(()));
+    }
+
+    #[test]
+    fn type_error_reports_location_and_stacks() {
+        let module = module_with_body(FunctionType::new(&[], &[ValType::I32]), vec![
+            Instr::Const(Val::I32(1)),
+            Instr::Const(Val::I64(2)),
+            Instr::Binary(BinaryOp::I32Add),
+            Instr::End,
+        ]);
+        let error = validate(&module).unwrap_err();
+        assert_eq!(error.0.function_idx, Idx::from(0u32));
+        assert_eq!(error.0.instruction_idx, Idx::from(2u32));
+        assert_eq!(error.0.kind, ValidationErrorKind::Type {
+            message: "expected type i32, but got i64".to_string(),
+            expected: Some(vec![ValType::I32, ValType::I32]),
+            actual: StackType::Reachable(vec![ValType::I32, ValType::I64]),
+        });
+        assert!(error.to_string().contains("actual stack [i32, i64]"), "{error}");
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors_not_panics() {
+        let mut module = module_with_body(FunctionType::empty(), vec![Instr::Call(5u32.into()), Instr::End]);
+        assert_eq!(kind(validate(&module)), ValidationErrorKind::IndexOutOfBounds { space: "function", index: 5, len: 1 });
+
+        module.function_mut(0u32.into()).code_mut().unwrap().body = vec![Instr::Local(LocalOp::Get, 0u32.into()), Instr::Drop, Instr::End];
+        assert_eq!(kind(validate(&module)), ValidationErrorKind::IndexOutOfBounds { space: "local", index: 0, len: 0 });
+
+        module.add_global(ValType::I32, Mutability::Const, vec![Instr::Const(Val::I32(0)), Instr::End]);
+        module.function_mut(0u32.into()).code_mut().unwrap().body = vec![Instr::Global(crate::GlobalOp::Get, 1u32.into()), Instr::Drop, Instr::End];
+        assert_eq!(kind(validate(&module)), ValidationErrorKind::IndexOutOfBounds { space: "global", index: 1, len: 1 });
+
+        module.function_mut(0u32.into()).code_mut().unwrap().body = vec![Instr::Block(FunctionType::empty()), Instr::Br(Label::from(2u32)), Instr::End, Instr::End];
+        assert_eq!(kind(validate(&module)), ValidationErrorKind::IndexOutOfBounds { space: "label", index: 2, len: 2 });
+    }
+
+    #[test]
+    fn missing_final_end_is_an_error() {
+        let module = module_with_body(FunctionType::empty(), vec![Instr::Nop]);
+        let error = validate(&module).unwrap_err();
+        assert_eq!(error.0.instruction_idx, Idx::from(1u32));
+        assert!(matches!(error.0.kind, ValidationErrorKind::Type { .. }));
+    }
+}
@@ -0,0 +1,88 @@
+//! Visitors for walking (and rewriting) all instructions of a module, without manually nesting
+//! loops over functions and their bodies.
+//!
+//! Example: Redirect all calls of one function to another one.
+//! ```
+//! use wasabi_wasm::{Function, Idx, Instr, Module};
+//! use wasabi_wasm::visit::{InstrLoc, VisitorMut};
+//!
+//! struct RenameCalls {
+//!     from: Idx<Function>,
+//!     to: Idx<Function>,
+//! }
+//!
+//! impl VisitorMut for RenameCalls {
+//!     fn visit_instr_mut(&mut self, instr: &mut Instr, _loc: InstrLoc) {
+//!         if let Instr::Call(target) = instr {
+//!             if *target == self.from {
+//!                 *target = self.to;
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! # let mut module = Module::new();
+//! # let from = module.add_function(wasabi_wasm::FunctionType::empty(), vec![], vec![Instr::End]);
+//! # let to = module.add_function(wasabi_wasm::FunctionType::empty(), vec![], vec![Instr::Call(from), Instr::End]);
+//! module.visit_mut(&mut RenameCalls { from, to });
+//! # assert_eq!(module.function(to).instrs()[0], Instr::Call(to));
+//! ```
+
+use crate::{Function, Idx, Instr, Module};
+
+/// Location of an instruction in the module, such that visitors have context.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct InstrLoc {
+    pub function: Idx<Function>,
+    pub instr: Idx<Instr>,
+}
+
+/// Read-only visitor, see `Module::visit`.
+/// All methods have empty default implementations, so override only those you need.
+pub trait Visitor {
+    /// Called for every function before its instructions, including imported functions.
+    fn visit_function(&mut self, _function: &Function, _idx: Idx<Function>) {}
+
+    /// Called for every instruction in every (non-imported) function body, in order.
+    fn visit_instr(&mut self, _instr: &Instr, _loc: InstrLoc) {}
+}
+
+/// Visitor for in-place rewriting of instructions, see `Module::visit_mut`.
+/// All methods have empty default implementations, so override only those you need.
+pub trait VisitorMut {
+    /// Called for every function before its instructions, including imported functions.
+    fn visit_function_mut(&mut self, _function: &mut Function, _idx: Idx<Function>) {}
+
+    /// Called for every instruction in every (non-imported) function body, in order.
+    fn visit_instr_mut(&mut self, _instr: &mut Instr, _loc: InstrLoc) {}
+}
+
+impl Module {
+    pub fn visit(&self, visitor: &mut impl Visitor) {
+        for (function_idx, function) in self.functions() {
+            visitor.visit_function(function, function_idx);
+            for (instr_idx, instr) in function.instrs().iter().enumerate() {
+                let loc = InstrLoc {
+                    function: function_idx,
+                    instr: instr_idx.into(),
+                };
+                visitor.visit_instr(instr, loc);
+            }
+        }
+    }
+
+    pub fn visit_mut(&mut self, visitor: &mut impl VisitorMut) {
+        for (function_idx, function) in self.functions_mut() {
+            visitor.visit_function_mut(function, function_idx);
+            if let Some(instrs) = function.instrs_mut() {
+                for (instr_idx, instr) in instrs.iter_mut().enumerate() {
+                    let loc = InstrLoc {
+                        function: function_idx,
+                        instr: instr_idx.into(),
+                    };
+                    visitor.visit_instr_mut(instr, loc);
+                }
+            }
+        }
+    }
+}
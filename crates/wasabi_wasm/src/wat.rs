@@ -0,0 +1,437 @@
+//! Printing of a module in the WebAssembly text format (`.wat`), e.g., for debugging the output of
+//! instrumentation.
+//!
+//! All functions, globals, tables, memories, element and data segments, locals, and block labels
+//! are referenced by symbolic identifiers. If the name section provides a name that is a valid
+//! (and unique) text format identifier, that one is used, otherwise a name is generated from the
+//! index, e.g., `$f3` for function 3 or `$l0` for the first parameter/local.
+//!
+//! Instructions are printed in linear (not folded) form, indented by block nesting.
+//! Function types are always inlined, i.e., there are no `(type ...)` declarations.
+//! Custom sections (except for the name section, see above) are not printed.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::{
+    Code, DataMode, ElementItems, ElementMode, Function, FunctionType, GlobalType, IndexType, Instr, Label, Limits, Memory, Module,
+    Mutability, RefType, Val,
+};
+
+/// Returns the module in the WebAssembly text format.
+pub fn module_to_wat(module: &Module) -> String {
+    let mut printer = Printer::new(module);
+    printer.module();
+    printer.out
+}
+
+struct Printer<'module> {
+    module: &'module Module,
+    out: String,
+
+    // Identifiers (without the leading `$`) for each index space, by index.
+    functions: Vec<String>,
+    globals: Vec<String>,
+    tables: Vec<String>,
+    memories: Vec<String>,
+    elements: Vec<String>,
+    data: Vec<String>,
+}
+
+impl<'module> Printer<'module> {
+    fn new(module: &'module Module) -> Self {
+        Printer {
+            module,
+            out: String::new(),
+            functions: unique_ids(module.functions.iter().map(|f| f.name.as_deref()), "f"),
+            globals: unique_ids(module.globals.iter().map(|g| g.name.as_deref()), "g"),
+            tables: unique_ids(module.tables.iter().map(|t| t.name.as_deref()), "t"),
+            memories: unique_ids(module.memories.iter().map(|m| m.name.as_deref()), "m"),
+            elements: unique_ids(module.elements.iter().map(|e| e.name.as_deref()), "e"),
+            data: unique_ids(module.data.iter().map(|d| d.name.as_deref()), "d"),
+        }
+    }
+
+    fn module(&mut self) {
+        let module = self.module;
+        self.out.push_str("(module");
+        if let Some(name) = module.name.as_deref().filter(|name| is_valid_id(name)) {
+            write!(self.out, " ${name}").unwrap();
+        }
+        self.out.push('\n');
+
+        // The text format requires all imports to come before any definitions.
+        for (idx, function) in module.functions() {
+            if let Some((import_module, import_name)) = function.import() {
+                write!(self.out, "  (import {} {} (func {}", string(import_module), string(import_name), id(&self.functions, idx.to_usize())).unwrap();
+                self.function_type(function.type_);
+                self.out.push_str("))\n");
+            }
+        }
+        for (idx, global) in module.globals() {
+            if let Some((import_module, import_name)) = global.import() {
+                writeln!(self.out, "  (import {} {} (global {} {}))", string(import_module), string(import_name), id(&self.globals, idx.to_usize()), global_type(global.type_)).unwrap();
+            }
+        }
+        for (idx, table) in module.tables() {
+            if let Some((import_module, import_name)) = table.import() {
+                writeln!(self.out, "  (import {} {} (table {} {} {}))", string(import_module), string(import_name), id(&self.tables, idx.to_usize()), limits(table.limits), table.ref_type).unwrap();
+            }
+        }
+        for (idx, memory) in module.memories() {
+            if let Some((import_module, import_name)) = memory.import() {
+                writeln!(self.out, "  (import {} {} (memory {} {}))", string(import_module), string(import_name), id(&self.memories, idx.to_usize()), memory_type(memory)).unwrap();
+            }
+        }
+
+        for (idx, table) in module.tables() {
+            if table.is_defined() {
+                writeln!(self.out, "  (table {} {} {})", id(&self.tables, idx.to_usize()), limits(table.limits), table.ref_type).unwrap();
+            }
+        }
+        for (idx, memory) in module.memories() {
+            if memory.is_defined() {
+                writeln!(self.out, "  (memory {} {})", id(&self.memories, idx.to_usize()), memory_type(memory)).unwrap();
+            }
+        }
+        for (idx, global) in module.globals() {
+            if let Some(init) = global.init() {
+                write!(self.out, "  (global {} {}", id(&self.globals, idx.to_usize()), global_type(global.type_)).unwrap();
+                self.const_expr(init);
+                self.out.push_str(")\n");
+            }
+        }
+        for (idx, function) in module.functions() {
+            if let Some(code) = function.code() {
+                self.function(function, code, idx.to_usize());
+            }
+        }
+
+        for (idx, entity) in module.functions() {
+            for export in &entity.export {
+                writeln!(self.out, "  (export {} (func {}))", string(export), id(&self.functions, idx.to_usize())).unwrap();
+            }
+        }
+        for (idx, entity) in module.globals() {
+            for export in &entity.export {
+                writeln!(self.out, "  (export {} (global {}))", string(export), id(&self.globals, idx.to_usize())).unwrap();
+            }
+        }
+        for (idx, entity) in module.tables() {
+            for export in &entity.export {
+                writeln!(self.out, "  (export {} (table {}))", string(export), id(&self.tables, idx.to_usize())).unwrap();
+            }
+        }
+        for (idx, entity) in module.memories() {
+            for export in &entity.export {
+                writeln!(self.out, "  (export {} (memory {}))", string(export), id(&self.memories, idx.to_usize())).unwrap();
+            }
+        }
+
+        if let Some(start) = module.start {
+            writeln!(self.out, "  (start {})", id(&self.functions, start.to_usize())).unwrap();
+        }
+
+        for (idx, element) in module.elements() {
+            write!(self.out, "  (elem {}", id(&self.elements, idx.to_usize())).unwrap();
+            match &element.mode {
+                ElementMode::Active { table, offset } => {
+                    write!(self.out, " (table {}) (offset", id(&self.tables, table.to_usize())).unwrap();
+                    self.const_expr(offset);
+                    self.out.push(')');
+                }
+                ElementMode::Passive => {}
+                ElementMode::Declared => self.out.push_str(" declare"),
+            }
+            match &element.items {
+                ElementItems::Functions(functions) => {
+                    self.out.push_str(" func");
+                    for function in functions {
+                        write!(self.out, " {}", id(&self.functions, function.to_usize())).unwrap();
+                    }
+                }
+                ElementItems::Expressions(exprs) => {
+                    write!(self.out, " {}", element.ref_type).unwrap();
+                    for expr in exprs {
+                        self.out.push_str(" (item");
+                        self.const_expr(expr);
+                        self.out.push(')');
+                    }
+                }
+            }
+            self.out.push_str(")\n");
+        }
+
+        for (idx, data) in module.data.iter().enumerate() {
+            write!(self.out, "  (data {}", id(&self.data, idx)).unwrap();
+            if let DataMode::Active { memory, offset } = &data.mode {
+                write!(self.out, " (memory {}) (offset", id(&self.memories, memory.to_usize())).unwrap();
+                self.const_expr(offset);
+                self.out.push(')');
+            }
+            writeln!(self.out, " {})", bytes(&data.bytes)).unwrap();
+        }
+
+        self.out.push_str(")\n");
+    }
+
+    fn function(&mut self, function: &Function, code: &Code, idx: usize) {
+        write!(self.out, "  (func {}", id(&self.functions, idx)).unwrap();
+
+        let locals = unique_ids(function.param_or_locals().map(|(_, local)| local.name()), "l");
+        for (param_id, param_ty) in locals.iter().zip(function.type_.inputs()) {
+            write!(self.out, " (param ${param_id} {param_ty})").unwrap();
+        }
+        result_types(&mut self.out, function.type_.results());
+        self.out.push('\n');
+        for (local_id, local) in locals[function.param_count()..].iter().zip(&code.locals) {
+            writeln!(self.out, "    (local ${local_id} {})", local.type_).unwrap();
+        }
+
+        // Label identifiers, by the index of the block instruction in the function body.
+        let block_count = code.body.iter().filter(|instr| matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_))).count();
+        let labels = unique_ids((0..block_count as u32).map(|idx| code.label_names.get(&idx).map(String::as_str)), "b");
+        let mut next_label = labels.iter();
+        // Identifiers of the currently open blocks, innermost last.
+        let mut label_stack: Vec<&str> = Vec::new();
+
+        for instr in &code.body {
+            let indent = match instr {
+                // The final `end` of the function body is implicit in the text format.
+                Instr::End if label_stack.is_empty() => break,
+                Instr::End | Instr::Else => label_stack.len() + 1,
+                _ => label_stack.len() + 2,
+            };
+            self.out.push_str(&"  ".repeat(indent));
+            match instr {
+                Instr::Block(ty) | Instr::Loop(ty) | Instr::If(ty) => {
+                    let label = next_label.next().expect("one label per block instruction");
+                    write!(self.out, "{} ${label}", instr.to_name()).unwrap();
+                    self.function_type(*ty);
+                    label_stack.push(label);
+                }
+                Instr::End => {
+                    self.out.push_str("end");
+                    label_stack.pop();
+                }
+                instr => self.instr(instr, &label_stack, &locals),
+            }
+            self.out.push('\n');
+        }
+
+        self.out.push_str("  )\n");
+    }
+
+    /// Prints a constant expression (without the final `end`) on a single line.
+    fn const_expr(&mut self, expr: &[Instr]) {
+        let instrs = match expr.split_last() {
+            Some((Instr::End, instrs)) => instrs,
+            _ => expr,
+        };
+        for instr in instrs {
+            self.out.push(' ');
+            self.instr(instr, &[], &[]);
+        }
+    }
+
+    /// Prints a single non-block instruction. `labels` are the identifiers of the enclosing
+    /// blocks (innermost last), for resolving branch targets, `locals` those of the current
+    /// function's parameters and locals.
+    fn instr(&mut self, instr: &Instr, labels: &[&str], locals: &[String]) {
+        let label = |label: &Label| match labels.iter().rev().nth(label.to_usize()) {
+            Some(id) => format!("${id}"),
+            // Branches to the function body itself (which cannot be named) or invalid labels.
+            None => label.to_u32().to_string(),
+        };
+
+        use Instr::*;
+        match instr {
+            Br(l) | BrIf(l) => write!(self.out, "{} {}", instr.to_name(), label(l)).unwrap(),
+            BrTable { table, default } => {
+                self.out.push_str(instr.to_name());
+                for l in table.iter().chain(std::iter::once(default)) {
+                    write!(self.out, " {}", label(l)).unwrap();
+                }
+            }
+            Call(idx) | ReturnCall(idx) | RefFunc(idx) => write!(self.out, "{} {}", instr.to_name(), id(&self.functions, idx.to_usize())).unwrap(),
+            CallIndirect(ty, table) | ReturnCallIndirect(ty, table) => {
+                write!(self.out, "{} {}", instr.to_name(), id(&self.tables, table.to_usize())).unwrap();
+                self.function_type(*ty);
+            }
+            Local(_, idx) => write!(self.out, "{} {}", instr.to_name(), id(locals, idx.to_usize())).unwrap(),
+            Global(_, idx) => write!(self.out, "{} {}", instr.to_name(), id(&self.globals, idx.to_usize())).unwrap(),
+            Select(Some(ty)) => write!(self.out, "{} (result {ty})", instr.to_name()).unwrap(),
+            RefNull(ty) => write!(self.out, "{} {}", instr.to_name(), match ty {
+                RefType::FuncRef => "func",
+                RefType::ExternRef => "extern",
+            }).unwrap(),
+            Const(Val::F32(val)) => write!(self.out, "{} {}", instr.to_name(), f32_to_wat(val.into_inner())).unwrap(),
+            Const(Val::F64(val)) => write!(self.out, "{} {}", instr.to_name(), f64_to_wat(val.into_inner())).unwrap(),
+            MemorySize(memory) | MemoryGrow(memory) | MemoryFill(memory) => {
+                self.out.push_str(instr.to_name());
+                if memory.to_u32() != 0 {
+                    write!(self.out, " {}", id(&self.memories, memory.to_usize())).unwrap();
+                }
+            }
+            MemoryCopy { dst, src } => {
+                self.out.push_str(instr.to_name());
+                if dst.to_u32() != 0 || src.to_u32() != 0 {
+                    write!(self.out, " {} {}", id(&self.memories, dst.to_usize()), id(&self.memories, src.to_usize())).unwrap();
+                }
+            }
+            MemoryInit(data, memory) => {
+                self.out.push_str(instr.to_name());
+                if memory.to_u32() != 0 {
+                    write!(self.out, " {}", id(&self.memories, memory.to_usize())).unwrap();
+                }
+                write!(self.out, " {}", id(&self.data, data.to_usize())).unwrap();
+            }
+            DataDrop(data) => write!(self.out, "{} {}", instr.to_name(), id(&self.data, data.to_usize())).unwrap(),
+            TableInit(element, table) => {
+                self.out.push_str(instr.to_name());
+                if table.to_u32() != 0 {
+                    write!(self.out, " {}", id(&self.tables, table.to_usize())).unwrap();
+                }
+                write!(self.out, " {}", id(&self.elements, element.to_usize())).unwrap();
+            }
+            ElemDrop(element) => write!(self.out, "{} {}", instr.to_name(), id(&self.elements, element.to_usize())).unwrap(),
+            TableCopy { dst, src } => {
+                self.out.push_str(instr.to_name());
+                if dst.to_u32() != 0 || src.to_u32() != 0 {
+                    write!(self.out, " {} {}", id(&self.tables, dst.to_usize()), id(&self.tables, src.to_usize())).unwrap();
+                }
+            }
+            // All other instructions are printed the same in the text format as by `Display`.
+            instr => write!(self.out, "{instr}").unwrap(),
+        }
+    }
+
+    fn function_type(&mut self, ty: FunctionType) {
+        if !ty.inputs().is_empty() {
+            self.out.push_str(" (param");
+            for ty in ty.inputs() {
+                write!(self.out, " {ty}").unwrap();
+            }
+            self.out.push(')');
+        }
+        result_types(&mut self.out, ty.results());
+    }
+}
+
+fn result_types(out: &mut String, results: &[crate::ValType]) {
+    if !results.is_empty() {
+        out.push_str(" (result");
+        for ty in results {
+            write!(out, " {ty}").unwrap();
+        }
+        out.push(')');
+    }
+}
+
+fn global_type(GlobalType(ty, mutability): GlobalType) -> String {
+    match mutability {
+        Mutability::Const => ty.to_string(),
+        Mutability::Mut => format!("(mut {ty})"),
+    }
+}
+
+fn memory_type(memory: &Memory) -> String {
+    let mut memory_type = match memory.index_type {
+        IndexType::I32 => limits(memory.limits),
+        IndexType::I64 => format!("i64 {}", limits(memory.limits)),
+    };
+    if memory.shared {
+        memory_type.push_str(" shared");
+    }
+    memory_type
+}
+
+fn limits(limits: Limits) -> String {
+    match limits.max_size {
+        Some(max_size) => format!("{} {max_size}", limits.initial_size),
+        None => limits.initial_size.to_string(),
+    }
+}
+
+// Floats are formatted such that they are parsed back to the exact same bits, including NaN
+// payloads. For regular numbers, `Debug` gives the shortest representation that round-trips.
+
+fn f32_to_wat(val: f32) -> String {
+    let sign = if val.is_sign_negative() { "-" } else { "" };
+    if val.is_nan() {
+        format!("{sign}nan:0x{:x}", val.to_bits() & 0x7f_ffff)
+    } else if val.is_infinite() {
+        format!("{sign}inf")
+    } else {
+        format!("{val:?}")
+    }
+}
+
+fn f64_to_wat(val: f64) -> String {
+    let sign = if val.is_sign_negative() { "-" } else { "" };
+    if val.is_nan() {
+        format!("{sign}nan:0x{:x}", val.to_bits() & 0xf_ffff_ffff_ffff)
+    } else if val.is_infinite() {
+        format!("{sign}inf")
+    } else {
+        format!("{val:?}")
+    }
+}
+
+fn string(str: &str) -> String {
+    bytes(str.as_bytes())
+}
+
+/// Formats bytes as a string literal, escaping everything but printable ASCII characters.
+fn bytes(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() + 2);
+    result.push('"');
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(result, "\\{:02x}", byte).unwrap(),
+            0x20..=0x7e => result.push(byte as char),
+            _ => write!(result, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// `$` followed by the identifier for `idx`, or just the index if there is none, i.e., for invalid
+/// (e.g., dangling) references, which the parser accepts and instrumentation may produce.
+fn id(ids: &[String], idx: usize) -> String {
+    match ids.get(idx) {
+        Some(id) => format!("${id}"),
+        None => idx.to_string(),
+    }
+}
+
+/// Whether `name` can be used as an identifier (after the `$`) in the text format.
+fn is_valid_id(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+}
+
+/// Returns one identifier per entity, using the given name if it is valid and unique, and
+/// otherwise `prefix` followed by the index.
+fn unique_ids<'a>(names: impl Iterator<Item = Option<&'a str>>, prefix: &str) -> Vec<String> {
+    let names: Vec<Option<&str>> = names.collect();
+    let mut used = HashSet::new();
+    // First, reserve all valid names, such that generated names cannot clash with them.
+    let mut ids: Vec<Option<String>> = names
+        .iter()
+        .map(|name| match name {
+            Some(name) if is_valid_id(name) && used.insert(name.to_string()) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect();
+    for (idx, id) in ids.iter_mut().enumerate() {
+        if id.is_none() {
+            let mut generated = format!("{prefix}{idx}");
+            while !used.insert(generated.clone()) {
+                generated.push('_');
+            }
+            *id = Some(generated);
+        }
+    }
+    ids.into_iter().map(|id| id.expect("assigned above")).collect()
+}
@@ -0,0 +1,17 @@
+(module
+  (memory 1)
+  (table 2 funcref)
+
+  (data (i32.const 0) "active")
+  (data $passive "passive")
+  (elem $funcs func $copy $copy)
+
+  (func $copy
+    ;; memory.copy dst=16 src=0 len=6
+    (memory.copy (i32.const 16) (i32.const 0) (i32.const 6))
+    (memory.fill (i32.const 32) (i32.const 0) (i32.const 8))
+    (memory.init $passive (i32.const 64) (i32.const 0) (i32.const 7))
+    (data.drop $passive)
+    (table.init $funcs (i32.const 0) (i32.const 0) (i32.const 2))
+    (elem.drop $funcs)
+    (table.copy (i32.const 1) (i32.const 0) (i32.const 1))))
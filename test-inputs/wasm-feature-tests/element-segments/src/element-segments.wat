@@ -0,0 +1,16 @@
+(module
+  (table $funcs 2 funcref)
+  (table $refs 1 externref)
+
+  (func $f0 (result i32) i32.const 0)
+  (func $f1 (result i32) i32.const 1)
+  (func $f2 (result funcref) ref.func $f2)
+
+  ;; MVP active segment.
+  (elem (i32.const 0) $f0)
+  ;; Passive segment, e.g., for table.init.
+  (elem $passive func $f0 $f1)
+  ;; Declares $f2 for the ref.func in its own body.
+  (elem declare func $f2)
+  ;; Active segment with expressions into a non-zero table.
+  (elem (table $refs) (i32.const 0) externref (ref.null extern)))
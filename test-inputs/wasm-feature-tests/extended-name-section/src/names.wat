@@ -0,0 +1,15 @@
+(module $names
+  (type $void (func))
+  (import "env" "imported_table" (table $imported_table 1 funcref))
+  (memory $mem 1)
+  (global $counter (mut i32) (i32.const 0))
+  (elem $elements (i32.const 0) $f)
+  (data $bytes (i32.const 0) "hello")
+  (func $f (type $void) (local $l i32)
+    block $outer
+      loop $inner
+        global.get $counter
+        br_if $outer
+        br $inner
+      end
+    end))
@@ -0,0 +1,30 @@
+(module
+  (func $swap (param i32 i32) (result i32 i32)
+    local.get 1
+    local.get 0)
+  (func (param i32) (result i32 i32)
+    local.get 0
+    block (param i32) (result i32 i32)
+      i32.const 1
+    end
+    call $swap)
+  (func (result i32) (local i32)
+    i32.const 10
+    loop (param i32) (result i32)
+      i32.const 1
+      i32.sub
+      local.tee 0
+      local.get 0
+      br_if 0
+    end)
+  (func (param i32) (result i64 i64)
+    i64.const 1
+    i64.const 2
+    local.get 0
+    if (param i64 i64) (result i64 i64)
+    else
+      drop
+      drop
+      i64.const 3
+      i64.const 4
+    end))
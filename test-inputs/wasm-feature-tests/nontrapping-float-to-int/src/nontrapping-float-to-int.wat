@@ -0,0 +1,7 @@
+(module
+  (func (export "trunc_sat_i32") (param f64) (result i32)
+    local.get 0
+    i32.trunc_sat_f64_s)
+  (func (export "trunc_sat_i64") (param f32) (result i64)
+    local.get 0
+    i64.trunc_sat_f32_u))
@@ -0,0 +1,14 @@
+(module
+  (import "env" "ref" (global $ref externref))
+  (global $self (mut funcref) (ref.func $is_null))
+  (table $externs 2 externref)
+
+  (func $is_null (export "is_null") (result i32)
+    global.get $ref
+    ref.is_null)
+
+  (func $select_ref (param $cond i32) (result funcref)
+    ref.null func
+    global.get $self
+    local.get $cond
+    select (result funcref)))
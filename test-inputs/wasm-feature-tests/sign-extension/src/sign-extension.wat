@@ -0,0 +1,10 @@
+(module
+  (func (export "extend_i32") (param i32) (result i32)
+    local.get 0
+    i32.extend8_s
+    i32.extend16_s)
+  (func (export "extend_i64") (param i64) (result i64)
+    local.get 0
+    i64.extend8_s
+    i64.extend16_s
+    i64.extend32_s))
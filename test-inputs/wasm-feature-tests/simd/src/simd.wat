@@ -0,0 +1,15 @@
+(module
+  (memory 1)
+  (func (export "simd") (param $addr i32) (result i32)
+    (v128.store offset=16
+      (local.get $addr)
+      (i8x16.shuffle 0 1 2 3 4 5 6 7 16 17 18 19 20 21 22 23
+        (i32x4.add
+          (v128.load (local.get $addr))
+          (v128.const i32x4 1 2 3 4))
+        (v128.load8_lane 3 (local.get $addr) (i32x4.splat (i32.const 42)))))
+    (i32x4.extract_lane 2
+      (v128.bitselect
+        (v128.load offset=16 (local.get $addr))
+        (v128.const i64x2 0 -1)
+        (i8x16.shl (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15) (i32.const 1))))))